@@ -67,7 +67,7 @@ pub(crate) fn proportional_derivative_kick_eliminator_feedback_loop_example(){
     let alpha: Ratio = Ratio::new::<ratio>(1.0);
 
     // simulation settings
-    let max_simulation_time: Time = Time::new::<second>(90 as f64);
+    let max_simulation_time: Time = Time::new::<second>(90.0);
     let timestep: Time = Time::new::<second>(0.02);
     let mut current_simulation_time: Time = Time::new::<second>(0.0);
 
@@ -85,7 +85,7 @@ pub(crate) fn proportional_derivative_kick_eliminator_feedback_loop_example(){
     AnalogController::new_filtered_pd_controller(
         Ratio::new::<ratio>(1.0),
         derivative_time,
-        alpha).unwrap().into();
+        alpha).unwrap();
 
     pd_controller.set_dead_time(measurement_delay);
     // now for the transfer function 
@@ -174,6 +174,91 @@ pub(crate) fn proportional_derivative_kick_eliminator_feedback_loop_example(){
 
 }
 
+/// the same feedback loop as 
+/// proportional_derivative_kick_eliminator_feedback_loop_example,
+/// but put together with the block diagram combinators 
+/// rather than by hand
+///
+/// the PI controller and process are in series, and the filtered 
+/// PD controller is in the feedback path. The measurement delay 
+/// means the loop is simulated one block at a time.
+pub(crate) fn block_diagram_feedback_loop_example(){
+
+    // controller settings
+    let controller_gain = Ratio::new::<ratio>(0.5);
+    let integral_time: Time = controller_gain / Frequency::new::<hertz>(1.2);
+    let derivative_time: Time = Time::new::<second>(1.0);
+    // derivative time ratio
+    let alpha: Ratio = Ratio::new::<ratio>(1.0);
+
+    // simulation settings
+    let max_simulation_time: Time = Time::new::<second>(90.0);
+    let timestep: Time = Time::new::<second>(0.02);
+    let mut current_simulation_time: Time = Time::new::<second>(0.0);
+
+    let pi_controller: AnalogController = 
+    AnalogController::new_pi_controller(controller_gain,
+        integral_time).unwrap();
+
+    let measurement_delay = Time::new::<millisecond>(0.1);
+    let mut pd_controller: AnalogController = 
+    AnalogController::new_filtered_pd_controller(
+        Ratio::new::<ratio>(1.0),
+        derivative_time,
+        alpha).unwrap();
+    pd_controller.set_dead_time(measurement_delay);
+
+    use uom::si::{Quantity, ISQ, SI};
+    use uom::typenum::*;
+    // type alias called TimeSquared
+    type TimeSquared = 
+    Quantity<ISQ<Z0, Z0, P2, Z0, Z0, Z0, Z0>, SI<f64>, f64>;
+
+    let one_second = Time::new::<second>(1.0);
+
+    let a1: TimeSquared = one_second * Time::new::<second>(2.5);
+    let b1: Time = -Time::new::<second>(0.5);
+    let c1: Ratio = Ratio::new::<ratio>(1.0);
+
+    let a2: TimeSquared =one_second * one_second* 3.0;
+    let b2: Time = Time::new::<second>(4.0);
+    let c2: Ratio = Ratio::new::<ratio>(4.0);
+
+    let tf: TransferFn = TransferFnSecondOrder::new(a1, b1, c1, a2, b2, c2)
+        .unwrap().into();
+
+    let open_loop = series(pi_controller, tf);
+    let mut closed_loop = feedback(open_loop, pd_controller, 
+        FeedbackSign::Negative);
+
+    let mut user_set_point = Ratio::ZERO;
+
+    let mut wtr = closed_loop.spawn_writer(
+        "pd_feedback_pi_controller_block_diagram_".to_string()).unwrap();
+
+    let stuff_to_do_in_simulation_loop = move ||{
+        // 0 to 5s, set point is zero 
+        // 5s onwards, set point is 5 (dimensionless)
+        if current_simulation_time > Time::new::<second>(5.0) {
+            user_set_point = Ratio::new::<ratio>(5.0);
+        } 
+
+        let tf_output = closed_loop.set_user_input_and_calc(
+            user_set_point, current_simulation_time).unwrap();
+
+        let writer_borrow = &mut wtr;
+        closed_loop.csv_write_values(
+            writer_borrow, current_simulation_time, 
+            user_set_point, tf_output).unwrap();
+
+        current_simulation_time += timestep;
+    };
+
+    let user_task_ptr = Arc::new(Mutex::new(stuff_to_do_in_simulation_loop));
+    simulation_template(max_simulation_time, timestep, current_simulation_time,
+        user_task_ptr);
+}
+
 fn simulation_template(
     max_simulation_time: Time,
     timestep: Time,
@@ -51,7 +51,7 @@ pub(crate) fn stable_first_order_with_delay_simulation_with_zeroes(){
     let b2: Ratio = Ratio::new::<ratio>(2.0);
     let dead_time = Time::new::<second>(2.0);
     let mut current_simulation_time: Time = Time::new::<second>(0.0);
-    let max_simulation_time: Time = Time::new::<second>(30.0 as f64);
+    let max_simulation_time: Time = Time::new::<second>(30.0);
     let timestep: Time = Time::new::<second>(0.1);
 
     let mut tf = TransferFnFirstOrder::new(a1, b1, a2, b2).unwrap();
@@ -148,7 +148,7 @@ pub(crate) fn stable_first_order_with_delay_simulation_no_zeroes(){
     let b2: Ratio = Ratio::new::<ratio>(2.0);
     let dead_time = Time::new::<second>(2.0);
     let mut current_simulation_time: Time = Time::new::<second>(0.0);
-    let max_simulation_time: Time = Time::new::<second>(30.0 as f64);
+    let max_simulation_time: Time = Time::new::<second>(30.0);
     let timestep: Time = Time::new::<second>(0.1);
 
     let mut tf = TransferFnFirstOrder::new(a1, b1, a2, b2).unwrap();
@@ -215,6 +215,63 @@ pub(crate) fn stable_first_order_with_delay_simulation_no_zeroes(){
 
 
 }
+/// 
+/// This is a simulation of an unstable system:
+///
+///          s + 1 
+/// G(s) = -------- exp(-1s)
+///         -2s + 1
+///
+/// The pole is at s = 0.5, so the step response grows 
+/// exponentially:
+///
+/// y(t) = 1 - 1.5 exp( (t-1)/2 ) for t > 1
+///
+pub(crate) fn unstable_first_order_with_delay_simulation(){
+
+    let a1: Time = Time::new::<second>(1.0);
+    let b1: Ratio = Ratio::new::<ratio>(1.0);
+
+    let a2: Time = Time::new::<second>(-2.0);
+    let b2: Ratio = Ratio::new::<ratio>(1.0);
+    let dead_time = Time::new::<second>(1.0);
+    let mut current_simulation_time: Time = Time::new::<second>(0.0);
+    let max_simulation_time: Time = Time::new::<second>(10.0);
+    let timestep: Time = Time::new::<second>(0.1);
+
+    let mut tf = TransferFnFirstOrder::new(a1, b1, a2, b2).unwrap();
+    tf.set_dead_time(dead_time);
+
+    let mut user_input = Ratio::ZERO;
+
+    // writer creation
+
+    let mut wtr = tf.spawn_writer("first_order_unstable_with_delay".to_string()).unwrap();
+
+    let stuff_to_do_in_simulation_loop = move ||{
+
+        // step up to 1 if t >= 0 
+        if current_simulation_time >= Time::ZERO {
+            user_input = Ratio::new::<ratio>(1.0);
+        }
+
+        let output = tf.set_user_input_and_calc(
+            user_input,current_simulation_time).unwrap();
+
+        let writer_borrow = &mut wtr;
+        tf.csv_write_values(writer_borrow, current_simulation_time, 
+            user_input, output).unwrap();
+
+        current_simulation_time += timestep;
+    };
+
+    let user_task_ptr = Arc::new(Mutex::new(stuff_to_do_in_simulation_loop));
+    simulation_template(max_simulation_time, timestep, current_simulation_time,
+        user_task_ptr);
+
+
+}
+
 fn simulation_template(
     max_simulation_time: Time,
     timestep: Time,
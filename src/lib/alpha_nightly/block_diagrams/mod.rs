@@ -0,0 +1,599 @@
+//! block diagram algebra, putting transfer functions and
+//! controllers together in series, in parallel and in feedback
+//! loops
+//!
+//! the result is a BlockDiagram, which implements TransferFnTraits,
+//! so a whole closed loop can be simulated and written to csv
+//! like a single transfer function. Block diagrams can also be
+//! used as blocks in bigger block diagrams.
+//!
+//! Where the overall transfer function is rational (ie. no dead
+//! time inside feedback loops, and equal dead times for parallel
+//! blocks), it is worked out analytically and simulated exactly
+//! using a StateSpaceModel. Otherwise, the blocks are simulated
+//! one after the other every timestep.
+
+use csv::Writer;
+use uom::si::ratio::ratio;
+use uom::si::f64::*;
+use uom::si::time::second;
+use uom::ConstZero;
+
+use super::controllers::{AnalogController, ControllerMode};
+use super::errors::ChemEngProcessControlSimulatorError;
+use super::polynomials;
+use super::transfer_fn_wrapper_and_enums::{StateSpaceModel, TransferFn, TransferFnTraits};
+
+/// anything which can go into a block diagram
+///
+/// new kinds of blocks are added as new variants, boxed so that
+/// a block stays small whatever goes in it
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub enum Block {
+    TransferFn(Box<TransferFn>),
+    Controller(Box<AnalogController>),
+    Diagram(Box<BlockDiagram>),
+}
+
+/// sign of the feedback signal at the summing junction
+#[derive(Debug,PartialEq, PartialOrd, Clone, Copy)]
+pub enum FeedbackSign {
+    /// e = r - H y, the usual case for control loops
+    Negative,
+    /// e = r + H y
+    Positive,
+}
+
+/// how the blocks in a block diagram are connected
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub enum BlockConnection {
+    /// u -> first -> second -> y
+    Series(Block, Block),
+    /// y = first(u) + second(u)
+    Parallel(Block, Block),
+    /// y = forward(u +/- feedback(y))
+    Feedback {
+        forward: Block,
+        feedback: Block,
+        sign: FeedbackSign,
+        /// output of the feedback block at the last timestep
+        previous_feedback_output: Ratio,
+    },
+}
+
+/// blocks connected together, see series, parallel and feedback
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub struct BlockDiagram {
+    pub(crate) connection: BlockConnection,
+    /// overall transfer function, if it is rational,
+    /// (numerator, denominator, dead time)
+    pub(crate) analytical_transfer_fn: Option<(Vec<f64>, Vec<f64>, Time)>,
+    /// exact simulation of the overall transfer function
+    pub(crate) analytical_model: Option<StateSpaceModel>,
+    /// extra dead time at the input of the block diagram
+    pub(crate) dead_time: Time,
+    pub(crate) previous_timestep_input: Ratio,
+    /// input seen by the blocks after the dead time
+    pub(crate) delayed_input: Ratio,
+    /// input changes waiting for the dead time to pass,
+    /// stored as (start time, input level after the change)
+    pub(crate) delayed_input_vec: Vec<(Time, Ratio)>,
+}
+
+/// G1 and G2 in series, G(s) = G2(s) G1(s)
+///
+/// dead times add up
+pub fn series(first_block: impl Into<Block>, second_block: impl Into<Block>) -> BlockDiagram {
+    let first_block: Block = first_block.into();
+    let second_block: Block = second_block.into();
+
+    let analytical_transfer_fn = match (first_block.rational_transfer_fn(),
+        second_block.rational_transfer_fn()) {
+        (Some((n1, d1, l1)), Some((n2, d2, l2))) => {
+            Some((polynomials::multiply(&n1, &n2),
+                polynomials::multiply(&d1, &d2),
+                l1 + l2))
+        },
+        _ => None,
+    };
+
+    BlockDiagram::new(BlockConnection::Series(first_block, second_block),
+        analytical_transfer_fn)
+}
+
+/// G1 and G2 in parallel, G(s) = G1(s) + G2(s)
+///
+/// this is only rational if both blocks have the same dead time
+pub fn parallel(first_block: impl Into<Block>, second_block: impl Into<Block>) -> BlockDiagram {
+    let first_block: Block = first_block.into();
+    let second_block: Block = second_block.into();
+
+    let analytical_transfer_fn = match (first_block.rational_transfer_fn(),
+        second_block.rational_transfer_fn()) {
+        (Some((n1, d1, l1)), Some((n2, d2, l2))) if l1 == l2 => {
+            Some((polynomials::add(
+                        &polynomials::multiply(&n1, &d2),
+                        &polynomials::multiply(&n2, &d1)),
+                polynomials::multiply(&d1, &d2),
+                l1))
+        },
+        _ => None,
+    };
+
+    BlockDiagram::new(BlockConnection::Parallel(first_block, second_block),
+        analytical_transfer_fn)
+}
+
+/// feedback loop with forward path G and feedback path H
+///
+/// for negative feedback,
+///
+/// G(s)/(1 + G(s) H(s))
+///
+/// = N_G D_H / (D_G D_H + N_G N_H)
+///
+/// and for positive feedback the plus sign in the denominator
+/// becomes a minus sign.
+///
+/// this is only rational if there is no dead time in the loop.
+/// Otherwise the loop is simulated one block at a time, and the
+/// feedback block output from the previous timestep is used at the
+/// summing junction (the same way as a loop written by hand).
+pub fn feedback(forward: impl Into<Block>,
+    feedback: impl Into<Block>,
+    sign: FeedbackSign) -> BlockDiagram {
+    let forward: Block = forward.into();
+    let feedback: Block = feedback.into();
+
+    let analytical_transfer_fn = match (forward.rational_transfer_fn(),
+        feedback.rational_transfer_fn()) {
+        (Some((n_g, d_g, l_g)), Some((n_h, d_h, l_h)))
+            if l_g == Time::ZERO && l_h == Time::ZERO => {
+            let loop_numerator = match sign {
+                FeedbackSign::Negative => polynomials::multiply(&n_g, &n_h),
+                FeedbackSign::Positive => polynomials::scale(
+                    &polynomials::multiply(&n_g, &n_h), -1.0),
+            };
+            Some((polynomials::multiply(&n_g, &d_h),
+                polynomials::add(&polynomials::multiply(&d_g, &d_h),
+                    &loop_numerator),
+                Time::ZERO))
+        },
+        _ => None,
+    };
+
+    BlockDiagram::new(BlockConnection::Feedback {
+        forward,
+        feedback,
+        sign,
+        previous_feedback_output: Ratio::ZERO,
+    }, analytical_transfer_fn)
+}
+
+impl BlockDiagram {
+
+    /// builds the exact simulation if the overall transfer function
+    /// is rational and proper, otherwise the blocks are simulated
+    /// one by one
+    fn new(connection: BlockConnection,
+        analytical_transfer_fn: Option<(Vec<f64>, Vec<f64>, Time)>) -> Self {
+
+        let analytical_transfer_fn = analytical_transfer_fn
+            .map(|(numerator, denominator, dead_time)| {
+                (polynomials::trim_leading_zeroes(&numerator),
+                    polynomials::trim_leading_zeroes(&denominator),
+                    dead_time)
+            });
+
+        let analytical_model = analytical_transfer_fn.as_ref()
+            .and_then(|(numerator, denominator, dead_time)| {
+                let mut model = StateSpaceModel::new_controllable_canonical(
+                    numerator.clone(), denominator.clone()).ok()?;
+                model.set_dead_time(*dead_time);
+                Some(model)
+            });
+
+        // an improper overall transfer function cannot be
+        // simulated exactly
+        let analytical_transfer_fn = match analytical_model {
+            Some(_) => analytical_transfer_fn,
+            None => None,
+        };
+
+        Self {
+            connection,
+            analytical_transfer_fn,
+            analytical_model,
+            dead_time: Time::ZERO,
+            previous_timestep_input: Ratio::ZERO,
+            delayed_input: Ratio::ZERO,
+            delayed_input_vec: vec![],
+        }
+    }
+
+    /// numerator of the overall transfer function, descending
+    /// powers of s, if it is rational
+    pub fn numerator(&self) -> Option<Vec<f64>> {
+        self.analytical_transfer_fn.as_ref()
+            .map(|(numerator, _, _)| numerator.clone())
+    }
+
+    /// denominator of the overall transfer function (for a feedback
+    /// loop, the closed loop characteristic polynomial), if it is
+    /// rational
+    pub fn denominator(&self) -> Option<Vec<f64>> {
+        self.analytical_transfer_fn.as_ref()
+            .map(|(_, denominator, _)| denominator.clone())
+    }
+
+    /// true if the block diagram is simulated exactly from its
+    /// overall transfer function
+    pub fn is_analytical(&self) -> bool {
+        self.analytical_model.is_some()
+    }
+
+    /// how the blocks are connected
+    pub fn connection(&self) -> &BlockConnection {
+        &self.connection
+    }
+
+    /// dead time of the whole block diagram, including the dead
+    /// time of the blocks if the overall transfer function is
+    /// rational
+    pub fn dead_time(&self) -> Time {
+        match &self.analytical_transfer_fn {
+            Some((_, _, dead_time)) => self.dead_time + *dead_time,
+            None => self.dead_time,
+        }
+    }
+
+    /// simulates the blocks one by one
+    fn calc_connection(&mut self, user_input: Ratio, time: Time)
+    -> Result<Ratio, ChemEngProcessControlSimulatorError> {
+        match &mut self.connection {
+            BlockConnection::Series(first_block, second_block) => {
+                let intermediate = first_block.set_user_input_and_calc(
+                    user_input, time)?;
+                second_block.set_user_input_and_calc(intermediate, time)
+            },
+            BlockConnection::Parallel(first_block, second_block) => {
+                Ok(first_block.set_user_input_and_calc(user_input, time)?
+                    + second_block.set_user_input_and_calc(user_input, time)?)
+            },
+            BlockConnection::Feedback {
+                forward, feedback, sign, previous_feedback_output } => {
+                let error = match sign {
+                    FeedbackSign::Negative => user_input - *previous_feedback_output,
+                    FeedbackSign::Positive => user_input + *previous_feedback_output,
+                };
+                let output = forward.set_user_input_and_calc(error, time)?;
+                *previous_feedback_output = feedback.set_user_input_and_calc(
+                    output, time)?;
+                Ok(output)
+            },
+        }
+    }
+}
+
+impl TransferFnTraits for BlockDiagram {
+    /// extra dead time at the input of the whole block diagram
+    fn set_dead_time(&mut self, dead_time: Time) {
+        self.dead_time = dead_time;
+        if let (Some(model), Some((_, _, block_dead_time))) =
+            (&mut self.analytical_model, &self.analytical_transfer_fn) {
+            model.set_dead_time(*block_dead_time + dead_time);
+        }
+    }
+
+    fn set_user_input_and_calc(&mut self, user_input: Ratio,
+        time: Time) ->
+    Result<Ratio, ChemEngProcessControlSimulatorError> {
+
+        if let Some(model) = &mut self.analytical_model {
+            return model.set_user_input_and_calc(user_input, time);
+        }
+
+        // case where input is not the same to 9 decimal places
+        let input_changed: bool =
+            (user_input.get::<ratio>() * 1e9).round()
+            - (self.previous_timestep_input
+                .get::<ratio>()*1e9).round() != 0.0 ;
+
+        if input_changed {
+            let start_time = time + self.dead_time;
+            self.delayed_input_vec.push((start_time, user_input));
+            self.previous_timestep_input = user_input;
+        }
+
+        // feed the input changes whose dead time has passed
+        // into the blocks, in the order they came in
+        while let Some(&(start_time, input)) = self.delayed_input_vec.first() {
+            if start_time > time {
+                break;
+            }
+            if start_time < time {
+                self.calc_connection(input, start_time)?;
+            }
+            self.delayed_input = input;
+            self.delayed_input_vec.remove(0);
+        }
+
+        self.calc_connection(self.delayed_input, time)
+    }
+
+    fn spawn_writer(&mut self, name: String) -> Result<Writer<std::fs::File>,
+    ChemEngProcessControlSimulatorError>{
+        let title_string: String = name + "block_diagram.csv";
+        let wtr = Writer::from_path(title_string)?;
+        Ok(wtr)
+    }
+
+    fn csv_write_values(&mut self,
+        wtr: &mut Writer<std::fs::File>,
+        time: Time,
+        input: Ratio,
+        output: Ratio) -> Result<(),
+    ChemEngProcessControlSimulatorError> {
+
+        let current_time_string = time.get::<second>().to_string();
+        let input_string = input.get::<ratio>().to_string();
+        let output_string = output.get::<ratio>().to_string();
+
+        wtr.write_record(&[current_time_string,
+            input_string,
+            output_string])?;
+
+        wtr.flush().unwrap();
+
+        Ok(())
+    }
+}
+
+impl Block {
+
+    /// (numerator, denominator, dead time) of the block,
+    /// if its transfer function is rational apart from the
+    /// dead time
+    ///
+    /// controllers with output limits, controllers not in auto and 
+    /// two degree of freedom controllers with setpoint weights other 
+    /// than one are not linear in the error, so loops with them are 
+    /// simulated one block at a time
+    pub fn rational_transfer_fn(&self) -> Option<(Vec<f64>, Vec<f64>, Time)> {
+        match self {
+            Block::TransferFn(transfer_fn) => {
+                Some((transfer_fn.numerator(), transfer_fn.denominator(),
+                    transfer_fn.dead_time()))
+            },
+            Block::Controller(controller) => {
+                let unit_setpoint_weights = match controller.setpoint_weights() {
+                    Some(setpoint_weights) => {
+                        setpoint_weights.proportional_weight.get::<ratio>() == 1.0
+                            && setpoint_weights.derivative_weight.get::<ratio>() == 1.0
+                    },
+                    None => true,
+                };
+                if controller.output_limits().is_some()
+                    || controller.mode() != ControllerMode::Auto
+                    || !unit_setpoint_weights {
+                    return None;
+                }
+                Some((controller.numerator(), controller.denominator(),
+                    controller.dead_time()))
+            },
+            Block::Diagram(block_diagram) => {
+                let (numerator, denominator, _) =
+                    block_diagram.analytical_transfer_fn.clone()?;
+                Some((numerator, denominator, block_diagram.dead_time()))
+            },
+        }
+    }
+}
+
+impl TransferFnTraits for Block {
+    fn set_dead_time(&mut self, dead_time: Time) {
+        match self {
+            Block::TransferFn(transfer_fn) => {
+                transfer_fn.set_dead_time(dead_time)
+            },
+            Block::Controller(controller) => {
+                controller.set_dead_time(dead_time)
+            },
+            Block::Diagram(block_diagram) => {
+                block_diagram.set_dead_time(dead_time)
+            },
+        }
+    }
+
+    fn set_user_input_and_calc(&mut self,
+        user_input: Ratio,
+        time_of_input: Time) ->
+    Result<Ratio, ChemEngProcessControlSimulatorError> {
+        match self {
+            Block::TransferFn(transfer_fn) => {
+                transfer_fn.set_user_input_and_calc(user_input, time_of_input)
+            },
+            Block::Controller(controller) => {
+                controller.set_user_input_and_calc(user_input, time_of_input)
+            },
+            Block::Diagram(block_diagram) => {
+                block_diagram.set_user_input_and_calc(user_input, time_of_input)
+            },
+        }
+    }
+
+    fn spawn_writer(&mut self, name: String) -> Result<Writer<std::fs::File>,
+    ChemEngProcessControlSimulatorError>{
+        match self {
+            Block::TransferFn(transfer_fn) => {
+                transfer_fn.spawn_writer(name)
+            },
+            Block::Controller(controller) => {
+                controller.spawn_writer(name)
+            },
+            Block::Diagram(block_diagram) => {
+                block_diagram.spawn_writer(name)
+            },
+        }
+    }
+
+    fn csv_write_values(&mut self,
+        wtr: &mut Writer<std::fs::File>,
+        time: Time,
+        input: Ratio,
+        output: Ratio) -> Result<(),
+    ChemEngProcessControlSimulatorError> {
+        match self {
+            Block::TransferFn(transfer_fn) => {
+                transfer_fn.csv_write_values(wtr, time, input, output)
+            },
+            Block::Controller(controller) => {
+                controller.csv_write_values(wtr, time, input, output)
+            },
+            Block::Diagram(block_diagram) => {
+                block_diagram.csv_write_values(wtr, time, input, output)
+            },
+        }
+    }
+}
+
+impl From<TransferFn> for Block {
+    fn from(transfer_fn: TransferFn) -> Self {
+        Block::TransferFn(Box::new(transfer_fn))
+    }
+}
+
+impl From<AnalogController> for Block {
+    fn from(controller: AnalogController) -> Self {
+        Block::Controller(Box::new(controller))
+    }
+}
+
+impl From<BlockDiagram> for Block {
+    fn from(block_diagram: BlockDiagram) -> Self {
+        Block::Diagram(Box::new(block_diagram))
+    }
+}
+
+#[test]
+pub fn test_closed_loop_algebra(){
+    use super::transfer_fn_wrapper_and_enums::TransferFnFirstOrder;
+    use uom::si::time::second;
+
+    let first_order = |time_constant: f64, dead_time: f64| {
+        let mut transfer_fn: TransferFn = TransferFnFirstOrder::new(
+            Time::ZERO, Ratio::new::<ratio>(1.0),
+            Time::new::<second>(time_constant), Ratio::new::<ratio>(1.0))
+            .unwrap().into();
+        transfer_fn.set_dead_time(Time::new::<second>(dead_time));
+        transfer_fn
+    };
+    let unity_gain: TransferFn = TransferFnFirstOrder::new(
+        Time::new::<second>(1.0), Ratio::new::<ratio>(1.0),
+        Time::new::<second>(1.0), Ratio::new::<ratio>(1.0)).unwrap().into();
+    let proportional_controller: AnalogController = 
+        super::controllers::ProportionalController::new(
+            Ratio::new::<ratio>(2.0)).unwrap().into();
+
+    // P control of 1/(s + 1) with unity feedback,
+    //
+    // 2/(s + 1) / (1 + 2/(s + 1)) = 2/(s + 3)
+    //
+    // the step response is 2/3 (1 - exp(-3t))
+    let open_loop = series(proportional_controller.clone(),
+        first_order(1.0, 0.0));
+    let mut closed_loop = feedback(open_loop, unity_gain.clone(),
+        FeedbackSign::Negative);
+    assert!(closed_loop.is_analytical());
+
+    let numerator = closed_loop.numerator().unwrap();
+    let denominator = closed_loop.denominator().unwrap();
+    let closed_loop_poles = polynomials::roots(&denominator).unwrap();
+    // the unity gain block (s + 1)/(s + 1) leaves a cancelled pole
+    // at s = -1, the other closed loop pole is at s = -3
+    assert!(closed_loop_poles.iter().any(|pole| (pole.re + 3.0).abs() < 1e-9));
+    approx::assert_relative_eq!(
+        numerator.last().unwrap()/denominator.last().unwrap(), 2.0/3.0,
+        max_relative = 1e-12);
+
+    let step = Ratio::new::<ratio>(1.0);
+    closed_loop.set_user_input_and_calc(step, Time::ZERO).unwrap();
+    let output = closed_loop.set_user_input_and_calc(step,
+        Time::new::<second>(0.5)).unwrap();
+    approx::assert_relative_eq!(output.get::<ratio>(),
+        2.0/3.0 * (1.0 - (-1.5_f64).exp()), max_relative = 1e-9);
+
+    // a dead time inside the loop means the loop is simulated
+    // one block at a time, with a small timestep it should still
+    // settle at the same steady state
+    let open_loop_with_dead_time = series(proportional_controller,
+        first_order(1.0, 0.1));
+    let mut closed_loop_with_dead_time = feedback(open_loop_with_dead_time,
+        unity_gain, FeedbackSign::Negative);
+    assert!(!closed_loop_with_dead_time.is_analytical());
+
+    let mut output = Ratio::ZERO;
+    for step_number in 0..2001 {
+        let time = Time::new::<second>(step_number as f64 * 0.01);
+        output = closed_loop_with_dead_time.set_user_input_and_calc(
+            step, time).unwrap();
+    }
+    approx::assert_relative_eq!(output.get::<ratio>(), 2.0/3.0,
+        max_relative = 1e-3);
+
+    // parallel blocks with the same dead time,
+    // 1/(s + 1) + 1/(2s + 1) with 1s of dead time
+    let mut parallel_blocks = parallel(first_order(1.0, 1.0),
+        first_order(2.0, 1.0));
+    assert!(parallel_blocks.is_analytical());
+    parallel_blocks.set_user_input_and_calc(step, Time::ZERO).unwrap();
+    let output = parallel_blocks.set_user_input_and_calc(step,
+        Time::new::<second>(3.0)).unwrap();
+    approx::assert_relative_eq!(output.get::<ratio>(),
+        2.0 - (-2.0_f64).exp() - (-1.0_f64).exp(), max_relative = 1e-9);
+}
+
+#[test]
+pub fn test_saturating_controller_in_loop(){
+    use super::transfer_fn_wrapper_and_enums::TransferFnZeroOrderHold;
+    use uom::si::time::second;
+
+    // zero order hold blocks, so each timestep costs the same
+    let plant: TransferFn = TransferFnZeroOrderHold::new_first_order(
+        Time::ZERO, Ratio::new::<ratio>(1.0),
+        Time::new::<second>(1.0), Ratio::new::<ratio>(1.0)).unwrap().into();
+    let unity_gain: TransferFn = TransferFnZeroOrderHold::new(
+        vec![1.0], vec![1.0]).unwrap().into();
+    let pi_controller = AnalogController::new_pi_controller(
+        Ratio::new::<ratio>(2.0), Time::new::<second>(1.0)).unwrap();
+    let mut saturating_pi_controller = pi_controller.clone();
+    saturating_pi_controller.set_output_limits(Ratio::ZERO,
+        Ratio::new::<ratio>(0.5)).unwrap();
+
+    // PI control of 1/(s + 1), the linear closed loop is 2/(s + 2)
+    let mut linear_loop = feedback(series(pi_controller, plant.clone()),
+        unity_gain.clone(), FeedbackSign::Negative);
+    assert!(linear_loop.is_analytical());
+
+    // with the controller output limited to 0.5, the loop
+    // can only get to 0.5
+    let mut saturating_loop = feedback(series(saturating_pi_controller, 
+            plant), unity_gain, FeedbackSign::Negative);
+    assert!(!saturating_loop.is_analytical());
+    assert!(saturating_loop.numerator().is_none());
+
+    let step = Ratio::new::<ratio>(1.0);
+    let mut linear_output = Ratio::ZERO;
+    let mut saturating_output = Ratio::ZERO;
+    for step_number in 0..201 {
+        let time = Time::new::<second>(f64::from(step_number) * 0.05);
+        linear_output = linear_loop.set_user_input_and_calc(step, time)
+            .unwrap();
+        saturating_output = saturating_loop.set_user_input_and_calc(step, 
+            time).unwrap();
+    }
+    approx::assert_relative_eq!(linear_output.get::<ratio>(), 1.0,
+        max_relative = 1e-6);
+    approx::assert_relative_eq!(saturating_output.get::<ratio>(), 0.5,
+        max_relative = 1e-3);
+}
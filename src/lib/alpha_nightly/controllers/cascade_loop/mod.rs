@@ -0,0 +1,406 @@
+use csv::Writer;
+use uom::si::f64::*;
+use uom::si::ratio::ratio;
+use uom::si::time::second;
+use uom::ConstZero;
+
+use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
+use crate::alpha_nightly::transfer_fn_wrapper_and_enums::{TransferFn,
+    TransferFnTraits};
+use super::{AnalogController, ControllerMode};
+
+/// cascade control loop, eg. a temperature (primary) controller
+/// setting the setpoint of a flow (secondary) controller
+///
+/// ```text
+///  r1 -->(PC)--r2-->(SC)--u--> G2 --y2--> G1 --y1-->
+///          ^          ^            |            |
+///          |          +-----y2-----+            |
+///          +-----------------y1-----------------+
+/// ```
+///
+/// the secondary process G2 takes the secondary controller output
+/// u and gives the secondary measurement y2, which feeds the
+/// primary process G1.
+///
+/// the cascade is closed while the secondary controller is in
+/// cascade mode, where it uses the primary output as a remote
+/// setpoint. Putting the secondary controller in auto (or manual)
+/// breaks the cascade, and the primary controller output then
+/// tracks the secondary setpoint so that closing the cascade again
+/// does not bump anything.
+///
+/// the secondary controller may do without integral action, eg. a
+/// P flow controller, as the primary controller takes out its
+/// offset. Bumpless switching of a controller needs integral action
+/// though.
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub struct CascadeLoop {
+    pub(crate) primary_controller: AnalogController,
+    pub(crate) secondary_controller: AnalogController,
+    /// G1, secondary measurement to primary measurement
+    pub(crate) primary_process: TransferFn,
+    /// G2, secondary controller output to secondary measurement
+    pub(crate) secondary_process: TransferFn,
+    pub(crate) primary_measurement: Ratio,
+    pub(crate) secondary_measurement: Ratio,
+    /// primary controller mode to go back to once the cascade
+    /// is closed, while the cascade is open the primary controller
+    /// sits in manual, tracking the secondary setpoint
+    pub(crate) primary_mode_before_open: Option<ControllerMode>,
+}
+
+/// all the signals in a cascade loop at one timestep
+#[derive(Debug,PartialEq, PartialOrd, Clone, Copy)]
+pub struct CascadeSignals {
+    pub time: Time,
+    pub primary_setpoint: Ratio,
+    pub primary_measurement: Ratio,
+    /// which is the remote setpoint while the cascade is closed
+    pub primary_output: Ratio,
+    pub secondary_setpoint: Ratio,
+    pub secondary_measurement: Ratio,
+    /// the manipulated variable, eg. valve opening
+    pub secondary_output: Ratio,
+    pub cascade_closed: bool,
+}
+
+impl CascadeLoop {
+
+    /// the cascade starts closed, with the secondary controller
+    /// in cascade mode and the primary controller in auto
+    pub fn new(primary_controller: AnalogController,
+        secondary_controller: AnalogController,
+        primary_process: TransferFn,
+        secondary_process: TransferFn) -> Result<Self,
+    ChemEngProcessControlSimulatorError> {
+
+        let mut primary_controller = primary_controller;
+        let mut secondary_controller = secondary_controller;
+        primary_controller.set_mode(ControllerMode::Auto);
+        secondary_controller.set_mode(ControllerMode::Cascade);
+
+        Ok(Self {
+            primary_controller,
+            secondary_controller,
+            primary_process,
+            secondary_process,
+            primary_measurement: Ratio::ZERO,
+            secondary_measurement: Ratio::ZERO,
+            primary_mode_before_open: None,
+        })
+    }
+
+    /// local setpoint of the primary controller
+    pub fn set_setpoint(&mut self, setpoint: Ratio) {
+        self.primary_controller.set_setpoint(setpoint)
+    }
+
+    /// limits on the secondary controller output, ie. the
+    /// manipulated variable
+    pub fn set_inner_loop_output_limits(&mut self, lower_limit: Ratio,
+        upper_limit: Ratio) -> Result<(), ChemEngProcessControlSimulatorError> {
+        self.secondary_controller.set_output_limits(lower_limit, upper_limit)
+    }
+
+    /// limits on the primary controller output, ie. the range of
+    /// the remote setpoint
+    pub fn set_outer_loop_output_limits(&mut self, lower_limit: Ratio,
+        upper_limit: Ratio) -> Result<(), ChemEngProcessControlSimulatorError> {
+        self.primary_controller.set_output_limits(lower_limit, upper_limit)
+    }
+
+    /// puts the secondary controller back in cascade,
+    /// the primary controller carries on from the secondary setpoint
+    pub fn close_cascade(&mut self) {
+        self.secondary_controller.set_mode(ControllerMode::Cascade);
+        self.restore_primary_mode()
+    }
+
+    /// puts the secondary controller in local auto, keeping
+    /// its current setpoint
+    pub fn open_cascade(&mut self) {
+        self.secondary_controller.set_mode(ControllerMode::Auto)
+    }
+
+    /// true if the secondary controller is in cascade
+    pub fn is_cascade_closed(&self) -> bool {
+        self.secondary_controller.mode() == ControllerMode::Cascade
+    }
+
+    pub fn primary_controller(&self) -> &AnalogController {
+        &self.primary_controller
+    }
+
+    /// eg. to put the primary controller in manual
+    pub fn primary_controller_mut(&mut self) -> &mut AnalogController {
+        &mut self.primary_controller
+    }
+
+    pub fn secondary_controller(&self) -> &AnalogController {
+        &self.secondary_controller
+    }
+
+    /// eg. to change the secondary setpoint while the cascade is open
+    pub fn secondary_controller_mut(&mut self) -> &mut AnalogController {
+        &mut self.secondary_controller
+    }
+
+    pub fn primary_measurement(&self) -> Ratio {
+        self.primary_measurement
+    }
+
+    pub fn secondary_measurement(&self) -> Ratio {
+        self.secondary_measurement
+    }
+
+    fn restore_primary_mode(&mut self) {
+        if let Some(primary_mode) = self.primary_mode_before_open.take() {
+            self.primary_controller.set_mode(primary_mode);
+        }
+    }
+
+    /// runs both loops for one timestep,
+    ///
+    /// the controllers act on the measurements from the last
+    /// timestep, then the secondary and primary processes are
+    /// updated in turn
+    pub fn calc(&mut self, time_of_input: Time) -> Result<CascadeSignals,
+    ChemEngProcessControlSimulatorError> {
+
+        let cascade_closed = self.is_cascade_closed();
+
+        if cascade_closed {
+            // eg. the secondary controller was put in cascade
+            // directly rather than with close_cascade
+            self.restore_primary_mode();
+        } else {
+            // outer loop tracks the secondary setpoint, and with
+            // setpoint tracking on, its own measurement
+            if self.primary_mode_before_open.is_none() {
+                self.primary_mode_before_open =
+                    Some(self.primary_controller.mode());
+                self.primary_controller.set_mode(ControllerMode::Manual);
+            }
+            self.primary_controller.set_manual_output(
+                self.secondary_controller.setpoint());
+        }
+
+        let primary_output = self.primary_controller
+            .set_measurement_and_calc(self.primary_measurement, time_of_input)?;
+        let primary_setpoint = self.primary_controller.setpoint();
+
+        if cascade_closed {
+            self.secondary_controller.set_remote_setpoint(primary_output);
+        }
+
+        let secondary_output = self.secondary_controller
+            .set_measurement_and_calc(self.secondary_measurement, time_of_input)?;
+        let secondary_setpoint = self.secondary_controller.setpoint();
+
+        let signals = CascadeSignals {
+            time: time_of_input,
+            primary_setpoint,
+            primary_measurement: self.primary_measurement,
+            primary_output,
+            secondary_setpoint,
+            secondary_measurement: self.secondary_measurement,
+            secondary_output,
+            cascade_closed,
+        };
+
+        self.secondary_measurement = self.secondary_process
+            .set_user_input_and_calc(secondary_output, time_of_input)?;
+        self.primary_measurement = self.primary_process
+            .set_user_input_and_calc(self.secondary_measurement, time_of_input)?;
+
+        Ok(signals)
+    }
+
+    pub fn spawn_writer(&mut self, name: String) -> Result<Writer<std::fs::File>,
+    ChemEngProcessControlSimulatorError> {
+        let title_string: String = name + "_cascade_loop.csv";
+        let wtr = Writer::from_path(title_string)?;
+        Ok(wtr)
+    }
+
+    /// one row per timestep, in the order
+    ///
+    /// time (s), primary setpoint, primary measurement,
+    /// primary output, secondary setpoint, secondary measurement,
+    /// secondary output, cascade closed (1 or 0)
+    pub fn csv_write_values(&mut self,
+        wtr: &mut Writer<std::fs::File>,
+        signals: CascadeSignals) -> Result<(),
+    ChemEngProcessControlSimulatorError> {
+
+        let cascade_closed: f64 = if signals.cascade_closed { 1.0 } else { 0.0 };
+
+        wtr.write_record(&[signals.time.get::<second>().to_string(),
+            signals.primary_setpoint.get::<ratio>().to_string(),
+            signals.primary_measurement.get::<ratio>().to_string(),
+            signals.primary_output.get::<ratio>().to_string(),
+            signals.secondary_setpoint.get::<ratio>().to_string(),
+            signals.secondary_measurement.get::<ratio>().to_string(),
+            signals.secondary_output.get::<ratio>().to_string(),
+            cascade_closed.to_string()])?;
+
+        wtr.flush().unwrap();
+        Ok(())
+    }
+}
+
+#[test]
+pub fn test_cascade_loop(){
+    use crate::alpha_nightly::transfer_fn_wrapper_and_enums::TransferFnZeroOrderHold;
+
+    // the zero order hold backend keeps the simulation cost per 
+    // timestep constant
+    let first_order = |time_constant: f64| -> TransferFn {
+        TransferFnZeroOrderHold::new_first_order(Time::ZERO, 
+            Ratio::new::<ratio>(1.0), Time::new::<second>(time_constant), 
+            Ratio::new::<ratio>(1.0)).unwrap().into()
+    };
+
+    // slow temperature loop, fast flow loop
+    let primary_controller = AnalogController::new_pi_controller(
+        Ratio::new::<ratio>(1.0), Time::new::<second>(20.0)).unwrap();
+    let secondary_controller = AnalogController::new_pi_controller(
+        Ratio::new::<ratio>(1.0), Time::new::<second>(1.0)).unwrap();
+    let mut cascade_loop = CascadeLoop::new(primary_controller,
+        secondary_controller, first_order(20.0), first_order(1.0)).unwrap();
+    cascade_loop.set_inner_loop_output_limits(Ratio::ZERO,
+        Ratio::new::<ratio>(2.0)).unwrap();
+    cascade_loop.set_setpoint(Ratio::new::<ratio>(1.0));
+    assert!(cascade_loop.is_cascade_closed());
+
+    let timestep: f64 = 0.1;
+    let mut step: usize = 0;
+    let mut run = |cascade_loop: &mut CascadeLoop, duration: f64|
+    -> Vec<CascadeSignals> {
+        let mut signals: Vec<CascadeSignals> = vec![];
+        let end_step = step + (duration/timestep).round() as usize;
+        while step < end_step {
+            let time = Time::new::<second>(step as f64 * timestep);
+            signals.push(cascade_loop.calc(time).unwrap());
+            step += 1;
+        }
+        signals
+    };
+
+    // closed cascade settles at the setpoint, the valve stays
+    // within its limits
+    let closed_signals = run(&mut cascade_loop, 200.0);
+    assert!(closed_signals.iter().all(|signals|
+            signals.secondary_output.get::<ratio>() <= 2.0 + 1e-12
+            && signals.secondary_output.get::<ratio>() >= -1e-12));
+    let last_signals = closed_signals.last().unwrap();
+    approx::assert_abs_diff_eq!(last_signals.primary_measurement.get::<ratio>(),
+        1.0, epsilon = 1e-3);
+    approx::assert_abs_diff_eq!(last_signals.secondary_setpoint.get::<ratio>(),
+        last_signals.primary_output.get::<ratio>(), epsilon = 1e-12);
+
+    // inner loop in local auto breaks the cascade, the secondary
+    // setpoint is kept, and the outer loop tracks it
+    cascade_loop.open_cascade();
+    cascade_loop.secondary_controller_mut()
+        .set_setpoint(Ratio::new::<ratio>(0.5));
+    let open_signals = run(&mut cascade_loop, 200.0);
+    for signals in open_signals.iter() {
+        assert!(!signals.cascade_closed);
+        approx::assert_abs_diff_eq!(signals.primary_output.get::<ratio>(),
+            0.5, epsilon = 1e-9);
+        approx::assert_abs_diff_eq!(signals.primary_setpoint.get::<ratio>(),
+            signals.primary_measurement.get::<ratio>(), epsilon = 1e-12);
+    }
+    approx::assert_abs_diff_eq!(cascade_loop.primary_measurement().get::<ratio>(),
+        0.5, epsilon = 1e-3);
+    assert_eq!(cascade_loop.primary_controller().mode(), ControllerMode::Manual);
+
+    // closing the cascade again, no bump in the secondary setpoint
+    cascade_loop.close_cascade();
+    assert_eq!(cascade_loop.primary_controller().mode(), ControllerMode::Auto);
+    let reclosed_signals = run(&mut cascade_loop, 1.0);
+    approx::assert_abs_diff_eq!(reclosed_signals[0].secondary_setpoint
+        .get::<ratio>(), 0.5, epsilon = 1e-3);
+
+    // the primary setpoint tracked the measurement, so the primary
+    // controller can be moved to a new setpoint from there
+    cascade_loop.set_setpoint(Ratio::new::<ratio>(0.8));
+    let last_signals = *run(&mut cascade_loop, 200.0).last().unwrap();
+    approx::assert_abs_diff_eq!(last_signals.primary_measurement.get::<ratio>(),
+        0.8, epsilon = 1e-3);
+
+    // one csv row per step
+    let file_name = std::env::temp_dir()
+        .join("test_cascade_loop").to_str().unwrap().to_owned();
+    let mut wtr = cascade_loop.spawn_writer(file_name.clone()).unwrap();
+    cascade_loop.csv_write_values(&mut wtr, last_signals).unwrap();
+    drop(wtr);
+    let contents = std::fs::read_to_string(file_name + "_cascade_loop.csv")
+        .unwrap();
+    assert_eq!(contents.lines().count(), 1);
+    assert_eq!(contents.lines().next().unwrap().split(',').count(), 8);
+}
+
+#[test]
+pub fn test_cascade_loop_proportional_secondary(){
+    use crate::alpha_nightly::transfer_fn_wrapper_and_enums::TransferFnZeroOrderHold;
+
+    // the zero order hold backend keeps the simulation cost per 
+    // timestep constant
+    let first_order = |time_constant: f64| -> TransferFn {
+        TransferFnZeroOrderHold::new_first_order(Time::ZERO, 
+            Ratio::new::<ratio>(1.0), Time::new::<second>(time_constant), 
+            Ratio::new::<ratio>(1.0)).unwrap().into()
+    };
+
+    // PI primary, P secondary with Kc = 4
+    let primary_controller = AnalogController::new_pi_controller(
+        Ratio::new::<ratio>(1.0), Time::new::<second>(20.0)).unwrap();
+    let secondary_controller: AnalogController =
+        super::ProportionalController::new(Ratio::new::<ratio>(4.0))
+        .unwrap().into();
+    let mut cascade_loop = CascadeLoop::new(primary_controller,
+        secondary_controller, first_order(20.0), first_order(1.0)).unwrap();
+    cascade_loop.set_setpoint(Ratio::new::<ratio>(1.0));
+    assert!(cascade_loop.is_cascade_closed());
+
+    let timestep: f64 = 0.1;
+    let mut last_signals = None;
+    for step in 0..4000 {
+        let time = Time::new::<second>(step as f64 * timestep);
+        last_signals = Some(cascade_loop.calc(time).unwrap());
+    }
+    let last_signals = last_signals.unwrap();
+
+    // the primary measurement has no offset, while the secondary
+    // loop keeps its proportional offset, y2 = 4/5 r2
+    approx::assert_abs_diff_eq!(last_signals.primary_measurement
+        .get::<ratio>(), 1.0, epsilon = 1e-3);
+    approx::assert_abs_diff_eq!(last_signals.secondary_setpoint
+        .get::<ratio>(), 1.25, epsilon = 1e-3);
+    approx::assert_abs_diff_eq!(last_signals.secondary_output
+        .get::<ratio>(), 1.0, epsilon = 1e-3);
+
+    // the valve can only open to 0.9, so the setpoint cannot be 
+    // reached, the remote setpoint goes to its own upper limit of 2
+    cascade_loop.set_inner_loop_output_limits(Ratio::ZERO,
+        Ratio::new::<ratio>(0.9)).unwrap();
+    cascade_loop.set_outer_loop_output_limits(Ratio::ZERO,
+        Ratio::new::<ratio>(2.0)).unwrap();
+    let mut limited_signals: Vec<CascadeSignals> = vec![];
+    for step in 4000..6000 {
+        let time = Time::new::<second>(step as f64 * timestep);
+        limited_signals.push(cascade_loop.calc(time).unwrap());
+    }
+    assert!(limited_signals.iter().all(|signals|
+            signals.secondary_output.get::<ratio>() <= 0.9));
+    let last_signals = limited_signals.last().unwrap();
+    approx::assert_abs_diff_eq!(last_signals.secondary_output
+        .get::<ratio>(), 0.9);
+    approx::assert_abs_diff_eq!(last_signals.secondary_setpoint
+        .get::<ratio>(), 2.0);
+    approx::assert_abs_diff_eq!(last_signals.primary_measurement
+        .get::<ratio>(), 0.9, epsilon = 1e-3);
+}
@@ -0,0 +1,189 @@
+use uom::si::f64::*;
+use uom::ConstZero;
+
+use super::integral_controller::OutputLimits;
+
+/// operating mode of a controller, as on a DCS faceplate
+#[derive(Debug,PartialEq, PartialOrd, Clone, Copy)]
+pub enum ControllerMode {
+    /// the operator sets the output directly,
+    /// the controller does not act on the error
+    Manual,
+    /// the controller acts on its local setpoint,
+    /// set by the operator
+    Auto,
+    /// the controller acts on a remote setpoint, usually the
+    /// output of a primary (outer loop) controller
+    Cascade,
+}
+
+/// mode, setpoints, manual output and output limits of a controller
+///
+/// every AnalogController carries one of these on its leading 
+/// part (see AnalogController), the default is auto with 
+/// everything at zero. Switching back to auto without 
+/// bumping the output is done by re-initialising the integral, 
+/// so that part needs integral action.
+#[derive(Debug,PartialEq, PartialOrd, Clone, Copy)]
+pub struct ModeSettings {
+    pub(crate) mode: ControllerMode,
+    /// output set by the operator in manual
+    pub(crate) manual_output: Ratio,
+    /// setpoint used in auto
+    pub(crate) local_setpoint: Ratio,
+    /// setpoint used in cascade
+    pub(crate) remote_setpoint: Ratio,
+    /// if true, the local setpoint follows the measurement while
+    /// in manual, so there is no error to act on when switching
+    /// back to auto
+    pub(crate) setpoint_tracking: bool,
+    /// controller output at the last timestep
+    pub(crate) last_output: Ratio,
+    /// limits on the controller output, in any mode
+    pub(crate) output_limits: Option<OutputLimits>,
+}
+
+impl Default for ModeSettings {
+    /// auto, with all setpoints and outputs at zero,
+    /// setpoint tracking on and no output limits
+    fn default() -> Self {
+        Self {
+            mode: ControllerMode::Auto,
+            manual_output: Ratio::ZERO,
+            local_setpoint: Ratio::ZERO,
+            remote_setpoint: Ratio::ZERO,
+            setpoint_tracking: true,
+            last_output: Ratio::ZERO,
+            output_limits: None,
+        }
+    }
+}
+
+impl ModeSettings {
+
+    /// setpoint the controller is acting on
+    pub(crate) fn active_setpoint(&self) -> Ratio {
+        match self.mode {
+            ControllerMode::Cascade => self.remote_setpoint,
+            ControllerMode::Manual | ControllerMode::Auto => self.local_setpoint,
+        }
+    }
+
+    /// changes mode,
+    ///
+    /// going into manual, the manual output starts at the last
+    /// controller output. Going into cascade, the remote setpoint
+    /// starts at the local setpoint, and dropping out of cascade
+    /// into auto, the local setpoint starts at the remote setpoint.
+    /// Either way, the controller carries on from where it was.
+    pub(crate) fn set_mode(&mut self, mode: ControllerMode) {
+        if mode == self.mode {
+            return;
+        }
+        match mode {
+            ControllerMode::Manual => {
+                self.manual_output = self.last_output;
+            },
+            ControllerMode::Auto => {
+                if self.mode == ControllerMode::Cascade {
+                    self.local_setpoint = self.remote_setpoint;
+                }
+            },
+            ControllerMode::Cascade => {
+                self.remote_setpoint = self.local_setpoint;
+            },
+        }
+        self.mode = mode;
+    }
+}
+
+#[test]
+pub fn test_bumpless_transfer(){
+    use uom::si::ratio::ratio;
+    use uom::si::time::second;
+    use super::AnalogController;
+
+    // PI controller, Kc = 2, tau_I = 5 s
+    let mut controller = AnalogController::new_pi_controller(
+        Ratio::new::<ratio>(2.0), Time::new::<second>(5.0)).unwrap();
+    controller.set_setpoint(Ratio::new::<ratio>(1.0));
+
+    let timestep: f64 = 0.1;
+    let mut step: usize = 0;
+    let mut run = |controller: &mut AnalogController, 
+        measurement: f64, duration: f64| -> Vec<f64> {
+        let mut outputs: Vec<f64> = vec![];
+        let end_step = step + (duration/timestep).round() as usize;
+        while step < end_step {
+            let time = Time::new::<second>(step as f64 * timestep);
+            outputs.push(controller.set_measurement_and_calc(
+                    Ratio::new::<ratio>(measurement), time).unwrap()
+                .get::<ratio>());
+            step += 1;
+        }
+        outputs
+    };
+
+    // auto, error of 1, output is 2 + 0.4 t
+    let auto_outputs = run(&mut controller, 0.0, 5.0);
+    let last_auto_output = *auto_outputs.last().unwrap();
+    approx::assert_abs_diff_eq!(last_auto_output, 2.0 + 0.4 * 4.9, 
+        epsilon = 1e-9);
+
+    // into manual, the output stays where it was
+    controller.set_mode(ControllerMode::Manual);
+    let manual_outputs = run(&mut controller, 0.3, 1.0);
+    approx::assert_abs_diff_eq!(manual_outputs[0], last_auto_output, 
+        epsilon = 1e-9);
+
+    // operator moves the output, and the setpoint tracks 
+    // the measurement
+    controller.set_manual_output(Ratio::new::<ratio>(0.5));
+    let manual_outputs = run(&mut controller, 0.3, 1.0);
+    assert!(manual_outputs.iter().all(|output| (output - 0.5).abs() < 1e-9));
+    approx::assert_abs_diff_eq!(controller.setpoint().get::<ratio>(), 0.3, 
+        epsilon = 1e-12);
+
+    // back to auto, no bump, and no error to act on
+    controller.set_mode(ControllerMode::Auto);
+    let auto_outputs = run(&mut controller, 0.3, 1.0);
+    assert!(auto_outputs.iter().all(|output| (output - 0.5).abs() < 1e-9));
+
+    // into cascade, the remote setpoint starts at the local 
+    // setpoint so the output does not jump
+    controller.set_mode(ControllerMode::Cascade);
+    approx::assert_abs_diff_eq!(controller.setpoint().get::<ratio>(), 0.3);
+    let cascade_outputs = run(&mut controller, 0.3, 1.0);
+    assert!(cascade_outputs.iter().all(|output| (output - 0.5).abs() < 1e-9));
+
+    // then the remote setpoint is used, eg. from a primary 
+    // controller
+    controller.set_remote_setpoint(Ratio::new::<ratio>(0.8));
+    approx::assert_abs_diff_eq!(controller.setpoint().get::<ratio>(), 0.8);
+    let cascade_outputs = run(&mut controller, 0.3, 1.0);
+    approx::assert_abs_diff_eq!(cascade_outputs[0], 0.5 + 2.0 * 0.5, 
+        epsilon = 1e-9);
+
+    // dropping back to auto keeps the remote setpoint
+    controller.set_mode(ControllerMode::Auto);
+    approx::assert_abs_diff_eq!(controller.setpoint().get::<ratio>(), 0.8);
+
+    // without integral action, the output is still held in manual
+    let mut p_controller: AnalogController = 
+        super::ProportionalController::new(Ratio::new::<ratio>(1.0))
+        .unwrap().into();
+    p_controller.set_setpoint(Ratio::new::<ratio>(1.0));
+    p_controller.set_mode(ControllerMode::Manual);
+    p_controller.set_manual_output(Ratio::new::<ratio>(0.4));
+    let output = p_controller.set_measurement_and_calc(
+        Ratio::new::<ratio>(0.2), Time::ZERO).unwrap();
+    assert_eq!(output.get::<ratio>(), 0.4);
+    assert_eq!(p_controller.mode(), ControllerMode::Manual);
+
+    // and in auto, it acts on the error again
+    p_controller.set_mode(ControllerMode::Auto);
+    p_controller.set_setpoint(Ratio::new::<ratio>(1.0));
+    let output = p_controller.set_measurement_and_calc(
+        Ratio::new::<ratio>(0.2), Time::new::<second>(1.0)).unwrap();
+    approx::assert_abs_diff_eq!(output.get::<ratio>(), 0.8, epsilon = 1e-9);
+}
@@ -0,0 +1,479 @@
+use csv::Writer;
+use uom::si::angular_velocity::radian_per_second;
+use uom::si::f64::*;
+use uom::si::ratio::ratio;
+use uom::si::time::second;
+use uom::ConstZero;
+
+use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
+use crate::alpha_nightly::transfer_fn_wrapper_and_enums::TransferFnTraits;
+use super::integral_controller::OutputLimits;
+
+/// how the controller output is worked out every sample
+#[derive(Debug,PartialEq, PartialOrd, Clone, Copy)]
+pub enum DigitalAlgorithm {
+    /// the whole output is worked out every sample,
+    ///
+    /// u_k = Kc e_k + I_k + D_k
+    Position,
+    /// only the change in output is worked out every sample and
+    /// added to the last output,
+    ///
+    /// u_k = u_(k-1) + delta u_k
+    ///
+    /// as the last output is the clamped one, this does not wind
+    /// up when the output is limited
+    Velocity,
+}
+
+/// how the integral and derivative terms are turned into
+/// difference equations
+#[derive(Debug,PartialEq, PartialOrd, Clone, Copy)]
+pub enum Discretisation {
+    /// s = (1 - z^(-1))/T
+    BackwardEuler,
+    /// s = 2/T (1 - z^(-1))/(1 + z^(-1))
+    Tustin,
+    /// s = omega/tan(omega T/2) (1 - z^(-1))/(1 + z^(-1))
+    ///
+    /// the digital controller then has exactly the same frequency
+    /// response as the analog one at omega, usually chosen near
+    /// the crossover frequency
+    TustinPrewarp {
+        prewarp_frequency: AngularVelocity,
+    },
+}
+
+/// sampled (discrete time) PID controller, as on a DCS which
+/// runs its control loops at a fixed scan time
+///
+/// the analog PID controller it is based on is, in ISA form,
+///
+/// G(s) = Kc (1 + 1/(tau_I s) + tau_D s/(alpha tau_D s + 1))
+///
+/// the input (error) is only read at every sample time,
+/// and the output is held in between (zero order hold),
+/// so the controller can be called with a simulation timestep
+/// smaller than the sample period.
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub struct DigitalController {
+    pub(crate) controller_gain: Ratio,
+    pub(crate) integral_time: Time,
+    pub(crate) derivative_time: Time,
+    pub(crate) alpha: Ratio,
+    /// scan time of the controller
+    pub(crate) sample_period: Time,
+    pub(crate) algorithm: DigitalAlgorithm,
+    pub(crate) discretisation: Discretisation,
+    pub(crate) output_limits: Option<OutputLimits>,
+    pub(crate) dead_time: Time,
+    /// time of the next sample, None before the first sample
+    pub(crate) next_sample_time: Option<Time>,
+    /// inputs waiting out the dead time, (time they arrive, input)
+    pub(crate) delayed_input_vec: Vec<(Time, Ratio)>,
+    pub(crate) previous_timestep_input: Ratio,
+    /// input as seen by the controller after the dead time
+    pub(crate) delayed_input: Ratio,
+    /// error at the last sample
+    pub(crate) previous_error: Ratio,
+    /// integral term at the last sample
+    pub(crate) integral_term: Ratio,
+    /// filtered derivative term at the last sample
+    pub(crate) derivative_term: Ratio,
+    /// output held between samples
+    pub(crate) held_output: Ratio,
+}
+
+impl DigitalController {
+
+    /// PID controller with a sample period,
+    ///
+    /// the derivative time can be zero for a PI controller
+    pub fn new(controller_gain: Ratio,
+        integral_time: Time,
+        derivative_time: Time,
+        alpha: Ratio,
+        sample_period: Time,
+        algorithm: DigitalAlgorithm,
+        discretisation: Discretisation,
+    ) -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        if sample_period.get::<second>() <= 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "sample period must be positive".to_owned()));
+        }
+        if integral_time.get::<second>() <= 0.0
+            || derivative_time.get::<second>() < 0.0
+            || alpha.get::<ratio>() < 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "integral time must be positive, derivative time and \
+                    alpha must not be negative".to_owned()));
+        }
+
+        let mut controller = Self {
+            controller_gain,
+            integral_time,
+            derivative_time,
+            alpha,
+            sample_period,
+            algorithm,
+            discretisation: Discretisation::BackwardEuler,
+            output_limits: None,
+            dead_time: Time::ZERO,
+            next_sample_time: None,
+            delayed_input_vec: vec![],
+            previous_timestep_input: Ratio::ZERO,
+            delayed_input: Ratio::ZERO,
+            previous_error: Ratio::ZERO,
+            integral_term: Ratio::ZERO,
+            derivative_term: Ratio::ZERO,
+            held_output: Ratio::ZERO,
+        };
+        controller.set_discretisation(discretisation)?;
+
+        Ok(controller)
+    }
+
+    /// changes the discretisation,
+    ///
+    /// for Tustin with prewarping, omega T/2 must be below pi/2
+    pub fn set_discretisation(&mut self, discretisation: Discretisation)
+    -> Result<(), ChemEngProcessControlSimulatorError> {
+        if let Discretisation::TustinPrewarp { prewarp_frequency } = discretisation {
+            let half_angle: f64 = prewarp_frequency.get::<radian_per_second>()
+                * self.sample_period.get::<second>()/2.0;
+            if half_angle <= 0.0 || half_angle >= std::f64::consts::FRAC_PI_2 {
+                return Err(ChemEngProcessControlSimulatorError::
+                    GenericStringError(
+                        "prewarp frequency must be positive and below the \
+                        Nyquist frequency".to_owned()));
+            }
+        }
+        self.discretisation = discretisation;
+        Ok(())
+    }
+
+    /// changes between position and velocity algorithms,
+    /// the output carries on from where it was
+    pub fn set_algorithm(&mut self, algorithm: DigitalAlgorithm) {
+        if algorithm == DigitalAlgorithm::Position {
+            // the integral term takes up whatever the proportional
+            // and derivative terms do not account for
+            self.integral_term = self.held_output
+                - self.controller_gain * self.previous_error
+                - self.derivative_term;
+        }
+        self.algorithm = algorithm;
+    }
+
+    /// limits the controller output,
+    ///
+    /// the position algorithm stops integrating when the output is
+    /// at a limit (conditional integration), the velocity algorithm
+    /// does not wind up in the first place
+    pub fn set_output_limits(&mut self, lower_limit: Ratio,
+        upper_limit: Ratio) -> Result<(), ChemEngProcessControlSimulatorError> {
+        self.output_limits = Some(OutputLimits::new(lower_limit, upper_limit)?);
+        Ok(())
+    }
+
+    /// removes the output limits
+    pub fn remove_output_limits(&mut self) {
+        self.output_limits = None;
+    }
+
+    pub fn sample_period(&self) -> Time {
+        self.sample_period
+    }
+
+    pub fn algorithm(&self) -> DigitalAlgorithm {
+        self.algorithm
+    }
+
+    pub fn discretisation(&self) -> Discretisation {
+        self.discretisation
+    }
+
+    /// output held since the last sample
+    pub fn held_output(&self) -> Ratio {
+        self.held_output
+    }
+
+    /// the constant in s = c (1 - z^(-1))/(1 + z^(-1)) for the
+    /// Tustin methods
+    fn tustin_constant(&self) -> f64 {
+        let sample_period: f64 = self.sample_period.get::<second>();
+        match self.discretisation {
+            Discretisation::TustinPrewarp { prewarp_frequency } => {
+                let omega: f64 = prewarp_frequency.get::<radian_per_second>();
+                omega/(omega * sample_period/2.0).tan()
+            },
+            _ => 2.0/sample_period,
+        }
+    }
+
+    /// (change in integral term, new derivative term)
+    /// for the error at this sample
+    ///
+    /// backward Euler:
+    ///
+    /// I_k = I_(k-1) + Kc T/tau_I e_k
+    ///
+    /// D_k = (alpha tau_D D_(k-1) + Kc tau_D (e_k - e_(k-1)))/(alpha tau_D + T)
+    ///
+    /// Tustin (c = 2/T, or omega/tan(omega T/2) when prewarped):
+    ///
+    /// I_k = I_(k-1) + Kc/(c tau_I) (e_k + e_(k-1))
+    ///
+    /// D_k = ((alpha tau_D c - 1) D_(k-1) + Kc tau_D c (e_k - e_(k-1)))
+    /// /(alpha tau_D c + 1)
+    fn integral_and_derivative_update(&self, error: f64) -> (f64, f64) {
+        let controller_gain: f64 = self.controller_gain.get::<ratio>();
+        let integral_time: f64 = self.integral_time.get::<second>();
+        let derivative_time: f64 = self.derivative_time.get::<second>();
+        let filter_time: f64 = self.alpha.get::<ratio>() * derivative_time;
+        let sample_period: f64 = self.sample_period.get::<second>();
+        let previous_error: f64 = self.previous_error.get::<ratio>();
+        let previous_derivative: f64 = self.derivative_term.get::<ratio>();
+
+        if let Discretisation::BackwardEuler = self.discretisation {
+            let integral_change = controller_gain * sample_period/integral_time
+                * error;
+            let derivative_term = if derivative_time == 0.0 {
+                0.0
+            } else {
+                (filter_time * previous_derivative
+                    + controller_gain * derivative_time * (error - previous_error))
+                    /(filter_time + sample_period)
+            };
+            return (integral_change, derivative_term);
+        }
+
+        let tustin_constant = self.tustin_constant();
+        let integral_change = controller_gain/(tustin_constant * integral_time)
+            * (error + previous_error);
+        let derivative_term = if derivative_time == 0.0 {
+            0.0
+        } else {
+            ((filter_time * tustin_constant - 1.0) * previous_derivative
+                + controller_gain * derivative_time * tustin_constant
+                * (error - previous_error))
+                /(filter_time * tustin_constant + 1.0)
+        };
+        (integral_change, derivative_term)
+    }
+
+    /// works out a new output from the error at a sample time
+    fn sample(&mut self, error: Ratio) {
+        let (integral_change, derivative_term) =
+            self.integral_and_derivative_update(error.get::<ratio>());
+        let integral_change = Ratio::new::<ratio>(integral_change);
+        let derivative_term = Ratio::new::<ratio>(derivative_term);
+
+        let output = match self.algorithm {
+            DigitalAlgorithm::Position => {
+                let proportional_term = self.controller_gain * error;
+                let unlimited_output = proportional_term
+                    + self.integral_term + integral_change + derivative_term;
+
+                // conditional integration, only integrate if it
+                // does not push the output further past a limit
+                let winding_up = match self.output_limits {
+                    Some(limits) => {
+                        (unlimited_output > limits.upper_limit
+                            && integral_change > Ratio::ZERO)
+                        || (unlimited_output < limits.lower_limit
+                            && integral_change < Ratio::ZERO)
+                    },
+                    None => false,
+                };
+                if !winding_up {
+                    self.integral_term += integral_change;
+                }
+                proportional_term + self.integral_term + derivative_term
+            },
+            DigitalAlgorithm::Velocity => {
+                self.held_output
+                    + self.controller_gain * (error - self.previous_error)
+                    + integral_change
+                    + (derivative_term - self.derivative_term)
+            },
+        };
+
+        self.held_output = match self.output_limits {
+            Some(limits) => limits.clamp(output),
+            None => output,
+        };
+        self.previous_error = error;
+        self.derivative_term = derivative_term;
+    }
+}
+
+impl TransferFnTraits for DigitalController {
+    fn set_dead_time(&mut self, dead_time: Time) {
+        self.dead_time = dead_time;
+    }
+
+    /// takes in the error at any time, but only acts on it
+    /// at sample times, in between the output is held
+    fn set_user_input_and_calc(&mut self,
+        user_input: Ratio,
+        time_of_input: Time) -> Result<Ratio,
+    ChemEngProcessControlSimulatorError> {
+
+        // case where input is not the same to 9 decimal places
+        let input_changed: bool =
+            (user_input.get::<ratio>() * 1e9).round()
+            - (self.previous_timestep_input
+                .get::<ratio>()*1e9).round() != 0.0 ;
+
+        if input_changed {
+            self.delayed_input_vec.push((time_of_input + self.dead_time,
+                    user_input));
+            self.previous_timestep_input = user_input;
+        }
+
+        while let Some(&(arrival_time, input)) = self.delayed_input_vec.first() {
+            if arrival_time > time_of_input {
+                break;
+            }
+            self.delayed_input = input;
+            self.delayed_input_vec.remove(0);
+        }
+
+        // small tolerance so that floating point error in the
+        // simulation time does not miss a sample
+        let tolerance = self.sample_period * 1e-6;
+        let mut next_sample_time = self.next_sample_time
+            .unwrap_or(time_of_input);
+
+        // if the simulation timestep is longer than the sample
+        // period, every missed sample sees the same input
+        while time_of_input + tolerance >= next_sample_time {
+            self.sample(self.delayed_input);
+            next_sample_time += self.sample_period;
+        }
+        self.next_sample_time = Some(next_sample_time);
+
+        Ok(self.held_output)
+    }
+
+    fn spawn_writer(&mut self, name: String) -> Result<Writer<std::fs::File>,
+    ChemEngProcessControlSimulatorError> {
+        let title_string: String = name + "_digital_controller.csv";
+        let wtr = Writer::from_path(title_string)?;
+        Ok(wtr)
+    }
+
+    fn csv_write_values(&mut self,
+        wtr: &mut Writer<std::fs::File>,
+        time: Time,
+        input: Ratio,
+        output: Ratio) -> Result<(),
+    ChemEngProcessControlSimulatorError> {
+        let current_time_string = time.get::<second>().to_string();
+        let input_string = input.get::<ratio>().to_string();
+        let output_string = output.get::<ratio>().to_string();
+
+        wtr.write_record(&[current_time_string,
+            input_string,
+            output_string])?;
+
+        wtr.flush().unwrap();
+        Ok(())
+    }
+}
+
+#[test]
+pub fn test_digital_controller(){
+    let controller_gain = Ratio::new::<ratio>(2.0);
+    let integral_time = Time::new::<second>(5.0);
+    let derivative_time = Time::new::<second>(1.0);
+    let alpha = Ratio::new::<ratio>(0.1);
+    let sample_period = Time::new::<second>(0.5);
+
+    // backward Euler PI, unit error from t = 0,
+    // at the kth sample u = Kc + Kc T/tau_I (k + 1)
+    let mut pi_controller = DigitalController::new(controller_gain,
+        integral_time, Time::ZERO, alpha, sample_period,
+        DigitalAlgorithm::Position, Discretisation::BackwardEuler).unwrap();
+    for step in 0..30 {
+        let time = Time::new::<second>(step as f64 * 0.1);
+        let output = pi_controller.set_user_input_and_calc(
+            Ratio::new::<ratio>(1.0), time).unwrap();
+        // the output is held between samples
+        let samples_taken = (step/5 + 1) as f64;
+        approx::assert_abs_diff_eq!(output.get::<ratio>(),
+            2.0 + 2.0 * 0.5/5.0 * samples_taken, epsilon = 1e-9);
+    }
+
+    // without limits, position and velocity algorithms agree for
+    // every discretisation
+    let discretisations = [Discretisation::BackwardEuler,
+        Discretisation::Tustin,
+        Discretisation::TustinPrewarp {
+            prewarp_frequency: AngularVelocity::new::<radian_per_second>(1.0)
+        }];
+    for discretisation in discretisations {
+        let mut position = DigitalController::new(controller_gain,
+            integral_time, derivative_time, alpha, sample_period,
+            DigitalAlgorithm::Position, discretisation).unwrap();
+        let mut velocity = position.clone();
+        velocity.set_algorithm(DigitalAlgorithm::Velocity);
+
+        for step in 0..100 {
+            let time = Time::new::<second>(step as f64 * 0.1);
+            let error = Ratio::new::<ratio>((0.3 * step as f64).sin());
+            let position_output = position.set_user_input_and_calc(
+                error, time).unwrap();
+            let velocity_output = velocity.set_user_input_and_calc(
+                error, time).unwrap();
+            approx::assert_abs_diff_eq!(position_output.get::<ratio>(),
+                velocity_output.get::<ratio>(), epsilon = 1e-9);
+        }
+    }
+
+    // prewarping at a low frequency is almost the same as Tustin
+    let mut tustin = DigitalController::new(controller_gain,
+        integral_time, derivative_time, alpha, sample_period,
+        DigitalAlgorithm::Position, Discretisation::Tustin).unwrap();
+    let mut prewarped = DigitalController::new(controller_gain,
+        integral_time, derivative_time, alpha, sample_period,
+        DigitalAlgorithm::Position, Discretisation::TustinPrewarp {
+            prewarp_frequency: AngularVelocity::new::<radian_per_second>(1e-3)
+        }).unwrap();
+    for step in 0..20 {
+        let time = Time::new::<second>(step as f64 * 0.5);
+        let error = Ratio::new::<ratio>(1.0);
+        approx::assert_abs_diff_eq!(
+            tustin.set_user_input_and_calc(error, time).unwrap().get::<ratio>(),
+            prewarped.set_user_input_and_calc(error, time).unwrap().get::<ratio>(),
+            epsilon = 1e-6);
+    }
+
+    // with the output limited, the velocity algorithm comes off the
+    // limit as soon as the error changes sign
+    let mut velocity = DigitalController::new(controller_gain,
+        integral_time, Time::ZERO, alpha, sample_period,
+        DigitalAlgorithm::Velocity, Discretisation::BackwardEuler).unwrap();
+    velocity.set_output_limits(Ratio::new::<ratio>(0.0),
+        Ratio::new::<ratio>(1.0)).unwrap();
+    for step in 0..100 {
+        let time = Time::new::<second>(step as f64 * 0.5);
+        let output = velocity.set_user_input_and_calc(
+            Ratio::new::<ratio>(1.0), time).unwrap();
+        approx::assert_abs_diff_eq!(output.get::<ratio>(), 1.0);
+    }
+    let output = velocity.set_user_input_and_calc(
+        Ratio::new::<ratio>(-0.1), Time::new::<second>(50.0)).unwrap();
+    assert!(output.get::<ratio>() < 1.0);
+
+    // prewarping above the Nyquist frequency is not allowed
+    assert!(DigitalController::new(controller_gain,
+        integral_time, derivative_time, alpha, sample_period,
+        DigitalAlgorithm::Position, Discretisation::TustinPrewarp {
+            prewarp_frequency: AngularVelocity::new::<radian_per_second>(10.0)
+        }).is_err());
+}
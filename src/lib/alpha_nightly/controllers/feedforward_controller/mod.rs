@@ -0,0 +1,297 @@
+use csv::Writer;
+use uom::si::f64::*;
+use uom::si::ratio::ratio;
+use uom::si::time::second;
+use uom::ConstZero;
+
+use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
+use crate::alpha_nightly::transfer_fn_wrapper_and_enums::{TransferFn,
+    TransferFnFirstOrder, TransferFnTraits};
+
+/// measured disturbance feedforward,
+///
+/// G_ff(s) = K_ff (tau_lead s + 1)/(tau_lag s + 1) exp(-theta_ff s)
+///
+/// it takes in the measured disturbance and gives out a
+/// correction to add to the feedback controller output.
+///
+/// ideal feedforward is G_ff = -G_d/G_p, where G_d is the
+/// disturbance model and G_p the process model. from_models
+/// works out the static gain, lead-lag and dead time from those.
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub struct FeedforwardController {
+    pub(crate) gain: Ratio,
+    pub(crate) lead_time: Time,
+    pub(crate) lag_time: Time,
+    pub(crate) dead_time: Time,
+    pub(crate) lead_lag: TransferFnFirstOrder,
+}
+
+impl FeedforwardController {
+
+    /// the lag time must be positive, the lead time and dead time
+    /// must not be negative
+    ///
+    /// lead time equal to lag time gives a static gain
+    pub fn new(gain: Ratio,
+        lead_time: Time,
+        lag_time: Time,
+        dead_time: Time) -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        if lag_time.get::<second>() <= 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "feedforward lag time must be positive".to_owned()));
+        }
+        if lead_time.get::<second>() < 0.0 || dead_time.get::<second>() < 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "feedforward lead time and dead time must not be negative"
+                    .to_owned()));
+        }
+
+        // (K tau_lead s + K)/(tau_lag s + 1)
+        let mut lead_lag = TransferFnFirstOrder::new(gain * lead_time,
+            gain, lag_time, Ratio::new::<ratio>(1.0))?;
+        lead_lag.set_dead_time(dead_time);
+
+        Ok(Self { gain, lead_time, lag_time, dead_time, lead_lag })
+    }
+
+    /// designs G_ff = -G_d/G_p from the disturbance and process models
+    ///
+    /// each model is reduced to K exp(-theta s)/(tau s + 1), with
+    /// tau from the first order coefficients of the numerator and
+    /// denominator, tau = d1/d0 - n1/n0 (which matches the mean
+    /// residence time). This is exact for first order models.
+    /// Then
+    ///
+    /// K_ff = -K_d/K_p, tau_lead = tau_p, tau_lag = tau_d,
+    /// theta_ff = theta_d - theta_p
+    ///
+    /// if the disturbance gets to the output faster than the
+    /// manipulated variable does (theta_d < theta_p), ideal
+    /// feedforward would need a prediction, so the dead time is
+    /// set to zero. Both models need a finite, nonzero steady
+    /// state gain.
+    pub fn from_models(disturbance_model: &TransferFn,
+        process_model: &TransferFn) -> Result<Self,
+    ChemEngProcessControlSimulatorError> {
+
+        let (disturbance_gain, disturbance_time_constant) =
+            first_order_equivalent(disturbance_model)?;
+        let (process_gain, process_time_constant) =
+            first_order_equivalent(process_model)?;
+
+        if process_gain.get::<ratio>() == 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "process model has zero steady state gain".to_owned()));
+        }
+        if disturbance_time_constant.get::<second>() <= 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "disturbance model has no lag to use for the \
+                    feedforward lag, use new instead".to_owned()));
+        }
+
+        let dead_time = (disturbance_model.dead_time()
+            - process_model.dead_time()).max(Time::ZERO);
+
+        Self::new(-disturbance_gain/process_gain,
+            process_time_constant.max(Time::ZERO),
+            disturbance_time_constant,
+            dead_time)
+    }
+
+    /// static gain K_ff
+    pub fn gain(&self) -> Ratio {
+        self.gain
+    }
+
+    pub fn lead_time(&self) -> Time {
+        self.lead_time
+    }
+
+    pub fn lag_time(&self) -> Time {
+        self.lag_time
+    }
+
+    pub fn dead_time(&self) -> Time {
+        self.dead_time
+    }
+
+    /// G_ff(s) as a transfer function, dead time included
+    pub fn transfer_fn(&self) -> TransferFn {
+        self.lead_lag.clone().into()
+    }
+
+    /// feedforward plus feedback, eg. from an AnalogController
+    /// acting on the error,
+    ///
+    /// u = u_fb + G_ff d
+    pub fn set_disturbance_and_feedback_and_calc(&mut self,
+        measured_disturbance: Ratio,
+        feedback_output: Ratio,
+        time_of_input: Time) -> Result<Ratio,
+    ChemEngProcessControlSimulatorError> {
+        let feedforward_output = self.set_user_input_and_calc(
+            measured_disturbance, time_of_input)?;
+        Ok(feedback_output + feedforward_output)
+    }
+}
+
+/// (K, tau) of K exp(-theta s)/(tau s + 1) for the rational part
+/// of a transfer function
+fn first_order_equivalent(transfer_fn: &TransferFn) -> Result<(Ratio, Time),
+ChemEngProcessControlSimulatorError> {
+
+    let steady_state_gain = transfer_fn.steady_state_gain()?;
+
+    // polynomials are stored highest power first
+    let first_order_coefficient = |polynomial: &[f64]| -> f64 {
+        if polynomial.len() >= 2 {
+            polynomial[polynomial.len() - 2]
+        } else {
+            0.0
+        }
+    };
+    let numerator = transfer_fn.numerator();
+    let denominator = transfer_fn.denominator();
+    let numerator_constant = *numerator.last().unwrap_or(&0.0);
+    let denominator_constant = *denominator.last().unwrap_or(&0.0);
+
+    let mut time_constant = first_order_coefficient(&denominator)
+        /denominator_constant;
+    if numerator_constant != 0.0 {
+        time_constant -= first_order_coefficient(&numerator)/numerator_constant;
+    }
+
+    Ok((steady_state_gain, Time::new::<second>(time_constant)))
+}
+
+impl TransferFnTraits for FeedforwardController {
+    /// sets theta_ff
+    fn set_dead_time(&mut self, dead_time: Time) {
+        self.dead_time = dead_time;
+        self.lead_lag.set_dead_time(dead_time);
+    }
+
+    /// user input is the measured disturbance (in deviation
+    /// variables), the output is the feedforward correction
+    fn set_user_input_and_calc(&mut self,
+        user_input: Ratio,
+        time_of_input: Time) -> Result<Ratio,
+    ChemEngProcessControlSimulatorError> {
+        self.lead_lag.set_user_input_and_calc(user_input, time_of_input)
+    }
+
+    fn spawn_writer(&mut self, name: String) -> Result<Writer<std::fs::File>,
+    ChemEngProcessControlSimulatorError> {
+        let title_string: String = name + "_feedforward_controller.csv";
+        let wtr = Writer::from_path(title_string)?;
+        Ok(wtr)
+    }
+
+    fn csv_write_values(&mut self,
+        wtr: &mut Writer<std::fs::File>,
+        time: Time,
+        input: Ratio,
+        output: Ratio) -> Result<(),
+    ChemEngProcessControlSimulatorError> {
+        let current_time_string = time.get::<second>().to_string();
+        let input_string = input.get::<ratio>().to_string();
+        let output_string = output.get::<ratio>().to_string();
+
+        wtr.write_record(&[current_time_string,
+            input_string,
+            output_string])?;
+
+        wtr.flush().unwrap();
+        Ok(())
+    }
+}
+
+#[test]
+pub fn test_feedforward_controller(){
+    use super::AnalogController;
+
+    let first_order = |gain: f64, time_constant: f64, dead_time: f64|
+    -> TransferFnFirstOrder {
+        let mut transfer_fn = TransferFnFirstOrder::new(Time::ZERO,
+            Ratio::new::<ratio>(gain), Time::new::<second>(time_constant),
+            Ratio::new::<ratio>(1.0)).unwrap();
+        transfer_fn.set_dead_time(Time::new::<second>(dead_time));
+        transfer_fn
+    };
+
+    // G_d = 2 exp(-5 s)/(10 s + 1), G_p = exp(-2 s)/(5 s + 1)
+    // G_ff = -2 (5 s + 1)/(10 s + 1) exp(-3 s)
+    let mut disturbance_model = first_order(2.0, 10.0, 5.0);
+    let mut process_model = first_order(1.0, 5.0, 2.0);
+    let mut feedforward_controller = FeedforwardController::from_models(
+        &disturbance_model.clone().into(), &process_model.clone().into())
+        .unwrap();
+    approx::assert_abs_diff_eq!(feedforward_controller.gain().get::<ratio>(),
+        -2.0, epsilon = 1e-12);
+    approx::assert_abs_diff_eq!(feedforward_controller.lead_time()
+        .get::<second>(), 5.0, epsilon = 1e-12);
+    approx::assert_abs_diff_eq!(feedforward_controller.lag_time()
+        .get::<second>(), 10.0, epsilon = 1e-12);
+    approx::assert_abs_diff_eq!(feedforward_controller.dead_time()
+        .get::<second>(), 3.0, epsilon = 1e-12);
+    approx::assert_abs_diff_eq!(feedforward_controller.transfer_fn()
+        .steady_state_gain().unwrap().get::<ratio>(), -2.0, epsilon = 1e-12);
+
+    // feedback alone for comparison
+    let mut feedback_only_disturbance_model = disturbance_model.clone();
+    let mut feedback_only_process_model = process_model.clone();
+    let mut feedback_only_controller = AnalogController::new_pi_controller(
+        Ratio::new::<ratio>(1.0), Time::new::<second>(5.0)).unwrap();
+
+    // unit step in the measured disturbance, the feedforward
+    // cancels it so the feedback controller has nothing to do
+    let mut feedback_controller = AnalogController::new_pi_controller(
+        Ratio::new::<ratio>(1.0), Time::new::<second>(5.0)).unwrap();
+    let disturbance = Ratio::new::<ratio>(1.0);
+    let timestep: f64 = 0.1;
+    let mut output = Ratio::ZERO;
+    let mut feedback_only_output = Ratio::ZERO;
+    let mut largest_deviation: f64 = 0.0;
+    let mut largest_feedback_only_deviation: f64 = 0.0;
+    for step in 0..=600 {
+        let time = Time::new::<second>(step as f64 * timestep);
+
+        let feedback_output = feedback_controller
+            .set_user_input_and_calc(-output, time).unwrap();
+        let manipulated_variable = feedforward_controller
+            .set_disturbance_and_feedback_and_calc(disturbance,
+                feedback_output, time).unwrap();
+        output = process_model.set_user_input_and_calc(
+            manipulated_variable, time).unwrap()
+            + disturbance_model.set_user_input_and_calc(
+                disturbance, time).unwrap();
+        largest_deviation = largest_deviation.max(output.get::<ratio>().abs());
+
+        let manipulated_variable = feedback_only_controller
+            .set_user_input_and_calc(-feedback_only_output, time).unwrap();
+        feedback_only_output = feedback_only_process_model
+            .set_user_input_and_calc(manipulated_variable, time).unwrap()
+            + feedback_only_disturbance_model.set_user_input_and_calc(
+                disturbance, time).unwrap();
+        largest_feedback_only_deviation = largest_feedback_only_deviation
+            .max(feedback_only_output.get::<ratio>().abs());
+    }
+    assert!(largest_deviation < 0.05);
+    assert!(largest_feedback_only_deviation > 0.5);
+
+    // disturbance faster than the process, no dead time
+    let faster_disturbance: TransferFn = first_order(2.0, 10.0, 0.0).into();
+    let slow_process: TransferFn = first_order(1.0, 5.0, 2.0).into();
+    let feedforward_controller = FeedforwardController::from_models(
+        &faster_disturbance, &slow_process).unwrap();
+    assert_eq!(feedforward_controller.dead_time(), Time::ZERO);
+
+    assert!(FeedforwardController::new(Ratio::new::<ratio>(1.0),
+            Time::ZERO, Time::ZERO, Time::ZERO).is_err());
+}
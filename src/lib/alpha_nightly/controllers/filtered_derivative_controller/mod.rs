@@ -5,7 +5,7 @@ use uom::si::ratio::ratio;
 use uom::si::time::second;
 use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
 
-use super::AnalogController;
+use super::{AnalogController, ModeSettings};
 
 /// a filtered derivative controller 
 /// in the form:
@@ -20,6 +20,12 @@ use super::AnalogController;
 #[derive(Debug,PartialEq, PartialOrd, Clone)]
 pub struct FilteredDerivativeController{
     pub transfer_fn: TransferFnFirstOrder,
+    pub(crate) controller_gain: Ratio,
+    pub(crate) derivative_time: Time,
+    /// the filter time is alpha tau_d
+    pub(crate) alpha: Ratio,
+    /// mode settings, used when this is a standalone controller
+    pub(crate) mode_settings: ModeSettings,
 }
 
 impl FilteredDerivativeController {
@@ -43,7 +49,79 @@ impl FilteredDerivativeController {
         let a2 = derivative_time * alpha;
         let transfer_fn = TransferFnFirstOrder::new(a1, b1, a2, b2).unwrap();
 
-        Ok(Self { transfer_fn })
+        Ok(Self { transfer_fn, controller_gain, derivative_time, alpha,
+            mode_settings: ModeSettings::default() })
+    }
+
+    /// changes K_c, tau_d and alpha at the given time
+    ///
+    /// the filter keeps its state and the output is rescaled as 
+    /// if the new gain had always been in place, so a steady 
+    /// input still gives zero derivative action
+    ///
+    /// a tau_d of zero switches the derivative action off, 
+    /// as in new, where this is a zero gain
+    pub(crate) fn retune(&mut self, controller_gain: Ratio,
+        derivative_time: Time,
+        alpha: Ratio,
+        time: Time) -> Result<(), ChemEngProcessControlSimulatorError> {
+
+        if derivative_time.get::<second>() == 0.0 {
+            // a fresh (0 s + 0)/(s + 1), so nothing is left in 
+            // the filter
+            let dead_time = self.transfer_fn.dead_time();
+            self.transfer_fn = TransferFnFirstOrder::new(Time::ZERO, 
+                Ratio::ZERO, Time::ZERO, Ratio::new::<ratio>(1.0))?;
+            self.transfer_fn.set_dead_time(dead_time);
+            self.controller_gain = controller_gain;
+            self.derivative_time = derivative_time;
+            self.alpha = alpha;
+            return Ok(());
+        }
+
+        // G(s) = K_c/alpha - (K_c/alpha)/(alpha tau_d s + 1)
+        let filter_time = derivative_time * alpha;
+        let feedthrough_gain = controller_gain/alpha;
+        let previous_feedthrough_gain = match &self.transfer_fn {
+            TransferFnFirstOrder::Stable(_, for_zeroes) => {
+                for_zeroes.process_gain
+            },
+            _ => {
+                return Err(ChemEngProcessControlSimulatorError::
+                    WrongTransferFnType);
+            },
+        };
+
+        if previous_feedthrough_gain.get::<ratio>() == 0.0 {
+            // derivative action was off, so the filter starts out 
+            // settled at the current input, and a steady input 
+            // still gives no derivative kick
+            self.transfer_fn.retune_parameters(Ratio::ZERO,
+                feedthrough_gain, filter_time, time)?;
+            self.transfer_fn.settle(time)?;
+        } else {
+            self.transfer_fn.retune_parameters(Ratio::ZERO,
+                previous_feedthrough_gain, filter_time, time)?;
+            self.transfer_fn.scale_gain((feedthrough_gain
+                    /previous_feedthrough_gain).get::<ratio>())?;
+        }
+
+        self.controller_gain = controller_gain;
+        self.derivative_time = derivative_time;
+        self.alpha = alpha;
+        Ok(())
+    }
+
+    pub fn controller_gain(&self) -> Ratio {
+        self.controller_gain
+    }
+
+    pub fn derivative_time(&self) -> Time {
+        self.derivative_time
+    }
+
+    pub fn alpha(&self) -> Ratio {
+        self.alpha
     }
 }
 
@@ -64,7 +142,13 @@ impl Default for FilteredDerivativeController {
         let a2 = Time::new::<second>(0.1);
         let transfer_fn = TransferFnFirstOrder::new(a1, b1, a2, b2).unwrap();
 
-        return Self { transfer_fn };
+        return Self { 
+            transfer_fn,
+            controller_gain: Ratio::new::<ratio>(1.0),
+            derivative_time: a1,
+            alpha: Ratio::new::<ratio>(0.1),
+            mode_settings: ModeSettings::default(),
+        };
 
     }
 }
@@ -0,0 +1,342 @@
+use csv::Writer;
+use uom::si::f64::*;
+use uom::si::ratio::ratio;
+use uom::si::time::second;
+use uom::ConstZero;
+
+use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
+use crate::alpha_nightly::transfer_fn_wrapper_and_enums::TransferFnTraits;
+use super::AnalogController;
+
+/// one row of a gain schedule, the ISA PID parameters to use
+/// when the scheduling variable is at this value
+#[derive(Debug,PartialEq, PartialOrd, Clone, Copy)]
+pub struct ScheduleBreakpoint {
+    pub scheduling_variable: Ratio,
+    pub controller_gain: Ratio,
+    pub integral_time: Time,
+    pub derivative_time: Time,
+}
+
+impl ScheduleBreakpoint {
+    pub fn new(scheduling_variable: Ratio,
+        controller_gain: Ratio,
+        integral_time: Time,
+        derivative_time: Time) -> Self {
+        Self {
+            scheduling_variable,
+            controller_gain,
+            integral_time,
+            derivative_time,
+        }
+    }
+}
+
+/// gain scheduled PID controller
+///
+/// K_c, tau_I and tau_D are interpolated linearly from a table of
+/// breakpoints using a scheduling signal, eg. throughput or
+/// valve position for a process whose gain changes with operating
+/// point. Outside the table, the end breakpoints are used.
+///
+/// when the tuning changes, the controller is retuned in place 
+/// (see AnalogController::retune) rather than rebuilt, so the 
+/// output does not bump when the tuning changes.
+///
+/// it takes in the error and gives out the controller output,
+/// just like an AnalogController
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub struct GainScheduledController {
+    /// sorted by scheduling variable
+    pub(crate) breakpoints: Vec<ScheduleBreakpoint>,
+    pub(crate) controller: AnalogController,
+    /// derivative filter time is alpha tau_D
+    pub(crate) alpha: Ratio,
+    pub(crate) scheduling_variable: Ratio,
+    /// (K_c, tau_I, tau_D) in use
+    pub(crate) tuning: (Ratio, Time, Time),
+}
+
+impl GainScheduledController {
+
+    /// the breakpoints need distinct scheduling variables,
+    /// positive integral times and controller gains of the same
+    /// sign. Either every derivative time is zero (PI control) or
+    /// every derivative time is positive (filtered PID).
+    ///
+    /// the controller starts on the tuning of the first
+    /// breakpoint, until set_scheduling_variable is called
+    pub fn new(mut breakpoints: Vec<ScheduleBreakpoint>,
+        alpha: Ratio) -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        if breakpoints.is_empty() {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "gain schedule needs at least one breakpoint".to_owned()));
+        }
+        breakpoints.sort_by(|first, other| first.scheduling_variable
+            .get::<ratio>().total_cmp(&other.scheduling_variable
+                .get::<ratio>()));
+
+        let repeated_breakpoint = breakpoints.windows(2)
+            .any(|pair| pair[0].scheduling_variable
+                == pair[1].scheduling_variable);
+        if repeated_breakpoint {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "gain schedule breakpoints must have distinct \
+                    scheduling variables".to_owned()));
+        }
+        if breakpoints.iter().any(|breakpoint|
+            breakpoint.integral_time.get::<second>() <= 0.0) {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "integral time must be positive".to_owned()));
+        }
+
+        let first_gain: f64 = breakpoints[0].controller_gain.get::<ratio>();
+        let same_sign_gains = breakpoints.iter().all(|breakpoint| {
+            breakpoint.controller_gain.get::<ratio>() * first_gain > 0.0
+        });
+        if !same_sign_gains {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "controller gains must be nonzero and of the same sign"
+                    .to_owned()));
+        }
+
+        let pi_control = breakpoints.iter().all(|breakpoint|
+            breakpoint.derivative_time == Time::ZERO);
+        let pid_control = breakpoints.iter().all(|breakpoint|
+            breakpoint.derivative_time.get::<second>() > 0.0);
+        if !pi_control && !pid_control {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "derivative times must all be zero or all be positive"
+                    .to_owned()));
+        }
+        if pid_control && alpha.get::<ratio>() <= 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "derivative filter alpha must be positive".to_owned()));
+        }
+
+        let first_breakpoint = breakpoints[0];
+        let controller = if pi_control {
+            AnalogController::new_pi_controller(
+                first_breakpoint.controller_gain,
+                first_breakpoint.integral_time)?
+        } else {
+            AnalogController::new_filtered_pid_controller(
+                first_breakpoint.controller_gain,
+                first_breakpoint.integral_time,
+                first_breakpoint.derivative_time,
+                alpha)?
+        };
+
+        Ok(Self {
+            breakpoints,
+            controller,
+            alpha,
+            scheduling_variable: first_breakpoint.scheduling_variable,
+            tuning: (first_breakpoint.controller_gain,
+                first_breakpoint.integral_time,
+                first_breakpoint.derivative_time),
+        })
+    }
+
+    /// sets the scheduling signal, the new tuning is put in at
+    /// the next call to set_user_input_and_calc
+    pub fn set_scheduling_variable(&mut self, scheduling_variable: Ratio) {
+        self.scheduling_variable = scheduling_variable;
+    }
+
+    pub fn scheduling_variable(&self) -> Ratio {
+        self.scheduling_variable
+    }
+
+    /// (K_c, tau_I, tau_D) interpolated at the current value of
+    /// the scheduling variable
+    pub fn scheduled_tuning(&self) -> (Ratio, Time, Time) {
+        let scheduling_variable = self.scheduling_variable;
+        let first_breakpoint = self.breakpoints[0];
+        let last_breakpoint = self.breakpoints[self.breakpoints.len() - 1];
+
+        let tuning_at = |breakpoint: ScheduleBreakpoint| {
+            (breakpoint.controller_gain, breakpoint.integral_time,
+                breakpoint.derivative_time)
+        };
+        if scheduling_variable <= first_breakpoint.scheduling_variable {
+            return tuning_at(first_breakpoint);
+        }
+        if scheduling_variable >= last_breakpoint.scheduling_variable {
+            return tuning_at(last_breakpoint);
+        }
+
+        let upper_index = self.breakpoints.iter()
+            .position(|breakpoint| breakpoint.scheduling_variable
+                > scheduling_variable)
+            .unwrap_or(self.breakpoints.len() - 1);
+        let lower = self.breakpoints[upper_index - 1];
+        let upper = self.breakpoints[upper_index];
+        let fraction: f64 = ((scheduling_variable - lower.scheduling_variable)
+            /(upper.scheduling_variable - lower.scheduling_variable))
+            .get::<ratio>();
+
+        (lower.controller_gain
+            + (upper.controller_gain - lower.controller_gain) * fraction,
+        lower.integral_time
+            + (upper.integral_time - lower.integral_time) * fraction,
+        lower.derivative_time
+            + (upper.derivative_time - lower.derivative_time) * fraction)
+    }
+
+    /// (K_c, tau_I, tau_D) the controller is running on
+    pub fn current_tuning(&self) -> (Ratio, Time, Time) {
+        self.tuning
+    }
+
+    pub fn breakpoints(&self) -> &[ScheduleBreakpoint] {
+        &self.breakpoints
+    }
+
+    /// the underlying PI or PID controller, eg. to change its
+    /// limits or mode
+    pub fn controller_mut(&mut self) -> &mut AnalogController {
+        &mut self.controller
+    }
+
+    pub fn controller(&self) -> &AnalogController {
+        &self.controller
+    }
+
+    /// puts in new tuning at the given time, see 
+    /// AnalogController::retune
+    fn retune(&mut self, tuning: (Ratio, Time, Time),
+        time: Time) -> Result<(), ChemEngProcessControlSimulatorError> {
+        let (controller_gain, integral_time, derivative_time) = tuning;
+        self.controller.retune(controller_gain, integral_time, 
+            derivative_time, time)?;
+        self.tuning = tuning;
+        Ok(())
+    }
+}
+
+impl TransferFnTraits for GainScheduledController {
+    fn set_dead_time(&mut self, dead_time: Time) {
+        self.controller.set_dead_time(dead_time)
+    }
+
+    /// user input is the error, r - y, the tuning is updated
+    /// from the scheduling variable first
+    fn set_user_input_and_calc(&mut self,
+        user_input: Ratio,
+        time_of_input: Time) -> Result<Ratio,
+    ChemEngProcessControlSimulatorError> {
+
+        let tuning = self.scheduled_tuning();
+        if tuning != self.tuning {
+            self.retune(tuning, time_of_input)?;
+        }
+
+        self.controller.set_user_input_and_calc(user_input, time_of_input)
+    }
+
+    fn spawn_writer(&mut self, name: String) -> Result<Writer<std::fs::File>,
+    ChemEngProcessControlSimulatorError> {
+        let title_string: String = name + "_gain_scheduled_controller.csv";
+        let wtr = Writer::from_path(title_string)?;
+        Ok(wtr)
+    }
+
+    fn csv_write_values(&mut self,
+        wtr: &mut Writer<std::fs::File>,
+        time: Time,
+        input: Ratio,
+        output: Ratio) -> Result<(),
+    ChemEngProcessControlSimulatorError> {
+        let current_time_string = time.get::<second>().to_string();
+        let input_string = input.get::<ratio>().to_string();
+        let output_string = output.get::<ratio>().to_string();
+
+        wtr.write_record(&[current_time_string,
+            input_string,
+            output_string])?;
+
+        wtr.flush().unwrap();
+        Ok(())
+    }
+}
+
+#[test]
+pub fn test_gain_scheduled_controller(){
+
+    let breakpoints = vec![
+        ScheduleBreakpoint::new(Ratio::new::<ratio>(1.0),
+            Ratio::new::<ratio>(3.0), Time::new::<second>(20.0),
+            Time::new::<second>(2.0)),
+        ScheduleBreakpoint::new(Ratio::ZERO,
+            Ratio::new::<ratio>(1.0), Time::new::<second>(10.0),
+            Time::new::<second>(1.0)),
+    ];
+    let mut controller = GainScheduledController::new(breakpoints,
+        Ratio::new::<ratio>(0.1)).unwrap();
+
+    // interpolated halfway, clamped outside the table
+    let tuning_at = |controller: &mut GainScheduledController, value: f64| {
+        controller.set_scheduling_variable(Ratio::new::<ratio>(value));
+        let (controller_gain, integral_time, derivative_time) =
+            controller.scheduled_tuning();
+        (controller_gain.get::<ratio>(), integral_time.get::<second>(),
+            derivative_time.get::<second>())
+    };
+    assert_eq!(tuning_at(&mut controller, 0.5), (2.0, 15.0, 1.5));
+    assert_eq!(tuning_at(&mut controller, -1.0), (1.0, 10.0, 1.0));
+    assert_eq!(tuning_at(&mut controller, 2.0), (3.0, 20.0, 2.0));
+
+    // constant error of 1, at t = 5 s the output is about
+    // K_c (1 + t/tau_I) = 1.5
+    controller.set_scheduling_variable(Ratio::ZERO);
+    let error = Ratio::new::<ratio>(1.0);
+    let timestep: f64 = 0.1;
+    let mut output: f64 = 0.0;
+    for step in 0..=50 {
+        let time = Time::new::<second>(step as f64 * timestep);
+        output = controller.set_user_input_and_calc(error, time).unwrap()
+            .get::<ratio>();
+    }
+    approx::assert_abs_diff_eq!(output, 1.5, epsilon = 1e-6);
+
+    // switching to K_c = 3, tau_I = 20 s does not bump the output
+    // (the step at t = 5.1 s is still on the old tuning), and the
+    // integral carries on at K_c/tau_I = 0.15 per second from
+    // where it was
+    controller.set_scheduling_variable(Ratio::new::<ratio>(1.0));
+    let mut outputs: Vec<f64> = vec![];
+    for step in 51..=100 {
+        let time = Time::new::<second>(step as f64 * timestep);
+        outputs.push(controller.set_user_input_and_calc(error, time).unwrap()
+            .get::<ratio>());
+    }
+    assert_eq!(controller.current_tuning(), (Ratio::new::<ratio>(3.0),
+            Time::new::<second>(20.0), Time::new::<second>(2.0)));
+    approx::assert_abs_diff_eq!(outputs[0], 1.51, epsilon = 1e-6);
+    approx::assert_abs_diff_eq!(outputs[49], 1.51 + 0.15 * 4.9,
+        epsilon = 1e-6);
+
+    // rebuilding the controller instead would have given
+    // K_c (1 + t/tau_I) = 3 at t = 5 s
+    assert!(outputs[0] < 2.0);
+
+    let mixed_derivative_times = vec![
+        ScheduleBreakpoint::new(Ratio::ZERO, Ratio::new::<ratio>(1.0),
+            Time::new::<second>(10.0), Time::ZERO),
+        ScheduleBreakpoint::new(Ratio::new::<ratio>(1.0),
+            Ratio::new::<ratio>(1.0), Time::new::<second>(10.0),
+            Time::new::<second>(1.0)),
+    ];
+    assert!(GainScheduledController::new(mixed_derivative_times,
+            Ratio::new::<ratio>(0.1)).is_err());
+    assert!(GainScheduledController::new(vec![],
+            Ratio::new::<ratio>(0.1)).is_err());
+}
@@ -0,0 +1,413 @@
+use csv::Writer;
+use num_complex::Complex;
+use uom::si::f64::*;
+use uom::si::ratio::ratio;
+use uom::si::time::second;
+use uom::ConstZero;
+
+use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
+use crate::alpha_nightly::polynomials;
+use crate::alpha_nightly::transfer_fn_wrapper_and_enums::{TransferFn,
+    TransferFnTraits};
+use crate::alpha_nightly::transfer_fn_wrapper_and_enums::
+generic_zero_order_hold::TransferFnZeroOrderHold;
+use super::AnalogController;
+
+/// internal model control (IMC),
+///
+/// the process model is factored into
+///
+/// G(s) = G+(s) G-(s)
+///
+/// where G+ holds the parts which cannot be inverted, the dead time
+/// and any right half plane zeros, as
+///
+/// G+(s) = exp(-theta s) prod (1 - s/z)
+///
+/// so that G+(0) = 1. The IMC controller is then
+///
+/// Q(s) = f(s)/G-(s), f(s) = 1/(lambda s + 1)^r
+///
+/// with r just large enough for Q to be proper. With a perfect
+/// model, the setpoint response is G+(s) f(s).
+///
+/// The controller runs the internal model alongside the process,
+/// and takes in the error r - y like an AnalogController, so it
+/// drops into the same feedback loop.
+///
+/// the model must be stable, with no zeros on the imaginary axis.
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub struct ImcController {
+    /// internal model, dead time included
+    pub(crate) model: TransferFn,
+    /// Q(s)
+    pub(crate) q_controller: TransferFn,
+    pub(crate) filter_time_constant: Time,
+    pub(crate) filter_order: usize,
+    /// prod (1 - s/z) over the right half plane zeros
+    pub(crate) non_invertible_numerator: Vec<f64>,
+    /// what is left of the numerator, steady state gain included
+    pub(crate) invertible_numerator: Vec<f64>,
+    pub(crate) denominator: Vec<f64>,
+    /// controller output at the last timestep,
+    /// which is what the internal model sees
+    pub(crate) last_output: Ratio,
+}
+
+impl ImcController {
+
+    /// builds the IMC controller from a process model (eg. a
+    /// TransferFnFirstOrder or TransferFnSecondOrder with its
+    /// dead time set) and the filter time constant lambda,
+    ///
+    /// lambda is the closed loop time constant, smaller is faster
+    /// but less robust to model error
+    pub fn new(process_model: impl Into<TransferFn>,
+        filter_time_constant: Time) -> Result<Self,
+    ChemEngProcessControlSimulatorError> {
+
+        if filter_time_constant.get::<second>() <= 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "IMC filter time constant must be positive".to_owned()));
+        }
+
+        let model: TransferFn = process_model.into();
+        let numerator = polynomials::trim_leading_zeroes(&model.numerator());
+        let denominator = polynomials::trim_leading_zeroes(&model.denominator());
+
+        if polynomials::roots(&denominator)?.iter().any(|pole| pole.re >= 0.0) {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "IMC needs a stable process model".to_owned()));
+        }
+        let (non_invertible_numerator, invertible_numerator) =
+            factor_numerator(&numerator)?;
+
+        // r, so that Q = D/(N- (lambda s + 1)^r) is proper
+        let filter_order = (polynomials::degree(&denominator)
+            .saturating_sub(polynomials::degree(&invertible_numerator)))
+            .max(1);
+        let q_denominator = polynomials::multiply(&invertible_numerator,
+            &filter_polynomial(filter_time_constant, filter_order));
+        let q_controller: TransferFn = TransferFnZeroOrderHold::new(
+            denominator.clone(), q_denominator)?.into();
+
+        Ok(Self {
+            model,
+            q_controller,
+            filter_time_constant,
+            filter_order,
+            non_invertible_numerator,
+            invertible_numerator,
+            denominator,
+            last_output: Ratio::ZERO,
+        })
+    }
+
+    /// lambda
+    pub fn filter_time_constant(&self) -> Time {
+        self.filter_time_constant
+    }
+
+    /// r in f(s) = 1/(lambda s + 1)^r
+    pub fn filter_order(&self) -> usize {
+        self.filter_order
+    }
+
+    /// numerator of G+ without the dead time, prod (1 - s/z),
+    /// which is [1.0] for a minimum phase model
+    pub fn non_invertible_numerator(&self) -> Vec<f64> {
+        self.non_invertible_numerator.clone()
+    }
+
+    /// Q(s), the IMC controller itself
+    pub fn q_controller(&self) -> TransferFn {
+        self.q_controller.clone()
+    }
+
+    pub fn model(&self) -> TransferFn {
+        self.model.clone()
+    }
+
+    /// equivalent classical PID settings (Kc, tau_I, tau_D) in
+    /// ISA form
+    ///
+    /// the dead time is replaced by a first order Pade
+    /// approximation, and a first order filter is used, as in the
+    /// usual IMC-PID tables. The classical controller
+    ///
+    /// C(s) = Q/(1 - G Q) = D (theta s/2 + 1)/(N- s R(s))
+    ///
+    /// where s R(s) = (lambda s + 1) - N+ (1 - theta s/2), is then
+    /// expanded as C(s) = (f0 + f1 s + f2 s^2 + ...)/s and
+    ///
+    /// Kc = f1, tau_I = f1/f0, tau_D = f2/f1
+    ///
+    /// which is exact when C(s) is a PID controller (eg. for a
+    /// FOPDT or SOPDT model). For a FOPDT model, this gives the
+    /// same settings as TuningRule::Imc.
+    pub fn pid_parameters(&self) -> Result<(Ratio, Time, Time),
+    ChemEngProcessControlSimulatorError> {
+
+        let theta: f64 = self.model.dead_time().get::<second>();
+        let pade_numerator = vec![-0.5 * theta, 1.0];
+        let pade_denominator = vec![0.5 * theta, 1.0];
+
+        let non_invertible = polynomials::multiply(
+            &self.non_invertible_numerator, &pade_numerator);
+        let filter = filter_polynomial(self.filter_time_constant, 1);
+        // (lambda s + 1) - N+ (1 - theta s/2), the constant terms
+        // cancel, so dropping the last coefficient divides by s
+        let difference = polynomials::add(&filter,
+            &polynomials::scale(&non_invertible, -1.0));
+        let remainder = &difference[..difference.len() - 1];
+
+        let controller_numerator = polynomials::multiply(
+            &self.denominator, &pade_denominator);
+        let controller_denominator = polynomials::multiply(
+            &self.invertible_numerator, remainder);
+
+        let series = maclaurin_series(&controller_numerator,
+            &controller_denominator, 3)?;
+        let (f0, f1, f2) = (series[0], series[1], series[2]);
+        if f0 == 0.0 || f1 == 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "IMC controller has no PID equivalent".to_owned()));
+        }
+
+        Ok((Ratio::new::<ratio>(f1),
+            Time::new::<second>(f1/f0),
+            Time::new::<second>(f2/f1)))
+    }
+
+    /// equivalent filtered PID controller, alpha sets the
+    /// derivative filter
+    pub fn pid_controller(&self, alpha: Ratio)
+    -> Result<AnalogController, ChemEngProcessControlSimulatorError> {
+        let (controller_gain, integral_time, derivative_time) =
+            self.pid_parameters()?;
+        AnalogController::new_filtered_pid_controller(controller_gain,
+            integral_time, derivative_time, alpha)
+    }
+}
+
+/// (lambda s + 1)^r
+fn filter_polynomial(filter_time_constant: Time, filter_order: usize) -> Vec<f64> {
+    let lambda: f64 = filter_time_constant.get::<second>();
+    (0..filter_order).fold(vec![1.0], |polynomial, _| {
+        polynomials::multiply(&polynomial, &[lambda, 1.0])
+    })
+}
+
+/// splits N(s) into N+(s) N-(s), with N+ = prod (1 - s/z) over the
+/// right half plane zeros
+fn factor_numerator(numerator: &[f64]) -> Result<(Vec<f64>, Vec<f64>),
+ChemEngProcessControlSimulatorError> {
+
+    if polynomials::degree(numerator) == 0 {
+        return Ok((vec![1.0], numerator.to_vec()));
+    }
+
+    let zeros = polynomials::roots(numerator)?;
+    if zeros.iter().any(|zero| zero.re.abs() < 1e-12) {
+        return Err(ChemEngProcessControlSimulatorError::
+            GenericStringError(
+                "IMC cannot handle zeros on the imaginary axis".to_owned()));
+    }
+    let (right_half_plane_zeros, left_half_plane_zeros): (Vec<Complex<f64>>,
+        Vec<Complex<f64>>) = zeros.into_iter().partition(|zero| zero.re > 0.0);
+
+    // prod (s - z) has constant term prod (-z), dividing by that
+    // gives prod (1 - s/z)
+    let right_half_plane_factor = polynomials::from_roots(&right_half_plane_zeros);
+    let normalisation: f64 = *right_half_plane_factor.last().unwrap();
+    let non_invertible_numerator = polynomials::scale(&right_half_plane_factor,
+        1.0/normalisation);
+
+    let invertible_numerator = polynomials::scale(
+        &polynomials::from_roots(&left_half_plane_zeros),
+        numerator[0] * normalisation);
+
+    Ok((non_invertible_numerator, invertible_numerator))
+}
+
+/// first few coefficients (lowest power first) of the power series
+/// of A(s)/B(s) about s = 0
+fn maclaurin_series(numerator: &[f64], denominator: &[f64],
+    number_of_terms: usize) -> Result<Vec<f64>,
+ChemEngProcessControlSimulatorError> {
+
+    // ascending powers are easier for series division
+    let ascending = |polynomial: &[f64]| -> Vec<f64> {
+        polynomials::trim_leading_zeroes(polynomial).into_iter().rev().collect()
+    };
+    let numerator = ascending(numerator);
+    let denominator = ascending(denominator);
+    let coefficient = |polynomial: &[f64], power: usize| -> f64 {
+        *polynomial.get(power).unwrap_or(&0.0)
+    };
+
+    if denominator[0] == 0.0 {
+        return Err(ChemEngProcessControlSimulatorError::
+            GenericStringError(
+                "power series does not exist, pole at s = 0".to_owned()));
+    }
+
+    let mut series: Vec<f64> = vec![];
+    for power in 0..number_of_terms {
+        let known: f64 = (1..=power).map(|i| {
+            coefficient(&denominator, i) * series[power - i]
+        }).sum();
+        series.push((coefficient(&numerator, power) - known)/denominator[0]);
+    }
+
+    Ok(series)
+}
+
+impl TransferFnTraits for ImcController {
+    /// sets the dead time of the internal model
+    fn set_dead_time(&mut self, dead_time: Time) {
+        self.model.set_dead_time(dead_time);
+    }
+
+    /// user input is the error, r - y, from the real process
+    ///
+    /// Q acts on r - (y - y_model) = (r - y) + y_model
+    fn set_user_input_and_calc(&mut self,
+        user_input: Ratio,
+        time_of_input: Time) -> Result<Ratio,
+    ChemEngProcessControlSimulatorError> {
+
+        let model_output = self.model
+            .set_user_input_and_calc(self.last_output, time_of_input)?;
+        let output = self.q_controller.set_user_input_and_calc(
+            user_input + model_output, time_of_input)?;
+        self.last_output = output;
+
+        Ok(output)
+    }
+
+    fn spawn_writer(&mut self, name: String) -> Result<Writer<std::fs::File>,
+    ChemEngProcessControlSimulatorError> {
+        let title_string: String = name + "_imc_controller.csv";
+        let wtr = Writer::from_path(title_string)?;
+        Ok(wtr)
+    }
+
+    fn csv_write_values(&mut self,
+        wtr: &mut Writer<std::fs::File>,
+        time: Time,
+        input: Ratio,
+        output: Ratio) -> Result<(),
+    ChemEngProcessControlSimulatorError> {
+        let current_time_string = time.get::<second>().to_string();
+        let input_string = input.get::<ratio>().to_string();
+        let output_string = output.get::<ratio>().to_string();
+
+        wtr.write_record(&[current_time_string,
+            input_string,
+            output_string])?;
+
+        wtr.flush().unwrap();
+        Ok(())
+    }
+}
+
+#[test]
+pub fn test_imc_controller(){
+    use crate::alpha_nightly::transfer_fn_wrapper_and_enums::{
+        TransferFnFirstOrder, TransferFnSecondOrder};
+    use crate::alpha_nightly::tuning::{ProcessModel, TuningRule};
+
+    // FOPDT, 2 exp(-3 s)/(10 s + 1), lambda = 5 s
+    let mut process = TransferFnFirstOrder::new(Time::ZERO,
+        Ratio::new::<ratio>(2.0), Time::new::<second>(10.0),
+        Ratio::new::<ratio>(1.0)).unwrap();
+    process.set_dead_time(Time::new::<second>(3.0));
+    let lambda = Time::new::<second>(5.0);
+    let mut imc_controller = ImcController::new(process.clone(), lambda)
+        .unwrap();
+    assert_eq!(imc_controller.filter_order(), 1);
+    assert_eq!(imc_controller.non_invertible_numerator(), vec![1.0]);
+
+    // same settings as the IMC tuning rule
+    let (controller_gain, integral_time, derivative_time) =
+        imc_controller.pid_parameters().unwrap();
+    let (table_gain, table_integral_time, table_derivative_time) =
+        ProcessModel::new_first_order_plus_dead_time(Ratio::new::<ratio>(2.0),
+            Time::new::<second>(10.0), Time::new::<second>(3.0)).unwrap()
+        .pid_parameters(TuningRule::Imc { closed_loop_time_constant: lambda })
+        .unwrap();
+    approx::assert_abs_diff_eq!(controller_gain.get::<ratio>(),
+        table_gain.get::<ratio>(), epsilon = 1e-12);
+    approx::assert_abs_diff_eq!(integral_time.get::<second>(),
+        table_integral_time.get::<second>(), epsilon = 1e-12);
+    approx::assert_abs_diff_eq!(derivative_time.get::<second>(),
+        table_derivative_time.get::<second>(), epsilon = 1e-12);
+    assert!(imc_controller.pid_controller(Ratio::new::<ratio>(0.1)).is_ok());
+
+    // perfect model, the setpoint response is exp(-3 s)/(5 s + 1)
+    let timestep: f64 = 0.05;
+    let mut measurement = Ratio::ZERO;
+    for step in 0..=800 {
+        let time = Time::new::<second>(step as f64 * timestep);
+        let output = imc_controller.set_user_input_and_calc(
+            Ratio::new::<ratio>(1.0) - measurement, time).unwrap();
+        measurement = process.set_user_input_and_calc(output, time).unwrap();
+
+        let time = step as f64 * timestep;
+        let expected = if time > 3.0 {
+            1.0 - (-(time - 3.0)/5.0).exp()
+        } else {
+            0.0
+        };
+        approx::assert_abs_diff_eq!(measurement.get::<ratio>(), expected,
+            epsilon = 0.02);
+    }
+
+    // the analog PID demo process, with complex right half plane
+    // zeros,
+    //
+    //        2.5 s^2 - 0.5 s + 1
+    // G(s) = -------------------
+    //         3 s^2 + 4 s + 4
+    let one_second = Time::new::<second>(1.0);
+    let mut process = TransferFnSecondOrder::new(
+        one_second * Time::new::<second>(2.5), -Time::new::<second>(0.5),
+        Ratio::new::<ratio>(1.0), one_second * one_second * 3.0,
+        Time::new::<second>(4.0), Ratio::new::<ratio>(4.0)).unwrap();
+    let mut imc_controller = ImcController::new(process.clone(),
+        Time::new::<second>(2.0)).unwrap();
+
+    let non_invertible_numerator = imc_controller.non_invertible_numerator();
+    for (coefficient, expected) in non_invertible_numerator.iter()
+        .zip([2.5, -0.5, 1.0].iter()) {
+        approx::assert_abs_diff_eq!(coefficient, expected, epsilon = 1e-9);
+    }
+    // G- = 1/(3 s^2 + 4 s + 4), needs a second order filter
+    assert_eq!(imc_controller.filter_order(), 2);
+    let (controller_gain, integral_time, derivative_time) =
+        imc_controller.pid_parameters().unwrap();
+    assert!(controller_gain.get::<ratio>() > 0.0);
+    assert!(integral_time.get::<second>() > 0.0);
+    assert!(derivative_time.get::<second>() > 0.0);
+
+    // no offset
+    let mut measurement = Ratio::ZERO;
+    for step in 0..=1200 {
+        let time = Time::new::<second>(step as f64 * timestep);
+        let output = imc_controller.set_user_input_and_calc(
+            Ratio::new::<ratio>(1.0) - measurement, time).unwrap();
+        measurement = process.set_user_input_and_calc(output, time).unwrap();
+    }
+    approx::assert_abs_diff_eq!(measurement.get::<ratio>(), 1.0,
+        epsilon = 0.01);
+
+    // unstable models cannot be used
+    let unstable = TransferFnFirstOrder::new(Time::ZERO,
+        Ratio::new::<ratio>(1.0), Time::new::<second>(-10.0),
+        Ratio::new::<ratio>(1.0)).unwrap();
+    assert!(ImcController::new(unstable, lambda).is_err());
+}
@@ -8,6 +8,7 @@ use uom::si::time::second;
 use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
 
 use super::AnalogController;
+use super::controller_modes::ModeSettings;
 
 /// Integral controller with transfer function
 ///
@@ -21,6 +22,94 @@ use super::AnalogController;
 pub struct IntegralController{
     pub(crate) ramp_function: RampResponseRealTime,
     pub(crate) delay_function: TransferFnFirstOrder,
+    /// what happens to the integral when the output is limited
+    pub(crate) anti_windup: AntiWindup,
+    /// measured actuator position, for external reset feedback, 
+    /// used up at the next timestep
+    pub(crate) external_reset_feedback: Option<Ratio>,
+    /// mode settings, used when this is a standalone controller
+    pub(crate) mode_settings: ModeSettings,
+}
+
+/// lower and upper limits of a controller output
+#[derive(Debug,PartialEq, PartialOrd, Clone, Copy)]
+pub struct OutputLimits {
+    pub(crate) lower_limit: Ratio,
+    pub(crate) upper_limit: Ratio,
+}
+
+impl OutputLimits {
+
+    /// lower limit must be below the upper limit
+    pub fn new(lower_limit: Ratio, 
+        upper_limit: Ratio) -> Result<Self, ChemEngProcessControlSimulatorError> {
+        if lower_limit >= upper_limit {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "lower output limit must be below the upper limit"
+                    .to_owned()));
+        }
+
+        Ok(Self { lower_limit, upper_limit })
+    }
+
+    pub fn lower_limit(&self) -> Ratio {
+        self.lower_limit
+    }
+
+    pub fn upper_limit(&self) -> Ratio {
+        self.upper_limit
+    }
+
+    /// keeps a value within the limits
+    pub fn clamp(&self, value: Ratio) -> Ratio {
+        if value > self.upper_limit {
+            self.upper_limit
+        } else if value < self.lower_limit {
+            self.lower_limit
+        } else {
+            value
+        }
+    }
+}
+
+/// anti windup strategies
+///
+/// when the controller output is stuck at a limit (eg. a valve 
+/// fully open), the error does not go away, and the integral term 
+/// keeps growing. When the error finally changes sign, the integral 
+/// has to unwind before the output comes off the limit, which gives 
+/// a big overshoot.
+#[derive(Debug,PartialEq, PartialOrd, Clone, Copy)]
+pub enum AntiWindup {
+    /// output is limited, but the integral keeps going
+    None,
+    /// clamping, ie. the integral is held whenever the output is 
+    /// at a limit and the integral would push it further past 
+    /// that limit
+    ConditionalIntegration,
+    /// the integral is pulled back by 
+    ///
+    /// (limited output - unlimited output)/tracking_time
+    ///
+    /// a tracking time of about sqrt(tau_I tau_D) (or tau_I for 
+    /// PI controllers) is usually suggested
+    BackCalculation { tracking_time: Time },
+    /// external reset feedback (Shinskey), the integral term is 
+    /// replaced by a first order lag of the measured actuator 
+    /// position u_m (see set_external_reset_feedback)
+    ///
+    /// output = Kc e + u_m/(tau_I s + 1)
+    ///
+    /// if the actuator follows the controller output, this is the 
+    /// same as a PI controller. If it is stuck at a limit, the 
+    /// output is Kc e plus that limit, so it comes off the limit as 
+    /// soon as the error changes sign. This also takes care of 
+    /// valves with their own limits which the controller does not 
+    /// know about, so it works with or without output limits. 
+    /// Without a measured position, the (limited) controller 
+    /// output is used.
+    ExternalResetFeedback,
 }
 
 impl Default for IntegralController {
@@ -106,9 +195,133 @@ impl IntegralController {
         Ok(Self {
             ramp_function,
             delay_function,
+            anti_windup: AntiWindup::ConditionalIntegration,
+            external_reset_feedback: None,
+            mode_settings: ModeSettings::default(),
         })
     }
 
+    /// calculates the integral term, then works out the whole 
+    /// controller output (proportional and derivative terms 
+    /// included), limiting it and stopping the integral from 
+    /// winding up
+    ///
+    /// without output limits, this is the same as 
+    /// set_user_input_and_calc plus the other terms, except for 
+    /// external reset feedback. The limits and controller mode 
+    /// belong to the AnalogController.
+    pub(crate) fn set_user_input_and_calc_with_limits(&mut self,
+        user_input: Ratio,
+        time_of_input: Time,
+        proportional_and_derivative_output: Ratio,
+        output_limits: Option<OutputLimits>) -> Result<Ratio, 
+    ChemEngProcessControlSimulatorError> {
+
+        let previous_integral_output = self.ramp_function.current_output();
+        let previous_time = self.ramp_function.current_time;
+
+        let integral_output = self.set_user_input_and_calc(
+            user_input, time_of_input)?;
+        let unlimited_output = proportional_and_derivative_output 
+            + integral_output;
+
+        // the actuator may have limits of its own, so the reset 
+        // feedback is used whether or not the output is limited here
+        if self.anti_windup == AntiWindup::ExternalResetFeedback {
+            let clamp = |output: Ratio| match output_limits {
+                Some(output_limits) => output_limits.clamp(output),
+                None => output,
+            };
+            let reset_feedback = self.external_reset_feedback.take()
+                .unwrap_or_else(|| clamp(proportional_and_derivative_output 
+                        + previous_integral_output));
+            let timestep_fraction: Ratio = ((time_of_input - previous_time)
+                /self.ramp_function.integral_time).min(Ratio::new::<ratio>(1.0));
+            let reset_output = previous_integral_output 
+                + (reset_feedback - previous_integral_output) 
+                * timestep_fraction;
+            self.ramp_function.set_output(reset_output, time_of_input);
+            return Ok(clamp(proportional_and_derivative_output + reset_output));
+        }
+
+        let output_limits = match output_limits {
+            Some(output_limits) => output_limits,
+            None => return Ok(unlimited_output),
+        };
+        let limited_output = output_limits.clamp(unlimited_output);
+
+        // pulls the integral towards the tracking signal
+        let mut back_calculate = |tracking_signal: Ratio, tracking_time: Time| {
+            let timestep_fraction: Ratio = ((time_of_input - previous_time)
+                /tracking_time).min(Ratio::new::<ratio>(1.0));
+            let corrected_integral_output = integral_output 
+                + (tracking_signal - unlimited_output) * timestep_fraction;
+            self.ramp_function.set_output(corrected_integral_output, 
+                time_of_input);
+            output_limits.clamp(proportional_and_derivative_output 
+                + corrected_integral_output)
+        };
+
+        let output = match self.anti_windup {
+            AntiWindup::None => limited_output,
+            AntiWindup::ConditionalIntegration => {
+                let winding_up: bool = 
+                    (unlimited_output > output_limits.upper_limit 
+                     && integral_output > previous_integral_output)
+                    || (unlimited_output < output_limits.lower_limit 
+                        && integral_output < previous_integral_output);
+                if winding_up {
+                    // hold the integral where it was
+                    self.ramp_function.set_output(previous_integral_output, 
+                        time_of_input);
+                    output_limits.clamp(proportional_and_derivative_output 
+                        + previous_integral_output)
+                } else {
+                    limited_output
+                }
+            },
+            AntiWindup::BackCalculation { tracking_time } => {
+                back_calculate(limited_output, tracking_time)
+            },
+            // dealt with above
+            AntiWindup::ExternalResetFeedback => limited_output,
+        };
+
+        Ok(output)
+    }
+
+    /// changes K_c and tau_I at the given time without resetting 
+    /// the integral
+    pub(crate) fn retune(&mut self, controller_gain: Ratio,
+        integral_time: Time,
+        time: Time) -> Result<(), ChemEngProcessControlSimulatorError> {
+        self.ramp_function.set_tuning(controller_gain, integral_time, time)
+    }
+
+    /// changes K_c mid simulation, the integral output carries 
+    /// on from where it is and only its rate of change is affected
+    pub fn set_controller_gain(&mut self, controller_gain: Ratio,
+        time: Time) -> Result<(), ChemEngProcessControlSimulatorError> {
+        let integral_time = self.ramp_function.integral_time;
+        self.retune(controller_gain, integral_time, time)
+    }
+
+    /// changes tau_I mid simulation, the integral output carries 
+    /// on from where it is and only its rate of change is affected
+    pub fn set_integral_time(&mut self, integral_time: Time,
+        time: Time) -> Result<(), ChemEngProcessControlSimulatorError> {
+        let controller_gain = self.ramp_function.controller_gain;
+        self.retune(controller_gain, integral_time, time)
+    }
+
+    pub fn controller_gain(&self) -> Ratio {
+        self.ramp_function.controller_gain
+    }
+
+    pub fn integral_time(&self) -> Time {
+        self.ramp_function.integral_time
+    }
+
 }
 
 /// Ramp response for integral controller 
@@ -202,6 +415,42 @@ impl RampResponseRealTime {
 
         return Ok(output);
     }
+
+    /// output at the last time it was calculated
+    pub fn current_output(&self) -> Ratio {
+        self.current_time * self.gradient_gain + self.offset
+    }
+
+    /// re-initialises the integral so that the output at 
+    /// time_of_output is the given output, 
+    /// the gradient is left alone
+    pub fn set_output(&mut self, output: Ratio, time_of_output: Time) {
+        self.current_time = time_of_output;
+        self.offset = output - time_of_output * self.gradient_gain;
+    }
+
+    /// changes K_c and tau_I at the given time, 
+    /// the output carries on from where it is (nothing 
+    /// accumulated so far is lost) and only the gradient changes
+    pub fn set_tuning(&mut self, controller_gain: Ratio,
+        integral_time: Time,
+        time: Time) -> Result<(), ChemEngProcessControlSimulatorError> {
+
+        if integral_time.get::<second>() <= 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "integral time must be positive".to_owned()));
+        }
+        let output = time * self.gradient_gain + self.offset;
+
+        self.controller_gain = controller_gain;
+        self.integral_time = integral_time;
+        self.gradient_gain = self.previous_timestep_input 
+            * controller_gain/integral_time;
+        self.set_output(output, time);
+
+        Ok(())
+    }
 }
 
 
@@ -307,3 +556,242 @@ impl Into<AnalogController> for IntegralController {
     }
 }
 
+
+#[test]
+pub fn test_anti_windup(){
+
+    // PI controller, Kc = 1, tau_I = 1 s, output between -1 and 1
+    //
+    // error of 2 for 10 s, which saturates the output, 
+    // then the error goes to -0.5
+    let run_pi_controller = |anti_windup: AntiWindup,
+        external_reset_feedback: Option<Ratio>| -> Vec<(f64, f64)> {
+        let mut controller = AnalogController::new_pi_controller(
+            Ratio::new::<ratio>(1.0), Time::new::<second>(1.0)).unwrap();
+        controller.set_output_limits(Ratio::new::<ratio>(-1.0), 
+            Ratio::new::<ratio>(1.0)).unwrap();
+        controller.set_anti_windup(anti_windup).unwrap();
+
+        let mut outputs: Vec<(f64, f64)> = vec![];
+        for step in 0..1500 {
+            let t: f64 = step as f64 * 0.01;
+            let error = if t < 10.0 { 2.0 } else { -0.5 };
+            if let Some(actuator_position) = external_reset_feedback {
+                controller.set_external_reset_feedback(actuator_position)
+                    .unwrap();
+            }
+            let output = controller.set_user_input_and_calc(
+                Ratio::new::<ratio>(error), Time::new::<second>(t)).unwrap();
+            outputs.push((t, output.get::<ratio>()));
+        }
+        outputs
+    };
+    let output_at = |outputs: &[(f64, f64)], time: f64| -> f64 {
+        outputs.iter().find(|(t, _)| (t - time).abs() < 1e-6).unwrap().1
+    };
+
+    // no anti windup, the integral gets to about 20 and the output 
+    // is still stuck at the upper limit 2 s after the error changes
+    let outputs = run_pi_controller(AntiWindup::None, None);
+    assert!(outputs.iter().all(|(_, output)| output.abs() <= 1.0));
+    assert_eq!(output_at(&outputs, 12.0), 1.0);
+
+    // conditional integration, the integral is held at zero 
+    // so the output comes off the limit straight away,
+    // -0.5 - 0.5 (t - 10) 
+    // (give or take one timestep of integration at t = 10 s)
+    let outputs = run_pi_controller(AntiWindup::ConditionalIntegration, None);
+    approx::assert_abs_diff_eq!(output_at(&outputs, 10.5), -0.75, 
+        epsilon = 0.025);
+
+    // back calculation, the integral settles at 1 - 2 = -1 while 
+    // saturated, so the output goes to the lower limit
+    let outputs = run_pi_controller(AntiWindup::BackCalculation { 
+        tracking_time: Time::new::<second>(0.1) }, None);
+    approx::assert_abs_diff_eq!(output_at(&outputs, 9.5), 1.0, 
+        epsilon = 1e-9);
+    approx::assert_abs_diff_eq!(output_at(&outputs, 10.5), -1.0, 
+        epsilon = 1e-9);
+
+    // external reset feedback, valve stuck at 0.8, the reset term 
+    // settles at 0.8, so once the error changes the output is 
+    // -0.5 + 0.8 
+    let outputs = run_pi_controller(AntiWindup::ExternalResetFeedback, 
+        Some(Ratio::new::<ratio>(0.8)));
+    approx::assert_abs_diff_eq!(output_at(&outputs, 9.99), 1.0, 
+        epsilon = 1e-9);
+    approx::assert_abs_diff_eq!(output_at(&outputs, 10.01), 0.3, 
+        epsilon = 0.01);
+
+    // without output limits, external reset feedback from the 
+    // controller output is the same as a PI controller, 
+    // 2 + 2t
+    let mut controller = AnalogController::new_pi_controller(
+        Ratio::new::<ratio>(1.0), Time::new::<second>(1.0)).unwrap();
+    controller.set_anti_windup(AntiWindup::ExternalResetFeedback).unwrap();
+    let mut output = Ratio::ZERO;
+    for step in 0..=100 {
+        let time = Time::new::<second>(step as f64 * 0.01);
+        controller.set_external_reset_feedback(output).unwrap();
+        output = controller.set_user_input_and_calc(
+            Ratio::new::<ratio>(2.0), time).unwrap();
+    }
+    approx::assert_abs_diff_eq!(output.get::<ratio>(), 4.0, epsilon = 0.05);
+
+    // a valve which stops at 0.8 by itself, which the controller 
+    // has no limits for, the output settles at 2 + 0.8 and comes 
+    // straight back to -0.5 + 0.8 once the error changes
+    let mut controller = AnalogController::new_pi_controller(
+        Ratio::new::<ratio>(1.0), Time::new::<second>(1.0)).unwrap();
+    controller.set_anti_windup(AntiWindup::ExternalResetFeedback).unwrap();
+    let mut output = Ratio::ZERO;
+    for step in 0..=1100 {
+        let t: f64 = step as f64 * 0.01;
+        let error = if t < 10.0 { 2.0 } else { -0.5 };
+        let valve_position = output.min(Ratio::new::<ratio>(0.8));
+        controller.set_external_reset_feedback(valve_position).unwrap();
+        output = controller.set_user_input_and_calc(
+            Ratio::new::<ratio>(error), Time::new::<second>(t)).unwrap();
+        if step == 999 {
+            approx::assert_abs_diff_eq!(output.get::<ratio>(), 2.8, 
+                epsilon = 1e-3);
+        }
+        if step == 1001 {
+            approx::assert_abs_diff_eq!(output.get::<ratio>(), 0.3, 
+                epsilon = 0.01);
+        }
+    }
+
+    // once the valve position is no longer given, the controller 
+    // output is fed back, so the integral carries on as in a PI 
+    // controller rather than tracking the last position, 
+    // rising at Kc e/tau_I = -0.5 per second
+    let mut outputs: Vec<f64> = vec![];
+    for step in 1101..=1300 {
+        let time = Time::new::<second>(step as f64 * 0.01);
+        outputs.push(controller.set_user_input_and_calc(
+                Ratio::new::<ratio>(-0.5), time).unwrap().get::<ratio>());
+    }
+    approx::assert_abs_diff_eq!(outputs[199] - outputs[99], -0.5, 
+        epsilon = 0.01);
+
+    // no integral action, the output is still limited
+    let mut pd_controller = AnalogController::new_filtered_pd_controller(
+        Ratio::new::<ratio>(1.0), Time::new::<second>(1.0), 
+        Ratio::new::<ratio>(0.1)).unwrap();
+    pd_controller.set_output_limits(Ratio::new::<ratio>(-1.0), 
+        Ratio::new::<ratio>(1.0)).unwrap();
+    assert!(pd_controller.set_anti_windup(AntiWindup::None).is_err());
+    let pd_outputs: Vec<f64> = (0..100).map(|step| {
+        pd_controller.set_user_input_and_calc(Ratio::new::<ratio>(2.0), 
+            Time::new::<second>(step as f64 * 0.1)).unwrap().get::<ratio>()
+    }).collect();
+    assert!(pd_outputs.iter().all(|output| *output == 1.0));
+
+    pd_controller.remove_output_limits();
+    let output = pd_controller.set_user_input_and_calc(
+        Ratio::new::<ratio>(2.0), Time::new::<second>(10.0)).unwrap();
+    approx::assert_abs_diff_eq!(output.get::<ratio>(), 2.0, epsilon = 1e-6);
+}
+
+#[test]
+pub fn test_online_retuning(){
+    use super::FilteredDerivativeController;
+
+    // integral controller, Kc = 1, tau_I = 10 s, error of 1,
+    // so the output at 5 s is 0.5
+    let error = Ratio::new::<ratio>(1.0);
+    let run_to = |controller: &mut dyn TransferFnTraits, 
+        start: f64, end: f64| -> f64 {
+        let mut output = Ratio::ZERO;
+        let mut time = start;
+        while time <= end + 1e-9 {
+            output = controller.set_user_input_and_calc(error, 
+                Time::new::<second>(time)).unwrap();
+            time += 0.5;
+        }
+        output.get::<ratio>()
+    };
+    let mut integral_controller = IntegralController::new(
+        Ratio::new::<ratio>(1.0), Time::new::<second>(10.0)).unwrap();
+    approx::assert_abs_diff_eq!(run_to(&mut integral_controller, 0.0, 5.0), 
+        0.5, epsilon = 1e-9);
+
+    // doubling Kc doubles the rate of change, not what has been 
+    // accumulated
+    integral_controller.set_controller_gain(Ratio::new::<ratio>(2.0), 
+        Time::new::<second>(5.0)).unwrap();
+    approx::assert_abs_diff_eq!(run_to(&mut integral_controller, 5.0, 5.0), 
+        0.5, epsilon = 1e-9);
+    approx::assert_abs_diff_eq!(run_to(&mut integral_controller, 5.5, 10.0), 
+        1.5, epsilon = 1e-9);
+
+    integral_controller.set_integral_time(Time::new::<second>(20.0), 
+        Time::new::<second>(10.0)).unwrap();
+    approx::assert_abs_diff_eq!(run_to(&mut integral_controller, 10.5, 15.0), 
+        2.0, epsilon = 1e-9);
+    assert_eq!(integral_controller.controller_gain().get::<ratio>(), 2.0);
+    assert_eq!(integral_controller.integral_time().get::<second>(), 20.0);
+
+    // PID controller, Kc = 1, tau_I = 10 s, tau_D = 1 s, the 
+    // derivative kick has died away by 5 s, output is 1 + 0.5
+    let mut controller = AnalogController::new_filtered_pid_controller(
+        Ratio::new::<ratio>(1.0), Time::new::<second>(10.0), 
+        Time::new::<second>(1.0), Ratio::new::<ratio>(0.1)).unwrap();
+    approx::assert_abs_diff_eq!(run_to(&mut controller, 0.0, 5.0), 
+        1.5, epsilon = 1e-9);
+
+    // the proportional jump is taken up by the integral, so the 
+    // output holds at 1.5 and then rises at Kc/tau_I = 0.2 per 
+    // second. A new controller with Kc = 2 would be at 3.
+    controller.set_controller_gain(Ratio::new::<ratio>(2.0), 
+        Time::new::<second>(5.0)).unwrap();
+    approx::assert_abs_diff_eq!(run_to(&mut controller, 5.0, 5.0), 
+        1.5, epsilon = 1e-9);
+    approx::assert_abs_diff_eq!(run_to(&mut controller, 5.5, 10.0), 
+        2.5, epsilon = 1e-9);
+
+    controller.set_integral_time(Time::new::<second>(5.0), 
+        Time::new::<second>(10.0)).unwrap();
+    approx::assert_abs_diff_eq!(run_to(&mut controller, 10.5, 15.0), 
+        4.5, epsilon = 1e-9);
+
+    // K_c, tau_I and tau_D all at once, still no bump, then 
+    // rising at 0.1 per second
+    controller.retune(Ratio::new::<ratio>(1.0), Time::new::<second>(10.0), 
+        Time::new::<second>(2.0), Time::new::<second>(15.0)).unwrap();
+    approx::assert_abs_diff_eq!(run_to(&mut controller, 15.0, 15.0), 
+        4.5, epsilon = 1e-9);
+    approx::assert_abs_diff_eq!(run_to(&mut controller, 15.5, 20.0), 
+        5.0, epsilon = 1e-9);
+    assert_eq!(controller.controller_gain().unwrap().get::<ratio>(), 1.0);
+    assert_eq!(controller.integral_time().unwrap().get::<second>(), 10.0);
+    assert_eq!(controller.derivative_time().unwrap().get::<second>(), 2.0);
+    assert!(controller.retune(Ratio::new::<ratio>(1.0), Time::ZERO, 
+            Time::new::<second>(2.0), Time::new::<second>(20.0)).is_err());
+
+    // without integral action, the output jumps with K_c and 
+    // tau_I is ignored
+    let mut p_controller: AnalogController = 
+        super::ProportionalController::new(Ratio::new::<ratio>(1.0))
+        .unwrap().into();
+    approx::assert_abs_diff_eq!(run_to(&mut p_controller, 0.0, 5.0), 
+        1.0, epsilon = 1e-9);
+    p_controller.retune(Ratio::new::<ratio>(2.0), Time::ZERO, Time::ZERO,
+        Time::new::<second>(5.0)).unwrap();
+    approx::assert_abs_diff_eq!(run_to(&mut p_controller, 5.5, 6.0), 
+        2.0, epsilon = 1e-9);
+    assert!(p_controller.integral_time().is_none());
+
+    // a steady error gives no derivative action before or after
+    let mut derivative_controller = AnalogController::
+        DerivativeFilteredStandalone(FilteredDerivativeController::default());
+    approx::assert_abs_diff_eq!(run_to(&mut derivative_controller, 0.0, 5.0), 
+        0.0, epsilon = 1e-6);
+    derivative_controller.set_controller_gain(
+            Ratio::new::<ratio>(2.0), Time::new::<second>(5.0)).unwrap();
+    approx::assert_abs_diff_eq!(run_to(&mut derivative_controller, 5.5, 6.0), 
+        0.0, epsilon = 1e-6);
+    assert_eq!(derivative_controller.controller_gain().unwrap()
+        .get::<ratio>(), 2.0);
+}
@@ -2,18 +2,42 @@ use csv::Writer;
 use uom::si::ratio::ratio;
 use uom::si::time::second;
 use uom::si::f64::*;
+use uom::ConstZero;
 
-pub use self::integral_controller::IntegralController;
+pub use self::integral_controller::{AntiWindup, IntegralController, OutputLimits};
+pub use self::controller_modes::{ControllerMode, ModeSettings};
 pub use self::proportional_controller::ProportionalController;
 pub use self::filtered_derivative_controller::FilteredDerivativeController;
+pub use self::two_degrees_of_freedom::{PidStructure, SetpointWeights};
+pub use self::digital_controller::{DigitalAlgorithm, DigitalController, Discretisation};
+pub use self::smith_predictor::SmithPredictor;
+pub use self::cascade_loop::{CascadeLoop, CascadeSignals};
+pub use self::feedforward_controller::FeedforwardController;
+pub use self::imc_controller::ImcController;
+pub use self::gain_scheduled_controller::{GainScheduledController, ScheduleBreakpoint};
 
 use super::errors::ChemEngProcessControlSimulatorError;
+use super::polynomials;
 use super::transfer_fn_wrapper_and_enums::TransferFnTraits;
 pub(crate) mod proportional_controller;
 pub mod integral_controller;
 pub(crate) mod filtered_derivative_controller;
+pub mod controller_modes;
+pub mod two_degrees_of_freedom;
+pub mod digital_controller;
+pub mod smith_predictor;
+pub mod cascade_loop;
+pub mod feedforward_controller;
+pub mod imc_controller;
+pub mod gain_scheduled_controller;
 
 /// generic enum for a Continuous Time Controller
+///
+/// the mode settings (manual, auto or cascade, setpoints, manual 
+/// output and output limits) belong to the controller as a whole, 
+/// they are kept on its leading part, ie. the proportional 
+/// controller, or the integral or derivative controller for the 
+/// standalone variants
 #[derive(Debug,PartialEq, PartialOrd, Clone)]
 pub enum AnalogController {
     PIDFiltered(ProportionalController,IntegralController,FilteredDerivativeController),
@@ -22,6 +46,10 @@ pub enum AnalogController {
     PDFiltered(ProportionalController, FilteredDerivativeController),
     IntegralStandalone(IntegralController),
     DerivativeFilteredStandalone(FilteredDerivativeController),
+    /// PID with setpoint weights b and c on the proportional and 
+    /// derivative terms
+    PIDTwoDegreesOfFreedom(ProportionalController,IntegralController,
+        FilteredDerivativeController,SetpointWeights),
 }
 
 impl AnalogController {
@@ -60,6 +88,602 @@ impl AnalogController {
 
         Ok(Self::PDFiltered(p_controller, d_controller))
     }
+
+    /// two degree of freedom PID controller, 
+    ///
+    /// the parameters can be given in ISA, parallel or series form,
+    /// and the derivative filter time is alpha tau_D.
+    /// Setpoint weights only matter when the controller is given the 
+    /// setpoint and measurement separately 
+    /// (set_setpoint_and_measurement_and_calc or 
+    /// set_measurement_and_calc), given just the error it acts 
+    /// like a normal PID controller
+    pub fn new_two_degrees_of_freedom_pid(structure: PidStructure,
+        alpha: Ratio,
+        setpoint_weights: SetpointWeights,
+    ) -> Result<Self,ChemEngProcessControlSimulatorError> {
+
+        let (controller_gain, integral_time, derivative_time) = 
+            structure.isa_parameters()?;
+
+        let p_controller = ProportionalController::new(controller_gain)?;
+        let i_controller = IntegralController::new(controller_gain,
+            integral_time)?;
+        let d_controller = FilteredDerivativeController::new(
+            controller_gain, derivative_time, alpha)?;
+
+        Ok(Self::PIDTwoDegreesOfFreedom(p_controller, i_controller, 
+                d_controller, setpoint_weights))
+    }
+
+    /// setpoint weights (b, c), if this is a two degree of 
+    /// freedom controller
+    pub fn setpoint_weights(&self) -> Option<SetpointWeights> {
+        match self {
+            AnalogController::PIDTwoDegreesOfFreedom(_, _, _, 
+                setpoint_weights) => Some(*setpoint_weights),
+            _ => None,
+        }
+    }
+
+    /// numerator coefficients of the controller transfer function,
+    /// descending powers of s with coefficients of s^k in seconds^k
+    ///
+    /// the proportional, integral and derivative parts are added
+    /// together, eg. for a PI controller
+    ///
+    /// Kc + Kc/(tau_I s) = (Kc tau_I s + Kc)/(tau_I s)
+    pub fn numerator(&self) -> Vec<f64> {
+        self.polynomials().0
+    }
+
+    /// denominator coefficients of the controller transfer function,
+    /// descending powers of s with coefficients of s^k in seconds^k
+    pub fn denominator(&self) -> Vec<f64> {
+        self.polynomials().1
+    }
+
+    /// dead time of the controller
+    pub fn dead_time(&self) -> Time {
+        match self {
+            AnalogController::PIDFiltered(p_controller, _, _) => {
+                p_controller.transfer_fn.dead_time()
+            },
+            AnalogController::PI(p_controller, _) => {
+                p_controller.transfer_fn.dead_time()
+            },
+            AnalogController::P(p_controller) => {
+                p_controller.transfer_fn.dead_time()
+            },
+            AnalogController::PDFiltered(p_controller, _) => {
+                p_controller.transfer_fn.dead_time()
+            },
+            AnalogController::IntegralStandalone(i_controller) => {
+                i_controller.delay_function.dead_time()
+            },
+            AnalogController::DerivativeFilteredStandalone(d_controller) => {
+                d_controller.transfer_fn.dead_time()
+            },
+            AnalogController::PIDTwoDegreesOfFreedom(p_controller, _, _, _) => {
+                p_controller.transfer_fn.dead_time()
+            },
+        }
+    }
+
+    /// limits the controller output, eg. to a valve opening 
+    /// between 0 and 1
+    ///
+    /// any controller can be limited, in every mode. For 
+    /// controllers with integral action (PI, PID and integral 
+    /// only), the integral is also kept from winding up, with 
+    /// conditional integration unless changed with set_anti_windup.
+    pub fn set_output_limits(&mut self, lower_limit: Ratio,
+        upper_limit: Ratio) -> Result<(), ChemEngProcessControlSimulatorError> {
+        let output_limits = OutputLimits::new(lower_limit, upper_limit)?;
+        self.mode_settings_mut().output_limits = Some(output_limits);
+        Ok(())
+    }
+
+    /// removes the output limits
+    pub fn remove_output_limits(&mut self) {
+        self.mode_settings_mut().output_limits = None;
+    }
+
+    /// output limits, if any
+    pub fn output_limits(&self) -> Option<OutputLimits> {
+        self.mode_settings().output_limits
+    }
+
+    /// chooses what happens to the integral term when the output 
+    /// is at a limit, 
+    /// this only does anything once output limits are set, apart 
+    /// from external reset feedback, which also deals with 
+    /// actuators limiting themselves
+    pub fn set_anti_windup(&mut self, anti_windup: AntiWindup)
+    -> Result<(), ChemEngProcessControlSimulatorError> {
+        if let AntiWindup::BackCalculation { tracking_time } = anti_windup {
+            if tracking_time.get::<second>() <= 0.0 {
+                return Err(ChemEngProcessControlSimulatorError::
+                    GenericStringError(
+                        "tracking time must be positive".to_owned()));
+            }
+        }
+        self.integral_controller_mut()?.anti_windup = anti_windup;
+        Ok(())
+    }
+
+    /// measured actuator position for AntiWindup::ExternalResetFeedback,
+    /// to be given every timestep before set_user_input_and_calc
+    ///
+    /// it is only used for the next timestep, so a position which 
+    /// is no longer updated does not hang around. Without one, the 
+    /// controller output is fed back instead, which is the same as 
+    /// a PI controller (with limits, if any).
+    pub fn set_external_reset_feedback(&mut self, 
+        actuator_position: Ratio) -> Result<(), ChemEngProcessControlSimulatorError> {
+        self.integral_controller_mut()?.external_reset_feedback = 
+            Some(actuator_position);
+        Ok(())
+    }
+
+    /// switches between manual, auto and cascade without bumping 
+    /// the output
+    ///
+    /// going back to auto from manual is only bumpless for 
+    /// controllers with integral action, as the integral is what 
+    /// gets re-initialised. Without one, the output goes straight 
+    /// back to what the controller works out from the error.
+    pub fn set_mode(&mut self, mode: ControllerMode) {
+        self.mode_settings_mut().set_mode(mode);
+    }
+
+    /// current mode
+    pub fn mode(&self) -> ControllerMode {
+        self.mode_settings().mode
+    }
+
+    /// output used in manual, set by the operator
+    pub fn set_manual_output(&mut self, manual_output: Ratio) {
+        self.mode_settings_mut().manual_output = manual_output;
+    }
+
+    /// local setpoint, used in auto (and manual)
+    pub fn set_setpoint(&mut self, setpoint: Ratio) {
+        self.mode_settings_mut().local_setpoint = setpoint;
+    }
+
+    /// remote setpoint, used in cascade
+    pub fn set_remote_setpoint(&mut self, remote_setpoint: Ratio) {
+        self.mode_settings_mut().remote_setpoint = remote_setpoint;
+    }
+
+    /// setpoint which the controller is acting on, 
+    /// depends on the mode
+    pub fn setpoint(&self) -> Ratio {
+        self.mode_settings().active_setpoint()
+    }
+
+    /// whether the local setpoint follows the measurement in 
+    /// manual (on by default)
+    pub fn set_setpoint_tracking(&mut self, setpoint_tracking: bool) {
+        self.mode_settings_mut().setpoint_tracking = setpoint_tracking;
+    }
+
+    /// controller output at the last timestep
+    pub fn last_output(&self) -> Ratio {
+        self.mode_settings().last_output
+    }
+
+    /// takes in the measurement (process value) rather than the 
+    /// error, and uses the setpoint for the current mode
+    ///
+    /// in manual, with setpoint tracking on, the local setpoint 
+    /// is set to the measurement first
+    pub fn set_measurement_and_calc(&mut self, 
+        measurement: Ratio,
+        time_of_input: Time) -> Result<Ratio, 
+    ChemEngProcessControlSimulatorError> {
+
+        let mode_settings = self.mode_settings_mut();
+        if mode_settings.mode == ControllerMode::Manual 
+            && mode_settings.setpoint_tracking {
+            mode_settings.local_setpoint = measurement;
+        }
+        let setpoint = mode_settings.active_setpoint();
+
+        self.set_setpoint_and_measurement_and_calc(setpoint, 
+            measurement, time_of_input)
+    }
+
+    /// takes in the setpoint and measurement separately, 
+    ///
+    /// for a two degree of freedom controller, the proportional 
+    /// term acts on b r - y, the derivative term on c r - y and the 
+    /// integral term on r - y. Every other controller acts on 
+    /// the error r - y.
+    pub fn set_setpoint_and_measurement_and_calc(&mut self,
+        setpoint: Ratio,
+        measurement: Ratio,
+        time_of_input: Time) -> Result<Ratio,
+    ChemEngProcessControlSimulatorError> {
+
+        let output_limits = self.output_limits();
+        match self {
+            AnalogController::PIDTwoDegreesOfFreedom(proportional_controller,
+                integral_controller, filtered_derivative_controller,
+                setpoint_weights) => {
+                    let proportional_error = 
+                        setpoint_weights.proportional_weight * setpoint 
+                        - measurement;
+                    let derivative_error = 
+                        setpoint_weights.derivative_weight * setpoint 
+                        - measurement;
+
+                    let p_output = proportional_controller
+                        .set_user_input_and_calc(proportional_error, time_of_input)?;
+                    let d_output = filtered_derivative_controller
+                        .set_user_input_and_calc(derivative_error, time_of_input)?;
+
+                    let output = integral_controller
+                        .set_user_input_and_calc_with_limits(
+                            setpoint - measurement, time_of_input, 
+                            p_output + d_output, output_limits)?;
+                    Ok(self.apply_mode(output, p_output + d_output, 
+                            time_of_input))
+                },
+            _ => {
+                self.set_user_input_and_calc(setpoint - measurement, 
+                    time_of_input)
+            },
+        }
+    }
+
+    /// changes K_c, tau_I and tau_D mid simulation, without 
+    /// rebuilding the controller
+    ///
+    /// the proportional and derivative terms take the new tuning
+    /// straight away, the derivative filter keeping its state. For 
+    /// controllers with integral action, the integral keeps what 
+    /// it has accumulated and takes up the jump in the other 
+    /// terms, so the output does not bump. Without integral action 
+    /// the output jumps with the proportional term.
+    ///
+    /// tau_I is ignored if there is no integral action and tau_D 
+    /// if there is no derivative action. A tau_D of zero switches 
+    /// the derivative action off, eg. for a PID controller from a 
+    /// tuning rule giving PI settings. The derivative filter 
+    /// ratio alpha stays as it is.
+    pub fn retune(&mut self, controller_gain: Ratio,
+        integral_time: Time,
+        derivative_time: Time,
+        time: Time) -> Result<(), ChemEngProcessControlSimulatorError> {
+
+        let (proportional_controller, integral_controller,
+            derivative_controller) = match self {
+            AnalogController::PIDFiltered(proportional_controller,
+                integral_controller, derivative_controller) => {
+                (Some(proportional_controller), Some(integral_controller),
+                    Some(derivative_controller))
+            },
+            AnalogController::PI(proportional_controller,
+                integral_controller) => {
+                (Some(proportional_controller), Some(integral_controller),
+                    None)
+            },
+            AnalogController::P(proportional_controller) => {
+                (Some(proportional_controller), None, None)
+            },
+            AnalogController::PDFiltered(proportional_controller,
+                derivative_controller) => {
+                (Some(proportional_controller), None,
+                    Some(derivative_controller))
+            },
+            AnalogController::IntegralStandalone(integral_controller) => {
+                (None, Some(integral_controller), None)
+            },
+            AnalogController::DerivativeFilteredStandalone(
+                derivative_controller) => {
+                (None, None, Some(derivative_controller))
+            },
+            AnalogController::PIDTwoDegreesOfFreedom(proportional_controller,
+                integral_controller, derivative_controller, _) => {
+                (Some(proportional_controller), Some(integral_controller),
+                    Some(derivative_controller))
+            },
+        };
+
+        // check everything before changing anything
+        if integral_controller.is_some() 
+            && integral_time.get::<second>() <= 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "integral time must be positive".to_owned()));
+        }
+        if derivative_controller.is_some() 
+            && derivative_time.get::<second>() < 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "derivative time must not be negative".to_owned()));
+        }
+
+        // change in the proportional and derivative output
+        let mut output_change = Ratio::ZERO;
+        if let Some(proportional_controller) = proportional_controller {
+            let previous_output = proportional_controller.transfer_fn
+                .calc_without_input_change(time)?;
+            proportional_controller.retune(controller_gain, time)?;
+            output_change += proportional_controller.transfer_fn
+                .calc_without_input_change(time)? - previous_output;
+        }
+        if let Some(derivative_controller) = derivative_controller {
+            let previous_output = derivative_controller.transfer_fn
+                .calc_without_input_change(time)?;
+            let alpha = derivative_controller.alpha;
+            derivative_controller.retune(controller_gain, derivative_time,
+                alpha, time)?;
+            output_change += derivative_controller.transfer_fn
+                .calc_without_input_change(time)? - previous_output;
+        }
+
+        if let Some(integral_controller) = integral_controller {
+            integral_controller.retune(controller_gain, integral_time, time)?;
+            let integral_output = integral_controller.ramp_function
+                .current_output();
+            integral_controller.ramp_function.set_output(
+                integral_output - output_change, time);
+        }
+
+        Ok(())
+    }
+
+    /// changes K_c mid simulation, see retune, 
+    /// tau_I and tau_D stay as they are
+    pub fn set_controller_gain(&mut self, controller_gain: Ratio,
+        time: Time) -> Result<(), ChemEngProcessControlSimulatorError> {
+        let integral_time = self.integral_time().unwrap_or(Time::ZERO);
+        let derivative_time = self.derivative_time().unwrap_or(Time::ZERO);
+        self.retune(controller_gain, integral_time, derivative_time, time)
+    }
+
+    /// changes tau_I mid simulation, the integral keeps what it
+    /// has accumulated so the output does not bump
+    pub fn set_integral_time(&mut self, integral_time: Time,
+        time: Time) -> Result<(), ChemEngProcessControlSimulatorError> {
+        self.integral_controller_mut()?;
+        let controller_gain = self.controller_gain()?;
+        let derivative_time = self.derivative_time().unwrap_or(Time::ZERO);
+        self.retune(controller_gain, integral_time, derivative_time, time)
+    }
+
+    /// K_c of the controller
+    pub fn controller_gain(&self) -> Result<Ratio, 
+    ChemEngProcessControlSimulatorError> {
+        match self {
+            AnalogController::PIDFiltered(proportional_controller, _, _) 
+                | AnalogController::PI(proportional_controller, _)
+                | AnalogController::P(proportional_controller)
+                | AnalogController::PDFiltered(proportional_controller, _)
+                | AnalogController::PIDTwoDegreesOfFreedom(
+                    proportional_controller, _, _, _) => {
+                proportional_controller.transfer_fn.steady_state_gain()
+            },
+            AnalogController::IntegralStandalone(integral_controller) => {
+                Ok(integral_controller.controller_gain())
+            },
+            AnalogController::DerivativeFilteredStandalone(
+                derivative_controller) => {
+                Ok(derivative_controller.controller_gain())
+            },
+        }
+    }
+
+    /// tau_I, if there is integral action
+    pub fn integral_time(&self) -> Option<Time> {
+        self.integral_controller()
+            .map(|integral_controller| integral_controller.integral_time())
+    }
+
+    /// tau_D, if there is derivative action
+    pub fn derivative_time(&self) -> Option<Time> {
+        match self {
+            AnalogController::PIDFiltered(_, _, derivative_controller) 
+                | AnalogController::PDFiltered(_, derivative_controller)
+                | AnalogController::DerivativeFilteredStandalone(
+                    derivative_controller)
+                | AnalogController::PIDTwoDegreesOfFreedom(
+                    _, _, derivative_controller, _) => {
+                Some(derivative_controller.derivative_time())
+            },
+            _ => None,
+        }
+    }
+
+    /// the integral part of the controller, if there is one
+    fn integral_controller(&self) -> Option<&IntegralController> {
+        match self {
+            AnalogController::PIDFiltered(_, integral_controller, _) => {
+                Some(integral_controller)
+            },
+            AnalogController::PI(_, integral_controller) => {
+                Some(integral_controller)
+            },
+            AnalogController::IntegralStandalone(integral_controller) => {
+                Some(integral_controller)
+            },
+            AnalogController::PIDTwoDegreesOfFreedom(_, integral_controller, 
+                _, _) => {
+                Some(integral_controller)
+            },
+            _ => None,
+        }
+    }
+
+    /// the integral part of the controller, if there is one
+    fn integral_controller_mut(&mut self) -> Result<&mut IntegralController, 
+    ChemEngProcessControlSimulatorError> {
+        match self {
+            AnalogController::PIDFiltered(_, integral_controller, _) => {
+                Ok(integral_controller)
+            },
+            AnalogController::PI(_, integral_controller) => {
+                Ok(integral_controller)
+            },
+            AnalogController::IntegralStandalone(integral_controller) => {
+                Ok(integral_controller)
+            },
+            AnalogController::PIDTwoDegreesOfFreedom(_, integral_controller, 
+                _, _) => {
+                Ok(integral_controller)
+            },
+            _ => Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "controller has no integral action".to_owned())),
+        }
+    }
+
+    /// mode, setpoints and manual output, kept on the leading 
+    /// part of the controller
+    fn mode_settings(&self) -> &ModeSettings {
+        match self {
+            AnalogController::PIDFiltered(p_controller, _, _) 
+                | AnalogController::PI(p_controller, _)
+                | AnalogController::P(p_controller)
+                | AnalogController::PDFiltered(p_controller, _)
+                | AnalogController::PIDTwoDegreesOfFreedom(
+                    p_controller, _, _, _) => &p_controller.mode_settings,
+            AnalogController::IntegralStandalone(i_controller) => {
+                &i_controller.mode_settings
+            },
+            AnalogController::DerivativeFilteredStandalone(d_controller) => {
+                &d_controller.mode_settings
+            },
+        }
+    }
+
+    /// mode, setpoints and manual output, kept on the leading 
+    /// part of the controller
+    fn mode_settings_mut(&mut self) -> &mut ModeSettings {
+        match self {
+            AnalogController::PIDFiltered(p_controller, _, _) 
+                | AnalogController::PI(p_controller, _)
+                | AnalogController::P(p_controller)
+                | AnalogController::PDFiltered(p_controller, _)
+                | AnalogController::PIDTwoDegreesOfFreedom(
+                    p_controller, _, _, _) => &mut p_controller.mode_settings,
+            AnalogController::IntegralStandalone(i_controller) => {
+                &mut i_controller.mode_settings
+            },
+            AnalogController::DerivativeFilteredStandalone(d_controller) => {
+                &mut d_controller.mode_settings
+            },
+        }
+    }
+
+    /// limits the output, and in manual, the output is the manual 
+    /// output, and the integral (if there is one) is re-initialised 
+    /// every timestep so that it makes up the difference. Switching 
+    /// back to auto then does not bump the output.
+    ///
+    /// output is what the controller works out in auto, 
+    /// proportional_and_derivative_output is the part of it 
+    /// not from the integral
+    fn apply_mode(&mut self, output: Ratio, 
+        proportional_and_derivative_output: Ratio,
+        time_of_input: Time) -> Ratio {
+
+        let mode_settings = *self.mode_settings();
+        let mut output = output;
+
+        if mode_settings.mode == ControllerMode::Manual {
+            output = mode_settings.manual_output;
+        }
+        // the integral has already been limited, but controllers 
+        // without one (P and PD) have not
+        if let Some(output_limits) = mode_settings.output_limits {
+            output = output_limits.clamp(output);
+        }
+
+        if mode_settings.mode == ControllerMode::Manual {
+            if let Ok(integral_controller) = self.integral_controller_mut() {
+                integral_controller.ramp_function.set_output(
+                    output - proportional_and_derivative_output, 
+                    time_of_input);
+            }
+        }
+
+        self.mode_settings_mut().last_output = output;
+        output
+    }
+
+    /// (numerator, denominator) of the whole controller
+    fn polynomials(&self) -> (Vec<f64>, Vec<f64>) {
+
+        // Kc, the proportional controller is stored as 
+        // (Kc s + Kc)/(s + 1)
+        let proportional_polynomials = 
+            |p_controller: &ProportionalController| {
+            let numerator = p_controller.transfer_fn.numerator();
+            let denominator = p_controller.transfer_fn.denominator();
+            (vec![numerator[1]/denominator[1]], vec![1.0])
+        };
+        // Kc/(tau_I s)
+        let integral_polynomials = 
+            |i_controller: &IntegralController| {
+            let ramp_function = &i_controller.ramp_function;
+            (vec![ramp_function.controller_gain.get::<ratio>()],
+                vec![ramp_function.integral_time.get::<second>(), 0.0])
+        };
+        // Kc tau_D s/(alpha tau_D s + 1)
+        let derivative_polynomials = 
+            |d_controller: &FilteredDerivativeController| {
+            (d_controller.transfer_fn.numerator(),
+                d_controller.transfer_fn.denominator())
+        };
+
+        // N1/D1 + N2/D2 = (N1 D2 + N2 D1)/(D1 D2)
+        let add_fractions = |first_fraction: (Vec<f64>, Vec<f64>),
+            second_fraction: (Vec<f64>, Vec<f64>)| {
+            (polynomials::add(
+                    &polynomials::multiply(&first_fraction.0, &second_fraction.1),
+                    &polynomials::multiply(&second_fraction.0, &first_fraction.1)),
+                polynomials::multiply(&first_fraction.1, &second_fraction.1))
+        };
+
+        match self {
+            AnalogController::PIDFiltered(p_controller, 
+                i_controller, d_controller) => {
+                add_fractions(
+                    add_fractions(proportional_polynomials(p_controller),
+                        integral_polynomials(i_controller)),
+                    derivative_polynomials(d_controller))
+            },
+            AnalogController::PI(p_controller, i_controller) => {
+                add_fractions(proportional_polynomials(p_controller),
+                    integral_polynomials(i_controller))
+            },
+            AnalogController::P(p_controller) => {
+                proportional_polynomials(p_controller)
+            },
+            AnalogController::PDFiltered(p_controller, d_controller) => {
+                add_fractions(proportional_polynomials(p_controller),
+                    derivative_polynomials(d_controller))
+            },
+            AnalogController::IntegralStandalone(i_controller) => {
+                integral_polynomials(i_controller)
+            },
+            AnalogController::DerivativeFilteredStandalone(d_controller) => {
+                derivative_polynomials(d_controller)
+            },
+            // the feedback part, ie. b = c = 1
+            AnalogController::PIDTwoDegreesOfFreedom(p_controller, 
+                i_controller, d_controller, _) => {
+                add_fractions(
+                    add_fractions(proportional_polynomials(p_controller),
+                        integral_polynomials(i_controller)),
+                    derivative_polynomials(d_controller))
+            },
+        }
+    }
 }
 
 impl TransferFnTraits for AnalogController {
@@ -90,6 +714,12 @@ impl TransferFnTraits for AnalogController {
                 filtered_derivative_controller.set_dead_time(dead_time)
 
             },
+            AnalogController::PIDTwoDegreesOfFreedom(proportional_controller,
+                integral_controller, filtered_derivative_controller, _) => {
+                    proportional_controller.set_dead_time(dead_time);
+                    integral_controller.set_dead_time(dead_time);
+                    filtered_derivative_controller.set_dead_time(dead_time)
+                },
 
         }
     }
@@ -98,33 +728,41 @@ impl TransferFnTraits for AnalogController {
         user_input: uom::si::f64::Ratio,
         time_of_input: uom::si::f64::Time) -> Result<uom::si::f64::Ratio, 
     super::errors::ChemEngProcessControlSimulatorError> {
-        match self {
+        let output_limits = self.output_limits();
+        // (output in auto, proportional and derivative part of it)
+        let (output, proportional_and_derivative_output) = match self {
             AnalogController::PIDFiltered(proportional_controller,
                 integral_controller, filtered_derivative_controller) => {
                     let p_output = 
                     proportional_controller.set_user_input_and_calc(user_input, time_of_input)?;
-                    let i_output = 
-                    integral_controller.set_user_input_and_calc(user_input, time_of_input)?;
                     let d_output = 
                     filtered_derivative_controller.set_user_input_and_calc(user_input, time_of_input)?;
 
-                    return Ok(p_output + i_output + d_output);
+                    // the integral term goes last, as it is limited 
+                    // based on the whole controller output
+                    (integral_controller.set_user_input_and_calc_with_limits(
+                        user_input, time_of_input, p_output + d_output,
+                        output_limits)?,
+                        p_output + d_output)
                 },
             AnalogController::PI(proportional_controller, integral_controller) => {
                 let p_output = 
                 proportional_controller.set_user_input_and_calc(user_input, time_of_input)?;
-                let i_output = 
-                integral_controller.set_user_input_and_calc(user_input, time_of_input)?;
-                return Ok(p_output + i_output);
+                (integral_controller.set_user_input_and_calc_with_limits(
+                    user_input, time_of_input, p_output, output_limits)?, 
+                    p_output)
             },
             AnalogController::P(ctrl) => {
-                ctrl.set_user_input_and_calc(user_input, time_of_input)
+                let p_output = ctrl.set_user_input_and_calc(user_input, time_of_input)?;
+                (p_output, p_output)
             },
             AnalogController::IntegralStandalone(ctrl) => {
-                ctrl.set_user_input_and_calc(user_input, time_of_input)
+                (ctrl.set_user_input_and_calc_with_limits(user_input, 
+                    time_of_input, Ratio::ZERO, output_limits)?, Ratio::ZERO)
             },
             AnalogController::DerivativeFilteredStandalone(ctrl) => {
-                ctrl.set_user_input_and_calc(user_input, time_of_input)
+                let d_output = ctrl.set_user_input_and_calc(user_input, time_of_input)?;
+                (d_output, d_output)
             },
             AnalogController::PDFiltered(proportional_controller, 
                 filtered_derivative_controller) => {
@@ -133,10 +771,27 @@ impl TransferFnTraits for AnalogController {
                     let d_output = 
                     filtered_derivative_controller.set_user_input_and_calc(user_input, time_of_input)?;
 
-                    return Ok(p_output + d_output);
+                    (p_output + d_output, p_output + d_output)
                 },
+            // only the error is known here, so this is the same 
+            // as b = c = 1
+            AnalogController::PIDTwoDegreesOfFreedom(proportional_controller,
+                integral_controller, filtered_derivative_controller, _) => {
+                    let p_output = 
+                    proportional_controller.set_user_input_and_calc(user_input, time_of_input)?;
+                    let d_output = 
+                    filtered_derivative_controller.set_user_input_and_calc(user_input, time_of_input)?;
 
-        }
+                    (integral_controller.set_user_input_and_calc_with_limits(
+                        user_input, time_of_input, p_output + d_output,
+                        output_limits)?,
+                        p_output + d_output)
+                },
+
+        };
+
+        Ok(self.apply_mode(output, proportional_and_derivative_output, 
+                time_of_input))
     }
 
     fn spawn_writer(&mut self, name: String) -> Result<csv::Writer<std::fs::File>,
@@ -163,6 +818,9 @@ impl TransferFnTraits for AnalogController {
                 _filtered_derivative_controller) => {
                 title_string += "_PD_controller.csv"
             },
+            AnalogController::PIDTwoDegreesOfFreedom(_, _, _, _) => {
+                title_string += "_2DOF_PID_controller.csv"
+            },
         }
         let wtr = Writer::from_path(title_string)?;
         Ok(wtr)
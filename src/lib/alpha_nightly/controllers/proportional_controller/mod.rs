@@ -4,7 +4,7 @@ use uom::si::ratio::ratio;
 use uom::si::time::second;
 use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
 
-use super::AnalogController;
+use super::{AnalogController, ModeSettings};
 
 /// a filtered derivative controller 
 ///
@@ -19,6 +19,8 @@ use super::AnalogController;
 #[derive(Debug,PartialEq, PartialOrd, Clone)]
 pub struct ProportionalController{
     pub transfer_fn: TransferFnFirstOrder,
+    /// mode settings of the AnalogController this is part of
+    pub(crate) mode_settings: ModeSettings,
 }
 
 impl ProportionalController {
@@ -40,7 +42,15 @@ impl ProportionalController {
         let b2 = Ratio::new::<ratio>(1.0);
         let transfer_fn = TransferFnFirstOrder::new(a1, b1, a2, b2).unwrap();
 
-        Ok(Self { transfer_fn })
+        Ok(Self { transfer_fn, mode_settings: ModeSettings::default() })
+    }
+
+    /// changes K_c at the given time, the output jumps to 
+    /// K_c times the current input
+    pub(crate) fn retune(&mut self, controller_gain: Ratio,
+        time: Time) -> Result<(), ChemEngProcessControlSimulatorError> {
+        self.transfer_fn.retune_parameters(controller_gain, controller_gain,
+            Time::new::<second>(1.0), time)
     }
 }
 impl Default for ProportionalController {
@@ -72,7 +82,7 @@ impl Default for ProportionalController {
         let a2 = Time::new::<second>(1.0);
         let transfer_fn = TransferFnFirstOrder::new(a1, b1, a2, b2).unwrap();
 
-        return Self { transfer_fn };
+        return Self { transfer_fn, mode_settings: ModeSettings::default() };
 
     }
 }
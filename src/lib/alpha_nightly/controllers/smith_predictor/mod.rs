@@ -0,0 +1,172 @@
+use csv::Writer;
+use uom::si::f64::*;
+use uom::si::ratio::ratio;
+use uom::si::time::second;
+use uom::ConstZero;
+
+use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
+use crate::alpha_nightly::transfer_fn_wrapper_and_enums::{TransferFn,
+    TransferFnTraits};
+use super::AnalogController;
+
+/// Smith predictor, for processes with long dead times
+///
+/// the controller acts on
+///
+/// e' = e - (G(s) - G(s) exp(-theta s)) u
+///
+/// where G(s) is the internal process model without dead time
+/// and theta the model dead time. If the model is right, the
+/// feedback from the real process cancels the delayed model output,
+/// so the controller sees the process as if it had no dead time and
+/// can be tuned for that. The setpoint response is then the
+/// delay free closed loop response, delayed by theta.
+///
+/// it takes in the error and gives out the controller output,
+/// just like an AnalogController, so it drops into the same loop.
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub struct SmithPredictor {
+    pub(crate) controller: AnalogController,
+    /// G(s), the model without dead time
+    pub(crate) undelayed_model: TransferFn,
+    /// G(s) exp(-theta s)
+    pub(crate) delayed_model: TransferFn,
+    pub(crate) model_dead_time: Time,
+    /// controller output at the last timestep,
+    /// which is what the models see
+    pub(crate) last_output: Ratio,
+}
+
+impl SmithPredictor {
+
+    /// the model dead time is taken from the process model,
+    /// and can be changed with set_dead_time before the loop
+    /// starts running
+    pub fn new(controller: AnalogController,
+        process_model: TransferFn) -> Self {
+
+        let model_dead_time = process_model.dead_time();
+        let mut undelayed_model = process_model.clone();
+        undelayed_model.set_dead_time(Time::ZERO);
+
+        Self {
+            controller,
+            undelayed_model,
+            delayed_model: process_model,
+            model_dead_time,
+            last_output: Ratio::ZERO,
+        }
+    }
+
+    /// theta, the dead time of the internal model
+    pub fn model_dead_time(&self) -> Time {
+        self.model_dead_time
+    }
+
+    /// the primary controller, eg. to change its limits or mode
+    pub fn controller_mut(&mut self) -> &mut AnalogController {
+        &mut self.controller
+    }
+
+    pub fn controller(&self) -> &AnalogController {
+        &self.controller
+    }
+}
+
+impl TransferFnTraits for SmithPredictor {
+    /// sets the dead time of the internal model
+    fn set_dead_time(&mut self, dead_time: Time) {
+        self.model_dead_time = dead_time;
+        self.delayed_model.set_dead_time(dead_time);
+    }
+
+    /// user input is the error, r - y, from the real process
+    fn set_user_input_and_calc(&mut self,
+        user_input: Ratio,
+        time_of_input: Time) -> Result<Ratio,
+    ChemEngProcessControlSimulatorError> {
+
+        let undelayed_prediction = self.undelayed_model
+            .set_user_input_and_calc(self.last_output, time_of_input)?;
+        let delayed_prediction = self.delayed_model
+            .set_user_input_and_calc(self.last_output, time_of_input)?;
+
+        let corrected_error = user_input
+            - (undelayed_prediction - delayed_prediction);
+        let output = self.controller.set_user_input_and_calc(
+            corrected_error, time_of_input)?;
+        self.last_output = output;
+
+        Ok(output)
+    }
+
+    fn spawn_writer(&mut self, name: String) -> Result<Writer<std::fs::File>,
+    ChemEngProcessControlSimulatorError> {
+        let title_string: String = name + "_smith_predictor.csv";
+        let wtr = Writer::from_path(title_string)?;
+        Ok(wtr)
+    }
+
+    fn csv_write_values(&mut self,
+        wtr: &mut Writer<std::fs::File>,
+        time: Time,
+        input: Ratio,
+        output: Ratio) -> Result<(),
+    ChemEngProcessControlSimulatorError> {
+        let current_time_string = time.get::<second>().to_string();
+        let input_string = input.get::<ratio>().to_string();
+        let output_string = output.get::<ratio>().to_string();
+
+        wtr.write_record(&[current_time_string,
+            input_string,
+            output_string])?;
+
+        wtr.flush().unwrap();
+        Ok(())
+    }
+}
+
+#[test]
+pub fn test_smith_predictor(){
+    use crate::alpha_nightly::transfer_fn_wrapper_and_enums::TransferFnFirstOrder;
+
+    // 1/(10 s + 1) with 10 s dead time
+    let mut process = TransferFnFirstOrder::new(Time::ZERO,
+        Ratio::new::<ratio>(1.0), Time::new::<second>(10.0),
+        Ratio::new::<ratio>(1.0)).unwrap();
+    process.set_dead_time(Time::new::<second>(10.0));
+
+    // PI tuned for the process without dead time, Kc/(tau_I s)
+    // open loop gives 1/(5 s + 1) closed loop
+    let controller = AnalogController::new_pi_controller(
+        Ratio::new::<ratio>(2.0), Time::new::<second>(10.0)).unwrap();
+    let model: TransferFn = TransferFnFirstOrder::new(Time::ZERO,
+        Ratio::new::<ratio>(1.0), Time::new::<second>(10.0),
+        Ratio::new::<ratio>(1.0)).unwrap().into();
+    let mut smith_predictor = SmithPredictor::new(controller, model);
+    smith_predictor.set_dead_time(Time::new::<second>(10.0));
+    assert_eq!(smith_predictor.model_dead_time(), Time::new::<second>(10.0));
+
+    let timestep: f64 = 0.05;
+    let mut measurement = Ratio::ZERO;
+    let mut measurements: Vec<f64> = vec![];
+    for step in 0..=1000 {
+        let time = Time::new::<second>(step as f64 * timestep);
+        let error = Ratio::new::<ratio>(1.0) - measurement;
+        let output = smith_predictor.set_user_input_and_calc(error, time)
+            .unwrap();
+        measurement = process.set_user_input_and_calc(output, time).unwrap();
+        measurements.push(measurement.get::<ratio>());
+    }
+
+    // delay free response, 1 - exp(-t/5), shifted by the dead time
+    for (step, measurement) in measurements.iter().enumerate() {
+        let time = step as f64 * timestep;
+        let expected = if time > 10.0 {
+            1.0 - (-(time - 10.0)/5.0).exp()
+        } else {
+            0.0
+        };
+        approx::assert_abs_diff_eq!(*measurement, expected, epsilon = 0.02);
+    }
+}
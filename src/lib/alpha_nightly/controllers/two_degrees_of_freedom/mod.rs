@@ -0,0 +1,223 @@
+use uom::si::f64::*;
+use uom::si::ratio::ratio;
+use uom::si::time::second;
+use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
+
+/// the usual ways of writing down PID parameters
+///
+/// they are all converted to the ISA form inside the controller.
+/// In every form, the derivative term is filtered with a filter
+/// time of alpha tau_D (using the ISA derivative time).
+#[derive(Debug,PartialEq, PartialOrd, Clone, Copy)]
+pub enum PidStructure {
+    /// ISA (ideal or standard) form,
+    ///
+    /// Kc (1 + 1/(tau_I s) + tau_D s)
+    Isa {
+        controller_gain: Ratio,
+        integral_time: Time,
+        derivative_time: Time,
+    },
+    /// parallel (independent gains) form,
+    ///
+    /// Kp + Ki/s + Kd s
+    Parallel {
+        proportional_gain: Ratio,
+        integral_gain: Frequency,
+        derivative_gain: Time,
+    },
+    /// series (interacting) form, as in older pneumatic and
+    /// many DCS controllers,
+    ///
+    /// Kc' (1 + 1/(tau_I' s)) (1 + tau_D' s)
+    Series {
+        controller_gain: Ratio,
+        integral_time: Time,
+        derivative_time: Time,
+    },
+}
+
+impl PidStructure {
+
+    /// (Kc, tau_I, tau_D) in ISA form
+    ///
+    /// parallel: Kc = Kp, tau_I = Kp/Ki, tau_D = Kd/Kp
+    ///
+    /// series: Kc = Kc' (tau_I' + tau_D')/tau_I',
+    /// tau_I = tau_I' + tau_D', tau_D = tau_I' tau_D'/(tau_I' + tau_D')
+    pub fn isa_parameters(&self) -> Result<(Ratio, Time, Time),
+    ChemEngProcessControlSimulatorError> {
+
+        let (controller_gain, integral_time, derivative_time) = match *self {
+            PidStructure::Isa { controller_gain, integral_time,
+                derivative_time } => {
+                (controller_gain, integral_time, derivative_time)
+            },
+            PidStructure::Parallel { proportional_gain, integral_gain,
+                derivative_gain } => {
+                if proportional_gain.get::<ratio>() == 0.0 {
+                    return Err(ChemEngProcessControlSimulatorError::
+                        GenericStringError(
+                            "parallel form needs a non zero proportional \
+                            gain to convert to ISA form".to_owned()));
+                }
+                (proportional_gain,
+                    proportional_gain/integral_gain,
+                    derivative_gain/proportional_gain)
+            },
+            PidStructure::Series { controller_gain, integral_time,
+                derivative_time } => {
+                let interaction_factor: Ratio =
+                    (integral_time + derivative_time)/integral_time;
+                (controller_gain * interaction_factor,
+                    integral_time + derivative_time,
+                    derivative_time/interaction_factor)
+            },
+        };
+
+        if integral_time.get::<second>() <= 0.0
+            || derivative_time.get::<second>() < 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "integral time must be positive and derivative time \
+                    must not be negative".to_owned()));
+        }
+
+        Ok((controller_gain, integral_time, derivative_time))
+    }
+}
+
+/// setpoint weights for a two degree of freedom PID controller
+///
+/// u = Kc [ (b r - y) + 1/(tau_I s) (r - y)
+/// + tau_D s/(alpha tau_D s + 1) (c r - y) ]
+///
+/// b and c only change how the controller responds to setpoint
+/// changes, the response to disturbances is the same as a normal
+/// PID controller. b < 1 gives less overshoot on setpoint changes,
+/// and c = 0 (derivative on measurement) gets rid of the
+/// derivative kick.
+#[derive(Debug,PartialEq, PartialOrd, Clone, Copy)]
+pub struct SetpointWeights {
+    /// b, weight of the setpoint in the proportional term
+    pub(crate) proportional_weight: Ratio,
+    /// c, weight of the setpoint in the derivative term
+    pub(crate) derivative_weight: Ratio,
+}
+
+impl Default for SetpointWeights {
+    /// b = 1, c = 0, ie. derivative on measurement only
+    fn default() -> Self {
+        Self {
+            proportional_weight: Ratio::new::<ratio>(1.0),
+            derivative_weight: Ratio::new::<ratio>(0.0),
+        }
+    }
+}
+
+impl SetpointWeights {
+
+    /// b is the proportional weight, c is the derivative weight,
+    /// both are usually between 0 and 1
+    pub fn new(proportional_weight: Ratio,
+        derivative_weight: Ratio) -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        if proportional_weight.get::<ratio>() < 0.0
+            || derivative_weight.get::<ratio>() < 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "setpoint weights must not be negative".to_owned()));
+        }
+
+        Ok(Self { proportional_weight, derivative_weight })
+    }
+
+    /// b
+    pub fn proportional_weight(&self) -> Ratio {
+        self.proportional_weight
+    }
+
+    /// c
+    pub fn derivative_weight(&self) -> Ratio {
+        self.derivative_weight
+    }
+}
+
+#[test]
+pub fn test_two_degrees_of_freedom_pid(){
+    use super::AnalogController;
+    use crate::alpha_nightly::transfer_fn_wrapper_and_enums::TransferFnTraits;
+
+    let controller_gain = Ratio::new::<ratio>(2.0);
+    let integral_time = Time::new::<second>(5.0);
+    let derivative_time = Time::new::<second>(1.0);
+    let alpha = Ratio::new::<ratio>(0.1);
+    let isa = PidStructure::Isa { controller_gain, integral_time, 
+        derivative_time };
+
+    // b = c = 1 is the normal PID controller
+    let mut pid = AnalogController::new_filtered_pid_controller(
+        controller_gain, integral_time, derivative_time, alpha).unwrap();
+    let mut two_dof_pid = AnalogController::new_two_degrees_of_freedom_pid(
+        isa, alpha, SetpointWeights::new(Ratio::new::<ratio>(1.0), 
+            Ratio::new::<ratio>(1.0)).unwrap()).unwrap();
+
+    for step in 0..50 {
+        let time = Time::new::<second>(step as f64 * 0.1);
+        let setpoint = Ratio::new::<ratio>(1.0);
+        let measurement = Ratio::new::<ratio>(0.02 * step as f64);
+        let pid_output = pid.set_user_input_and_calc(
+            setpoint - measurement, time).unwrap();
+        let two_dof_output = two_dof_pid.set_setpoint_and_measurement_and_calc(
+            setpoint, measurement, time).unwrap();
+        approx::assert_abs_diff_eq!(pid_output.get::<ratio>(), 
+            two_dof_output.get::<ratio>(), epsilon = 1e-9);
+    }
+
+    // b = 0.5, c = 0, a setpoint step only kicks the output by 
+    // Kc b, and there is no derivative kick
+    let mut two_dof_pid = AnalogController::new_two_degrees_of_freedom_pid(
+        isa, alpha, SetpointWeights::new(Ratio::new::<ratio>(0.5), 
+            Ratio::new::<ratio>(0.0)).unwrap()).unwrap();
+    let first_output = two_dof_pid.set_setpoint_and_measurement_and_calc(
+        Ratio::new::<ratio>(1.0), Ratio::new::<ratio>(0.0), 
+        Time::new::<second>(0.0)).unwrap();
+    approx::assert_abs_diff_eq!(first_output.get::<ratio>(), 2.0 * 0.5, 
+        epsilon = 1e-9);
+
+    // the same controller given only the error acts like b = c = 1
+    assert_eq!(two_dof_pid.setpoint_weights().unwrap().proportional_weight(),
+        Ratio::new::<ratio>(0.5));
+    assert_eq!(two_dof_pid.numerator(), pid.numerator());
+
+    // series form, Kc' = 2, tau_I' = 4 s, tau_D' = 1 s
+    let (series_gain, series_integral_time, series_derivative_time) = 
+        PidStructure::Series { 
+            controller_gain: Ratio::new::<ratio>(2.0),
+            integral_time: Time::new::<second>(4.0),
+            derivative_time: Time::new::<second>(1.0),
+        }.isa_parameters().unwrap();
+    approx::assert_abs_diff_eq!(series_gain.get::<ratio>(), 2.5, 
+        epsilon = 1e-12);
+    approx::assert_abs_diff_eq!(series_integral_time.get::<second>(), 5.0, 
+        epsilon = 1e-12);
+    approx::assert_abs_diff_eq!(series_derivative_time.get::<second>(), 0.8, 
+        epsilon = 1e-12);
+
+    // parallel form, Kp = 2, Ki = 0.5 /s, Kd = 1 s
+    let (parallel_gain, parallel_integral_time, parallel_derivative_time) = 
+        PidStructure::Parallel { 
+            proportional_gain: Ratio::new::<ratio>(2.0),
+            integral_gain: Frequency::new::<uom::si::frequency::hertz>(0.5),
+            derivative_gain: Time::new::<second>(1.0),
+        }.isa_parameters().unwrap();
+    approx::assert_abs_diff_eq!(parallel_gain.get::<ratio>(), 2.0, 
+        epsilon = 1e-12);
+    approx::assert_abs_diff_eq!(parallel_integral_time.get::<second>(), 4.0, 
+        epsilon = 1e-12);
+    approx::assert_abs_diff_eq!(parallel_derivative_time.get::<second>(), 0.5, 
+        epsilon = 1e-12);
+
+    assert!(SetpointWeights::new(Ratio::new::<ratio>(-0.1), 
+            Ratio::new::<ratio>(0.0)).is_err());
+}
@@ -15,9 +15,31 @@ pub enum ChemEngProcessControlSimulatorError {
     #[error("Unstable Damping Factor for Stable Transfer Function")]
     UnstableDampingFactorForStableTransferFunction,
 
+    /// when transfer function is stable when it should be 
+    /// unstable 
+    #[error("Stable Damping Factor for Unstable Transfer Function")]
+    StableDampingFactorForUnstableTransferFunction,
+
+    /// when a second order transfer function has poles on the 
+    /// imaginary axis (undamped) or at the origin (integrating) 
+    /// where the constructor does not take them
+    #[error("Undamped or Integrating Second Order Transfer Function")]
+    UndampedOrIntegratingSecondOrderTransferFunction,
+
+    /// when the numerator has a higher order than the 
+    /// denominator, the step response contains impulses 
+    /// which cannot be simulated
+    #[error("Improper Transfer Function")]
+    ImproperTransferFunction,
+
     #[error("wrong transfer function type")]
     WrongTransferFnType,
 
+    /// when a matrix which must be inverted (eg. a steady state 
+    /// gain matrix) is singular
+    #[error("Singular Matrix")]
+    SingularMatrix,
+
     #[error("csv error")]
     CsvError(csv::Error),
 
@@ -49,9 +71,21 @@ impl Into<String> for ChemEngProcessControlSimulatorError {
                 // thermal hydraulics
                 "unstable damping factor".to_owned()
             },
+            ChemEngProcessControlSimulatorError::StableDampingFactorForUnstableTransferFunction => {
+                "stable damping factor".to_owned()
+            },
+            ChemEngProcessControlSimulatorError::UndampedOrIntegratingSecondOrderTransferFunction => {
+                "undamped or integrating second order transfer function".to_owned()
+            },
+            ChemEngProcessControlSimulatorError::ImproperTransferFunction => {
+                "improper transfer function".to_owned()
+            },
             ChemEngProcessControlSimulatorError::WrongTransferFnType => {
                 "wrong transfer function type".to_owned()
             },
+            ChemEngProcessControlSimulatorError::SingularMatrix => {
+                "singular matrix".to_owned()
+            },
             ChemEngProcessControlSimulatorError::CsvError(err) => {
                 err.to_string()
             },
@@ -0,0 +1,529 @@
+//! frequency response analysis, ie. the complex gain G(jω) over
+//! a range of frequencies
+//!
+//! this is what Bode and Nyquist plots are drawn from. For a loop
+//! transfer function L(s) = Gc(s) Gp(s), the gain margin, phase
+//! margin and peak sensitivity Ms can also be found, which is what
+//! frequency domain tuning methods work with.
+//!
+//! Dead time does not change the magnitude, but adds a phase lag
+//! of ω θ radians, which grows without bound at high frequencies.
+//! The phase here is unwrapped (ie. it is allowed to go past -180
+//! degrees) so that the dead time lag shows up properly.
+
+use csv::Writer;
+use num_complex::Complex;
+use uom::si::angle::{degree, radian};
+use uom::si::angular_velocity::radian_per_second;
+use uom::si::f64::*;
+use uom::si::ratio::ratio;
+use uom::si::time::second;
+
+use super::block_diagrams::{Block, BlockConnection, BlockDiagram, FeedbackSign};
+use super::controllers::AnalogController;
+use super::errors::ChemEngProcessControlSimulatorError;
+use super::numerical_search::{log_bisect, log_golden_section_maximum};
+use super::polynomials;
+use super::transfer_fn_wrapper_and_enums::TransferFn;
+
+/// G(jω) at one frequency
+#[derive(Debug,PartialEq, Clone, Copy)]
+pub struct FrequencyResponsePoint {
+    /// ω, in radians per second
+    pub angular_frequency: AngularVelocity,
+    /// G(jω), including the dead time
+    pub complex_gain: Complex<f64>,
+    /// unwrapped phase of G(jω), including the dead time lag
+    pub phase: Angle,
+}
+
+impl FrequencyResponsePoint {
+
+    /// |G(jω)|
+    pub fn magnitude(&self) -> Ratio {
+        Ratio::new::<ratio>(self.complex_gain.norm())
+    }
+
+    /// 20 log10 |G(jω)|
+    pub fn magnitude_decibels(&self) -> f64 {
+        20.0 * self.complex_gain.norm().log10()
+    }
+
+    /// Re G(jω), for Nyquist plots
+    pub fn real_part(&self) -> Ratio {
+        Ratio::new::<ratio>(self.complex_gain.re)
+    }
+
+    /// Im G(jω), for Nyquist plots
+    pub fn imaginary_part(&self) -> Ratio {
+        Ratio::new::<ratio>(self.complex_gain.im)
+    }
+}
+
+/// stability margins of a loop transfer function L(s)
+///
+/// None means that there is no crossover in the frequency range
+/// given, eg. a first order loop never reaches -180 degrees, so
+/// its gain margin is infinite
+#[derive(Debug,PartialEq, PartialOrd, Clone, Copy)]
+pub struct StabilityMargins {
+    /// 1/|L(jω_180)|, by how much the loop gain can be increased
+    /// before the loop becomes unstable
+    pub gain_margin: Option<Ratio>,
+    /// ω_180, where the phase of L is -180 degrees
+    pub phase_crossover_frequency: Option<AngularVelocity>,
+    /// 180 degrees + phase of L(jω_c), how much extra phase lag
+    /// (eg. dead time) the loop can take
+    pub phase_margin: Option<Angle>,
+    /// ω_c, where |L| is 1
+    pub gain_crossover_frequency: Option<AngularVelocity>,
+    /// Ms, the largest value of |1/(1 + L(jω))|,
+    /// which is one over the closest distance of the Nyquist
+    /// curve to -1. Values of 1.2 to 2 are usually recommended.
+    pub peak_sensitivity: Ratio,
+    /// frequency where the peak sensitivity is
+    pub peak_sensitivity_frequency: AngularVelocity,
+}
+
+/// for anything with a transfer function that can be evaluated at
+/// s = jω
+///
+/// G(s) = G_r(s) exp(-θ s)
+///
+/// G_r(jω) and θ are supplied, the rest comes
+/// from the default methods
+pub trait FrequencyResponseTraits {
+
+    /// G_r(jω), ie. the complex gain without the dead time θ
+    fn complex_gain_without_dead_time(&self,
+        angular_frequency: AngularVelocity) -> Complex<f64>;
+
+    /// θ, dead time multiplying the whole transfer function
+    fn frequency_response_dead_time(&self) -> Time;
+
+    /// G(jω), including the dead time
+    fn complex_gain(&self, angular_frequency: AngularVelocity) -> Complex<f64> {
+        let dead_time_lag: f64 = angular_frequency.get::<radian_per_second>()
+            * self.frequency_response_dead_time().get::<second>();
+        self.complex_gain_without_dead_time(angular_frequency)
+            * Complex::from_polar(1.0, -dead_time_lag)
+    }
+
+    /// G(jω) over a frequency grid, which should be in
+    /// ascending order (see logarithmic_frequency_grid)
+    ///
+    /// the phase is unwrapped from one frequency to the next, so
+    /// the grid should be fine enough that the phase of G_r does
+    /// not change by more than 180 degrees between points.
+    /// The dead time lag is added exactly.
+    fn frequency_response(&self, angular_frequencies: &[AngularVelocity])
+    -> Vec<FrequencyResponsePoint> {
+
+        let mut previous_phase: Option<f64> = None;
+
+        angular_frequencies.iter().map(|angular_frequency| {
+            let complex_gain_without_dead_time =
+                self.complex_gain_without_dead_time(*angular_frequency);
+            let phase_without_dead_time = unwrap_phase(
+                complex_gain_without_dead_time.arg(), previous_phase);
+            previous_phase = Some(phase_without_dead_time);
+
+            let dead_time_lag: f64 = angular_frequency.get::<radian_per_second>()
+                * self.frequency_response_dead_time().get::<second>();
+
+            FrequencyResponsePoint {
+                angular_frequency: *angular_frequency,
+                complex_gain: complex_gain_without_dead_time
+                    * Complex::from_polar(1.0, -dead_time_lag),
+                phase: Angle::new::<radian>(phase_without_dead_time
+                    - dead_time_lag),
+            }
+        }).collect()
+    }
+
+    /// gain margin, phase margin, crossover frequencies and peak
+    /// sensitivity, treating this as the loop transfer function L(s)
+    ///
+    /// the crossovers are first found on the frequency grid and
+    /// then refined by bisection. If there are several crossovers,
+    /// the smallest margin is reported.
+    ///
+    /// these margins only make sense if L(s) itself is stable
+    /// (or integrating); otherwise use the Nyquist plot.
+    fn stability_margins(&self, angular_frequencies: &[AngularVelocity])
+    -> Result<StabilityMargins, ChemEngProcessControlSimulatorError> {
+
+        let frequencies: Vec<f64> = angular_frequencies.iter()
+            .map(|angular_frequency| angular_frequency.get::<radian_per_second>())
+            .collect();
+
+        if frequencies.len() < 2
+            || frequencies[0] <= 0.0
+            || frequencies.windows(2).any(|pair| pair[1] <= pair[0]) {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "frequency grid must have at least two positive \
+                    frequencies in ascending order".to_owned()));
+        }
+
+        let dead_time: f64 = self.frequency_response_dead_time().get::<second>();
+        let response = self.frequency_response(angular_frequencies);
+
+        // unwrapped phase in radians at ω, taking the phase of G_r
+        // closest to the one at a nearby grid point
+        let phase_near = |frequency: f64, grid_index: usize| -> f64 {
+            let reference: f64 = response[grid_index].phase.get::<radian>()
+                + frequencies[grid_index] * dead_time;
+            unwrap_phase(self.complex_gain_without_dead_time(
+                    AngularVelocity::new::<radian_per_second>(frequency)).arg(),
+                Some(reference)) - frequency * dead_time
+        };
+        let magnitude = |frequency: f64| -> f64 {
+            self.complex_gain(
+                AngularVelocity::new::<radian_per_second>(frequency)).norm()
+        };
+
+        let mut gain_margin: Option<(f64, f64)> = None;
+        let mut phase_margin: Option<(f64, f64)> = None;
+
+        for i in 0..(frequencies.len() - 1) {
+            let (low, high) = (frequencies[i], frequencies[i+1]);
+
+            // gain crossover, |L| goes through 1
+            let log_magnitude_low = response[i].complex_gain.norm().ln();
+            let log_magnitude_high = response[i+1].complex_gain.norm().ln();
+            if log_magnitude_low * log_magnitude_high <= 0.0
+                && log_magnitude_low != log_magnitude_high {
+                let crossover_frequency = log_bisect(low, high,
+                    |frequency| magnitude(frequency).ln());
+                let margin = PI_DEGREES
+                    + phase_near(crossover_frequency, i).to_degrees();
+                if phase_margin.is_none_or(|(_, smallest)| margin < smallest) {
+                    phase_margin = Some((crossover_frequency, margin));
+                }
+            }
+
+            // phase crossover, phase of L goes through
+            // -180 degrees (or -540, -900 and so on)
+            let turns_low = (response[i].phase.get::<degree>() + PI_DEGREES)
+                / (2.0 * PI_DEGREES);
+            let turns_high = (response[i+1].phase.get::<degree>() + PI_DEGREES)
+                / (2.0 * PI_DEGREES);
+            if turns_low.floor() != turns_high.floor() {
+                let target_phase: f64 = (turns_low.max(turns_high).floor()
+                    * 2.0 * PI_DEGREES - PI_DEGREES).to_radians();
+                let crossover_frequency = log_bisect(low, high,
+                    |frequency| phase_near(frequency, i) - target_phase);
+                let margin = 1.0/magnitude(crossover_frequency);
+                if gain_margin.is_none_or(|(_, smallest)| margin < smallest) {
+                    gain_margin = Some((crossover_frequency, margin));
+                }
+            }
+        }
+
+        // peak sensitivity, largest |1/(1 + L)| on the grid, refined
+        // between the neighbouring grid points
+        let sensitivity = |frequency: f64| -> f64 {
+            1.0/(self.complex_gain(AngularVelocity::new::<radian_per_second>(
+                        frequency)) + 1.0).norm()
+        };
+        let peak_index = response.iter().enumerate()
+            .map(|(i, point)| (i, 1.0/(point.complex_gain + 1.0).norm()))
+            .fold((0, f64::MIN), |best, (i, value)| {
+                if value > best.1 { (i, value) } else { best }
+            }).0;
+        let peak_sensitivity_frequency = log_golden_section_maximum(
+            frequencies[peak_index.saturating_sub(1)],
+            frequencies[(peak_index + 1).min(frequencies.len() - 1)],
+            sensitivity);
+
+        Ok(StabilityMargins {
+            gain_margin: gain_margin.map(|(_, margin)|
+                Ratio::new::<ratio>(margin)),
+            phase_crossover_frequency: gain_margin.map(|(frequency, _)|
+                AngularVelocity::new::<radian_per_second>(frequency)),
+            phase_margin: phase_margin.map(|(_, margin)|
+                Angle::new::<degree>(margin)),
+            gain_crossover_frequency: phase_margin.map(|(frequency, _)|
+                AngularVelocity::new::<radian_per_second>(frequency)),
+            peak_sensitivity: Ratio::new::<ratio>(
+                sensitivity(peak_sensitivity_frequency)),
+            peak_sensitivity_frequency: AngularVelocity::new::<radian_per_second>(
+                peak_sensitivity_frequency),
+        })
+    }
+
+    /// spawns a csv writer for frequency responses
+    fn spawn_frequency_response_writer(&self, name: String)
+    -> Result<Writer<std::fs::File>, ChemEngProcessControlSimulatorError>{
+        let title_string: String = name + "_frequency_response.csv";
+        let wtr = Writer::from_path(title_string)?;
+        Ok(wtr)
+    }
+
+    /// writes a frequency response into the csv file, one row per
+    /// frequency, with a header row
+    ///
+    /// columns are angular frequency (rad/s), magnitude, magnitude
+    /// (dB), phase (degrees), real part and imaginary part, so both
+    /// Bode and Nyquist plots can be drawn from it
+    fn csv_write_frequency_response(&self,
+        wtr: &mut Writer<std::fs::File>,
+        response: &[FrequencyResponsePoint]) -> Result<(),
+    ChemEngProcessControlSimulatorError> {
+
+        wtr.write_record(["angular_frequency_rad_per_s",
+            "magnitude",
+            "magnitude_db",
+            "phase_degrees",
+            "real",
+            "imaginary"])?;
+
+        for point in response.iter() {
+            wtr.write_record(&[
+                point.angular_frequency.get::<radian_per_second>().to_string(),
+                point.magnitude().get::<ratio>().to_string(),
+                point.magnitude_decibels().to_string(),
+                point.phase.get::<degree>().to_string(),
+                point.real_part().get::<ratio>().to_string(),
+                point.imaginary_part().get::<ratio>().to_string()])?;
+        }
+
+        wtr.flush().unwrap();
+
+        Ok(())
+    }
+}
+
+const PI_DEGREES: f64 = 180.0;
+
+/// evenly spaced frequencies on a log scale, from lowest to
+/// highest inclusive, as for a Bode plot
+pub fn logarithmic_frequency_grid(lowest: AngularVelocity,
+    highest: AngularVelocity,
+    number_of_points: usize) -> Vec<AngularVelocity> {
+
+    let log_lowest = lowest.get::<radian_per_second>().log10();
+    let log_highest = highest.get::<radian_per_second>().log10();
+
+    if number_of_points < 2 {
+        return vec![lowest];
+    }
+
+    (0..number_of_points).map(|i| {
+        let fraction = i as f64/(number_of_points - 1) as f64;
+        AngularVelocity::new::<radian_per_second>(
+            10.0_f64.powf(log_lowest + fraction * (log_highest - log_lowest)))
+    }).collect()
+}
+
+/// shifts a phase by multiples of 2 pi so that it is closest to
+/// the reference phase
+fn unwrap_phase(phase: f64, reference: Option<f64>) -> f64 {
+    match reference {
+        Some(reference) => {
+            let turns = ((reference - phase)/std::f64::consts::TAU).round();
+            phase + turns * std::f64::consts::TAU
+        },
+        None => phase,
+    }
+}
+
+/// N(jω)/D(jω)
+fn rational_complex_gain(numerator: &[f64], denominator: &[f64],
+    angular_frequency: AngularVelocity) -> Complex<f64> {
+    let s = Complex::new(0.0, angular_frequency.get::<radian_per_second>());
+    polynomials::evaluate(numerator, s)/polynomials::evaluate(denominator, s)
+}
+
+impl FrequencyResponseTraits for TransferFn {
+    fn complex_gain_without_dead_time(&self,
+        angular_frequency: AngularVelocity) -> Complex<f64> {
+        rational_complex_gain(&self.numerator(), &self.denominator(),
+            angular_frequency)
+    }
+
+    fn frequency_response_dead_time(&self) -> Time {
+        self.dead_time()
+    }
+}
+
+impl FrequencyResponseTraits for AnalogController {
+    fn complex_gain_without_dead_time(&self,
+        angular_frequency: AngularVelocity) -> Complex<f64> {
+        rational_complex_gain(&self.numerator(), &self.denominator(),
+            angular_frequency)
+    }
+
+    fn frequency_response_dead_time(&self) -> Time {
+        self.dead_time()
+    }
+}
+
+impl FrequencyResponseTraits for Block {
+    fn complex_gain_without_dead_time(&self,
+        angular_frequency: AngularVelocity) -> Complex<f64> {
+        match self {
+            Block::TransferFn(transfer_fn) => {
+                transfer_fn.complex_gain_without_dead_time(angular_frequency)
+            },
+            Block::Controller(controller) => {
+                controller.complex_gain_without_dead_time(angular_frequency)
+            },
+            Block::Diagram(block_diagram) => {
+                block_diagram.complex_gain_without_dead_time(angular_frequency)
+            },
+        }
+    }
+
+    fn frequency_response_dead_time(&self) -> Time {
+        match self {
+            Block::TransferFn(transfer_fn) => {
+                transfer_fn.frequency_response_dead_time()
+            },
+            Block::Controller(controller) => {
+                controller.frequency_response_dead_time()
+            },
+            Block::Diagram(block_diagram) => {
+                block_diagram.frequency_response_dead_time()
+            },
+        }
+    }
+}
+
+/// block diagrams with a rational transfer function use it directly,
+/// otherwise the complex gains of the blocks are combined the same
+/// way as the transfer functions would be
+impl FrequencyResponseTraits for BlockDiagram {
+    fn complex_gain_without_dead_time(&self,
+        angular_frequency: AngularVelocity) -> Complex<f64> {
+
+        if let Some((numerator, denominator, _)) = &self.analytical_transfer_fn {
+            return rational_complex_gain(numerator, denominator,
+                angular_frequency);
+        }
+
+        match &self.connection {
+            BlockConnection::Series(first_block, second_block) => {
+                first_block.complex_gain_without_dead_time(angular_frequency)
+                    * second_block.complex_gain_without_dead_time(
+                        angular_frequency)
+            },
+            BlockConnection::Parallel(first_block, second_block) => {
+                first_block.complex_gain(angular_frequency)
+                    + second_block.complex_gain(angular_frequency)
+            },
+            BlockConnection::Feedback { forward, feedback, sign, .. } => {
+                let forward_gain = forward.complex_gain(angular_frequency);
+                let loop_gain = forward_gain
+                    * feedback.complex_gain(angular_frequency);
+                match sign {
+                    FeedbackSign::Negative => forward_gain/(loop_gain + 1.0),
+                    FeedbackSign::Positive => forward_gain/(1.0 - loop_gain),
+                }
+            },
+        }
+    }
+
+    fn frequency_response_dead_time(&self) -> Time {
+        if self.analytical_transfer_fn.is_some() {
+            return self.dead_time();
+        }
+
+        match &self.connection {
+            BlockConnection::Series(first_block, second_block) => {
+                self.dead_time
+                    + first_block.frequency_response_dead_time()
+                    + second_block.frequency_response_dead_time()
+            },
+            BlockConnection::Parallel(..) | BlockConnection::Feedback { .. } => {
+                self.dead_time
+            },
+        }
+    }
+}
+
+#[test]
+pub fn test_stability_margins(){
+    use super::block_diagrams::{feedback, series};
+    use super::controllers::ProportionalController;
+    use super::transfer_fn_wrapper_and_enums::{TransferFnNthOrder,
+        TransferFnTraits};
+
+    let grid = logarithmic_frequency_grid(
+        AngularVelocity::new::<radian_per_second>(1e-3),
+        AngularVelocity::new::<radian_per_second>(1e3), 600);
+
+    // L(s) = 4/(s + 1)^3
+    //
+    // phase is -180 degrees at ω = sqrt(3), where |L| = 4/8,
+    // so the gain margin is 2
+    //
+    // |L| = 1 at ω = sqrt(4^(2/3) - 1)
+    let third_order: TransferFn = TransferFnNthOrder::new(vec![4.0],
+        vec![1.0, 3.0, 3.0, 1.0]).unwrap().into();
+    let margins = third_order.stability_margins(&grid).unwrap();
+
+    approx::assert_relative_eq!(margins.gain_margin.unwrap().get::<ratio>(),
+        2.0, max_relative = 1e-9);
+    approx::assert_relative_eq!(margins.phase_crossover_frequency.unwrap()
+        .get::<radian_per_second>(), 3.0_f64.sqrt(), max_relative = 1e-9);
+
+    let gain_crossover: f64 = (4.0_f64.powf(2.0/3.0) - 1.0).sqrt();
+    approx::assert_relative_eq!(margins.gain_crossover_frequency.unwrap()
+        .get::<radian_per_second>(), gain_crossover, max_relative = 1e-9);
+    approx::assert_relative_eq!(margins.phase_margin.unwrap().get::<degree>(),
+        180.0 - 3.0 * gain_crossover.atan().to_degrees(), max_relative = 1e-9);
+
+    // peak sensitivity against a brute force search
+    let fine_grid = logarithmic_frequency_grid(
+        AngularVelocity::new::<radian_per_second>(0.1),
+        AngularVelocity::new::<radian_per_second>(10.0), 200001);
+    let brute_force_peak = fine_grid.iter()
+        .map(|frequency| 1.0/(third_order.complex_gain(*frequency) + 1.0).norm())
+        .fold(0.0_f64, f64::max);
+    approx::assert_relative_eq!(margins.peak_sensitivity.get::<ratio>(),
+        brute_force_peak, max_relative = 1e-6);
+
+    // L(s) = exp(-s)/s, put together as a P controller in series
+    // with an integrating process with dead time
+    //
+    // |L| = 1 at ω = 1, phase margin is 90 degrees - 1 radian
+    // phase is -180 degrees at ω = pi/2, gain margin is pi/2
+    let mut integrating_process: TransferFn = TransferFnNthOrder::new(
+        vec![1.0], vec![1.0, 0.0]).unwrap().into();
+    integrating_process.set_dead_time(Time::new::<second>(1.0));
+    let p_controller: AnalogController = ProportionalController::new(
+        Ratio::new::<ratio>(1.0)).unwrap().into();
+    let loop_transfer_fn = series(p_controller, integrating_process.clone());
+
+    let margins = loop_transfer_fn.stability_margins(&grid).unwrap();
+    approx::assert_relative_eq!(margins.phase_margin.unwrap().get::<degree>(),
+        90.0 - 1.0_f64.to_degrees(), max_relative = 1e-9);
+    approx::assert_relative_eq!(margins.gain_margin.unwrap().get::<ratio>(),
+        std::f64::consts::FRAC_PI_2, max_relative = 1e-9);
+
+    // the dead time keeps the phase going down, past -540 degrees
+    let response = loop_transfer_fn.frequency_response(&grid);
+    let last_point = response.last().unwrap();
+    approx::assert_relative_eq!(last_point.phase.get::<radian>(),
+        -std::f64::consts::FRAC_PI_2 - 1e3, max_relative = 1e-9);
+    approx::assert_relative_eq!(last_point.magnitude().get::<ratio>(),
+        1e-3, max_relative = 1e-9);
+
+    // the closed loop has dead time inside the loop, so its
+    // complex gain comes from the blocks, L/(1 + L)
+    let unity_feedback: TransferFn = TransferFnNthOrder::new(
+        vec![1.0], vec![1.0]).unwrap().into();
+    let closed_loop = feedback(integrating_process, unity_feedback,
+        FeedbackSign::Negative);
+    assert!(!closed_loop.is_analytical());
+
+    let frequency = AngularVelocity::new::<radian_per_second>(0.7);
+    let loop_gain = loop_transfer_fn.complex_gain(frequency);
+    let closed_loop_gain = closed_loop.complex_gain(frequency);
+    approx::assert_relative_eq!(closed_loop_gain.re,
+        (loop_gain/(loop_gain + 1.0)).re, max_relative = 1e-12);
+    approx::assert_relative_eq!(closed_loop_gain.im,
+        (loop_gain/(loop_gain + 1.0)).im, max_relative = 1e-12);
+}
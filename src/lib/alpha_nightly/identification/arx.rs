@@ -0,0 +1,595 @@
+use num_complex::Complex;
+use uom::si::f64::*;
+use uom::si::ratio::ratio;
+use uom::si::time::second;
+
+use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
+use crate::alpha_nightly::matrices;
+use crate::alpha_nightly::polynomials;
+use crate::alpha_nightly::transfer_fn_wrapper_and_enums::{TransferFn,
+    TransferFnTraits};
+use crate::alpha_nightly::transfer_fn_wrapper_and_enums::
+    generic_zero_order_hold::TransferFnZeroOrderHold;
+
+use super::ProcessData;
+
+/// discrete time input output model structures,
+/// q^(-1) is the backward shift operator (one sample back)
+#[derive(Debug,PartialEq, PartialOrd, Clone, Copy)]
+pub enum DiscreteModelStructure {
+    /// A(q) y(k) = B(q) u(k - nk) + e(k),
+    ///
+    /// fitted by linear least squares
+    Arx,
+    /// A(q) y(k) = B(q) u(k - nk) + C(q) e(k),
+    ///
+    /// the noise is coloured by C(q), fitted by extended least
+    /// squares (the residuals are used as extra regressors)
+    Armax,
+}
+
+/// how to choose the orders and the input delay
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub enum OrderSelection {
+    /// Akaike's information criterion on the estimation data,
+    ///
+    /// AIC = N ln(V) + 2 d
+    ///
+    /// where V is the residual variance and d the number of
+    /// parameters
+    Aic,
+    /// mean squared error of the model simulated (not one step
+    /// ahead predicted) on a separate set of data
+    ValidationError(ProcessData),
+}
+
+/// identified discrete time model, in deviation from the mean
+/// input and output of the estimation data
+///
+/// A(q) = 1 + a_1 q^(-1) + ... + a_na q^(-na)
+///
+/// B(q) = b_0 + b_1 q^(-1) + ... + b_(nb-1) q^(-nb+1)
+///
+/// C(q) = 1 + c_1 q^(-1) + ... + c_nc q^(-nc)
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub struct DiscreteTimeModel {
+    /// [1, a_1, ... a_na]
+    pub(crate) a_coefficients: Vec<f64>,
+    /// [b_0, ... b_(nb-1)]
+    pub(crate) b_coefficients: Vec<f64>,
+    /// [1, c_1, ... c_nc], just [1] for ARX
+    pub(crate) c_coefficients: Vec<f64>,
+    /// nk, in samples
+    pub(crate) input_delay: usize,
+    pub(crate) sample_period: Time,
+    pub(crate) input_mean: Ratio,
+    pub(crate) output_mean: Ratio,
+    /// V, variance of the one step ahead prediction errors
+    pub(crate) residual_variance: f64,
+    pub(crate) number_of_residuals: usize,
+}
+
+/// iterations of extended least squares for ARMAX
+const EXTENDED_LEAST_SQUARES_ITERATIONS: usize = 50;
+
+impl ProcessData {
+
+    /// sample period, the data must be evenly spaced in time
+    pub fn sample_period(&self) -> Result<Time, ChemEngProcessControlSimulatorError> {
+        let sample_period = self.times[1] - self.times[0];
+        let evenly_spaced = self.times.windows(2).all(|pair| {
+            ((pair[1] - pair[0] - sample_period)/sample_period)
+                .get::<ratio>().abs() < 1e-6
+        });
+        if !evenly_spaced {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "discrete time models need evenly spaced data".to_owned()));
+        }
+        Ok(sample_period)
+    }
+
+    /// ARX model with na poles, nb numerator coefficients and an
+    /// input delay of nk samples
+    ///
+    /// for a process held between samples without dead time, nk = 1
+    pub fn fit_arx(&self, na: usize, nb: usize, nk: usize)
+    -> Result<DiscreteTimeModel, ChemEngProcessControlSimulatorError> {
+        self.fit_discrete_model(na, nb, 0, nk)
+    }
+
+    /// ARMAX model, as for ARX with nc noise coefficients
+    pub fn fit_armax(&self, na: usize, nb: usize, nc: usize, nk: usize)
+    -> Result<DiscreteTimeModel, ChemEngProcessControlSimulatorError> {
+        self.fit_discrete_model(na, nb, nc, nk)
+    }
+
+    /// tries every na and nb from 1 up to max_order, nc from 1 up to
+    /// max_order (ARMAX only), and nk from 1 up to max_input_delay,
+    /// and keeps the best model by the selection criterion
+    pub fn select_discrete_model(&self, structure: DiscreteModelStructure,
+        max_order: usize,
+        max_input_delay: usize,
+        selection: &OrderSelection)
+    -> Result<DiscreteTimeModel, ChemEngProcessControlSimulatorError> {
+
+        let noise_orders: Vec<usize> = match structure {
+            DiscreteModelStructure::Arx => vec![0],
+            DiscreteModelStructure::Armax => (1..=max_order).collect(),
+        };
+
+        let mut best: Option<(f64, DiscreteTimeModel)> = None;
+        for na in 1..=max_order {
+            for nb in 1..=max_order {
+                for nc in noise_orders.iter() {
+                    for nk in 1..=max_input_delay {
+                        let model = match self.fit_discrete_model(na, nb, *nc, nk) {
+                            Ok(model) => model,
+                            Err(_) => continue,
+                        };
+                        let score = match selection {
+                            OrderSelection::Aic => model.aic(),
+                            OrderSelection::ValidationError(validation_data) => {
+                                model.validation_error(validation_data)?
+                            },
+                        };
+                        if score.is_finite() && best.as_ref()
+                            .is_none_or(|(best_score, _)| score < *best_score) {
+                            best = Some((score, model));
+                        }
+                    }
+                }
+            }
+        }
+
+        best.map(|(_, model)| model).ok_or_else(||
+            ChemEngProcessControlSimulatorError::GenericStringError(
+                "no discrete time model could be fitted".to_owned()))
+    }
+
+    /// inputs and outputs with their means taken off
+    fn detrended(&self) -> (Vec<f64>, Vec<f64>, f64, f64) {
+        let inputs: Vec<f64> = self.inputs.iter().map(|u| u.get::<ratio>()).collect();
+        let outputs: Vec<f64> = self.outputs.iter().map(|y| y.get::<ratio>()).collect();
+        let input_mean: f64 = inputs.iter().sum::<f64>()/inputs.len() as f64;
+        let output_mean: f64 = outputs.iter().sum::<f64>()/outputs.len() as f64;
+        (inputs.iter().map(|u| u - input_mean).collect(),
+            outputs.iter().map(|y| y - output_mean).collect(),
+            input_mean, output_mean)
+    }
+
+    fn fit_discrete_model(&self, na: usize, nb: usize, nc: usize, nk: usize)
+    -> Result<DiscreteTimeModel, ChemEngProcessControlSimulatorError> {
+
+        if nb == 0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "need at least one B coefficient".to_owned()));
+        }
+        let sample_period = self.sample_period()?;
+        let (inputs, outputs, input_mean, output_mean) = self.detrended();
+
+        let first_sample = na.max(nk + nb - 1).max(nc);
+        let number_of_parameters = na + nb + nc;
+        if outputs.len() <= first_sample + number_of_parameters {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "not enough data for the model orders".to_owned()));
+        }
+
+        // phi(k) = [-y(k-1) .. -y(k-na), u(k-nk) .. u(k-nk-nb+1),
+        // e(k-1) .. e(k-nc)]
+        let regressors = |k: usize, residuals: &[f64]| -> Vec<f64> {
+            (1..=na).map(|i| -outputs[k - i])
+                .chain((0..nb).map(|j| inputs[k - nk - j]))
+                .chain((1..=nc).map(|i| residuals[k - i]))
+                .collect()
+        };
+
+        let mut residuals: Vec<f64> = vec![0.0; outputs.len()];
+        let mut parameters: Vec<f64> = vec![];
+        // ARMAX starts from the residuals of an ARX fit, as a column
+        // of zero residuals would make the normal equations singular
+        if nc > 0 {
+            let arx = self.fit_discrete_model(na, nb, 0, nk)?;
+            let arx_parameters: Vec<f64> = arx.a_coefficients[1..].iter()
+                .chain(arx.b_coefficients.iter()).cloned().collect();
+            for k in first_sample..outputs.len() {
+                let prediction: f64 = regressors(k, &residuals).iter()
+                    .zip(arx_parameters.iter()).map(|(phi, theta)| phi * theta)
+                    .sum();
+                residuals[k] = outputs[k] - prediction;
+            }
+        }
+        // ARX is one linear least squares, ARMAX repeats it with the
+        // residuals from the last pass
+        let iterations = if nc == 0 { 1 } else { EXTENDED_LEAST_SQUARES_ITERATIONS };
+        for _ in 0..iterations {
+            let regressor_rows: Vec<Vec<f64>> = (first_sample..outputs.len())
+                .map(|k| regressors(k, &residuals)).collect();
+            let targets: Vec<f64> = outputs[first_sample..].to_vec();
+            let new_parameters = least_squares(&regressor_rows, &targets)?;
+
+            // residuals worked out in order, as each one depends on
+            // the ones before
+            let mut new_residuals: Vec<f64> = vec![0.0; outputs.len()];
+            for k in first_sample..outputs.len() {
+                let prediction: f64 = regressors(k, &new_residuals).iter()
+                    .zip(new_parameters.iter()).map(|(phi, theta)| phi * theta)
+                    .sum();
+                new_residuals[k] = outputs[k] - prediction;
+            }
+
+            let change: f64 = new_parameters.iter()
+                .zip(parameters.iter().chain(std::iter::repeat(&f64::INFINITY)))
+                .map(|(new, old)| (new - old).abs()).fold(0.0, f64::max);
+            parameters = new_parameters;
+            residuals = new_residuals;
+            if change < 1e-10 {
+                break;
+            }
+        }
+
+        let number_of_residuals = outputs.len() - first_sample;
+        let residual_variance: f64 = residuals[first_sample..].iter()
+            .map(|e| e * e).sum::<f64>()/number_of_residuals as f64;
+
+        let mut a_coefficients = vec![1.0];
+        a_coefficients.extend_from_slice(&parameters[..na]);
+        let b_coefficients = parameters[na..na + nb].to_vec();
+        let mut c_coefficients = vec![1.0];
+        c_coefficients.extend_from_slice(&parameters[na + nb..]);
+
+        Ok(DiscreteTimeModel {
+            a_coefficients,
+            b_coefficients,
+            c_coefficients,
+            input_delay: nk,
+            sample_period,
+            input_mean: Ratio::new::<ratio>(input_mean),
+            output_mean: Ratio::new::<ratio>(output_mean),
+            residual_variance,
+            number_of_residuals,
+        })
+    }
+}
+
+/// solves the normal equations (Phi^T Phi) theta = Phi^T y
+fn least_squares(regressor_rows: &[Vec<f64>], targets: &[f64])
+-> Result<Vec<f64>, ChemEngProcessControlSimulatorError> {
+    let size = regressor_rows[0].len();
+    let mut normal_matrix = matrices::zeroes(size, size);
+    let mut right_hand_side = vec![0.0; size];
+    for (row, target) in regressor_rows.iter().zip(targets.iter()) {
+        for i in 0..size {
+            right_hand_side[i] += row[i] * target;
+            for j in 0..size {
+                normal_matrix[i][j] += row[i] * row[j];
+            }
+        }
+    }
+    Ok(matrices::multiply_vector(&matrices::inverse(&normal_matrix)?,
+        &right_hand_side))
+}
+
+impl DiscreteTimeModel {
+
+    /// [1, a_1, ... a_na]
+    pub fn a_coefficients(&self) -> &[f64] {
+        &self.a_coefficients
+    }
+
+    /// [b_0, ... b_(nb-1)]
+    pub fn b_coefficients(&self) -> &[f64] {
+        &self.b_coefficients
+    }
+
+    /// [1, c_1, ... c_nc]
+    pub fn c_coefficients(&self) -> &[f64] {
+        &self.c_coefficients
+    }
+
+    /// nk, in samples
+    pub fn input_delay(&self) -> usize {
+        self.input_delay
+    }
+
+    pub fn sample_period(&self) -> Time {
+        self.sample_period
+    }
+
+    /// variance of the one step ahead prediction errors
+    pub fn residual_variance(&self) -> f64 {
+        self.residual_variance
+    }
+
+    /// AIC = N ln(V) + 2 d
+    pub fn aic(&self) -> f64 {
+        let number_of_parameters = self.a_coefficients.len() - 1
+            + self.b_coefficients.len() + self.c_coefficients.len() - 1;
+        self.number_of_residuals as f64 * self.residual_variance.ln()
+            + 2.0 * number_of_parameters as f64
+    }
+
+    /// simulated output for a sequence of inputs, one per sample,
+    /// starting from the mean output
+    ///
+    /// the noise model is not used, this is what the model
+    /// predicts from the inputs alone
+    pub fn simulate(&self, inputs: &[Ratio]) -> Vec<Ratio> {
+        let inputs: Vec<f64> = inputs.iter()
+            .map(|u| (*u - self.input_mean).get::<ratio>()).collect();
+        let mut outputs: Vec<f64> = vec![0.0; inputs.len()];
+        for k in 0..inputs.len() {
+            let autoregressive: f64 = self.a_coefficients.iter().enumerate().skip(1)
+                .filter(|(i, _)| *i <= k)
+                .map(|(i, a)| -a * outputs[k - i]).sum();
+            let exogenous: f64 = self.b_coefficients.iter().enumerate()
+                .filter(|(j, _)| self.input_delay + j <= k)
+                .map(|(j, b)| b * inputs[k - self.input_delay - j]).sum();
+            outputs[k] = autoregressive + exogenous;
+        }
+        outputs.iter().map(|y| self.output_mean + Ratio::new::<ratio>(*y))
+            .collect()
+    }
+
+    /// mean squared error of the simulated output on another set of
+    /// data, each set is taken about its own mean
+    pub fn validation_error(&self, validation_data: &ProcessData)
+    -> Result<f64, ChemEngProcessControlSimulatorError> {
+        let validation_sample_period = validation_data.sample_period()?;
+        if ((validation_sample_period - self.sample_period)/self.sample_period)
+            .get::<ratio>().abs() > 1e-6 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "validation data must have the same sample period"
+                    .to_owned()));
+        }
+        let (inputs, outputs, _, _) = validation_data.detrended();
+
+        let mut model = self.clone();
+        model.input_mean = Ratio::new::<ratio>(0.0);
+        model.output_mean = Ratio::new::<ratio>(0.0);
+        let simulated = model.simulate(&inputs.iter()
+            .map(|u| Ratio::new::<ratio>(*u)).collect::<Vec<Ratio>>());
+
+        Ok(simulated.iter().zip(outputs.iter())
+            .map(|(y_model, y)| (y_model.get::<ratio>() - y).powi(2))
+            .sum::<f64>()/outputs.len() as f64)
+    }
+
+    /// continuous time transfer function which gives this discrete
+    /// model when sampled with a zero order hold
+    ///
+    /// the discrete model is first written as
+    ///
+    /// G(z) = z^(-d) N(z)/D(z)
+    ///
+    /// with N/D of relative degree one (zero if nk = 0), which is
+    /// what sampling a process without dead time gives, so the d
+    /// samples of delay left over become dead time. Then, with
+    /// G(z) = D_0 + sum of rho_i/(z - lambda_i), each pole maps to
+    /// p_i = ln(lambda_i)/T and residue r_i = rho_i p_i/(lambda_i - 1),
+    /// which undoes the zero order hold sampling of r_i/(s - p_i).
+    ///
+    /// poles on the negative real axis (ringing between samples)
+    /// have no continuous equivalent and give an error, as do
+    /// repeated poles.
+    pub fn to_continuous(&self) -> Result<TransferFn, ChemEngProcessControlSimulatorError> {
+
+        let sample_period: f64 = self.sample_period.get::<second>();
+        let na = self.a_coefficients.len() - 1;
+        let nb = self.b_coefficients.len();
+
+        // D(z) = z^na A(1/z), and z^(-nk) B(1/z) = z^(-(nk + nb - 1)) N(z)
+        let denominator: Vec<f64> = self.a_coefficients.clone();
+        let numerator = polynomials::trim_leading_zeroes(&self.b_coefficients);
+        if numerator == vec![0.0] {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "model has no input to output gain".to_owned()));
+        }
+        let numerator_degree = numerator.len() - 1;
+
+        // leading zeroes in B are extra delay
+        let delay_samples: i64 = self.input_delay as i64
+            + (nb - 1 - numerator_degree) as i64 - 1;
+        let (delay_samples, numerator_shift): (usize, i64) = if delay_samples >= 0 {
+            (delay_samples as usize, na as i64 - numerator_degree as i64 - 1)
+        } else {
+            (0, na as i64 - numerator_degree as i64)
+        };
+        if numerator_shift < 0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "B has too many coefficients for the number of poles \
+                    to have a continuous equivalent".to_owned()));
+        }
+        let mut numerator = numerator;
+        numerator.extend(std::iter::repeat_n(0.0, numerator_shift as usize));
+
+        // feedthrough, and the strictly proper part
+        let feedthrough: f64 = if numerator.len() == denominator.len() {
+            numerator[0]
+        } else {
+            0.0
+        };
+        let strictly_proper_numerator = polynomials::add(&numerator,
+            &polynomials::scale(&denominator, -feedthrough));
+
+        let discrete_poles = polynomials::distinct_roots(&denominator)?;
+        if discrete_poles.iter().any(|(_, multiplicity)| *multiplicity > 1) {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "repeated discrete poles are not supported".to_owned()));
+        }
+        let denominator_derivative = polynomials::derivative(&denominator);
+
+        let mut continuous_poles: Vec<Complex<f64>> = vec![];
+        let mut continuous_residues: Vec<Complex<f64>> = vec![];
+        for (discrete_pole, _) in discrete_poles.iter() {
+            let on_negative_real_axis = discrete_pole.im.abs()
+                <= 1e-12 * discrete_pole.norm() && discrete_pole.re <= 0.0;
+            if on_negative_real_axis {
+                return Err(ChemEngProcessControlSimulatorError::
+                    GenericStringError(
+                        "discrete pole on the negative real axis has no \
+                        continuous equivalent".to_owned()));
+            }
+            // keep real poles real, so the imaginary parts cancel
+            let discrete_pole = if discrete_pole.im.abs()
+                <= 1e-12 * discrete_pole.norm() {
+                Complex::new(discrete_pole.re, 0.0)
+            } else {
+                *discrete_pole
+            };
+
+            let discrete_residue = polynomials::evaluate(
+                &strictly_proper_numerator, discrete_pole)
+                /polynomials::evaluate(&denominator_derivative, discrete_pole);
+            let continuous_pole = discrete_pole.ln()/sample_period;
+            let continuous_residue = if (discrete_pole - 1.0).norm() < 1e-12 {
+                discrete_residue/sample_period
+            } else {
+                discrete_residue * continuous_pole/(discrete_pole - 1.0)
+            };
+            continuous_poles.push(continuous_pole);
+            continuous_residues.push(continuous_residue);
+        }
+
+        // D_0 + sum of r_i/(s - p_i) over a common denominator
+        let continuous_denominator = polynomials::from_roots(&continuous_poles);
+        let mut continuous_numerator: Vec<Complex<f64>> = continuous_denominator
+            .iter().map(|coefficient| Complex::new(feedthrough * coefficient, 0.0))
+            .collect();
+        for (i, residue) in continuous_residues.iter().enumerate() {
+            let other_poles: Vec<Complex<f64>> = continuous_poles.iter()
+                .enumerate().filter(|(j, _)| *j != i).map(|(_, pole)| *pole)
+                .collect();
+            let term = complex_polynomial_from_roots(&other_poles);
+            // term is one degree lower, aligned at the constant
+            let offset = continuous_numerator.len() - term.len();
+            for (k, coefficient) in term.iter().enumerate() {
+                continuous_numerator[offset + k] += residue * coefficient;
+            }
+        }
+        let continuous_numerator: Vec<f64> = continuous_numerator.iter()
+            .map(|coefficient| coefficient.re).collect();
+
+        let mut transfer_fn = TransferFnZeroOrderHold::new(continuous_numerator,
+            continuous_denominator)?;
+        transfer_fn.set_dead_time(delay_samples as f64 * self.sample_period);
+
+        Ok(transfer_fn.into())
+    }
+}
+
+/// like polynomials::from_roots, but keeps the complex coefficients
+fn complex_polynomial_from_roots(roots: &[Complex<f64>]) -> Vec<Complex<f64>> {
+    let mut polynomial: Vec<Complex<f64>> = vec![Complex::new(1.0, 0.0)];
+    for root in roots {
+        let mut next = vec![Complex::new(0.0, 0.0); polynomial.len() + 1];
+        for (i, coefficient) in polynomial.iter().enumerate() {
+            next[i] += coefficient;
+            next[i + 1] -= coefficient * root;
+        }
+        polynomial = next;
+    }
+    polynomial
+}
+
+#[test]
+pub fn test_arx_and_armax(){
+    use uom::ConstZero;
+    use crate::alpha_nightly::transfer_fn_wrapper_and_enums::TransferFnFirstOrder;
+
+    // small linear congruential generator, so the test does not
+    // need a random number crate
+    let mut seed: u64 = 12345;
+    let mut random = move || -> f64 {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (seed >> 11) as f64/(1u64 << 53) as f64
+    };
+
+    // 2 exp(-s)/(5 s + 1) sampled every 0.5 s, with a random
+    // binary input around 50% and the output around 30%
+    let sample_period: f64 = 0.5;
+    let mut process = TransferFnFirstOrder::new(Time::ZERO,
+        Ratio::new::<ratio>(2.0), Time::new::<second>(5.0),
+        Ratio::new::<ratio>(1.0)).unwrap();
+    process.set_dead_time(Time::new::<second>(1.0));
+
+    let mut times: Vec<f64> = vec![];
+    let mut inputs: Vec<f64> = vec![];
+    let mut outputs: Vec<f64> = vec![];
+    let mut input: f64 = 0.0;
+    for step in 0..1200 {
+        if step % 4 == 0 {
+            input = if random() > 0.5 { 1.0 } else { -1.0 };
+        }
+        let time = Time::new::<second>(step as f64 * sample_period);
+        let output = process.set_user_input_and_calc(
+            Ratio::new::<ratio>(input), time).unwrap().get::<ratio>();
+        times.push(step as f64 * sample_period);
+        inputs.push(50.0 + input);
+        outputs.push(30.0 + output + 0.002 * (random() - 0.5));
+    }
+    let data = ProcessData::new_from_f64(&times[..800], &inputs[..800],
+        &outputs[..800]).unwrap();
+    let validation_data = ProcessData::new_from_f64(&times[800..],
+        &inputs[800..], &outputs[800..]).unwrap();
+
+    // one pole, one zero, one sample of zero order hold delay and
+    // two samples of dead time
+    let arx = data.fit_arx(1, 1, 3).unwrap();
+    let pole: f64 = (-sample_period/5.0).exp();
+    approx::assert_abs_diff_eq!(arx.a_coefficients()[1], -pole, epsilon = 1e-3);
+    approx::assert_abs_diff_eq!(arx.b_coefficients()[0], 2.0 * (1.0 - pole),
+        epsilon = 1e-3);
+
+    let continuous = arx.to_continuous().unwrap();
+    approx::assert_relative_eq!(continuous.steady_state_gain().unwrap()
+        .get::<ratio>(), 2.0, max_relative = 0.01);
+    approx::assert_abs_diff_eq!(continuous.dead_time().get::<second>(), 1.0,
+        epsilon = 1e-12);
+    let denominator = continuous.denominator();
+    approx::assert_relative_eq!(denominator[0]/denominator[1], 5.0,
+        max_relative = 0.02);
+
+    // the delay is picked out by both criteria
+    let by_aic = data.select_discrete_model(DiscreteModelStructure::Arx,
+        2, 5, &OrderSelection::Aic).unwrap();
+    assert_eq!(by_aic.input_delay(), 3);
+    let by_validation = data.select_discrete_model(DiscreteModelStructure::Arx,
+        2, 5, &OrderSelection::ValidationError(validation_data.clone())).unwrap();
+    assert_eq!(by_validation.input_delay(), 3);
+    // the validation data starts part way through a transient,
+    // so the error is small but not zero
+    assert!(by_validation.validation_error(&validation_data).unwrap() < 0.01);
+
+    // ARMAX, coloured noise e(k) + 0.7 e(k-1), which biases ARX
+    let (a, b, c): (f64, f64, f64) = (-0.9, 0.5, 0.7);
+    let mut outputs: Vec<f64> = vec![0.0; 2000];
+    let mut inputs: Vec<f64> = vec![0.0; 2000];
+    let mut noise: Vec<f64> = vec![0.0; 2000];
+    for k in 0..2000 {
+        inputs[k] = if random() > 0.5 { 1.0 } else { -1.0 };
+        noise[k] = 0.2 * (random() - 0.5);
+        if k >= 1 {
+            outputs[k] = -a * outputs[k - 1] + b * inputs[k - 1]
+                + noise[k] + c * noise[k - 1];
+        }
+    }
+    let times: Vec<f64> = (0..2000).map(|k| k as f64).collect();
+    let data = ProcessData::new_from_f64(&times, &inputs, &outputs).unwrap();
+    let armax = data.fit_armax(1, 1, 1, 1).unwrap();
+    approx::assert_abs_diff_eq!(armax.a_coefficients()[1], a, epsilon = 0.01);
+    approx::assert_abs_diff_eq!(armax.b_coefficients()[0], b, epsilon = 0.01);
+    approx::assert_abs_diff_eq!(armax.c_coefficients()[1], c, epsilon = 0.05);
+    assert!(armax.aic() < data.fit_arx(1, 1, 1).unwrap().aic());
+
+    // negative real discrete poles have no continuous equivalent
+    let mut ringing = armax.clone();
+    ringing.a_coefficients = vec![1.0, 0.5];
+    assert!(ringing.to_continuous().is_err());
+}
@@ -0,0 +1,233 @@
+//! process identification, ie. building transfer functions
+//! from plant (or simulated) test data
+//!
+//! data is read in the same time, input, output format which
+//! csv_write_values writes out, in deviation variables or not.
+
+use std::path::Path;
+
+use uom::si::f64::*;
+use uom::si::ratio::ratio;
+use uom::si::time::second;
+
+use super::errors::ChemEngProcessControlSimulatorError;
+
+pub mod step_test;
+pub use step_test::TwoPointMethod;
+pub mod arx;
+pub use arx::{DiscreteModelStructure, DiscreteTimeModel, OrderSelection};
+
+/// time series of process input and output from a plant test
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub struct ProcessData {
+    pub(crate) times: Vec<Time>,
+    pub(crate) inputs: Vec<Ratio>,
+    pub(crate) outputs: Vec<Ratio>,
+}
+
+/// how well an identified model matches the data
+#[derive(Debug,PartialEq, PartialOrd, Clone, Copy)]
+pub struct FitStatistics {
+    /// sum of (measured - model)^2
+    pub sum_of_squared_errors: f64,
+    /// sqrt(sum of squared errors/number of points)
+    pub root_mean_square_error: Ratio,
+    /// coefficient of determination, 1 - SSE/(sum of squares
+    /// about the mean), 1 is a perfect fit
+    pub r_squared: Ratio,
+    pub number_of_points: usize,
+}
+
+/// identified transfer function along with how well it fits
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub struct IdentifiedModel<T> {
+    pub transfer_fn: T,
+    pub fit_statistics: FitStatistics,
+}
+
+impl ProcessData {
+
+    /// times must be increasing, and all three vectors must be
+    /// the same length
+    pub fn new(times: Vec<Time>,
+        inputs: Vec<Ratio>,
+        outputs: Vec<Ratio>) -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        if times.len() != inputs.len() || times.len() != outputs.len() {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "times, inputs and outputs must be the same length"
+                    .to_owned()));
+        }
+        if times.len() < 3 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "need at least three data points".to_owned()));
+        }
+        if times.windows(2).any(|pair| pair[1] <= pair[0]) {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "times must be increasing".to_owned()));
+        }
+
+        Ok(Self { times, inputs, outputs })
+    }
+
+    /// from plain numbers, times in seconds
+    pub fn new_from_f64(times: &[f64],
+        inputs: &[f64],
+        outputs: &[f64]) -> Result<Self, ChemEngProcessControlSimulatorError> {
+        Self::new(times.iter().map(|t| Time::new::<second>(*t)).collect(),
+            inputs.iter().map(|u| Ratio::new::<ratio>(*u)).collect(),
+            outputs.iter().map(|y| Ratio::new::<ratio>(*y)).collect())
+    }
+
+    /// reads a csv file with time (s), input and output in the
+    /// first three columns, as written by csv_write_values
+    ///
+    /// rows which are not numbers (eg. a header) are skipped
+    pub fn from_csv<P: AsRef<Path>>(path: P)
+    -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_path(path)?;
+
+        let mut times: Vec<f64> = vec![];
+        let mut inputs: Vec<f64> = vec![];
+        let mut outputs: Vec<f64> = vec![];
+
+        for record in reader.records() {
+            let record = record?;
+            let values: Vec<f64> = record.iter().take(3)
+                .filter_map(|field| field.trim().parse::<f64>().ok())
+                .collect();
+            if values.len() == 3 {
+                times.push(values[0]);
+                inputs.push(values[1]);
+                outputs.push(values[2]);
+            }
+        }
+
+        Self::new_from_f64(&times, &inputs, &outputs)
+    }
+
+    pub fn times(&self) -> &[Time] {
+        &self.times
+    }
+
+    pub fn inputs(&self) -> &[Ratio] {
+        &self.inputs
+    }
+
+    pub fn outputs(&self) -> &[Ratio] {
+        &self.outputs
+    }
+
+    /// compares model outputs with the measured outputs
+    pub(crate) fn fit_statistics(&self, model_outputs: &[f64]) -> FitStatistics {
+        let measured: Vec<f64> = self.outputs.iter()
+            .map(|y| y.get::<ratio>()).collect();
+        let number_of_points = measured.len();
+        let mean: f64 = measured.iter().sum::<f64>()/number_of_points as f64;
+
+        let sum_of_squared_errors: f64 = measured.iter().zip(model_outputs.iter())
+            .map(|(y, y_model)| (y - y_model).powi(2)).sum();
+        let total_sum_of_squares: f64 = measured.iter()
+            .map(|y| (y - mean).powi(2)).sum();
+
+        let r_squared = if total_sum_of_squares > 0.0 {
+            1.0 - sum_of_squared_errors/total_sum_of_squares
+        } else {
+            1.0
+        };
+
+        FitStatistics {
+            sum_of_squared_errors,
+            root_mean_square_error: Ratio::new::<ratio>(
+                (sum_of_squared_errors/number_of_points as f64).sqrt()),
+            r_squared: Ratio::new::<ratio>(r_squared),
+            number_of_points,
+        }
+    }
+}
+
+/// Nelder Mead simplex minimisation, for the small nonlinear
+/// least squares problems here (a few parameters)
+///
+/// the starting simplex is the initial guess plus a step in
+/// each parameter
+pub(crate) fn nelder_mead(function: impl Fn(&[f64]) -> f64,
+    initial_guess: &[f64],
+    initial_steps: &[f64],
+    max_iterations: usize) -> Vec<f64> {
+
+    let size = initial_guess.len();
+    let mut simplex: Vec<(Vec<f64>, f64)> = (0..=size).map(|i| {
+        let mut point = initial_guess.to_vec();
+        if i > 0 {
+            point[i - 1] += initial_steps[i - 1];
+        }
+        let value = function(&point);
+        (point, value)
+    }).collect();
+
+    for _ in 0..max_iterations {
+        simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+        let best_value = simplex[0].1;
+        let worst_value = simplex[size].1;
+        if (worst_value - best_value).abs()
+            <= 1e-12 * (best_value.abs() + 1e-20) {
+            break;
+        }
+
+        // centroid of all but the worst point
+        let centroid: Vec<f64> = (0..size).map(|j| {
+            simplex[..size].iter().map(|(point, _)| point[j]).sum::<f64>()
+                /size as f64
+        }).collect();
+        let along_line = |factor: f64| -> Vec<f64> {
+            centroid.iter().zip(simplex[size].0.iter())
+                .map(|(c, worst)| c + factor * (c - worst)).collect()
+        };
+
+        let reflected = along_line(1.0);
+        let reflected_value = function(&reflected);
+
+        if reflected_value < best_value {
+            let expanded = along_line(2.0);
+            let expanded_value = function(&expanded);
+            simplex[size] = if expanded_value < reflected_value {
+                (expanded, expanded_value)
+            } else {
+                (reflected, reflected_value)
+            };
+        } else if reflected_value < simplex[size - 1].1 {
+            simplex[size] = (reflected, reflected_value);
+        } else {
+            let contracted = if reflected_value < worst_value {
+                along_line(0.5)
+            } else {
+                along_line(-0.5)
+            };
+            let contracted_value = function(&contracted);
+            if contracted_value < worst_value.min(reflected_value) {
+                simplex[size] = (contracted, contracted_value);
+            } else {
+                // shrink everything towards the best point
+                let best_point = simplex[0].0.clone();
+                for (point, value) in simplex.iter_mut().skip(1) {
+                    for (x, best) in point.iter_mut().zip(best_point.iter()) {
+                        *x = best + 0.5 * (*x - best);
+                    }
+                    *value = function(point);
+                }
+            }
+        }
+    }
+
+    simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+    simplex.swap_remove(0).0
+}
@@ -0,0 +1,449 @@
+use uom::si::f64::*;
+use uom::si::ratio::ratio;
+use uom::si::time::second;
+use uom::ConstZero;
+
+use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
+use crate::alpha_nightly::transfer_fn_wrapper_and_enums::{TransferFnFirstOrder,
+    TransferFnSecondOrder, TransferFnTraits};
+use crate::alpha_nightly::TimeSquared;
+
+use super::{nelder_mead, IdentifiedModel, ProcessData};
+
+/// graphical (two point) methods for a FOPDT model from a step test,
+/// using the times t1 and t2 (after the step) at which the output
+/// has made two fractions of its total change
+#[derive(Debug,PartialEq, PartialOrd, Clone, Copy)]
+pub enum TwoPointMethod {
+    /// Smith's method, 28.3% and 63.2% of the change,
+    ///
+    /// tau = 1.5 (t63 - t28), theta = t63 - tau
+    Smith,
+    /// Sundaresan and Krishnaswamy's method, 35.3% and 85.3%,
+    ///
+    /// tau = 0.67 (t85 - t35), theta = 1.3 t35 - 0.29 t85
+    SundaresanKrishnaswamy,
+}
+
+/// FOPDT or SOPDT parameters (K, tau_1, tau_2, theta), with
+/// tau_2 = 0 for FOPDT
+type ModelParameters = (f64, f64, f64, f64);
+
+impl ProcessData {
+
+    /// least squares fit of a FOPDT model,
+    ///
+    /// G(s) = K exp(-theta s)/(tau s + 1)
+    ///
+    /// the model is simulated with the recorded inputs (held
+    /// between samples), so any input sequence works, not just a
+    /// single step. The process is taken to be at steady state at
+    /// the first data point.
+    pub fn fit_first_order_plus_dead_time(&self)
+    -> Result<IdentifiedModel<TransferFnFirstOrder>,
+    ChemEngProcessControlSimulatorError> {
+
+        let (_, time_constant, _, dead_time) = self.least_squares_fit(false)?;
+        self.first_order_model(time_constant, dead_time)
+    }
+
+    /// least squares fit of an overdamped SOPDT model,
+    ///
+    /// G(s) = K exp(-theta s)/((tau_1 s + 1)(tau_2 s + 1))
+    ///
+    /// done the same way as the FOPDT fit
+    pub fn fit_second_order_plus_dead_time(&self)
+    -> Result<IdentifiedModel<TransferFnSecondOrder>,
+    ChemEngProcessControlSimulatorError> {
+
+        let (_, dominant_time_constant, second_time_constant, dead_time) =
+            self.least_squares_fit(true)?;
+
+        let (process_gain, _) = self.best_gain(dominant_time_constant,
+            second_time_constant, dead_time);
+        let mut transfer_fn = TransferFnSecondOrder::new(TimeSquared::ZERO,
+            Time::ZERO, Ratio::new::<ratio>(process_gain),
+            Time::new::<second>(dominant_time_constant)
+            * Time::new::<second>(second_time_constant),
+            Time::new::<second>(dominant_time_constant + second_time_constant),
+            Ratio::new::<ratio>(1.0))?;
+        transfer_fn.set_dead_time(Time::new::<second>(dead_time));
+
+        let fit_statistics = self.fit_statistics(&self.simulate(
+                (process_gain, dominant_time_constant, second_time_constant,
+                    dead_time)));
+
+        Ok(IdentifiedModel { transfer_fn, fit_statistics })
+    }
+
+    /// FOPDT model from a step test using a two point method,
+    ///
+    /// the input must make a single step and stay there, and the
+    /// output should have settled by the end of the data. The gain
+    /// is the change in output over the change in input.
+    pub fn two_point_first_order(&self, method: TwoPointMethod)
+    -> Result<IdentifiedModel<TransferFnFirstOrder>,
+    ChemEngProcessControlSimulatorError> {
+
+        let (process_gain, time_constant, _, dead_time) =
+            self.two_point_parameters(method)?;
+        let mut transfer_fn = TransferFnFirstOrder::new(Time::ZERO,
+            Ratio::new::<ratio>(process_gain),
+            Time::new::<second>(time_constant), Ratio::new::<ratio>(1.0))?;
+        transfer_fn.set_dead_time(Time::new::<second>(dead_time));
+
+        let fit_statistics = self.fit_statistics(&self.simulate(
+                (process_gain, time_constant, 0.0, dead_time)));
+
+        Ok(IdentifiedModel { transfer_fn, fit_statistics })
+    }
+
+    fn first_order_model(&self, time_constant: f64, dead_time: f64)
+    -> Result<IdentifiedModel<TransferFnFirstOrder>,
+    ChemEngProcessControlSimulatorError> {
+
+        let (process_gain, _) = self.best_gain(time_constant, 0.0, dead_time);
+        let mut transfer_fn = TransferFnFirstOrder::new(Time::ZERO,
+            Ratio::new::<ratio>(process_gain),
+            Time::new::<second>(time_constant), Ratio::new::<ratio>(1.0))?;
+        transfer_fn.set_dead_time(Time::new::<second>(dead_time));
+
+        let fit_statistics = self.fit_statistics(&self.simulate(
+                (process_gain, time_constant, 0.0, dead_time)));
+
+        Ok(IdentifiedModel { transfer_fn, fit_statistics })
+    }
+
+    /// index of the input step, the input change and the
+    /// output change
+    fn step_change(&self) -> Result<(usize, f64, f64),
+    ChemEngProcessControlSimulatorError> {
+
+        let inputs: Vec<f64> = self.inputs.iter()
+            .map(|u| u.get::<ratio>()).collect();
+        let initial_input = inputs[0];
+        let final_input = *inputs.last().unwrap();
+        let tolerance = 1e-9 * initial_input.abs().max(1.0);
+
+        let step_index = inputs.iter()
+            .position(|u| (u - initial_input).abs() > tolerance);
+        let step_index = match step_index {
+            Some(index) if inputs[index..].iter()
+                .all(|u| (u - final_input).abs() <= tolerance) => index,
+            _ => {
+                return Err(ChemEngProcessControlSimulatorError::
+                    GenericStringError(
+                        "two point methods need a single step in the input"
+                        .to_owned()));
+            },
+        };
+
+        let output_change = self.outputs.last().unwrap().get::<ratio>()
+            - self.outputs[0].get::<ratio>();
+        if output_change == 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "output does not change in the step test".to_owned()));
+        }
+
+        Ok((step_index, final_input - initial_input, output_change))
+    }
+
+    fn two_point_parameters(&self, method: TwoPointMethod)
+    -> Result<ModelParameters, ChemEngProcessControlSimulatorError> {
+
+        let (step_index, input_change, output_change) = self.step_change()?;
+        let step_time: f64 = self.times[step_index].get::<second>();
+        let initial_output: f64 = self.outputs[0].get::<ratio>();
+
+        // time after the step when the output first makes a fraction
+        // of its change, interpolated between samples
+        let time_to_reach = |fraction: f64| -> f64 {
+            let fractions: Vec<f64> = self.outputs.iter()
+                .map(|y| (y.get::<ratio>() - initial_output)/output_change)
+                .collect();
+            let index = fractions.iter().position(|f| *f >= fraction)
+                .unwrap_or(fractions.len() - 1);
+            let time: f64 = if index == 0 {
+                self.times[0].get::<second>()
+            } else {
+                let (t0, t1) = (self.times[index - 1].get::<second>(),
+                    self.times[index].get::<second>());
+                let (f0, f1) = (fractions[index - 1], fractions[index]);
+                t0 + (fraction - f0)/(f1 - f0) * (t1 - t0)
+            };
+            time - step_time
+        };
+
+        let (time_constant, dead_time) = match method {
+            TwoPointMethod::Smith => {
+                let (t28, t63) = (time_to_reach(0.283), time_to_reach(0.632));
+                let time_constant = 1.5 * (t63 - t28);
+                (time_constant, t63 - time_constant)
+            },
+            TwoPointMethod::SundaresanKrishnaswamy => {
+                let (t35, t85) = (time_to_reach(0.353), time_to_reach(0.853));
+                (0.67 * (t85 - t35), 1.3 * t35 - 0.29 * t85)
+            },
+        };
+
+        if time_constant <= 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "could not find a positive time constant from the \
+                    step test".to_owned()));
+        }
+
+        Ok((output_change/input_change, time_constant, 0.0,
+            dead_time.max(0.0)))
+    }
+
+    /// (K, tau_1, tau_2, theta) minimising the sum of squared errors
+    ///
+    /// K is found by linear least squares for each set of time
+    /// constants and dead time, the rest by Nelder Mead on
+    /// (ln tau_1, ln tau_2, theta)
+    fn least_squares_fit(&self, second_order: bool)
+    -> Result<ModelParameters, ChemEngProcessControlSimulatorError> {
+
+        let duration: f64 = (*self.times.last().unwrap() - self.times[0])
+            .get::<second>();
+
+        // starting guesses, from the two point method if this is a
+        // step test, and a rough guess otherwise
+        let mut first_order_guesses: Vec<(f64, f64)> =
+            vec![(duration/5.0, duration/20.0)];
+        if let Ok((_, time_constant, _, dead_time)) =
+            self.two_point_parameters(TwoPointMethod::Smith) {
+            first_order_guesses.insert(0, (time_constant, dead_time));
+        }
+
+        let sum_of_squared_errors = |time_constant: f64,
+            second_time_constant: f64, dead_time: f64| -> f64 {
+            self.best_gain(time_constant, second_time_constant, dead_time).1
+        };
+
+        let first_order_objective = |parameters: &[f64]| -> f64 {
+            sum_of_squared_errors(parameters[0].exp(), 0.0, parameters[1].abs())
+        };
+        let (time_constant, dead_time) = first_order_guesses.iter()
+            .map(|(time_constant, dead_time)| {
+                let best = nelder_mead(first_order_objective,
+                    &[time_constant.ln(), *dead_time],
+                    &[0.5, 0.1 * time_constant + 0.05 * duration], 2000);
+                (best[0].exp(), best[1].abs())
+            })
+            .min_by(|a, b| sum_of_squared_errors(a.0, 0.0, a.1)
+                .total_cmp(&sum_of_squared_errors(b.0, 0.0, b.1)))
+            .unwrap();
+
+        if !time_constant.is_finite() || time_constant <= 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "least squares fit did not converge".to_owned()));
+        }
+
+        if !second_order {
+            let (process_gain, _) = self.best_gain(time_constant, 0.0,
+                dead_time);
+            return Ok((process_gain, time_constant, 0.0, dead_time));
+        }
+
+        // for SOPDT, start from the FOPDT fit, undoing the half rule
+        // for a few splits of the dead time
+        let second_order_objective = |parameters: &[f64]| -> f64 {
+            sum_of_squared_errors(parameters[0].exp(), parameters[1].exp(),
+                parameters[2].abs())
+        };
+        let (dominant_time_constant, second_time_constant, dead_time) =
+            [0.1, 0.5, 0.9].iter().map(|split| {
+                let second_time_constant = 2.0 * split * dead_time
+                    + 0.05 * time_constant;
+                let dominant_time_constant = (time_constant
+                    - 0.5 * second_time_constant).max(0.1 * time_constant);
+                let best = nelder_mead(second_order_objective,
+                    &[dominant_time_constant.ln(), second_time_constant.ln(),
+                    (dead_time - 0.5 * second_time_constant).max(0.0)],
+                    &[0.3, 0.5, 0.1 * time_constant], 4000);
+                (best[0].exp(), best[1].exp(), best[2].abs())
+            })
+            .min_by(|a, b| sum_of_squared_errors(a.0, a.1, a.2)
+                .total_cmp(&sum_of_squared_errors(b.0, b.1, b.2)))
+            .unwrap();
+
+        let (dominant_time_constant, second_time_constant) =
+            if dominant_time_constant >= second_time_constant {
+                (dominant_time_constant, second_time_constant)
+            } else {
+                (second_time_constant, dominant_time_constant)
+            };
+        let (process_gain, _) = self.best_gain(dominant_time_constant,
+            second_time_constant, dead_time);
+
+        Ok((process_gain, dominant_time_constant, second_time_constant,
+            dead_time))
+    }
+
+    /// (K, sum of squared errors), with K from linear least squares
+    /// on the unit gain response
+    fn best_gain(&self, time_constant: f64, second_time_constant: f64,
+        dead_time: f64) -> (f64, f64) {
+
+        let initial_output: f64 = self.outputs[0].get::<ratio>();
+        let unit_response: Vec<f64> = self.simulate(
+            (1.0, time_constant, second_time_constant, dead_time))
+            .iter().map(|y| y - initial_output).collect();
+        let output_changes: Vec<f64> = self.outputs.iter()
+            .map(|y| y.get::<ratio>() - initial_output).collect();
+
+        let response_squared: f64 = unit_response.iter().map(|r| r * r).sum();
+        let process_gain: f64 = if response_squared > 0.0 {
+            unit_response.iter().zip(output_changes.iter())
+                .map(|(r, y)| r * y).sum::<f64>()/response_squared
+        } else {
+            0.0
+        };
+
+        let sum_of_squared_errors: f64 = unit_response.iter()
+            .zip(output_changes.iter())
+            .map(|(r, y)| (y - process_gain * r).powi(2)).sum();
+
+        (process_gain, sum_of_squared_errors)
+    }
+
+    /// model output at every data point, starting at steady state
+    /// with the first output, the input is held between samples
+    /// and then delayed by the dead time
+    ///
+    /// each lag is solved exactly over every stretch where the
+    /// delayed input is constant
+    fn simulate(&self, parameters: ModelParameters) -> Vec<f64> {
+        let (process_gain, time_constant, second_time_constant, dead_time) =
+            parameters;
+        let times: Vec<f64> = self.times.iter()
+            .map(|t| t.get::<second>()).collect();
+        let initial_input: f64 = self.inputs[0].get::<ratio>();
+        let input_changes: Vec<f64> = self.inputs.iter()
+            .map(|u| u.get::<ratio>() - initial_input).collect();
+        let initial_output: f64 = self.outputs[0].get::<ratio>();
+
+        // first and second lag states, in deviation from the
+        // initial steady state
+        let mut first_lag: f64 = 0.0;
+        let mut second_lag: f64 = 0.0;
+        let advance = |first_lag: &mut f64, second_lag: &mut f64,
+            step: f64, input: f64| {
+            let target = process_gain * input;
+            let first_decay = (-step/time_constant).exp();
+            if second_time_constant > 0.0 {
+                let second_decay = (-step/second_time_constant).exp();
+                let interaction = if (time_constant - second_time_constant).abs()
+                    < 1e-9 * time_constant {
+                    step/second_time_constant * second_decay
+                } else {
+                    time_constant/(time_constant - second_time_constant)
+                        * (first_decay - second_decay)
+                };
+                *second_lag = target + (*second_lag - target) * second_decay
+                    + (*first_lag - target) * interaction;
+            }
+            *first_lag = target + (*first_lag - target) * first_decay;
+        };
+
+        let mut model_outputs: Vec<f64> = vec![initial_output];
+        // index of the input sample which has just come through the
+        // dead time, None if nothing has come through yet
+        let mut delayed_index: Option<usize> = None;
+        for window in times.windows(2) {
+            let mut current_time = window[0];
+            let end_time = window[1];
+            while current_time < end_time {
+                let next_index = delayed_index.map_or(0, |index| index + 1);
+                let next_arrival = times.get(next_index)
+                    .map_or(f64::INFINITY, |t| t + dead_time);
+                let delayed_input = delayed_index
+                    .map_or(0.0, |index| input_changes[index]);
+
+                if next_arrival <= current_time {
+                    delayed_index = Some(next_index);
+                    continue;
+                }
+                let stretch_end = next_arrival.min(end_time);
+                advance(&mut first_lag, &mut second_lag,
+                    stretch_end - current_time, delayed_input);
+                current_time = stretch_end;
+            }
+            let lag_output = if second_time_constant > 0.0 {
+                second_lag
+            } else {
+                first_lag
+            };
+            model_outputs.push(initial_output + lag_output);
+        }
+
+        model_outputs
+    }
+}
+
+#[test]
+pub fn test_step_test_identification(){
+    // 2 exp(-2s)/((10 s + 1)(3 s + 1)), input steps by 0.5 at t = 1 s,
+    // output starts at 3
+    let mut process = TransferFnSecondOrder::new(TimeSquared::ZERO,
+        Time::ZERO, Ratio::new::<ratio>(2.0),
+        Time::new::<second>(10.0) * Time::new::<second>(3.0),
+        Time::new::<second>(13.0), Ratio::new::<ratio>(1.0)).unwrap();
+    process.set_dead_time(Time::new::<second>(2.0));
+
+    let path = std::env::temp_dir().join("sopdt_step_test.csv");
+    let mut wtr = csv::Writer::from_path(&path).unwrap();
+    for step in 0..=1000 {
+        let time = Time::new::<second>(step as f64 * 0.1);
+        let input = if step >= 10 { 0.5 } else { 0.0 };
+        let output = process.set_user_input_and_calc(
+            Ratio::new::<ratio>(input), time).unwrap();
+        process.csv_write_values(&mut wtr, time,
+            Ratio::new::<ratio>(input + 1.0),
+            output + Ratio::new::<ratio>(3.0)).unwrap();
+    }
+    drop(wtr);
+
+    let data = ProcessData::from_csv(&path).unwrap();
+    assert_eq!(data.times().len(), 1001);
+
+    let sopdt = data.fit_second_order_plus_dead_time().unwrap();
+    let poles = sopdt.transfer_fn.poles().unwrap();
+    let mut time_constants: Vec<f64> = poles.iter().map(|pole| -1.0/pole.re)
+        .collect();
+    time_constants.sort_by(|a, b| b.total_cmp(a));
+    approx::assert_relative_eq!(sopdt.transfer_fn.steady_state_gain().unwrap()
+        .get::<ratio>(), 2.0, max_relative = 0.01);
+    approx::assert_relative_eq!(time_constants[0], 10.0, max_relative = 0.03);
+    approx::assert_relative_eq!(time_constants[1], 3.0, max_relative = 0.05);
+    approx::assert_abs_diff_eq!(sopdt.transfer_fn.dead_time().get::<second>(),
+        2.0, epsilon = 0.1);
+    assert!(sopdt.fit_statistics.r_squared.get::<ratio>() > 0.9999);
+
+    // FOPDT is an approximation, but still a good fit
+    let fopdt = data.fit_first_order_plus_dead_time().unwrap();
+    assert!(fopdt.fit_statistics.r_squared.get::<ratio>() > 0.99);
+    assert!(fopdt.fit_statistics.sum_of_squared_errors
+        > sopdt.fit_statistics.sum_of_squared_errors);
+
+    // two point methods, the gain comes from the settled output
+    for method in [TwoPointMethod::Smith, TwoPointMethod::SundaresanKrishnaswamy] {
+        let two_point = data.two_point_first_order(method).unwrap();
+        approx::assert_relative_eq!(two_point.transfer_fn.steady_state_gain()
+            .unwrap().get::<ratio>(), 2.0, max_relative = 0.01);
+        assert!(two_point.fit_statistics.r_squared.get::<ratio>() > 0.98);
+        assert!(two_point.fit_statistics.sum_of_squared_errors
+            >= fopdt.fit_statistics.sum_of_squared_errors);
+    }
+
+    // no step, no two point method
+    let ramp = ProcessData::new_from_f64(&[0.0, 1.0, 2.0, 3.0],
+        &[0.0, 1.0, 2.0, 3.0], &[0.0, 0.0, 1.0, 2.0]).unwrap();
+    assert!(ramp.two_point_first_order(TwoPointMethod::Smith).is_err());
+    assert!(ProcessData::new_from_f64(&[0.0, 1.0, 1.0],
+        &[0.0; 3], &[0.0; 3]).is_err());
+}
@@ -0,0 +1,250 @@
+//! small dense matrix helper functions for state space models
+//!
+//! matrices are stored row by row as Vec<Vec<f64>>, and vectors
+//! as Vec<f64>. The systems in process control are usually small
+//! (a handful of states), so nothing fancy is done here.
+//!
+//! as with the polynomials, everything is in SI base units
+
+use super::errors::ChemEngProcessControlSimulatorError;
+
+/// n by n identity matrix
+pub fn identity(size: usize) -> Vec<Vec<f64>> {
+    (0..size).map(|i| {
+        let mut row = vec![0.0; size];
+        row[i] = 1.0;
+        row
+    }).collect()
+}
+
+/// rows by columns matrix of zeroes
+pub fn zeroes(rows: usize, columns: usize) -> Vec<Vec<f64>> {
+    vec![vec![0.0; columns]; rows]
+}
+
+/// multiplies two matrices
+pub fn multiply(first: &[Vec<f64>], second: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let columns = second.first().map_or(0, |row| row.len());
+    let mut product = zeroes(first.len(), columns);
+
+    for (i, first_row) in first.iter().enumerate() {
+        for (k, first_element) in first_row.iter().enumerate() {
+            if *first_element == 0.0 {
+                continue;
+            }
+            for (j, second_element) in second[k].iter().enumerate() {
+                product[i][j] += first_element * second_element;
+            }
+        }
+    }
+    product
+}
+
+/// multiplies a matrix by a vector
+pub fn multiply_vector(matrix: &[Vec<f64>], vector: &[f64]) -> Vec<f64> {
+    matrix.iter().map(|row| {
+        row.iter().zip(vector.iter())
+            .map(|(element, vector_element)| element * vector_element)
+            .sum()
+    }).collect()
+}
+
+/// adds two matrices of the same size
+pub fn add(first: &[Vec<f64>], second: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    first.iter().zip(second.iter()).map(|(first_row, second_row)| {
+        first_row.iter().zip(second_row.iter())
+            .map(|(first_element, second_element)|
+                first_element + second_element)
+            .collect()
+    }).collect()
+}
+
+/// multiplies a matrix by a constant
+pub fn scale(matrix: &[Vec<f64>], factor: f64) -> Vec<Vec<f64>> {
+    matrix.iter().map(|row| {
+        row.iter().map(|element| element * factor).collect()
+    }).collect()
+}
+
+/// one norm of a matrix (largest absolute column sum)
+pub fn norm_one(matrix: &[Vec<f64>]) -> f64 {
+    let columns = matrix.first().map_or(0, |row| row.len());
+    (0..columns).map(|j| {
+        matrix.iter().map(|row| row[j].abs()).sum::<f64>()
+    }).fold(0.0_f64, f64::max)
+}
+
+/// matrix exponential exp(M) of a square matrix
+///
+/// I'm using scaling and squaring, ie.
+///
+/// exp(M) = exp(M/2^k)^(2^k)
+///
+/// where k is chosen so that the one norm of M/2^k is less than
+/// 0.5. The taylor series of exp(M/2^k) then converges to machine
+/// precision in less than 20 terms.
+pub fn exponential(matrix: &[Vec<f64>])
+-> Result<Vec<Vec<f64>>, ChemEngProcessControlSimulatorError> {
+
+    let size = matrix.len();
+    if matrix.iter().any(|row| row.len() != size) {
+        return Err(ChemEngProcessControlSimulatorError::
+            GenericStringError("matrix exponential needs a square matrix"
+                .to_owned()));
+    }
+
+    let norm = norm_one(matrix);
+    if !norm.is_finite() {
+        return Err(ChemEngProcessControlSimulatorError::
+            GenericStringError("matrix has non finite elements".to_owned()));
+    }
+
+    let mut squarings: i32 = 0;
+    if norm > 0.5 {
+        squarings = (norm/0.5).log2().ceil() as i32;
+    }
+    let scaled_matrix = scale(matrix, 0.5_f64.powi(squarings));
+
+    // taylor series, sum of M^j/j!
+    let mut result = identity(size);
+    let mut term = identity(size);
+    for j in 1..=20 {
+        term = scale(&multiply(&term, &scaled_matrix), 1.0/(j as f64));
+        result = add(&result, &term);
+        if norm_one(&term) <= f64::EPSILON * norm_one(&result) {
+            break;
+        }
+    }
+
+    for _ in 0..squarings {
+        result = multiply(&result, &result);
+    }
+
+    Ok(result)
+}
+
+/// combined L and U factors, row permutation and 
+/// sign of the permutation
+type LuDecomposition = (Vec<Vec<f64>>, Vec<usize>, f64);
+
+/// LU decomposition with partial pivoting, returns the combined
+/// L and U factors (L has ones on the diagonal, which are not
+/// stored), the row permutation and the sign of the permutation
+fn lu_decomposition(matrix: &[Vec<f64>])
+-> Result<LuDecomposition, ChemEngProcessControlSimulatorError> {
+
+    let size = matrix.len();
+    if matrix.iter().any(|row| row.len() != size) {
+        return Err(ChemEngProcessControlSimulatorError::
+            GenericStringError("matrix must be square".to_owned()));
+    }
+
+    let mut factors = matrix.to_vec();
+    let mut permutation: Vec<usize> = (0..size).collect();
+    let mut permutation_sign: f64 = 1.0;
+
+    for k in 0..size {
+        // largest pivot in the column
+        let pivot_row = (k..size).fold(k, |best, i| {
+            if factors[i][k].abs() > factors[best][k].abs() { i } else { best }
+        });
+
+        if factors[pivot_row][k] == 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::SingularMatrix);
+        }
+
+        if pivot_row != k {
+            factors.swap(pivot_row, k);
+            permutation.swap(pivot_row, k);
+            permutation_sign = -permutation_sign;
+        }
+
+        let (upper_rows, lower_rows) = factors.split_at_mut(k + 1);
+        let pivot_row = &upper_rows[k];
+        for row in lower_rows.iter_mut() {
+            let multiplier = row[k]/pivot_row[k];
+            row[k] = multiplier;
+            for (element, pivot_element) in row[(k+1)..].iter_mut()
+                .zip(pivot_row[(k+1)..].iter()) {
+                *element -= multiplier * pivot_element;
+            }
+        }
+    }
+
+    Ok((factors, permutation, permutation_sign))
+}
+
+/// determinant of a square matrix
+///
+/// a singular matrix gives zero rather than an error
+pub fn determinant(matrix: &[Vec<f64>])
+-> Result<f64, ChemEngProcessControlSimulatorError> {
+    match lu_decomposition(matrix) {
+        Ok((factors, _, permutation_sign)) => {
+            Ok((0..factors.len()).fold(permutation_sign,
+                |product, i| product * factors[i][i]))
+        },
+        Err(ChemEngProcessControlSimulatorError::SingularMatrix) => Ok(0.0),
+        Err(error) => Err(error),
+    }
+}
+
+/// inverse of a square matrix
+pub fn inverse(matrix: &[Vec<f64>])
+-> Result<Vec<Vec<f64>>, ChemEngProcessControlSimulatorError> {
+
+    let (factors, permutation, _) = lu_decomposition(matrix)?;
+    let size = factors.len();
+    let mut inverse_matrix = zeroes(size, size);
+
+    // solve A x = e_j for each column j
+    for j in 0..size {
+        // forward substitution, L y = P e_j
+        let mut column: Vec<f64> = permutation.iter()
+            .map(|row| if *row == j { 1.0 } else { 0.0 }).collect();
+        for i in 0..size {
+            for k in 0..i {
+                column[i] -= factors[i][k] * column[k];
+            }
+        }
+        // back substitution, U x = y
+        for i in (0..size).rev() {
+            for k in (i+1)..size {
+                column[i] -= factors[i][k] * column[k];
+            }
+            column[i] /= factors[i][i];
+        }
+        for (inverse_row, element) in inverse_matrix.iter_mut()
+            .zip(column.iter()) {
+            inverse_row[j] = *element;
+        }
+    }
+
+    Ok(inverse_matrix)
+}
+
+#[test]
+pub fn test_matrix_exponential(){
+
+    // rotation generator
+    // exp([[0, 1], [-1, 0]] t) = [[cos t, sin t], [-sin t, cos t]]
+    let t: f64 = 2.5;
+    let generator = vec![vec![0.0, t], vec![-t, 0.0]];
+    let rotation = exponential(&generator).unwrap();
+
+    approx::assert_relative_eq!(rotation[0][0], t.cos(), max_relative = 1e-12);
+    approx::assert_relative_eq!(rotation[0][1], t.sin(), max_relative = 1e-12);
+    approx::assert_relative_eq!(rotation[1][0], -t.sin(), max_relative = 1e-12);
+    approx::assert_relative_eq!(rotation[1][1], t.cos(), max_relative = 1e-12);
+
+    // jordan block
+    // exp([[-1, 1], [0, -1]] t) = exp(-t) [[1, t], [0, 1]]
+    let jordan_block = vec![vec![-t, t], vec![0.0, -t]];
+    let jordan_exponential = exponential(&jordan_block).unwrap();
+
+    approx::assert_relative_eq!(jordan_exponential[0][0], (-t).exp(),
+        max_relative = 1e-12);
+    approx::assert_relative_eq!(jordan_exponential[0][1], t * (-t).exp(),
+        max_relative = 1e-12);
+    approx::assert_relative_eq!(jordan_exponential[1][0], 0.0);
+}
@@ -16,9 +16,19 @@
 /// limitations under the License.
 pub mod prelude;
 pub(crate) mod stable_transfer_functions;
+pub(crate) mod unstable_transfer_functions;
+pub(crate) mod undamped_transfer_functions;
+pub mod block_diagrams;
 pub mod controllers;
 pub mod errors;
+pub mod frequency_response;
+pub mod identification;
+pub mod matrices;
+pub mod model_predictive_control;
+pub(crate) mod numerical_search;
+pub mod polynomials;
 pub mod transfer_fn_wrapper_and_enums;
+pub mod tuning;
 
 
 use uom::si::{Quantity, ISQ, SI};
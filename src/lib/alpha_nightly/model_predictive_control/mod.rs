@@ -0,0 +1,697 @@
+//! model predictive control (MPC), as dynamic matrix control (DMC)
+//!
+//! the process is described by its step response coefficients,
+//! worked out by simulating a transfer function (or a matrix of
+//! them). At every sample, the controller predicts the outputs
+//! over the prediction horizon, and picks the input moves over
+//! the control horizon which minimise
+//!
+//! sum over outputs and the prediction horizon of w (r - y)^2
+//! + sum over inputs and the control horizon of lambda du^2
+//!
+//! subject to the input and rate limits. Only the first move is
+//! used, and the whole thing is repeated at the next sample
+//! (receding horizon). The difference between the measured and
+//! predicted outputs is taken as a constant disturbance over the
+//! prediction horizon, which removes offset.
+//!
+//! everything is in deviation variables, with the process at
+//! rest and all inputs at zero to start with.
+
+use csv::Writer;
+use uom::si::f64::*;
+use uom::si::ratio::ratio;
+use uom::si::time::second;
+use uom::ConstZero;
+
+use crate::alpha_nightly::controllers::OutputLimits;
+use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
+use crate::alpha_nightly::transfer_fn_wrapper_and_enums::{TransferFnMatrix,
+    TransferFnTraits};
+
+mod quadratic_program;
+use quadratic_program::solve_quadratic_program;
+
+/// simulation steps per sample period when working out the step
+/// response coefficients
+const STEP_RESPONSE_SUBSTEPS: usize = 10;
+
+/// sweeps of the dual QP solver at each sample
+const QP_MAX_ITERATIONS: usize = 2000;
+
+/// step response coefficients of a process, as used by DMC
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub struct StepResponseModel {
+    /// coefficients[output][input][i] is the change in the output
+    /// (i + 1) sample periods after a unit step in the input
+    pub(crate) coefficients: Vec<Vec<Vec<f64>>>,
+    pub(crate) sample_period: Time,
+}
+
+impl StepResponseModel {
+
+    /// from known coefficients, coefficients[output][input][i]
+    /// being the response (i + 1) samples after a unit step
+    ///
+    /// every entry needs the same number of coefficients
+    /// (the model horizon), which should be long enough for the
+    /// responses to settle
+    pub fn new(coefficients: Vec<Vec<Vec<f64>>>,
+        sample_period: Time) -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        if sample_period.get::<second>() <= 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "sample period must be positive".to_owned()));
+        }
+        let number_of_inputs = coefficients.first().map_or(0, |row| row.len());
+        let model_horizon = coefficients.first()
+            .and_then(|row| row.first())
+            .map_or(0, |entry| entry.len());
+        if number_of_inputs == 0 || model_horizon == 0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "step response model is empty".to_owned()));
+        }
+        if coefficients.iter().any(|row| row.len() != number_of_inputs
+            || row.iter().any(|entry| entry.len() != model_horizon)) {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "every entry needs the same number of step response \
+                    coefficients".to_owned()));
+        }
+
+        Ok(Self { coefficients, sample_period })
+    }
+
+    /// simulates a unit step into a SISO model, which should be
+    /// freshly made (at rest), the model itself is not changed
+    pub fn from_transfer_fn<T: TransferFnTraits + Clone>(model: &T,
+        sample_period: Time,
+        model_horizon: usize) -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        let step_response = simulate_step_response(model, sample_period,
+            model_horizon)?;
+        Self::new(vec![vec![step_response]], sample_period)
+    }
+
+    /// simulates a unit step into every entry of a MIMO model
+    pub fn from_transfer_fn_matrix(model: &TransferFnMatrix,
+        sample_period: Time,
+        model_horizon: usize) -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        let mut coefficients: Vec<Vec<Vec<f64>>> = vec![];
+        for output_index in 0..model.number_of_outputs() {
+            let mut row: Vec<Vec<f64>> = vec![];
+            for input_index in 0..model.number_of_inputs() {
+                row.push(simulate_step_response(
+                        model.entry(output_index, input_index),
+                        sample_period, model_horizon)?);
+            }
+            coefficients.push(row);
+        }
+        Self::new(coefficients, sample_period)
+    }
+
+    pub fn number_of_outputs(&self) -> usize {
+        self.coefficients.len()
+    }
+
+    pub fn number_of_inputs(&self) -> usize {
+        self.coefficients[0].len()
+    }
+
+    /// number of step response coefficients, N
+    pub fn model_horizon(&self) -> usize {
+        self.coefficients[0][0].len()
+    }
+
+    pub fn sample_period(&self) -> Time {
+        self.sample_period
+    }
+
+    /// step response coefficients from one input to one output
+    pub fn coefficients(&self, output_index: usize, input_index: usize)
+    -> &[f64] {
+        &self.coefficients[output_index][input_index]
+    }
+
+    /// response a number of samples after a unit step, held at the
+    /// last coefficient beyond the model horizon
+    pub(crate) fn coefficient(&self, output_index: usize,
+        input_index: usize,
+        samples_after_step: usize) -> f64 {
+        if samples_after_step == 0 {
+            return 0.0;
+        }
+        let entry = &self.coefficients[output_index][input_index];
+        entry[samples_after_step.min(entry.len()) - 1]
+    }
+}
+
+/// unit step response sampled at 1, 2 ... N sample periods
+fn simulate_step_response<T: TransferFnTraits + Clone>(model: &T,
+    sample_period: Time,
+    model_horizon: usize) -> Result<Vec<f64>, ChemEngProcessControlSimulatorError> {
+
+    if model_horizon == 0 {
+        return Err(ChemEngProcessControlSimulatorError::
+            GenericStringError(
+                "model horizon must be at least one sample".to_owned()));
+    }
+
+    let mut model = model.clone();
+    let unit_step = Ratio::new::<ratio>(1.0);
+    let timestep: Time = sample_period/STEP_RESPONSE_SUBSTEPS as f64;
+
+    model.set_user_input_and_calc(unit_step, Time::ZERO)?;
+    let mut step_response: Vec<f64> = vec![];
+    for step in 1..=(model_horizon * STEP_RESPONSE_SUBSTEPS) {
+        let response = model.set_user_input_and_calc(unit_step,
+            timestep * step as f64)?;
+        if step % STEP_RESPONSE_SUBSTEPS == 0 {
+            step_response.push(response.get::<ratio>());
+        }
+    }
+
+    Ok(step_response)
+}
+
+/// dynamic matrix control, SISO or MIMO
+///
+/// like the DigitalController, it samples every sample period and
+/// holds its outputs (the process inputs) in between, so it can be
+/// called every simulation timestep.
+///
+/// input limits and rate limits (largest move per sample) are hard
+/// constraints, handled by a small QP solver built in here.
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub struct DmcController {
+    pub(crate) model: StepResponseModel,
+    /// P, in samples
+    pub(crate) prediction_horizon: usize,
+    /// M, in samples
+    pub(crate) control_horizon: usize,
+    /// lambda for each input
+    pub(crate) move_suppression: Vec<f64>,
+    /// w for each output
+    pub(crate) output_weights: Vec<f64>,
+    pub(crate) input_limits: Vec<Option<OutputLimits>>,
+    /// largest move per sample for each input
+    pub(crate) rate_limits: Vec<Option<Ratio>>,
+    /// future setpoints for each output, one per sample starting
+    /// one sample ahead, the last one is held
+    pub(crate) setpoint_trajectories: Vec<Vec<Ratio>>,
+    /// predicted outputs at samples k, k+1 ... k+N-1 from the input
+    /// moves so far, model only (without the disturbance)
+    pub(crate) predicted_outputs: Vec<Vec<f64>>,
+    /// inputs applied to the process, held between samples
+    pub(crate) inputs: Vec<Ratio>,
+    pub(crate) next_sample_time: Option<Time>,
+}
+
+impl DmcController {
+
+    /// P and M are in samples, with 1 <= M <= P, and lambda
+    /// (the same for every input to start with) must not be
+    /// negative
+    ///
+    /// setpoints start at zero, with no limits
+    pub fn new(model: StepResponseModel,
+        prediction_horizon: usize,
+        control_horizon: usize,
+        move_suppression: f64) -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        if control_horizon == 0 || control_horizon > prediction_horizon {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "control horizon must be between one sample and the \
+                    prediction horizon".to_owned()));
+        }
+        check_weight(move_suppression)?;
+
+        let number_of_inputs = model.number_of_inputs();
+        let number_of_outputs = model.number_of_outputs();
+        let model_horizon = model.model_horizon();
+
+        Ok(Self {
+            model,
+            prediction_horizon,
+            control_horizon,
+            move_suppression: vec![move_suppression; number_of_inputs],
+            output_weights: vec![1.0; number_of_outputs],
+            input_limits: vec![None; number_of_inputs],
+            rate_limits: vec![None; number_of_inputs],
+            setpoint_trajectories: vec![vec![Ratio::ZERO]; number_of_outputs],
+            predicted_outputs: vec![vec![0.0; model_horizon]; number_of_outputs],
+            inputs: vec![Ratio::ZERO; number_of_inputs],
+            next_sample_time: None,
+        })
+    }
+
+    /// lambda for one input, larger values give smaller moves
+    pub fn set_move_suppression(&mut self, input_index: usize,
+        move_suppression: f64) -> Result<(), ChemEngProcessControlSimulatorError> {
+        self.check_input_index(input_index)?;
+        check_weight(move_suppression)?;
+        self.move_suppression[input_index] = move_suppression;
+        Ok(())
+    }
+
+    /// w for one output, how much its error counts
+    pub fn set_output_weight(&mut self, output_index: usize,
+        output_weight: f64) -> Result<(), ChemEngProcessControlSimulatorError> {
+        self.check_output_index(output_index)?;
+        check_weight(output_weight)?;
+        self.output_weights[output_index] = output_weight;
+        Ok(())
+    }
+
+    /// hard limits on one input, eg. a valve between 0 and 1
+    pub fn set_input_limits(&mut self, input_index: usize,
+        lower_limit: Ratio,
+        upper_limit: Ratio) -> Result<(), ChemEngProcessControlSimulatorError> {
+        self.check_input_index(input_index)?;
+        self.input_limits[input_index] =
+            Some(OutputLimits::new(lower_limit, upper_limit)?);
+        Ok(())
+    }
+
+    pub fn remove_input_limits(&mut self, input_index: usize)
+    -> Result<(), ChemEngProcessControlSimulatorError> {
+        self.check_input_index(input_index)?;
+        self.input_limits[input_index] = None;
+        Ok(())
+    }
+
+    /// hard limit on how far one input can move in one sample
+    pub fn set_rate_limit(&mut self, input_index: usize,
+        largest_move: Ratio) -> Result<(), ChemEngProcessControlSimulatorError> {
+        self.check_input_index(input_index)?;
+        if largest_move.get::<ratio>() <= 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "rate limit must be positive".to_owned()));
+        }
+        self.rate_limits[input_index] = Some(largest_move);
+        Ok(())
+    }
+
+    pub fn remove_rate_limit(&mut self, input_index: usize)
+    -> Result<(), ChemEngProcessControlSimulatorError> {
+        self.check_input_index(input_index)?;
+        self.rate_limits[input_index] = None;
+        Ok(())
+    }
+
+    /// constant setpoint for one output
+    pub fn set_setpoint(&mut self, output_index: usize,
+        setpoint: Ratio) -> Result<(), ChemEngProcessControlSimulatorError> {
+        self.set_setpoint_trajectory(output_index, vec![setpoint])
+    }
+
+    /// known future setpoints for one output, the first is one
+    /// sample ahead of the next sample, and so on. The trajectory
+    /// moves along one entry per sample, and its last setpoint is
+    /// held. The controller sees changes coming within the
+    /// prediction horizon and starts moving ahead of them.
+    pub fn set_setpoint_trajectory(&mut self, output_index: usize,
+        setpoint_trajectory: Vec<Ratio>) -> Result<(),
+    ChemEngProcessControlSimulatorError> {
+        self.check_output_index(output_index)?;
+        if setpoint_trajectory.is_empty() {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "setpoint trajectory is empty".to_owned()));
+        }
+        self.setpoint_trajectories[output_index] = setpoint_trajectory;
+        Ok(())
+    }
+
+    /// setpoint for one output at the next sample
+    pub fn setpoint(&self, output_index: usize) -> Ratio {
+        self.setpoint_trajectories[output_index][0]
+    }
+
+    /// inputs currently applied to the process
+    pub fn inputs(&self) -> &[Ratio] {
+        &self.inputs
+    }
+
+    pub fn prediction_horizon(&self) -> usize {
+        self.prediction_horizon
+    }
+
+    pub fn control_horizon(&self) -> usize {
+        self.control_horizon
+    }
+
+    pub fn model(&self) -> &StepResponseModel {
+        &self.model
+    }
+
+    /// takes in all the measured outputs, and gives out all the
+    /// process inputs
+    pub fn set_measurements_and_calc(&mut self,
+        measurements: &[Ratio],
+        time_of_input: Time) -> Result<Vec<Ratio>,
+    ChemEngProcessControlSimulatorError> {
+
+        if measurements.len() != self.model.number_of_outputs() {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(format!(
+                    "expected {} measurements, got {}",
+                    self.model.number_of_outputs(), measurements.len())));
+        }
+
+        // small tolerance so that floating point error in the
+        // simulation time does not miss a sample
+        let sample_period = self.model.sample_period;
+        let tolerance = sample_period * 1e-6;
+        let mut next_sample_time = self.next_sample_time
+            .unwrap_or(time_of_input);
+
+        while time_of_input + tolerance >= next_sample_time {
+            self.sample(measurements)?;
+            next_sample_time += sample_period;
+        }
+        self.next_sample_time = Some(next_sample_time);
+
+        Ok(self.inputs.clone())
+    }
+
+    /// SISO version of set_measurements_and_calc
+    pub fn set_measurement_and_calc(&mut self,
+        measurement: Ratio,
+        time_of_input: Time) -> Result<Ratio,
+    ChemEngProcessControlSimulatorError> {
+        if self.model.number_of_inputs() != 1 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "controller has more than one input".to_owned()));
+        }
+        Ok(self.set_measurements_and_calc(&[measurement], time_of_input)?[0])
+    }
+
+    /// one DMC calculation at sample k
+    fn sample(&mut self, measurements: &[Ratio]) -> Result<(),
+    ChemEngProcessControlSimulatorError> {
+
+        let number_of_inputs = self.model.number_of_inputs();
+        let number_of_outputs = self.model.number_of_outputs();
+        let model_horizon = self.model.model_horizon();
+        let prediction_horizon = self.prediction_horizon;
+        let control_horizon = self.control_horizon;
+        let number_of_moves = number_of_inputs * control_horizon;
+
+        // predicted error over the prediction horizon if the inputs
+        // stay where they are, rows are (output, sample j = 1 ... P)
+        let mut free_errors: Vec<f64> = vec![];
+        for (output_index, measurement) in measurements.iter().enumerate() {
+            let predicted_outputs = &self.predicted_outputs[output_index];
+            let disturbance = measurement.get::<ratio>() - predicted_outputs[0];
+            let setpoint_trajectory = &self.setpoint_trajectories[output_index];
+            for j in 1..=prediction_horizon {
+                let setpoint = setpoint_trajectory[
+                    (j - 1).min(setpoint_trajectory.len() - 1)];
+                free_errors.push(setpoint.get::<ratio>()
+                    - predicted_outputs[j.min(model_horizon - 1)]
+                    - disturbance);
+            }
+        }
+
+        // dynamic matrix, columns are (input, move l = 0 ... M-1)
+        let mut dynamic_matrix: Vec<Vec<f64>> = vec![];
+        for output_index in 0..number_of_outputs {
+            for j in 1..=prediction_horizon {
+                let mut row: Vec<f64> = vec![0.0; number_of_moves];
+                for input_index in 0..number_of_inputs {
+                    for l in 0..control_horizon.min(j) {
+                        row[input_index * control_horizon + l] =
+                            self.model.coefficient(output_index,
+                                input_index, j - l);
+                    }
+                }
+                dynamic_matrix.push(row);
+            }
+        }
+        let row_weight = |row: usize| -> f64 {
+            self.output_weights[row/prediction_horizon]
+        };
+
+        // H = A^T W A + Lambda, g = -A^T W e
+        let mut hessian: Vec<Vec<f64>> = vec![vec![0.0; number_of_moves];
+            number_of_moves];
+        let mut gradient: Vec<f64> = vec![0.0; number_of_moves];
+        for (row_index, row) in dynamic_matrix.iter().enumerate() {
+            let weight = row_weight(row_index);
+            for a in 0..number_of_moves {
+                gradient[a] -= row[a] * weight * free_errors[row_index];
+                for b in 0..number_of_moves {
+                    hessian[a][b] += row[a] * weight * row[b];
+                }
+            }
+        }
+        for input_index in 0..number_of_inputs {
+            for l in 0..control_horizon {
+                let a = input_index * control_horizon + l;
+                hessian[a][a] += self.move_suppression[input_index];
+            }
+        }
+
+        // C du <= b
+        let mut constraint_matrix: Vec<Vec<f64>> = vec![];
+        let mut constraint_bounds: Vec<f64> = vec![];
+        for input_index in 0..number_of_inputs {
+            let previous_input: f64 = self.inputs[input_index].get::<ratio>();
+            for l in 0..control_horizon {
+                if let Some(largest_move) = self.rate_limits[input_index] {
+                    let mut row: Vec<f64> = vec![0.0; number_of_moves];
+                    row[input_index * control_horizon + l] = 1.0;
+                    constraint_bounds.push(largest_move.get::<ratio>());
+                    constraint_bounds.push(largest_move.get::<ratio>());
+                    constraint_matrix.push(row.iter().map(|c| -c).collect());
+                    constraint_matrix.push(row);
+                }
+                if let Some(input_limits) = self.input_limits[input_index] {
+                    // u(k+l) = u(k-1) + sum of moves up to l
+                    let mut row: Vec<f64> = vec![0.0; number_of_moves];
+                    for m in 0..=l {
+                        row[input_index * control_horizon + m] = 1.0;
+                    }
+                    constraint_bounds.push(input_limits.upper_limit
+                        .get::<ratio>() - previous_input);
+                    constraint_bounds.push(previous_input - input_limits
+                        .lower_limit.get::<ratio>());
+                    constraint_matrix.push(row.clone());
+                    constraint_matrix.push(row.iter().map(|c| -c).collect());
+                }
+            }
+        }
+
+        let moves = solve_quadratic_program(&hessian, &gradient,
+            &constraint_matrix, &constraint_bounds, QP_MAX_ITERATIONS)?;
+
+        // only the first move is used, kept within the limits in
+        // case the QP could not meet all of them
+        let mut applied_moves: Vec<f64> = vec![];
+        for input_index in 0..number_of_inputs {
+            let previous_input = self.inputs[input_index];
+            let mut input_move = Ratio::new::<ratio>(
+                moves[input_index * control_horizon]);
+            if let Some(largest_move) = self.rate_limits[input_index] {
+                input_move = input_move.max(-largest_move).min(largest_move);
+            }
+            let mut input = previous_input + input_move;
+            if let Some(input_limits) = self.input_limits[input_index] {
+                input = input_limits.clamp(input);
+            }
+            self.inputs[input_index] = input;
+            applied_moves.push((input - previous_input).get::<ratio>());
+        }
+
+        // predictions from k+1 onwards, with the new moves
+        for output_index in 0..number_of_outputs {
+            let previous_predictions = &self.predicted_outputs[output_index];
+            let predicted_outputs: Vec<f64> = (0..model_horizon).map(|j| {
+                previous_predictions[(j + 1).min(model_horizon - 1)]
+                    + applied_moves.iter().enumerate()
+                    .map(|(input_index, input_move)| {
+                        self.model.coefficient(output_index, input_index,
+                            j + 1) * input_move
+                    }).sum::<f64>()
+            }).collect();
+            self.predicted_outputs[output_index] = predicted_outputs;
+        }
+
+        for setpoint_trajectory in self.setpoint_trajectories.iter_mut() {
+            if setpoint_trajectory.len() > 1 {
+                setpoint_trajectory.remove(0);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_input_index(&self, input_index: usize)
+    -> Result<(), ChemEngProcessControlSimulatorError> {
+        if input_index >= self.model.number_of_inputs() {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(format!(
+                    "input {} out of range, the model has {} inputs",
+                    input_index, self.model.number_of_inputs())));
+        }
+        Ok(())
+    }
+
+    fn check_output_index(&self, output_index: usize)
+    -> Result<(), ChemEngProcessControlSimulatorError> {
+        if output_index >= self.model.number_of_outputs() {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(format!(
+                    "output {} out of range, the model has {} outputs",
+                    output_index, self.model.number_of_outputs())));
+        }
+        Ok(())
+    }
+
+    pub fn spawn_writer(&mut self, name: String) -> Result<Writer<std::fs::File>,
+    ChemEngProcessControlSimulatorError> {
+        let title_string: String = name + "_dmc_controller.csv";
+        let wtr = Writer::from_path(title_string)?;
+        Ok(wtr)
+    }
+
+    /// one row per timestep, time (s), then every measurement,
+    /// then every process input
+    pub fn csv_write_values(&mut self,
+        wtr: &mut Writer<std::fs::File>,
+        time: Time,
+        measurements: &[Ratio],
+        inputs: &[Ratio]) -> Result<(),
+    ChemEngProcessControlSimulatorError> {
+
+        let mut record: Vec<String> = vec![time.get::<second>().to_string()];
+        record.extend(measurements.iter()
+            .map(|measurement| measurement.get::<ratio>().to_string()));
+        record.extend(inputs.iter()
+            .map(|input| input.get::<ratio>().to_string()));
+
+        wtr.write_record(&record)?;
+
+        wtr.flush().unwrap();
+        Ok(())
+    }
+}
+
+/// weights must be finite and not negative
+fn check_weight(weight: f64) -> Result<(), ChemEngProcessControlSimulatorError> {
+    if !weight.is_finite() || weight < 0.0 {
+        return Err(ChemEngProcessControlSimulatorError::
+            GenericStringError(
+                "MPC weights must not be negative".to_owned()));
+    }
+    Ok(())
+}
+
+#[test]
+pub fn test_dynamic_matrix_control(){
+    use crate::alpha_nightly::transfer_fn_wrapper_and_enums::{TransferFn,
+        TransferFnFirstOrder};
+
+    let first_order = |gain: f64, time_constant: f64, dead_time: f64|
+    -> TransferFn {
+        let mut transfer_fn: TransferFn = TransferFnFirstOrder::new(Time::ZERO,
+            Ratio::new::<ratio>(gain), Time::new::<second>(time_constant),
+            Ratio::new::<ratio>(1.0)).unwrap().into();
+        transfer_fn.set_dead_time(Time::new::<second>(dead_time));
+        transfer_fn
+    };
+    let sample_period = Time::new::<second>(1.0);
+    let timestep: f64 = 0.5;
+
+    // SISO, model exp(-2 s)/(10 s + 1), but the plant gain is 1.2
+    let model = StepResponseModel::from_transfer_fn(
+        &first_order(1.0, 10.0, 2.0), sample_period, 60).unwrap();
+    assert_eq!(model.model_horizon(), 60);
+    let coefficients = model.coefficients(0, 0);
+    assert_eq!(coefficients[1], 0.0);
+    approx::assert_abs_diff_eq!(coefficients[11], 1.0 - (-1.0_f64).exp(),
+        epsilon = 1e-6);
+
+    let mut dmc_controller = DmcController::new(model, 20, 4, 0.1).unwrap();
+    dmc_controller.set_input_limits(0, Ratio::new::<ratio>(-0.5),
+        Ratio::new::<ratio>(1.5)).unwrap();
+    dmc_controller.set_rate_limit(0, Ratio::new::<ratio>(0.2)).unwrap();
+    dmc_controller.set_setpoint(0, Ratio::new::<ratio>(1.0)).unwrap();
+
+    let mut plant = first_order(1.2, 10.0, 2.0);
+    let mut measurement = Ratio::ZERO;
+    let mut previous_input: f64 = 0.0;
+    for step in 0..=300 {
+        let time = Time::new::<second>(step as f64 * timestep);
+        let input = dmc_controller.set_measurement_and_calc(measurement, time)
+            .unwrap();
+        measurement = plant.set_user_input_and_calc(input, time).unwrap();
+
+        // hard limits on the input and on the moves
+        let input: f64 = input.get::<ratio>();
+        assert!((-0.5 - 1e-9..=1.5 + 1e-9).contains(&input));
+        assert!((input - previous_input).abs() <= 0.2 + 1e-9);
+        previous_input = input;
+    }
+    // no offset despite the model error
+    approx::assert_abs_diff_eq!(measurement.get::<ratio>(), 1.0, epsilon = 1e-3);
+    approx::assert_abs_diff_eq!(previous_input, 1.0/1.2, epsilon = 1e-3);
+
+    // setpoint drops to 0.5 in 10 samples, the controller moves
+    // before the setpoint does because of the dead time
+    let mut setpoint_trajectory = vec![Ratio::new::<ratio>(1.0); 10];
+    setpoint_trajectory.push(Ratio::new::<ratio>(0.5));
+    dmc_controller.set_setpoint_trajectory(0, setpoint_trajectory).unwrap();
+    let mut input_before_setpoint_change: f64 = previous_input;
+    for step in 301..=600 {
+        let time = Time::new::<second>(step as f64 * timestep);
+        let input = dmc_controller.set_measurement_and_calc(measurement, time)
+            .unwrap();
+        measurement = plant.set_user_input_and_calc(input, time).unwrap();
+        // the last sample before the setpoint drops, with 1 s 
+        // samples and 0.5 s timesteps the controller samples on 
+        // even steps, so this is the ninth sample (step 318) after 
+        // the trajectory was set
+        if step == 301 + 17 {
+            input_before_setpoint_change = input.get::<ratio>();
+        }
+    }
+    assert!(input_before_setpoint_change < previous_input - 0.05);
+    approx::assert_abs_diff_eq!(measurement.get::<ratio>(), 0.5, epsilon = 1e-3);
+
+    // 2 by 2 MIMO process with interaction
+    let process_matrix = || TransferFnMatrix::new(vec![
+        vec![first_order(1.0, 5.0, 0.0), first_order(0.5, 5.0, 0.0)],
+        vec![first_order(0.3, 4.0, 0.0), first_order(1.0, 4.0, 0.0)],
+    ]).unwrap();
+    let model = StepResponseModel::from_transfer_fn_matrix(&process_matrix(),
+        sample_period, 40).unwrap();
+    assert_eq!(model.number_of_outputs(), 2);
+    assert_eq!(model.number_of_inputs(), 2);
+    let mut dmc_controller = DmcController::new(model, 15, 3, 0.05).unwrap();
+    dmc_controller.set_setpoint(0, Ratio::new::<ratio>(1.0)).unwrap();
+    dmc_controller.set_setpoint(1, Ratio::new::<ratio>(-0.5)).unwrap();
+    assert!(dmc_controller.set_setpoint(2, Ratio::ZERO).is_err());
+    assert!(dmc_controller.set_measurement_and_calc(Ratio::ZERO, Time::ZERO)
+        .is_err());
+
+    let mut plant = process_matrix();
+    let mut measurements = vec![Ratio::ZERO; 2];
+    for step in 0..=160 {
+        let time = Time::new::<second>(step as f64 * timestep);
+        let inputs = dmc_controller.set_measurements_and_calc(&measurements,
+            time).unwrap();
+        measurements = plant.set_user_inputs_and_calc(&inputs, time).unwrap();
+    }
+    approx::assert_abs_diff_eq!(measurements[0].get::<ratio>(), 1.0,
+        epsilon = 1e-3);
+    approx::assert_abs_diff_eq!(measurements[1].get::<ratio>(), -0.5,
+        epsilon = 1e-3);
+}
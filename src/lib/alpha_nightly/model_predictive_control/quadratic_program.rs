@@ -0,0 +1,116 @@
+use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
+use crate::alpha_nightly::matrices;
+
+/// solves the convex quadratic program
+///
+/// minimise 1/2 x^T H x + g^T x
+///
+/// subject to C x <= b
+///
+/// with H symmetric positive definite, using Hildreth's method on
+/// the dual problem. The dual variables (Lagrange multipliers)
+/// are found one at a time,
+///
+/// lambda_i = max(0, -(k_i + sum_(j != i) P_ij lambda_j)/P_ii)
+///
+/// with P = C H^-1 C^T and k = b + C H^-1 g, sweeping until they
+/// stop changing. Then
+///
+/// x = -H^-1 (g + C^T lambda)
+///
+/// This needs no feasible starting point, and for the small
+/// problems in MPC (tens of variables and constraints) converges
+/// quickly. If the constraints cannot all be met, the result
+/// after max_iterations sweeps is returned, which is a compromise
+/// between the constraints.
+pub(crate) fn solve_quadratic_program(hessian: &[Vec<f64>],
+    gradient: &[f64],
+    constraint_matrix: &[Vec<f64>],
+    constraint_bounds: &[f64],
+    max_iterations: usize) -> Result<Vec<f64>,
+ChemEngProcessControlSimulatorError> {
+
+    let hessian_inverse = matrices::inverse(hessian)?;
+    let unconstrained_solution: Vec<f64> = matrices::multiply_vector(
+        &hessian_inverse, gradient).iter().map(|x| -x).collect();
+
+    let constraint_violated = |x: &[f64]| -> bool {
+        constraint_matrix.iter().zip(constraint_bounds.iter())
+            .any(|(row, bound)| dot(row, x) > bound + 1e-12)
+    };
+    if !constraint_violated(&unconstrained_solution) {
+        return Ok(unconstrained_solution);
+    }
+
+    // H^-1 C^T, one column per constraint
+    let transpose: Vec<Vec<f64>> = (0..gradient.len()).map(|i| {
+        constraint_matrix.iter().map(|row| row[i]).collect()
+    }).collect();
+    let hessian_inverse_constraints = matrices::multiply(&hessian_inverse,
+        &transpose);
+    let dual_hessian = matrices::multiply(constraint_matrix,
+        &hessian_inverse_constraints);
+    // b + C H^-1 g = b - C x_unconstrained
+    let dual_gradient: Vec<f64> = constraint_matrix.iter()
+        .zip(constraint_bounds.iter())
+        .map(|(row, bound)| bound - dot(row, &unconstrained_solution))
+        .collect();
+
+    let number_of_constraints = constraint_bounds.len();
+    let mut multipliers: Vec<f64> = vec![0.0; number_of_constraints];
+    for _ in 0..max_iterations {
+        let mut largest_change: f64 = 0.0;
+        let mut largest_multiplier: f64 = 0.0;
+        for i in 0..number_of_constraints {
+            if dual_hessian[i][i] <= 0.0 {
+                // constraint row of zeroes
+                continue;
+            }
+            let off_diagonal: f64 = (0..number_of_constraints)
+                .filter(|j| *j != i)
+                .map(|j| dual_hessian[i][j] * multipliers[j])
+                .sum();
+            let multiplier = (-(dual_gradient[i] + off_diagonal)
+                /dual_hessian[i][i]).max(0.0);
+            largest_change = largest_change
+                .max((multiplier - multipliers[i]).abs());
+            largest_multiplier = largest_multiplier.max(multiplier);
+            multipliers[i] = multiplier;
+        }
+        if largest_change <= 1e-12 * largest_multiplier.max(1.0) {
+            break;
+        }
+    }
+
+    let correction = matrices::multiply_vector(&hessian_inverse_constraints,
+        &multipliers);
+    Ok(unconstrained_solution.iter().zip(correction.iter())
+        .map(|(x, dx)| x - dx).collect())
+}
+
+fn dot(first: &[f64], second: &[f64]) -> f64 {
+    first.iter().zip(second.iter()).map(|(a, b)| a * b).sum()
+}
+
+#[test]
+pub fn test_quadratic_program(){
+    // minimise (x1 - 2)^2 + (x2 - 1)^2
+    // subject to x1 + x2 <= 2, x1 <= 1.5, -x2 <= 0
+    let hessian = vec![vec![2.0, 0.0], vec![0.0, 2.0]];
+    let gradient = vec![-4.0, -2.0];
+    let constraint_matrix = vec![vec![1.0, 1.0], vec![1.0, 0.0],
+        vec![0.0, -1.0]];
+    let constraint_bounds = vec![2.0, 1.5, 0.0];
+
+    // the line x1 + x2 = 2 is active, x1 = 1.5 is as well
+    let solution = solve_quadratic_program(&hessian, &gradient,
+        &constraint_matrix, &constraint_bounds, 1000).unwrap();
+    approx::assert_abs_diff_eq!(solution[0], 1.5, epsilon = 1e-9);
+    approx::assert_abs_diff_eq!(solution[1], 0.5, epsilon = 1e-9);
+
+    // nothing active, unconstrained minimum
+    let solution = solve_quadratic_program(&hessian, &gradient,
+        &constraint_matrix, &[5.0, 5.0, 5.0], 1000).unwrap();
+    approx::assert_abs_diff_eq!(solution[0], 2.0, epsilon = 1e-12);
+    approx::assert_abs_diff_eq!(solution[1], 1.0, epsilon = 1e-12);
+}
@@ -0,0 +1,52 @@
+//! one dimensional searches used for step response metrics and
+//! stability margins
+//!
+//! the functions are plain f64 in SI base units, and the searches
+//! run for a fixed number of iterations, which is plenty for 
+//! double precision
+
+/// finds where a function changes sign between low and high
+pub(crate) fn bisect(mut low: f64, mut high: f64, 
+    function: impl Fn(f64) -> f64) -> f64 {
+    let low_is_negative = function(low) < 0.0;
+    for _ in 0..60 {
+        let middle = 0.5 * (low + high);
+        if (function(middle) < 0.0) == low_is_negative {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    0.5 * (low + high)
+}
+
+/// finds the maximum of a function between low and high
+pub(crate) fn golden_section_maximum(mut low: f64, mut high: f64,
+    function: impl Fn(f64) -> f64) -> f64 {
+    let inverse_golden_ratio: f64 = (5.0_f64.sqrt() - 1.0)/2.0;
+    for _ in 0..80 {
+        let left = high - inverse_golden_ratio * (high - low);
+        let right = low + inverse_golden_ratio * (high - low);
+        if function(left) < function(right) {
+            low = left;
+        } else {
+            high = right;
+        }
+    }
+    0.5 * (low + high)
+}
+
+/// bisect on a log scale, eg. for frequencies,
+/// low and high must be positive
+pub(crate) fn log_bisect(low: f64, high: f64, 
+    function: impl Fn(f64) -> f64) -> f64 {
+    bisect(low.ln(), high.ln(), |log_x| function(log_x.exp())).exp()
+}
+
+/// golden section search on a log scale, 
+/// low and high must be positive
+pub(crate) fn log_golden_section_maximum(low: f64, high: f64,
+    function: impl Fn(f64) -> f64) -> f64 {
+    golden_section_maximum(low.ln(), high.ln(), 
+        |log_x| function(log_x.exp())).exp()
+}
@@ -0,0 +1,358 @@
+//! polynomial helper functions for transfer functions
+//!
+//! polynomials are stored as coefficients in descending powers
+//! of s, the same way as in scilab or matlab:
+//!
+//! [a_n, a_(n-1), ... a_1, a_0]
+//!
+//! means a_n s^n + a_(n-1) s^(n-1) + ... + a_1 s + a_0
+//!
+//! uom does not have units for arbitrary powers of time, so the
+//! coefficient of s^k is taken to be in seconds^k
+
+use num_complex::Complex;
+
+use super::errors::ChemEngProcessControlSimulatorError;
+
+/// removes leading zeroes from a polynomial
+///
+/// an empty polynomial or a polynomial of all zeroes is returned
+/// as [0.0]
+pub fn trim_leading_zeroes(polynomial: &[f64]) -> Vec<f64> {
+    let first_nonzero_index = polynomial.iter().position(
+        |coefficient| *coefficient != 0.0);
+
+    match first_nonzero_index {
+        Some(index) => polynomial[index..].to_vec(),
+        None => vec![0.0],
+    }
+}
+
+/// degree of the polynomial (leading zeroes are ignored)
+pub fn degree(polynomial: &[f64]) -> usize {
+    trim_leading_zeroes(polynomial).len() - 1
+}
+
+/// evaluates the polynomial at a complex value of s
+/// using horner's method
+pub fn evaluate(polynomial: &[f64], s: Complex<f64>) -> Complex<f64> {
+    polynomial.iter().fold(Complex::new(0.0, 0.0),
+        |accumulated, coefficient| accumulated * s + coefficient)
+}
+
+/// evaluates a polynomial with complex coefficients
+/// at a complex value of s using horner's method
+pub fn evaluate_complex(polynomial: &[Complex<f64>],
+    s: Complex<f64>) -> Complex<f64> {
+    polynomial.iter().fold(Complex::new(0.0, 0.0),
+        |accumulated, coefficient| accumulated * s + coefficient)
+}
+
+/// multiplies two polynomials
+pub fn multiply(first: &[f64], second: &[f64]) -> Vec<f64> {
+    if first.is_empty() || second.is_empty() {
+        return vec![0.0];
+    }
+    let mut product = vec![0.0; first.len() + second.len() - 1];
+
+    for (i, first_coefficient) in first.iter().enumerate() {
+        for (j, second_coefficient) in second.iter().enumerate() {
+            product[i + j] += first_coefficient * second_coefficient;
+        }
+    }
+    product
+}
+
+/// adds two polynomials
+pub fn add(first: &[f64], second: &[f64]) -> Vec<f64> {
+    let length = first.len().max(second.len());
+    let mut sum = vec![0.0; length];
+
+    // coefficients are aligned at the constant term (the end)
+    for (i, coefficient) in first.iter().rev().enumerate() {
+        sum[length - 1 - i] += coefficient;
+    }
+    for (i, coefficient) in second.iter().rev().enumerate() {
+        sum[length - 1 - i] += coefficient;
+    }
+    sum
+}
+
+/// multiplies a polynomial by a constant
+pub fn scale(polynomial: &[f64], factor: f64) -> Vec<f64> {
+    polynomial.iter().map(|coefficient| coefficient * factor).collect()
+}
+
+/// builds a real polynomial from its roots,
+/// complex roots must come in conjugate pairs
+///
+/// the leading coefficient is 1
+pub fn from_roots(roots: &[Complex<f64>]) -> Vec<f64> {
+    let mut polynomial: Vec<Complex<f64>> = vec![Complex::new(1.0, 0.0)];
+
+    for root in roots {
+        let mut next = vec![Complex::new(0.0, 0.0); polynomial.len() + 1];
+        for (i, coefficient) in polynomial.iter().enumerate() {
+            next[i] += coefficient;
+            next[i + 1] -= coefficient * root;
+        }
+        polynomial = next;
+    }
+
+    polynomial.iter().map(|coefficient| coefficient.re).collect()
+}
+
+/// finds all roots of a polynomial
+///
+/// I'm using the Aberth-Ehrlich method, which finds all roots
+/// at once, followed by a few newton iterations on the original
+/// polynomial to polish the roots.
+///
+/// Repeated roots only converge to about the square root (or
+/// cube root etc.) of machine precision, so use
+/// group_repeated_roots to tidy them up if needed.
+pub fn roots(polynomial: &[f64])
+-> Result<Vec<Complex<f64>>, ChemEngProcessControlSimulatorError> {
+
+    let polynomial = trim_leading_zeroes(polynomial);
+    let degree = polynomial.len() - 1;
+
+    if degree == 0 {
+        if polynomial[0] == 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError("zero polynomial has no roots".to_owned()));
+        }
+        return Ok(vec![]);
+    }
+
+    // roots at s = 0 are taken out first, so that they come out
+    // exactly
+    let number_of_zero_roots = polynomial.iter().rev()
+        .take_while(|coefficient| **coefficient == 0.0).count();
+    let reduced_polynomial: Vec<f64> =
+        polynomial[..polynomial.len() - number_of_zero_roots].to_vec();
+
+    let mut all_roots: Vec<Complex<f64>> =
+        vec![Complex::new(0.0, 0.0); number_of_zero_roots];
+
+    let reduced_degree = reduced_polynomial.len() - 1;
+    if reduced_degree == 0 {
+        return Ok(all_roots);
+    }
+
+    // make the polynomial monic
+    let leading_coefficient = reduced_polynomial[0];
+    let monic: Vec<f64> = scale(&reduced_polynomial, 1.0/leading_coefficient);
+    let monic_derivative = derivative(&monic);
+
+    // initial guesses spread out on a circle bounded by the
+    // cauchy bound of the roots, rotated so that none of them
+    // lie on the real axis
+    let cauchy_bound: f64 = 1.0 + monic[1..].iter()
+        .fold(0.0_f64, |max, coefficient| max.max(coefficient.abs()));
+    let radius = 0.5 * cauchy_bound;
+    let mut estimates: Vec<Complex<f64>> = (0..reduced_degree).map(|k| {
+        let angle = 2.0 * std::f64::consts::PI * (k as f64)
+            / (reduced_degree as f64) + 0.4;
+        Complex::from_polar(radius, angle)
+    }).collect();
+
+    let maximum_iterations = 500;
+    for _ in 0..maximum_iterations {
+        let mut largest_correction: f64 = 0.0;
+
+        for i in 0..reduced_degree {
+            let z = estimates[i];
+            let value = evaluate(&monic, z);
+            let slope = evaluate(&monic_derivative, z);
+
+            if value.norm() == 0.0 {
+                continue;
+            }
+
+            let newton_ratio = value / slope;
+            let repulsion: Complex<f64> = estimates.iter().enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, other)| 1.0/(z - other))
+                .sum();
+
+            let correction = newton_ratio
+                / (Complex::new(1.0, 0.0) - newton_ratio * repulsion);
+
+            if correction.is_finite() {
+                estimates[i] = z - correction;
+                largest_correction = largest_correction
+                    .max(correction.norm()/(1.0 + z.norm()));
+            }
+        }
+
+        if largest_correction < 1e-15 {
+            break;
+        }
+    }
+
+    // polish with newton iterations on the original polynomial
+    for estimate in estimates.iter_mut() {
+        for _ in 0..3 {
+            let value = evaluate(&monic, *estimate);
+            let slope = evaluate(&monic_derivative, *estimate);
+            let correction = value/slope;
+            if !correction.is_finite() || correction.norm() == 0.0 {
+                break;
+            }
+            let polished = *estimate - correction;
+            if evaluate(&monic, polished).norm() < value.norm() {
+                *estimate = polished;
+            } else {
+                break;
+            }
+        }
+    }
+
+    // roots of a real polynomial come in conjugate pairs,
+    // clean up tiny imaginary parts
+    for estimate in estimates.iter_mut() {
+        if estimate.im.abs() <= 1e-10 * (1.0 + estimate.re.abs()) {
+            estimate.im = 0.0;
+        }
+    }
+
+    all_roots.extend(estimates);
+    Ok(all_roots)
+}
+
+/// derivative of a polynomial
+pub fn derivative(polynomial: &[f64]) -> Vec<f64> {
+    let degree = polynomial.len() - 1;
+    if degree == 0 {
+        return vec![0.0];
+    }
+    polynomial[..degree].iter().enumerate().map(
+        |(i, coefficient)| coefficient * (degree - i) as f64).collect()
+}
+
+/// groups roots which are within a tolerance of each other
+/// and returns each distinct root with its multiplicity
+///
+/// the grouped roots are averaged, which is more accurate than
+/// any single estimate of a repeated root
+pub fn group_repeated_roots(roots: &[Complex<f64>], tolerance: f64)
+-> Vec<(Complex<f64>, usize)> {
+
+    let mut grouped: Vec<(Complex<f64>, usize)> = vec![];
+    let mut used = vec![false; roots.len()];
+
+    for i in 0..roots.len() {
+        if used[i] {
+            continue;
+        }
+        used[i] = true;
+        let mut sum = roots[i];
+        let mut multiplicity: usize = 1;
+
+        for j in (i+1)..roots.len() {
+            if used[j] {
+                continue;
+            }
+            let scale = 1.0 + roots[i].norm();
+            if (roots[j] - roots[i]).norm() <= tolerance * scale {
+                used[j] = true;
+                sum += roots[j];
+                multiplicity += 1;
+            }
+        }
+
+        let mut average = sum / (multiplicity as f64);
+        if average.im.abs() <= tolerance * (1.0 + average.re.abs()) {
+            average.im = 0.0;
+        }
+        grouped.push((average, multiplicity));
+    }
+
+    grouped
+}
+
+/// finds the distinct roots of a polynomial and their multiplicity
+///
+/// repeated roots come out of the root finder as a small cluster,
+/// the larger the multiplicity the larger the cluster. So I try
+/// grouping with looser and looser tolerances, and keep the loosest
+/// grouping which still gives back the original polynomial.
+///
+/// A root of multiplicity m is a simple root of the (m-1)th
+/// derivative of the polynomial, so each group is tidied up with
+/// newton iterations on that derivative before checking.
+pub fn distinct_roots(polynomial: &[f64])
+-> Result<Vec<(Complex<f64>, usize)>, ChemEngProcessControlSimulatorError> {
+
+    let polynomial = trim_leading_zeroes(polynomial);
+    let all_roots = roots(&polynomial)?;
+    let monic = scale(&polynomial, 1.0/polynomial[0]);
+    let coefficient_scale: f64 = monic.iter()
+        .fold(0.0_f64, |max, coefficient| max.max(coefficient.abs()));
+
+    let mut best_grouping = group_repeated_roots(&all_roots, 0.0);
+
+    for tolerance in [1e-8, 1e-6, 1e-4, 1e-3, 1e-2, 3e-2] {
+        let mut grouping = group_repeated_roots(&all_roots, tolerance);
+        if grouping.len() == best_grouping.len() {
+            continue;
+        }
+
+        for (root, multiplicity) in grouping.iter_mut() {
+            *root = refine_repeated_root(&monic, *root, *multiplicity);
+        }
+
+        let expanded_roots: Vec<Complex<f64>> = grouping.iter()
+            .flat_map(|(root, multiplicity)| 
+                std::iter::repeat_n(*root, *multiplicity))
+            .collect();
+        let rebuilt = from_roots(&expanded_roots);
+
+        let largest_error: f64 = rebuilt.iter().zip(monic.iter())
+            .fold(0.0_f64, |max, (rebuilt_coefficient, coefficient)| 
+                max.max((rebuilt_coefficient - coefficient).abs()));
+
+        if largest_error <= 1e-9 * coefficient_scale {
+            best_grouping = grouping;
+        }
+    }
+
+    Ok(best_grouping)
+}
+
+/// newton iterations on the (m-1)th derivative of the polynomial,
+/// for which a root of multiplicity m is a simple root
+fn refine_repeated_root(polynomial: &[f64],
+    root: Complex<f64>,
+    multiplicity: usize) -> Complex<f64> {
+
+    if multiplicity < 2 {
+        return root;
+    }
+
+    let mut derivative_polynomial = polynomial.to_vec();
+    for _ in 0..(multiplicity - 1) {
+        derivative_polynomial = derivative(&derivative_polynomial);
+    }
+    let second_derivative_polynomial = derivative(&derivative_polynomial);
+
+    let mut refined_root = root;
+    for _ in 0..20 {
+        let value = evaluate(&derivative_polynomial, refined_root);
+        let slope = evaluate(&second_derivative_polynomial, refined_root);
+        let correction = value/slope;
+        if !correction.is_finite() {
+            break;
+        }
+        refined_root -= correction;
+        if correction.norm() <= 1e-15 * (1.0 + refined_root.norm()) {
+            break;
+        }
+    }
+
+    if refined_root.im.abs() <= 1e-10 * (1.0 + refined_root.re.abs()) {
+        refined_root.im = 0.0;
+    }
+    refined_root
+}
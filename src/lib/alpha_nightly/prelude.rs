@@ -1,4 +1,9 @@
 pub use super::errors;
 pub use super::transfer_fn_wrapper_and_enums::*;
 pub use super::controllers::*;
+pub use super::block_diagrams::*;
+pub use super::frequency_response::*;
 
+pub use super::tuning::*;
+pub use super::identification::*;
+pub use super::model_predictive_control::*;
@@ -338,6 +338,22 @@ impl FirstOrderResponse {
         let response: Ratio = self.user_input * self.process_gain;
         response
     }
+
+    /// time where the response kicks in (after any dead time)
+    pub(crate) fn start_time(&self) -> Time {
+        self.start_time
+    }
+
+    /// size of the input step causing this response
+    pub(crate) fn user_input(&self) -> Ratio {
+        self.user_input
+    }
+
+    /// scales the response, as if the gain had always been 
+    /// factor times larger
+    pub(crate) fn scale(&mut self, factor: f64) {
+        self.user_input *= factor;
+    }
 }
 
 
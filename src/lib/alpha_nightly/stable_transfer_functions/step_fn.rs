@@ -93,6 +93,12 @@ impl StepFunction {
         let response: Ratio = self.user_input * self.process_gain;
         response
     }
+
+    /// scales the response, as if the gain had always been 
+    /// factor times larger
+    pub(crate) fn scale(&mut self, factor: f64) {
+        self.user_input *= factor;
+    }
 }
 
 
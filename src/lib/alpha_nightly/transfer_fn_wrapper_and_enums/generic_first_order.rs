@@ -1,17 +1,24 @@
 use csv::Writer;
+use num_complex::Complex;
 use uom::si::ratio::ratio;
 use uom::si::f64::*;
 use uom::si::time::second;
 use uom::ConstZero;
 
 use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
-use crate::alpha_nightly::stable_transfer_functions::first_order_transfer_fn::FirstOrderStableTransferFnNoZeroes;
+use crate::alpha_nightly::stable_transfer_functions::first_order_transfer_fn::{FirstOrderResponse, 
+    FirstOrderStableTransferFnNoZeroes};
 use crate::alpha_nightly::stable_transfer_functions::first_order_transfer_fn_with_zeroes::FirstOrderStableTransferFnForZeroes;
+use crate::alpha_nightly::stable_transfer_functions::step_fn::StepFunction;
+use crate::alpha_nightly::unstable_transfer_functions::first_order_transfer_fn::{FirstOrderUnstableResponse, 
+    FirstOrderUnstableTransferFnNoZeroes};
+use crate::alpha_nightly::unstable_transfer_functions::first_order_transfer_fn_with_zeroes::FirstOrderUnstableTransferFnForZeroes;
+use crate::alpha_nightly::undamped_transfer_functions::first_order_integrating_transfer_fn::FirstOrderIntegratingTransferFn;
 
-use super::{TransferFn, TransferFnTraits};
+use super::{StepResponseMetrics, TransferFn, TransferFnTraits};
+use super::step_response_metrics;
 
-/// an enum describing generic second order systems,
-/// only stable systems are implemented so far
+/// an enum describing generic first order systems
 ///
 /// you are meant to put in:
 ///
@@ -48,6 +55,19 @@ use super::{TransferFn, TransferFnTraits};
 /// the second term, is there due to the zeroes, therefore 
 /// it is take care of by 
 /// FirstOrderStableTransferFnForZeroes
+///
+/// If tau_p is negative, the same decomposition holds, except 
+/// that the pole is in the right half plane and the response grows 
+/// exponentially. These are taken care of by 
+/// FirstOrderUnstableTransferFnNoZeroes and 
+/// FirstOrderUnstableTransferFnForZeroes
+///
+/// If b2 is zero, the pole is at s = 0 and we have an integrating 
+/// (pure capacity) process such as a tank level:
+///
+/// G(s) = a1/a2 + (b1/a2) / s
+///
+/// This is taken care of by FirstOrderIntegratingTransferFn
 #[derive(Debug,PartialEq, PartialOrd, Clone)]
 pub enum TransferFnFirstOrder {
     /// this is arranged in the order
@@ -56,8 +76,13 @@ pub enum TransferFnFirstOrder {
     /// sine_term
     Stable(FirstOrderStableTransferFnNoZeroes,
         FirstOrderStableTransferFnForZeroes),
-        Unstable,
-        ConstantValueUndamped,
+        /// arranged in the same order as the stable system,
+        /// no_zero_transfer_fn,
+        /// transfer_fn_for_zeroes
+        Unstable(FirstOrderUnstableTransferFnNoZeroes,
+            FirstOrderUnstableTransferFnForZeroes),
+        /// integrating process, pole at s = 0
+        ConstantValueUndamped(FirstOrderIntegratingTransferFn),
 }
 
 impl Default for TransferFnFirstOrder {
@@ -80,8 +105,16 @@ impl TransferFnTraits for TransferFnFirstOrder {
                     transfer_fn_for_zeroes.delay = dead_time;
 
             },
-            TransferFnFirstOrder::Unstable => todo!(),
-            TransferFnFirstOrder::ConstantValueUndamped => todo!(),
+            TransferFnFirstOrder::Unstable(
+                transfer_fn_no_zeroes,
+                transfer_fn_for_zeroes) => {
+                    transfer_fn_no_zeroes.delay = dead_time;
+                    transfer_fn_for_zeroes.delay = dead_time;
+            },
+            TransferFnFirstOrder::ConstantValueUndamped(
+                integrating_transfer_fn) => {
+                    integrating_transfer_fn.delay = dead_time;
+            },
         }
     }
 
@@ -109,11 +142,27 @@ impl TransferFnTraits for TransferFnFirstOrder {
 
                     response += first_order_output_1;
                     response += first_order_output_2;
-                    return Ok(response);
+                    Ok(response)
 
                 },
-            TransferFnFirstOrder::Unstable => todo!(),
-            TransferFnFirstOrder::ConstantValueUndamped => todo!(),
+            TransferFnFirstOrder::Unstable(
+                first_order_no_zeroes,
+                first_order_zeroes) => {
+                    let first_order_output_1 = 
+                    first_order_no_zeroes.set_user_input_and_calc_output(
+                        time, user_input)?;
+
+                    let first_order_output_2 = 
+                    first_order_zeroes.set_user_input_and_calc_output(
+                        time, user_input)?;
+
+                    Ok(first_order_output_1 + first_order_output_2)
+                },
+            TransferFnFirstOrder::ConstantValueUndamped(
+                integrating_transfer_fn) => {
+                    integrating_transfer_fn.set_user_input_and_calc_output(
+                        time, user_input)
+            },
         }
 
     }
@@ -125,8 +174,12 @@ impl TransferFnTraits for TransferFnFirstOrder {
             TransferFnFirstOrder::Stable(_,_) => {
                 title_string += "1st_ord_transfer_fn_stable.csv";
             },
-            TransferFnFirstOrder::Unstable => todo!(),
-            TransferFnFirstOrder::ConstantValueUndamped => todo!(),
+            TransferFnFirstOrder::Unstable(_,_) => {
+                title_string += "1st_ord_transfer_fn_unstable.csv";
+            },
+            TransferFnFirstOrder::ConstantValueUndamped(_) => {
+                title_string += "1st_ord_transfer_fn_integrating.csv";
+            },
         }
         let wtr = Writer::from_path(title_string)?;
         Ok(wtr)
@@ -167,11 +220,27 @@ impl TransferFnFirstOrder {
     /// ----------
     /// a2 s + b2 
     ///
+    /// if b2 is zero, this is an integrating process
     /// 
     pub fn new(a1: Time, 
         b1: Ratio, 
         a2: Time,
         b2: Ratio) -> Result<Self,ChemEngProcessControlSimulatorError> {
+
+        if b2.get::<ratio>() == 0.0 {
+            // integrating process, (a1 s + b1)/(a2 s)
+            let integrating_transfer_fn = FirstOrderIntegratingTransferFn::
+                new(
+                    a1,
+                    b1,
+                    a2,
+                    Ratio::ZERO,
+                    Ratio::ZERO,
+                    Time::ZERO,
+                )?;
+
+            return Ok(Self::ConstantValueUndamped(integrating_transfer_fn));
+        }
         // process time 
         let tau_p: Time = a2/b2;
 
@@ -188,11 +257,44 @@ impl TransferFnFirstOrder {
         let tau_p_value: f64 = tau_p.get::<second>();
 
         if tau_p_value < 0.0 {
-            // unstable system
-            todo!("unstable system, not implemented");
+            // unstable system, same decomposition as the stable 
+            // system but with a right half plane pole
+            let first_ord_transfer_fn_no_zeroes = FirstOrderUnstableTransferFnNoZeroes::
+                new(
+                    k_p,
+                    tau_p,
+                    Ratio::ZERO,
+                    Ratio::ZERO,
+                    Time::ZERO,
+                )?;
+            let first_ord_transfer_fn_for_zeroes = FirstOrderUnstableTransferFnForZeroes::
+                new(
+                    k_p_for_zero,
+                    tau_p,
+                    Ratio::ZERO,
+                    Ratio::ZERO,
+                    Time::ZERO,
+                )?;
+
+            Ok(Self::Unstable(
+                    first_ord_transfer_fn_no_zeroes, 
+                    first_ord_transfer_fn_for_zeroes))
         } else if tau_p_value == 0.0 {
-            // undamped system
-            todo!("undamped constant system, not implemented");
+            // no pole, G(s) = (a1 s + b1)/b2
+            //
+            // a pure derivative gives an impulse for a step input, 
+            // which cannot be simulated
+            if a1.get::<second>() != 0.0 {
+                return Err(ChemEngProcessControlSimulatorError::
+                    ImproperTransferFunction);
+            }
+
+            // a pure gain is written as (k s + k)/(s + 1) 
+            // like the proportional controller
+            let one_second = Time::new::<second>(1.0);
+            let k_p: Ratio = b1/b2;
+            Self::new(one_second * k_p, k_p, 
+                one_second, Ratio::new::<ratio>(1.0))
 
         } else {
             // stable system
@@ -213,12 +315,475 @@ impl TransferFnFirstOrder {
                     Time::ZERO,
                 )?;
 
-            return Ok(Self::Stable(
+            Ok(Self::Stable(
                     first_ord_transfer_fn_no_zeroes, 
-                    first_ord_transfer_fn_for_zeroes));
+                    first_ord_transfer_fn_for_zeroes))
+        }
+
+
+    }
+
+    /// numerator coefficients [a1, b1] in SI units 
+    /// (seconds, dimensionless)
+    ///
+    /// these are worked back from the terms of the transfer function,
+    /// so they are scaled such that b2 = 1 (or a2 is the 
+    /// integral time for integrating processes)
+    pub fn numerator(&self) -> Vec<f64> {
+        match self {
+            TransferFnFirstOrder::Stable(
+                transfer_fn_no_zeroes, 
+                transfer_fn_for_zeroes) => {
+                    let tau_p: f64 = transfer_fn_no_zeroes.process_time
+                        .get::<second>();
+                    vec![tau_p * transfer_fn_for_zeroes.process_gain
+                        .get::<ratio>(),
+                        transfer_fn_no_zeroes.process_gain.get::<ratio>()]
+            },
+            TransferFnFirstOrder::Unstable(
+                transfer_fn_no_zeroes, 
+                transfer_fn_for_zeroes) => {
+                    let tau_p: f64 = transfer_fn_no_zeroes.process_time
+                        .get::<second>();
+                    vec![tau_p * transfer_fn_for_zeroes.process_gain
+                        .get::<ratio>(),
+                        transfer_fn_no_zeroes.process_gain.get::<ratio>()]
+            },
+            TransferFnFirstOrder::ConstantValueUndamped(
+                integrating_transfer_fn) => {
+                    let ramp_function = &integrating_transfer_fn.ramp_function;
+                    let a2: f64 = ramp_function.integral_time.get::<second>();
+                    vec![integrating_transfer_fn.static_gain.get::<ratio>() * a2,
+                        ramp_function.controller_gain.get::<ratio>()]
+            },
+        }
+    }
+
+    /// denominator coefficients [a2, b2] in SI units 
+    /// (seconds, dimensionless)
+    ///
+    /// scaled the same way as the numerator
+    pub fn denominator(&self) -> Vec<f64> {
+        match self {
+            TransferFnFirstOrder::Stable(transfer_fn_no_zeroes, _) => {
+                vec![transfer_fn_no_zeroes.process_time.get::<second>(), 1.0]
+            },
+            TransferFnFirstOrder::Unstable(transfer_fn_no_zeroes, _) => {
+                vec![transfer_fn_no_zeroes.process_time.get::<second>(), 1.0]
+            },
+            TransferFnFirstOrder::ConstantValueUndamped(
+                integrating_transfer_fn) => {
+                    vec![integrating_transfer_fn.ramp_function.integral_time
+                        .get::<second>(), 0.0]
+            },
+        }
+    }
+
+    /// dead time of the transfer function
+    pub fn dead_time(&self) -> Time {
+        match self {
+            TransferFnFirstOrder::Stable(transfer_fn_no_zeroes, _) => {
+                transfer_fn_no_zeroes.delay
+            },
+            TransferFnFirstOrder::Unstable(transfer_fn_no_zeroes, _) => {
+                transfer_fn_no_zeroes.delay
+            },
+            TransferFnFirstOrder::ConstantValueUndamped(
+                integrating_transfer_fn) => {
+                    integrating_transfer_fn.delay
+            },
+        }
+    }
+
+    /// pole of the transfer function, -b2/a2 in 1/s
+    ///
+    /// zero for an integrating process, positive if unstable, 
+    /// a pure gain (or zero) has none
+    pub fn poles(&self) -> Result<Vec<Complex<f64>>, 
+    ChemEngProcessControlSimulatorError> {
+        if self.is_pure_gain() || self.is_zero() {
+            return Ok(vec![]);
+        }
+        step_response_metrics::polynomial_roots(&self.denominator())
+    }
+
+    /// zero of the transfer function, -b1/a1 in 1/s, 
+    /// if there is one
+    pub fn zeros(&self) -> Result<Vec<Complex<f64>>, 
+    ChemEngProcessControlSimulatorError> {
+        if self.is_pure_gain() || self.is_zero() {
+            return Ok(vec![]);
+        }
+        step_response_metrics::polynomial_roots(&self.numerator())
+    }
+
+    /// steady state gain b1/b2,
+    /// integrating processes give an error
+    pub fn steady_state_gain(&self) -> Result<Ratio, 
+    ChemEngProcessControlSimulatorError> {
+        step_response_metrics::steady_state_gain(&self.numerator(), 
+            &self.denominator())
+    }
+
+    /// true if the numerator is zero, so the output always is
+    pub(crate) fn is_zero(&self) -> bool {
+        self.numerator().iter().all(|coefficient| *coefficient == 0.0)
+    }
+
+    /// true if the pole and zero cancel out, ie. the transfer 
+    /// function is a pure gain stored as (k tau s + k)/(tau s + 1)
+    /// with k not zero
+    pub(crate) fn is_pure_gain(&self) -> bool {
+        let (steady_state_gain, feedthrough_gain) = match self {
+            TransferFnFirstOrder::Stable(no_zeroes, for_zeroes) => {
+                (no_zeroes.process_gain, for_zeroes.process_gain)
+            },
+            TransferFnFirstOrder::Unstable(no_zeroes, for_zeroes) => {
+                (no_zeroes.process_gain, for_zeroes.process_gain)
+            },
+            TransferFnFirstOrder::ConstantValueUndamped(_) => {
+                return false;
+            },
+        };
+        let steady_state_gain: f64 = steady_state_gain.get::<ratio>();
+        let feedthrough_gain: f64 = feedthrough_gain.get::<ratio>();
+        // the zero transfer function is not a gain of anything
+        if steady_state_gain == 0.0 && feedthrough_gain == 0.0 {
+            return false;
+        }
+
+        (steady_state_gain - feedthrough_gain).abs() 
+            <= 1e-9 * steady_state_gain.abs().max(feedthrough_gain.abs())
+    }
+
+    /// process time constant a2/b2,
+    /// negative for unstable processes
+    ///
+    /// integrating processes, pure gains and the zero transfer 
+    /// function give an error
+    pub fn time_constant(&self) -> Result<Time, 
+    ChemEngProcessControlSimulatorError> {
+        if self.is_zero() {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "zero transfer function has no time constant, \
+                    its output is always zero".to_owned()));
+        }
+        if self.is_pure_gain() {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "pure gain has no time constant, the pole and \
+                    zero cancel out".to_owned()));
         }
+        match self {
+            TransferFnFirstOrder::Stable(transfer_fn_no_zeroes, _) => {
+                Ok(transfer_fn_no_zeroes.process_time)
+            },
+            TransferFnFirstOrder::Unstable(transfer_fn_no_zeroes, _) => {
+                Ok(transfer_fn_no_zeroes.process_time)
+            },
+            TransferFnFirstOrder::ConstantValueUndamped(_) => {
+                Err(ChemEngProcessControlSimulatorError::
+                    GenericStringError(
+                        "integrating process has no time constant"
+                        .to_owned()))
+            },
+        }
+    }
+
+    /// rise time, overshoot, decay ratio, settling time and 
+    /// inverse response for a unit step, without simulating
+    ///
+    /// only for stable processes
+    pub fn step_response_metrics(&self) -> Result<StepResponseMetrics, 
+    ChemEngProcessControlSimulatorError> {
+        step_response_metrics::step_response_metrics(&self.numerator(), 
+            &self.denominator(), self.dead_time())
+    }
+
+    /// changes the parameters of a stable or unstable transfer 
+    /// function mid simulation, written as
+    ///
+    /// G(s) = K_z + (K_p - K_z)/(tau_p s + 1)
+    ///
+    /// K_p is the steady state gain and K_z the feedthrough 
+    /// (high frequency) gain. The lag part of the output carries 
+    /// on from its current value and heads towards (or away from, 
+    /// if unstable) its new steady state with the new time constant. 
+    /// Inputs still inside the dead time get the new parameters 
+    /// once they arrive.
+    ///
+    /// the time constant has to keep its sign, a stable transfer 
+    /// function stays stable and an unstable one stays unstable
+    pub(crate) fn retune_parameters(&mut self,
+        steady_state_gain: Ratio,
+        feedthrough_gain: Ratio,
+        time_constant: Time,
+        time: Time) -> Result<(), ChemEngProcessControlSimulatorError> {
 
+        let time_constant_value: f64 = time_constant.get::<second>();
+        match self {
+            TransferFnFirstOrder::Stable(_, _) => {
+                if time_constant_value <= 0.0 {
+                    return Err(ChemEngProcessControlSimulatorError::
+                        GenericStringError(
+                            "stable first order transfer function needs \
+                            a positive time constant, build a new \
+                            transfer function to change its stability"
+                            .to_owned()));
+                }
+            },
+            TransferFnFirstOrder::Unstable(_, _) => {
+                if time_constant_value >= 0.0 {
+                    return Err(ChemEngProcessControlSimulatorError::
+                        GenericStringError(
+                            "unstable first order transfer function needs \
+                            a negative time constant, build a new \
+                            transfer function to change its stability"
+                            .to_owned()));
+                }
+            },
+            TransferFnFirstOrder::ConstantValueUndamped(_) => {
+                return Err(ChemEngProcessControlSimulatorError::
+                    GenericStringError(
+                        "integrating first order transfer function has \
+                        no time constant or steady state gain to retune"
+                        .to_owned()));
+            },
+        }
+
+        // bring everything up to the current time without 
+        // changing the input
+        let output = self.calc_without_input_change(time)?;
+
+        // inputs still in the dead time, (start time, input change), 
+        // and the input which has arrived
+        let (input, feedthrough_gain_before, pending_inputs): 
+            (Ratio, Ratio, Vec<(Time, Ratio)>) = match self {
+            TransferFnFirstOrder::Stable(no_zeroes, for_zeroes) => {
+                (no_zeroes.previous_timestep_input, for_zeroes.process_gain,
+                no_zeroes.response_vec.iter()
+                    .filter(|response| response.start_time() > time)
+                    .map(|response| (response.start_time(), 
+                            response.user_input()))
+                    .collect())
+            },
+            TransferFnFirstOrder::Unstable(no_zeroes, for_zeroes) => {
+                (no_zeroes.previous_timestep_input, for_zeroes.process_gain,
+                no_zeroes.response_vec.iter()
+                    .filter(|response| response.start_time() > time)
+                    .map(|response| (response.start_time(), 
+                            response.user_input()))
+                    .collect())
+            },
+            TransferFnFirstOrder::ConstantValueUndamped(_) => unreachable!(),
+        };
+        let arrived_input = input - pending_inputs.iter()
+            .map(|(_, user_input)| *user_input).sum::<Ratio>();
+
+        // lag state now, and where it is headed
+        let lag_output = output - feedthrough_gain_before * arrived_input;
+        let lag_steady_state = (steady_state_gain - feedthrough_gain)
+            * arrived_input;
+
+        match self {
+            TransferFnFirstOrder::Stable(no_zeroes, for_zeroes) => {
+                no_zeroes.process_gain = steady_state_gain;
+                no_zeroes.process_time = time_constant;
+                no_zeroes.offset = lag_output;
+                no_zeroes.response_vec = vec![FirstOrderResponse::new(
+                    Ratio::new::<ratio>(1.0), time_constant, time,
+                    lag_steady_state - lag_output, time)?];
 
+                for_zeroes.process_gain = feedthrough_gain;
+                for_zeroes.process_time = time_constant;
+                for_zeroes.offset = feedthrough_gain * arrived_input;
+                for_zeroes.first_order_response_vec = vec![];
+                for_zeroes.step_fn_response_vec = vec![];
+
+                for (start_time, user_input) in pending_inputs {
+                    no_zeroes.response_vec.push(FirstOrderResponse::new(
+                        steady_state_gain, time_constant, start_time,
+                        user_input, time)?);
+                    for_zeroes.first_order_response_vec.push(
+                        FirstOrderResponse::new(-feedthrough_gain, 
+                            time_constant, start_time, user_input, time)?);
+                    for_zeroes.step_fn_response_vec.push(StepFunction::new(
+                        feedthrough_gain, start_time, user_input, time)?);
+                }
+            },
+            TransferFnFirstOrder::Unstable(no_zeroes, for_zeroes) => {
+                no_zeroes.process_gain = steady_state_gain;
+                no_zeroes.process_time = time_constant;
+                no_zeroes.offset = lag_output;
+                no_zeroes.response_vec = vec![FirstOrderUnstableResponse::new(
+                    Ratio::new::<ratio>(1.0), time_constant, time,
+                    lag_steady_state - lag_output, time)?];
+
+                // K_z tau_p s/(tau_p s + 1) is K_z for inputs which 
+                // have arrived, the rest of it is in the lag above
+                for_zeroes.process_gain = feedthrough_gain;
+                for_zeroes.process_time = time_constant;
+                for_zeroes.offset = feedthrough_gain * arrived_input;
+                for_zeroes.response_vec = vec![];
+
+                for (start_time, user_input) in pending_inputs {
+                    no_zeroes.response_vec.push(FirstOrderUnstableResponse::new(
+                        steady_state_gain, time_constant, start_time,
+                        user_input, time)?);
+                    for_zeroes.response_vec.push(FirstOrderUnstableResponse::new(
+                        feedthrough_gain, time_constant, start_time,
+                        user_input, time)?);
+                }
+            },
+            TransferFnFirstOrder::ConstantValueUndamped(_) => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    /// puts the lag of a stable transfer function at its steady 
+    /// state for the input which has arrived, as if that input 
+    /// had always been there, inputs still in the dead time carry 
+    /// on as they are
+    pub(crate) fn settle(&mut self, 
+        time: Time) -> Result<(), ChemEngProcessControlSimulatorError> {
+        let (steady_state_gain, feedthrough_gain, time_constant) = match self {
+            TransferFnFirstOrder::Stable(no_zeroes, for_zeroes) => {
+                (no_zeroes.process_gain, for_zeroes.process_gain, 
+                 no_zeroes.process_time)
+            },
+            _ => {
+                return Err(ChemEngProcessControlSimulatorError::
+                    GenericStringError(
+                        "only stable first order transfer functions \
+                        can be settled".to_owned()));
+            },
+        };
+
+        // this leaves the lag heading for its steady state as the 
+        // first response, which is then taken up by the offset
+        self.retune_parameters(steady_state_gain, feedthrough_gain, 
+            time_constant, time)?;
+        if let TransferFnFirstOrder::Stable(no_zeroes, _) = self {
+            let lag_change = no_zeroes.response_vec.remove(0).user_input();
+            no_zeroes.offset += lag_change;
+        }
+        Ok(())
+    }
+
+    /// multiplies the gain of a stable transfer function by 
+    /// factor, as if it had always been that large, so the 
+    /// output is scaled by the same factor
+    pub(crate) fn scale_gain(&mut self, 
+        factor: f64) -> Result<(), ChemEngProcessControlSimulatorError> {
+        match self {
+            TransferFnFirstOrder::Stable(no_zeroes, for_zeroes) => {
+                no_zeroes.process_gain *= factor;
+                no_zeroes.offset *= factor;
+                no_zeroes.response_vec.iter_mut()
+                    .for_each(|response| response.scale(factor));
+
+                for_zeroes.process_gain *= factor;
+                for_zeroes.offset *= factor;
+                for_zeroes.first_order_response_vec.iter_mut()
+                    .for_each(|response| response.scale(factor));
+                for_zeroes.step_fn_response_vec.iter_mut()
+                    .for_each(|response| response.scale(factor));
+                Ok(())
+            },
+            _ => {
+                Err(ChemEngProcessControlSimulatorError::
+                    GenericStringError(
+                        "only stable first order transfer functions \
+                        can be rescaled".to_owned()))
+            },
+        }
+    }
+
+    /// changes the steady state gain b1/b2 mid simulation, 
+    /// eg. as a catalyst deactivates
+    ///
+    /// the a1 s part of the numerator is left alone, so for 
+    /// K_p/(tau_p s + 1) the output carries on from where it is 
+    /// and heads for the new steady state. Inputs already put in 
+    /// are not thrown away. This works for unstable processes too.
+    ///
+    /// a pure gain stays a pure gain, the output jumps to the new 
+    /// gain times the input.
+    ///
+    /// for an integrating process (a1 s + b1)/(a2 s), there is no 
+    /// steady state gain, so b1 is changed instead and the output 
+    /// ramps at b1/a2 times the input from where it is
+    pub fn set_process_gain(&mut self, process_gain: Ratio,
+        time: Time) -> Result<(), ChemEngProcessControlSimulatorError> {
+
+        if let TransferFnFirstOrder::ConstantValueUndamped(
+            integrating_transfer_fn) = self {
+            let input = integrating_transfer_fn.previous_timestep_input;
+            integrating_transfer_fn.set_user_input_and_calc_output(
+                time, input)?;
+            let integral_time = integrating_transfer_fn.ramp_function
+                .integral_time;
+            return integrating_transfer_fn.ramp_function.set_tuning(
+                process_gain, integral_time, time);
+        }
+
+        let time_constant = Time::new::<second>(self.denominator()[0]);
+        if self.is_pure_gain() {
+            return self.retune_parameters(process_gain, process_gain, 
+                time_constant, time);
+        }
+        let feedthrough_gain = Ratio::new::<ratio>(self.numerator()[0]
+            /time_constant.get::<second>());
+
+        self.retune_parameters(process_gain, feedthrough_gain, 
+            time_constant, time)
+    }
+
+    /// changes the time constant a2/b2 mid simulation, 
+    /// eg. as a heat exchanger fouls
+    ///
+    /// the numerator is left alone, and the output carries on 
+    /// from where it is at the new speed
+    ///
+    /// stable processes need a positive time constant and 
+    /// unstable processes a negative one. Integrating processes 
+    /// and pure gains have no time constant to change, so they 
+    /// give an error.
+    pub fn set_time_constant(&mut self, time_constant: Time,
+        time: Time) -> Result<(), ChemEngProcessControlSimulatorError> {
+        self.time_constant()?;
+        if time_constant == Time::ZERO {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "time constant must be nonzero, use set_process_gain \
+                    on a pure gain instead".to_owned()));
+        }
+        let numerator = self.numerator();
+        let feedthrough_gain = Ratio::new::<ratio>(numerator[0]
+            /time_constant.get::<second>());
+
+        self.retune_parameters(Ratio::new::<ratio>(numerator[1]), 
+            feedthrough_gain, time_constant, time)
+    }
+
+    /// output at the given time, with the input left as it was
+    pub(crate) fn calc_without_input_change(&mut self, 
+        time: Time) -> Result<Ratio, ChemEngProcessControlSimulatorError> {
+        let input = match self {
+            TransferFnFirstOrder::Stable(transfer_fn_no_zeroes, _) => {
+                transfer_fn_no_zeroes.previous_timestep_input
+            },
+            TransferFnFirstOrder::Unstable(transfer_fn_no_zeroes, _) => {
+                transfer_fn_no_zeroes.previous_timestep_input
+            },
+            TransferFnFirstOrder::ConstantValueUndamped(
+                integrating_transfer_fn) => {
+                    integrating_transfer_fn.previous_timestep_input
+            },
+        };
+        self.set_user_input_and_calc(input, time)
     }
 
 
@@ -249,3 +814,245 @@ impl TryFrom<TransferFn> for TransferFnFirstOrder {
     }
 }
 
+
+#[test]
+pub fn test_unstable_first_order_step_response(){
+    use uom::si::time::second;
+
+    // G(s) = exp(-1s)
+    //
+    //   s + 1
+    // ---------
+    // -2 s + 1
+    //
+    // step response (after the dead time) is:
+    // y(t) = 1 - 1.5 exp(t/2)
+    let a1: Time = Time::new::<second>(1.0);
+    let b1: Ratio = Ratio::new::<ratio>(1.0);
+    let a2: Time = Time::new::<second>(-2.0);
+    let b2: Ratio = Ratio::new::<ratio>(1.0);
+
+    let mut tf = TransferFnFirstOrder::new(a1, b1, a2, b2).unwrap();
+    tf.set_dead_time(Time::new::<second>(1.0));
+
+    let step = Ratio::new::<ratio>(1.0);
+    let output_before_dead_time = tf.set_user_input_and_calc(
+        step, Time::ZERO).unwrap();
+    assert_eq!(output_before_dead_time, Ratio::ZERO);
+
+    let output = tf.set_user_input_and_calc(
+        step, Time::new::<second>(5.0)).unwrap();
+    let expected: f64 = 1.0 - 1.5 * (2.0_f64).exp();
+
+    approx::assert_relative_eq!(output.get::<ratio>(), expected,
+        max_relative = 1e-9);
+}
+
+#[test]
+pub fn test_integrating_first_order_step_response(){
+    use uom::si::time::second;
+
+    // G(s) = exp(-1s)
+    //
+    // 2 s + 1
+    // -------
+    //   1 s
+    //
+    // step response (after the dead time) is:
+    // y(t) = 2 + (t - 1)
+    let a1: Time = Time::new::<second>(2.0);
+    let b1: Ratio = Ratio::new::<ratio>(1.0);
+    let a2: Time = Time::new::<second>(1.0);
+    let b2: Ratio = Ratio::ZERO;
+
+    let mut tf: TransferFn = TransferFnFirstOrder::new(a1, b1, a2, b2)
+        .unwrap().into();
+    tf.set_dead_time(Time::new::<second>(1.0));
+
+    let step = Ratio::new::<ratio>(1.0);
+    let output_before_dead_time = tf.set_user_input_and_calc(
+        step, Time::ZERO).unwrap();
+    assert_eq!(output_before_dead_time, Ratio::ZERO);
+
+    let output = tf.set_user_input_and_calc(
+        step, Time::new::<second>(3.0)).unwrap();
+    approx::assert_relative_eq!(output.get::<ratio>(), 4.0,
+        max_relative = 1e-9);
+
+    // bring the input back to zero at t = 3s, the ramp stops 
+    // at t = 4s once the dead time has passed, and the static 
+    // gain part drops out, so the level holds at 3
+    let _output = tf.set_user_input_and_calc(
+        Ratio::ZERO, Time::new::<second>(3.0)).unwrap();
+    let output = tf.set_user_input_and_calc(
+        Ratio::ZERO, Time::new::<second>(10.0)).unwrap();
+    approx::assert_relative_eq!(output.get::<ratio>(), 3.0,
+        max_relative = 1e-9);
+}
+
+#[test]
+pub fn test_first_order_step_response_metrics(){
+
+    // G(s) = 2/(5s + 1) exp(-s)
+    let mut first_order = TransferFnFirstOrder::new(
+        Time::ZERO, Ratio::new::<ratio>(2.0),
+        Time::new::<second>(5.0), Ratio::new::<ratio>(1.0)).unwrap();
+    first_order.set_dead_time(Time::new::<second>(1.0));
+
+    approx::assert_relative_eq!(first_order.poles().unwrap()[0].re, -0.2,
+        max_relative = 1e-12);
+    assert!(first_order.zeros().unwrap().is_empty());
+    approx::assert_relative_eq!(first_order.time_constant().unwrap()
+        .get::<second>(), 5.0, max_relative = 1e-12);
+
+    // 10% to 90% takes tau ln 9, and the response is within 5% 
+    // after tau ln 20 plus the dead time
+    let metrics = first_order.step_response_metrics().unwrap();
+    approx::assert_relative_eq!(metrics.rise_time.get::<second>(),
+        5.0 * 9.0_f64.ln(), max_relative = 1e-9);
+    approx::assert_relative_eq!(metrics.settling_time.get::<second>(),
+        5.0 * 20.0_f64.ln() + 1.0, max_relative = 1e-9);
+    assert_eq!(metrics.overshoot.get::<ratio>(), 0.0);
+
+    // integrating processes have no steady state gain
+    let integrating = TransferFnFirstOrder::new(
+        Time::ZERO, Ratio::new::<ratio>(2.0),
+        Time::new::<second>(5.0), Ratio::ZERO).unwrap();
+    assert!(integrating.steady_state_gain().is_err());
+    assert!(integrating.step_response_metrics().is_err());
+
+    // neither does a pure gain have a time constant
+    let pure_gain = TransferFnFirstOrder::new(
+        Time::ZERO, Ratio::new::<ratio>(2.0),
+        Time::ZERO, Ratio::new::<ratio>(1.0)).unwrap();
+    assert!(pure_gain.is_pure_gain());
+    assert!(pure_gain.time_constant().is_err());
+    approx::assert_relative_eq!(pure_gain.steady_state_gain().unwrap()
+        .get::<ratio>(), 2.0, max_relative = 1e-12);
+    assert!(!first_order.is_pure_gain());
+
+    // or any poles and zeros, even though it is stored as 
+    // (2 s + 2)/(s + 1)
+    assert!(pure_gain.poles().unwrap().is_empty());
+    assert!(pure_gain.zeros().unwrap().is_empty());
+
+    // the zero transfer function is not a pure gain, but has no 
+    // time constant, poles or zeros either
+    let zero = TransferFnFirstOrder::new(
+        Time::ZERO, Ratio::ZERO,
+        Time::new::<second>(5.0), Ratio::new::<ratio>(1.0)).unwrap();
+    assert!(!zero.is_pure_gain());
+    assert!(matches!(zero.time_constant(), 
+        Err(ChemEngProcessControlSimulatorError::GenericStringError(message)) 
+        if message.starts_with("zero transfer function")));
+    assert!(zero.poles().unwrap().is_empty());
+    assert!(zero.zeros().unwrap().is_empty());
+}
+
+#[test]
+pub fn test_first_order_parameter_changes(){
+
+    // G(s) = 2/(5s + 1), unit step at t = 0
+    let mut first_order = TransferFnFirstOrder::new(
+        Time::ZERO, Ratio::new::<ratio>(2.0),
+        Time::new::<second>(5.0), Ratio::new::<ratio>(1.0)).unwrap();
+    let step = Ratio::new::<ratio>(1.0);
+    first_order.set_user_input_and_calc(step, Time::ZERO).unwrap();
+    let output_at_change: f64 = first_order.set_user_input_and_calc(
+        step, Time::new::<second>(10.0)).unwrap().get::<ratio>();
+    approx::assert_relative_eq!(output_at_change, 
+        2.0 * (1.0 - (-2.0_f64).exp()), max_relative = 1e-9);
+
+    // the gain halves at t = 10 s, the output does not jump and 
+    // decays towards 1 with the same time constant
+    first_order.set_process_gain(Ratio::new::<ratio>(1.0), 
+        Time::new::<second>(10.0)).unwrap();
+    approx::assert_relative_eq!(first_order.steady_state_gain().unwrap()
+        .get::<ratio>(), 1.0, max_relative = 1e-12);
+    let output: f64 = first_order.set_user_input_and_calc(
+        step, Time::new::<second>(10.0)).unwrap().get::<ratio>();
+    approx::assert_relative_eq!(output, output_at_change, 
+        max_relative = 1e-9);
+    let output: f64 = first_order.set_user_input_and_calc(
+        step, Time::new::<second>(15.0)).unwrap().get::<ratio>();
+    approx::assert_relative_eq!(output, 
+        1.0 + (output_at_change - 1.0) * (-1.0_f64).exp(), 
+        max_relative = 1e-9);
+
+    // a step in the input made before the change still goes 
+    // through, with the new time constant of 1 s
+    first_order.set_user_input_and_calc(Ratio::new::<ratio>(2.0), 
+        Time::new::<second>(15.0)).unwrap();
+    first_order.set_time_constant(Time::new::<second>(1.0), 
+        Time::new::<second>(15.0)).unwrap();
+    let output: f64 = first_order.set_user_input_and_calc(
+        Ratio::new::<ratio>(2.0), Time::new::<second>(17.0)).unwrap()
+        .get::<ratio>();
+    let output_at_time_constant_change = 
+        1.0 + (output_at_change - 1.0) * (-1.0_f64).exp();
+    approx::assert_relative_eq!(output, 
+        2.0 + (output_at_time_constant_change - 2.0) * (-2.0_f64).exp(), 
+        max_relative = 1e-9);
+    approx::assert_relative_eq!(first_order.time_constant().unwrap()
+        .get::<second>(), 1.0, max_relative = 1e-12);
+
+    // the time constant cannot change sign
+    assert!(first_order.set_time_constant(Time::new::<second>(-1.0), 
+            Time::new::<second>(17.0)).is_err());
+
+    // a pure gain, 2, stays a pure gain when its gain changes, 
+    // the output jumps rather than lagging
+    let mut pure_gain = TransferFnFirstOrder::new(
+        Time::ZERO, Ratio::new::<ratio>(2.0),
+        Time::ZERO, Ratio::new::<ratio>(1.0)).unwrap();
+    pure_gain.set_user_input_and_calc(step, Time::ZERO).unwrap();
+    pure_gain.set_process_gain(Ratio::new::<ratio>(3.0), 
+        Time::new::<second>(1.0)).unwrap();
+    assert!(pure_gain.is_pure_gain());
+    let output: f64 = pure_gain.set_user_input_and_calc(
+        step, Time::new::<second>(1.0)).unwrap().get::<ratio>();
+    approx::assert_relative_eq!(output, 3.0, max_relative = 1e-9);
+    assert!(pure_gain.set_time_constant(Time::new::<second>(1.0), 
+            Time::new::<second>(1.0)).is_err());
+
+    // unstable, G(s) = 1/(-2s + 1), y = 1 - exp(t/2), the gain 
+    // doubles at t = 1 s and the output runs away from 2 instead
+    let mut unstable = TransferFnFirstOrder::new(
+        Time::ZERO, Ratio::new::<ratio>(1.0),
+        Time::new::<second>(-2.0), Ratio::new::<ratio>(1.0)).unwrap();
+    unstable.set_user_input_and_calc(step, Time::ZERO).unwrap();
+    let output_at_change: f64 = unstable.set_user_input_and_calc(
+        step, Time::new::<second>(1.0)).unwrap().get::<ratio>();
+    approx::assert_relative_eq!(output_at_change, 1.0 - 0.5_f64.exp(), 
+        max_relative = 1e-9);
+    unstable.set_process_gain(Ratio::new::<ratio>(2.0), 
+        Time::new::<second>(1.0)).unwrap();
+    let output: f64 = unstable.set_user_input_and_calc(
+        step, Time::new::<second>(2.0)).unwrap().get::<ratio>();
+    approx::assert_relative_eq!(output, 
+        2.0 + (output_at_change - 2.0) * 0.5_f64.exp(), max_relative = 1e-9);
+    unstable.set_time_constant(Time::new::<second>(-1.0), 
+        Time::new::<second>(2.0)).unwrap();
+    let output_after: f64 = unstable.set_user_input_and_calc(
+        step, Time::new::<second>(3.0)).unwrap().get::<ratio>();
+    approx::assert_relative_eq!(output_after, 
+        2.0 + (output - 2.0) * 1.0_f64.exp(), max_relative = 1e-9);
+    assert!(unstable.set_time_constant(Time::new::<second>(1.0), 
+            Time::new::<second>(3.0)).is_err());
+
+    // integrating, G(s) = 1/(2s), ramps at 0.5 per second, then 
+    // at 1.5 per second once b1 goes to 3 at t = 4 s
+    let mut integrating = TransferFnFirstOrder::new(
+        Time::ZERO, Ratio::new::<ratio>(1.0),
+        Time::new::<second>(2.0), Ratio::ZERO).unwrap();
+    integrating.set_user_input_and_calc(step, Time::ZERO).unwrap();
+    integrating.set_process_gain(Ratio::new::<ratio>(3.0), 
+        Time::new::<second>(4.0)).unwrap();
+    let output: f64 = integrating.set_user_input_and_calc(
+        step, Time::new::<second>(6.0)).unwrap().get::<ratio>();
+    approx::assert_relative_eq!(output, 5.0, max_relative = 1e-9);
+
+    // but there is no time constant to change
+    assert!(integrating.set_time_constant(Time::new::<second>(1.0), 
+            Time::new::<second>(6.0)).is_err());
+}
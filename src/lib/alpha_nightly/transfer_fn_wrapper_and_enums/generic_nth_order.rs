@@ -0,0 +1,426 @@
+use csv::Writer;
+use num_complex::Complex;
+use uom::si::ratio::ratio;
+use uom::si::f64::*;
+use uom::si::time::second;
+use uom::ConstZero;
+
+use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
+use crate::alpha_nightly::polynomials;
+
+use super::repeated_pole_transfer_fn::RepeatedPoleTransferFn;
+use super::{TransferFn, TransferFnFirstOrder, TransferFnSecondOrder, TransferFnTraits};
+
+/// a transfer function of any order
+///
+/// you are meant to put in:
+///
+/// G(s) =
+///
+/// b_m s^m + ... + b_1 s + b_0
+/// ---------------------------
+/// a_n s^n + ... + a_1 s + a_0
+///
+/// as two vectors of coefficients in descending powers of s,
+/// [b_m, ... b_1, b_0] and [a_n, ... a_1, a_0]
+///
+/// uom does not have units for arbitrary powers of time, so the
+/// coefficient of s^k is taken to be in seconds^k
+///
+/// The poles are found numerically, and the transfer function is
+/// broken up using partial fractions into:
+///
+/// 1. a static gain, if the numerator and denominator have the same
+///    degree
+/// 2. first order modes A/(s - p), one for each simple real pole,
+///    using TransferFnFirstOrder
+/// 3. second order modes, one for each simple complex conjugate
+///    pair of poles, using TransferFnSecondOrder
+/// 4. repeated pole modes, for poles of multiplicity two or more,
+///    using RepeatedPoleTransferFn
+///
+/// The output is the sum of the outputs of all the modes.
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub struct TransferFnNthOrder {
+    /// numerator coefficients, descending powers of s
+    pub(crate) numerator: Vec<f64>,
+    /// denominator coefficients, descending powers of s
+    pub(crate) denominator: Vec<f64>,
+    /// dead time
+    pub(crate) dead_time: Time,
+    /// static gain, first order and second order modes
+    pub(crate) modes: Vec<TransferFn>,
+    /// modes for repeated poles
+    pub(crate) repeated_pole_modes: Vec<RepeatedPoleTransferFn>,
+}
+
+impl Default for TransferFnNthOrder {
+    /// default is:
+    ///
+    /// 1 / (s + 1)^3
+    fn default() -> Self {
+        Self::new(vec![1.0], vec![1.0, 3.0, 3.0, 1.0]).unwrap()
+    }
+}
+
+impl TransferFnTraits for TransferFnNthOrder {
+    fn set_dead_time(&mut self, dead_time: Time) {
+        self.dead_time = dead_time;
+        for mode in self.modes.iter_mut() {
+            mode.set_dead_time(dead_time);
+        }
+        for repeated_pole_mode in self.repeated_pole_modes.iter_mut() {
+            repeated_pole_mode.delay = dead_time;
+        }
+    }
+
+    fn set_user_input_and_calc(&mut self, user_input: Ratio,
+        time: Time) ->
+    Result<Ratio, ChemEngProcessControlSimulatorError> {
+
+        let mut response: Ratio = Ratio::ZERO;
+
+        for mode in self.modes.iter_mut() {
+            response += mode.set_user_input_and_calc(user_input, time)?;
+        }
+        for repeated_pole_mode in self.repeated_pole_modes.iter_mut() {
+            response += repeated_pole_mode.set_user_input_and_calc_output(
+                time, user_input)?;
+        }
+
+        Ok(response)
+    }
+
+    fn spawn_writer(&mut self, name: String) -> Result<Writer<std::fs::File>,
+    ChemEngProcessControlSimulatorError>{
+        let title_string: String = name + "nth_ord_transfer_fn.csv";
+        let wtr = Writer::from_path(title_string)?;
+        Ok(wtr)
+    }
+
+    fn csv_write_values(&mut self,
+        wtr: &mut Writer<std::fs::File>,
+        time: Time,
+        input: Ratio,
+        output: Ratio) -> Result<(),
+    ChemEngProcessControlSimulatorError> {
+
+        let current_time_string = time.get::<second>().to_string();
+        let input_string = input.get::<ratio>().to_string();
+        let output_string = output.get::<ratio>().to_string();
+
+        wtr.write_record(&[current_time_string,
+            input_string,
+            output_string])?;
+
+        wtr.flush().unwrap();
+
+        Ok(())
+    }
+}
+
+impl TransferFnNthOrder {
+
+    /// generic constructor based on polynomials
+    ///
+    /// G(s) =
+    ///
+    /// b_m s^m + ... + b_1 s + b_0
+    /// ---------------------------
+    /// a_n s^n + ... + a_1 s + a_0
+    ///
+    /// numerator is [b_m, ... b_1, b_0],
+    /// denominator is [a_n, ... a_1, a_0]
+    ///
+    /// m must not be larger than n
+    pub fn new(numerator: Vec<f64>,
+        denominator: Vec<f64>) -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        let numerator = polynomials::trim_leading_zeroes(&numerator);
+        let denominator = polynomials::trim_leading_zeroes(&denominator);
+
+        if denominator == vec![0.0] {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError("denominator is zero".to_owned()));
+        }
+
+        let numerator_degree = numerator.len() - 1;
+        let denominator_degree = denominator.len() - 1;
+
+        if numerator_degree > denominator_degree {
+            return Err(ChemEngProcessControlSimulatorError::
+                ImproperTransferFunction);
+        }
+
+        let mut modes: Vec<TransferFn> = vec![];
+        let mut repeated_pole_modes: Vec<RepeatedPoleTransferFn> = vec![];
+
+        // first, take out the static gain if the degrees are equal
+        // G(s) = d + N'(s)/D(s)
+        let mut strictly_proper_numerator = numerator.clone();
+        if numerator_degree == denominator_degree {
+            let static_gain: f64 = numerator[0]/denominator[0];
+            strictly_proper_numerator = polynomials::add(&numerator,
+                &polynomials::scale(&denominator, -static_gain));
+            strictly_proper_numerator.remove(0);
+
+            if static_gain != 0.0 {
+                // pure gain, (k s + k)/(s + 1)
+                let k_p = Ratio::new::<ratio>(static_gain);
+                let one_second = Time::new::<second>(1.0);
+                let static_gain_mode = TransferFnFirstOrder::new(
+                    one_second * k_p, k_p,
+                    one_second, Ratio::new::<ratio>(1.0))?;
+                modes.push(static_gain_mode.into());
+            }
+        }
+
+        // a constant denominator means G(s) is just a static gain
+        if denominator_degree == 0 {
+            return Ok(Self {
+                numerator,
+                denominator,
+                dead_time: Time::ZERO,
+                modes,
+                repeated_pole_modes,
+            });
+        }
+
+        let poles = polynomials::distinct_roots(&denominator)?;
+
+        for (pole_index, &(pole, multiplicity)) in poles.iter().enumerate() {
+
+            // conjugate poles are dealt with together with the pole
+            // in the upper half plane
+            if pole.im < 0.0 {
+                continue;
+            }
+
+            let residues = Self::residues(&strictly_proper_numerator,
+                &denominator, &poles, pole_index);
+
+            if multiplicity > 1 {
+                repeated_pole_modes.push(RepeatedPoleTransferFn::new(
+                    pole, residues, Ratio::ZERO, Ratio::ZERO, Time::ZERO)?);
+                continue;
+            }
+
+            let residue = residues[0];
+            let one_second = Time::new::<second>(1.0);
+
+            if pole.im == 0.0 {
+                // A/(s - p)
+                // = (A * 1s)/(1s * s - p * 1s)
+                let first_order_mode = TransferFnFirstOrder::new(
+                    Time::ZERO,
+                    Ratio::new::<ratio>(residue.re),
+                    one_second,
+                    Ratio::new::<ratio>(-pole.re))?;
+                modes.push(first_order_mode.into());
+            } else {
+                // A/(s - p) + A*/(s - p*)
+                //
+                // 2 Re(A) s - 2 Re(A p*)
+                // ----------------------------
+                // s^2 - 2 Re(p) s + |p|^2
+                let mut real_part_of_pole = pole.re;
+
+                // roots on the imaginary axis do not always come out
+                // with a real part of exactly zero
+                if real_part_of_pole.abs() <= 1e-12 * pole.norm() {
+                    real_part_of_pole = 0.0;
+                }
+
+                let b1: Time = one_second * 2.0 * residue.re;
+                let c1: Ratio = Ratio::new::<ratio>(
+                    -2.0 * (residue * pole.conj()).re);
+                let b2: Time = one_second * (-2.0 * real_part_of_pole);
+                let c2: Ratio = Ratio::new::<ratio>(pole.norm_sqr());
+
+                let second_order_mode = TransferFnSecondOrder::new(
+                    one_second * Time::ZERO, b1, c1,
+                    one_second * one_second, b2, c2)?;
+                modes.push(second_order_mode.into());
+            }
+        }
+
+        Ok(Self {
+            numerator,
+            denominator,
+            dead_time: Time::ZERO,
+            modes,
+            repeated_pole_modes,
+        })
+    }
+
+    /// residues A_1 to A_m of N(s)/D(s) at the pole p
+    /// with multiplicity m
+    ///
+    /// N(s)/D(s) = A_1/(s - p) + ... + A_m/(s - p)^m + ...
+    ///
+    /// Let D(s) = (s - p)^m Q(s), then
+    ///
+    /// A_(m-j) = coefficient of h^j in the taylor series of
+    /// N(p + h)/Q(p + h)
+    fn residues(numerator: &[f64],
+        denominator: &[f64],
+        poles: &[(Complex<f64>, usize)],
+        pole_index: usize) -> Vec<Complex<f64>> {
+
+        let (pole, multiplicity) = poles[pole_index];
+
+        // Q(s) is built from the other poles, which is more
+        // accurate than dividing D(s) by (s - p)^m
+        let mut q_polynomial: Vec<Complex<f64>> =
+            vec![Complex::new(denominator[0], 0.0)];
+        for (other_index, &(other_pole, other_multiplicity))
+            in poles.iter().enumerate() {
+            if other_index == pole_index {
+                continue;
+            }
+            for _ in 0..other_multiplicity {
+                let mut next = vec![Complex::new(0.0, 0.0);
+                    q_polynomial.len() + 1];
+                for (i, coefficient) in q_polynomial.iter().enumerate() {
+                    next[i] += coefficient;
+                    next[i + 1] -= coefficient * other_pole;
+                }
+                q_polynomial = next;
+            }
+        }
+
+        let numerator: Vec<Complex<f64>> = numerator.iter()
+            .map(|coefficient| Complex::new(*coefficient, 0.0)).collect();
+
+        let numerator_taylor = Self::taylor_coefficients(
+            &numerator, pole, multiplicity);
+        let q_taylor = Self::taylor_coefficients(
+            &q_polynomial, pole, multiplicity);
+
+        // power series division
+        let mut quotient: Vec<Complex<f64>> = vec![];
+        for j in 0..multiplicity {
+            let mut value = numerator_taylor[j];
+            for i in 1..=j {
+                value -= q_taylor[i] * quotient[j - i];
+            }
+            quotient.push(value / q_taylor[0]);
+        }
+
+        // A_k = quotient[m - k]
+        quotient.reverse();
+        quotient
+    }
+
+    /// first few taylor coefficients of a polynomial about s = p,
+    /// P(p + h) = c_0 + c_1 h + c_2 h^2 + ...
+    ///
+    /// done by repeated synthetic division by (s - p), the
+    /// remainders are the taylor coefficients
+    fn taylor_coefficients(polynomial: &[Complex<f64>],
+        p: Complex<f64>,
+        count: usize) -> Vec<Complex<f64>> {
+
+        let mut coefficients: Vec<Complex<f64>> = vec![];
+        let mut quotient: Vec<Complex<f64>> = polynomial.to_vec();
+
+        for _ in 0..count {
+            if quotient.is_empty() {
+                coefficients.push(Complex::new(0.0, 0.0));
+                continue;
+            }
+            let mut next: Vec<Complex<f64>> = vec![];
+            let mut accumulated = Complex::new(0.0, 0.0);
+            for coefficient in quotient.iter() {
+                accumulated = accumulated * p + coefficient;
+                next.push(accumulated);
+            }
+            // the last value is the remainder
+            let remainder = next.pop().unwrap();
+            coefficients.push(remainder);
+            quotient = next;
+        }
+
+        coefficients
+    }
+
+    /// numerator coefficients, descending powers of s
+    pub fn numerator(&self) -> Vec<f64> {
+        self.numerator.clone()
+    }
+
+    /// denominator coefficients, descending powers of s
+    pub fn denominator(&self) -> Vec<f64> {
+        self.denominator.clone()
+    }
+}
+
+impl From<TransferFnNthOrder> for TransferFn {
+    fn from(transfer_fn: TransferFnNthOrder) -> Self {
+        TransferFn::NthOrder(transfer_fn)
+    }
+}
+
+impl TryFrom<TransferFn> for TransferFnNthOrder {
+    type Error = ChemEngProcessControlSimulatorError;
+    fn try_from(generic_transfer_function: TransferFn)
+    -> Result<Self, Self::Error> {
+
+        if let TransferFn::NthOrder(
+        nth_order) = generic_transfer_function {
+            Ok(nth_order)
+        } else {
+            Err(ChemEngProcessControlSimulatorError::WrongTransferFnType)
+        }
+    }
+}
+
+#[test]
+pub fn test_third_order_step_response(){
+    use uom::si::time::second;
+
+    // G(s) = 1/(s + 1)^3, step response is
+    // y(t) = 1 - exp(-t) [1 + t + t^2/2]
+    let mut repeated_poles = TransferFnNthOrder::new(
+        vec![1.0], vec![1.0, 3.0, 3.0, 1.0]).unwrap();
+    assert_eq!(repeated_poles.repeated_pole_modes.len(), 1);
+
+    // G(s) = 6/((s + 1)(s + 2)(s + 3)), step response is
+    // y(t) = 1 - 3 exp(-t) + 3 exp(-2t) - exp(-3t)
+    let mut distinct_poles = TransferFnNthOrder::new(
+        vec![6.0], vec![1.0, 6.0, 11.0, 6.0]).unwrap();
+    assert_eq!(distinct_poles.modes.len(), 3);
+
+    // G(s) = (s + 2)/((s + 1)(s^2 + 2s + 5)),
+    // poles -1 and -1 +/- 2j, step response is
+    // y(t) = 2/5 - 1/4 exp(-t)
+    // - exp(-t) [3/20 cos(2t) + 1/5 sin(2t)]
+    let mut complex_poles = TransferFnNthOrder::new(
+        vec![1.0, 2.0], vec![1.0, 3.0, 7.0, 5.0]).unwrap();
+    assert_eq!(complex_poles.modes.len(), 2);
+
+    let step = Ratio::new::<ratio>(1.0);
+    for tf in [&mut repeated_poles, &mut distinct_poles, &mut complex_poles] {
+        tf.set_user_input_and_calc(step, Time::ZERO).unwrap();
+    }
+
+    let t: f64 = 1.5;
+    let time = Time::new::<second>(t);
+
+    let output = repeated_poles.set_user_input_and_calc(step, time).unwrap();
+    let expected = 1.0 - (-t).exp() * (1.0 + t + 0.5 * t * t);
+    approx::assert_relative_eq!(output.get::<ratio>(), expected,
+        max_relative = 1e-6);
+
+    let output = distinct_poles.set_user_input_and_calc(step, time).unwrap();
+    let expected = 1.0 - 3.0 * (-t).exp() + 3.0 * (-2.0 * t).exp()
+        - (-3.0 * t).exp();
+    approx::assert_relative_eq!(output.get::<ratio>(), expected,
+        max_relative = 1e-9);
+
+    let output = complex_poles.set_user_input_and_calc(step, time).unwrap();
+    let expected = 0.4 - 0.25 * (-t).exp()
+        - (-t).exp() * (0.15 * (2.0 * t).cos() + 0.2 * (2.0 * t).sin());
+    approx::assert_relative_eq!(output.get::<ratio>(), expected,
+        max_relative = 1e-9);
+}
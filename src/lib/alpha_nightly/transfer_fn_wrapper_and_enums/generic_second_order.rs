@@ -1,18 +1,23 @@
 use csv::Writer;
+use num_complex::Complex;
 use uom::si::ratio::ratio;
 use uom::si::f64::*;
 use uom::si::time::second;
+use uom::si::frequency::hertz;
+use uom::si::angular_velocity::radian_per_second;
 use uom::ConstZero;
 
 use crate::alpha_nightly::{TimeSquared, stable_transfer_functions::decaying_exponentials::DecayingSecondOrderExponential};
 use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
 use crate::alpha_nightly::stable_transfer_functions::decaying_sinusoid::DecayingSinusoid;
 use crate::alpha_nightly::stable_transfer_functions::second_order_transfer_fn::SecondOrderStableTransferFnNoZeroes;
+use crate::alpha_nightly::unstable_transfer_functions::second_order_transfer_fn::{SecondOrderUnstableTransferFn, SecondOrderUnstableMode};
+use crate::alpha_nightly::undamped_transfer_functions::second_order_undamped_transfer_fn::SecondOrderUndampedTransferFn;
 
-use super::{TransferFn, TransferFnTraits};
+use super::{StepResponseMetrics, TransferFn, TransferFnTraits};
+use super::step_response_metrics;
 
-/// an enum describing generic second order systems,
-/// only stable systems are implemented so far
+/// an enum describing generic second order systems
 ///
 /// you are meant to put in:
 ///
@@ -34,8 +39,26 @@ pub enum TransferFnSecondOrder {
         DecayingSecondOrderExponential),
     StableOverdamped(SecondOrderStableTransferFnNoZeroes,
         DecayingSecondOrderExponential),
-    Unstable,
-    Undamped,
+    /// at least one root in the right half plane, 
+    /// growing oscillations (zeta < 0) or growing exponentials
+    Unstable(SecondOrderUnstableTransferFn),
+    /// zeta = 0, sustained oscillations
+    Undamped(SecondOrderUndampedTransferFn),
+}
+
+/// polynomial coefficients of a second order transfer function,
+///
+/// a1 s^2 + b1 s + c1
+/// ------------------
+/// a2 s^2 + b2 s + c2
+#[derive(Debug,PartialEq, PartialOrd, Clone, Copy)]
+pub struct SecondOrderCoefficients {
+    pub a1: TimeSquared,
+    pub b1: Time,
+    pub c1: Ratio,
+    pub a2: TimeSquared,
+    pub b2: Time,
+    pub c2: Ratio,
 }
 
 impl Default for TransferFnSecondOrder {
@@ -68,8 +91,12 @@ impl TransferFnTraits for TransferFnSecondOrder {
                 non_zero_steady_state_mode.delay = dead_time;
                 decaying_mode.delay = dead_time;
             },
-            TransferFnSecondOrder::Unstable => todo!(),
-            TransferFnSecondOrder::Undamped => todo!(),
+            TransferFnSecondOrder::Unstable(unstable_transfer_fn) => {
+                unstable_transfer_fn.delay = dead_time;
+            },
+            TransferFnSecondOrder::Undamped(undamped_transfer_fn) => {
+                undamped_transfer_fn.delay = dead_time;
+            },
         }
     }
 
@@ -134,8 +161,14 @@ impl TransferFnTraits for TransferFnSecondOrder {
 
                     return Ok(response);
                 },
-                TransferFnSecondOrder::Unstable => todo!(),
-                TransferFnSecondOrder::Undamped => todo!(),
+                TransferFnSecondOrder::Unstable(unstable_transfer_fn) => {
+                    unstable_transfer_fn.set_user_input_and_calc_output(
+                        time, user_input)
+                },
+                TransferFnSecondOrder::Undamped(undamped_transfer_fn) => {
+                    undamped_transfer_fn.set_user_input_and_calc_output(
+                        time, user_input)
+                },
             }
 
     }
@@ -153,8 +186,12 @@ impl TransferFnTraits for TransferFnSecondOrder {
                 TransferFnSecondOrder::StableOverdamped(_,_) => {
                     title_string += "2nd_ord_transfer_fn_stable_overdamped.csv";
                 },
-                TransferFnSecondOrder::Unstable => todo!(),
-                TransferFnSecondOrder::Undamped => todo!(),
+                TransferFnSecondOrder::Unstable(_) => {
+                    title_string += "2nd_ord_transfer_fn_unstable.csv";
+                },
+                TransferFnSecondOrder::Undamped(_) => {
+                    title_string += "2nd_ord_transfer_fn_undamped.csv";
+                },
             }
             let wtr = Writer::from_path(title_string)?;
             Ok(wtr)
@@ -210,12 +247,44 @@ impl TransferFnSecondOrder {
         b2: Time,
         c2: Ratio) -> Result<Self,ChemEngProcessControlSimulatorError> {
 
+        let coefficients = SecondOrderCoefficients { a1, b1, c1, a2, b2, c2 };
+
+        // c2 = 0 means a pole at the origin, there is no process 
+        // time or damping factor to speak of
+        if c2 == Ratio::ZERO {
+            return Err(ChemEngProcessControlSimulatorError::
+                UndampedOrIntegratingSecondOrderTransferFunction);
+        }
+
+        // a2 = 0 leaves a first order denominator, so there is no 
+        // process time or damping factor either, and with a1 the 
+        // numerator is of higher order
+        if a2 == TimeSquared::ZERO {
+            if a1 != TimeSquared::ZERO {
+                return Err(ChemEngProcessControlSimulatorError::
+                    ImproperTransferFunction);
+            }
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "a2 is zero, this is a first order transfer function, \
+                    use TransferFnFirstOrder".to_owned()));
+        }
+
+        // if a2 and c2 have opposite signs, the roots are real 
+        // and of opposite sign, one of them is in the right half plane
+        if (a2*c2).value < 0.0 {
+            let unstable_transfer_fn = SecondOrderUnstableTransferFn::new(
+                coefficients, Ratio::ZERO, Ratio::ZERO, Time::ZERO)?;
+            return Ok(Self::Unstable(unstable_transfer_fn));
+        }
 
         // process time 
         let tau_p: Time = (a2/c2).sqrt();
 
         // damping factor
-        let zeta: Ratio = 0.5 * b2 / (a2*c2).sqrt();
+        // (written as b2/a2 rather than b2/sqrt(a2 c2) so that 
+        // the sign is correct when a2 and c2 are both negative)
+        let zeta: Ratio = 0.5 * b2/a2 * tau_p;
 
         // process_gain 
         // I assume units of c1 are dimensionless
@@ -226,7 +295,14 @@ impl TransferFnSecondOrder {
 
         if zeta_value < 0.0 {
             // unstable system
-            todo!("unstable system, not implemented");
+            let unstable_transfer_fn = SecondOrderUnstableTransferFn::new(
+                coefficients, Ratio::ZERO, Ratio::ZERO, Time::ZERO)?;
+            Ok(Self::Unstable(unstable_transfer_fn))
+        } else if zeta_value == 0.0 {
+            // undamped system, sustained oscillations
+            let undamped_transfer_fn = SecondOrderUndampedTransferFn::new(
+                coefficients, Ratio::ZERO, Ratio::ZERO, Time::ZERO)?;
+            Ok(Self::Undamped(undamped_transfer_fn))
         } else if zeta_value < 1.0 {
             // angular frequency for decaying sinusoids IF we have 
             // an underdamped system
@@ -235,21 +311,21 @@ impl TransferFnSecondOrder {
         // note that this only applies for critical and underdamped cases
             let lambda: Frequency = 0.5 *b2/a2;
             // underdamped system
-            return Self::new_underdamped_stable_system(tau_p, 
+            Self::new_underdamped_stable_system(tau_p, 
                 zeta, 
-                k_p, lambda, omega, a1, a2, b1);
+                k_p, lambda, omega, coefficients)
 
         } else if zeta_value == 1.0 {
             // decay constant 
             // note that this only applies for critical and underdamped cases
             let lambda: Frequency = 0.5 *b2/a2;
             // critically damped system, not implemented yet
-            return Self::new_critdamped_stable_system(
-                tau_p, zeta, k_p, lambda, a1, a2, b1);
+            Self::new_critdamped_stable_system(
+                tau_p, zeta, k_p, lambda, coefficients)
         } else {
 
-            return Self::new_overdamped_stable_system(
-                tau_p, zeta, k_p, a1, a2, b1, b2, c2);
+            Self::new_overdamped_stable_system(
+                tau_p, zeta, k_p, coefficients)
         }
 
 
@@ -259,11 +335,10 @@ impl TransferFnSecondOrder {
     fn new_overdamped_stable_system(tau_p: Time,
         zeta: Ratio,
         k_p: Ratio,
-        a1: TimeSquared,
-        a2: TimeSquared,
-        b1: Time,
-        b2: Time,
-        c2: Ratio) -> Result<Self, ChemEngProcessControlSimulatorError>{
+        coefficients: SecondOrderCoefficients) 
+        -> Result<Self, ChemEngProcessControlSimulatorError>{
+
+        let SecondOrderCoefficients { a1, b1, a2, b2, c2, .. } = coefficients;
 
         // for this, we have two real roots
         // the c1 coefficient has a transfer function calculated,
@@ -322,9 +397,10 @@ impl TransferFnSecondOrder {
         zeta: Ratio,
         k_p: Ratio,
         lambda: Frequency,
-        a1: TimeSquared,
-        a2: TimeSquared,
-        b1: Time) -> Result<Self, ChemEngProcessControlSimulatorError>{
+        coefficients: SecondOrderCoefficients) 
+        -> Result<Self, ChemEngProcessControlSimulatorError>{
+
+        let SecondOrderCoefficients { a1, b1, a2, .. } = coefficients;
 
         // supposing has zeta, k_p, lambda
         // where lambda is 0.5 a2/b2
@@ -393,9 +469,10 @@ impl TransferFnSecondOrder {
         k_p: Ratio,
         lambda: Frequency,
         omega: Frequency,
-        a1: TimeSquared,
-        a2: TimeSquared,
-        b1: Time) -> Result<Self, ChemEngProcessControlSimulatorError>{
+        coefficients: SecondOrderCoefficients) 
+        -> Result<Self, ChemEngProcessControlSimulatorError>{
+
+        let SecondOrderCoefficients { a1, b1, a2, .. } = coefficients;
 
         // underdamped systems will contain two decaying_sinusoid
         // types and one SecondOrderStableTransferFunction Type
@@ -442,6 +519,214 @@ impl TransferFnSecondOrder {
 
     }
 
+    /// numerator coefficients [a1, b1, c1] in SI units 
+    /// (seconds^2, seconds, dimensionless)
+    ///
+    /// these are worked back from the terms of the transfer function,
+    /// so they are scaled such that a2 = 1 s^2
+    pub fn numerator(&self) -> Vec<f64> {
+        self.coefficients().0
+    }
+
+    /// denominator coefficients [a2, b2, c2] in SI units 
+    /// (seconds^2, seconds, dimensionless)
+    ///
+    /// scaled such that a2 = 1 s^2
+    pub fn denominator(&self) -> Vec<f64> {
+        self.coefficients().1
+    }
+
+    /// dead time of the transfer function
+    pub fn dead_time(&self) -> Time {
+        match self {
+            TransferFnSecondOrder::StableUnderdamped(
+                transfer_fn_no_zeroes, _, _) => transfer_fn_no_zeroes.delay,
+            TransferFnSecondOrder::StableCriticallydamped(
+                transfer_fn_no_zeroes, _) => transfer_fn_no_zeroes.delay,
+            TransferFnSecondOrder::StableOverdamped(
+                transfer_fn_no_zeroes, _) => transfer_fn_no_zeroes.delay,
+            TransferFnSecondOrder::Unstable(
+                unstable_transfer_fn) => unstable_transfer_fn.delay,
+            TransferFnSecondOrder::Undamped(
+                undamped_transfer_fn) => undamped_transfer_fn.delay,
+        }
+    }
+
+    /// poles of the transfer function in 1/s, 
+    /// ie. the roots of a2 s^2 + b2 s + c2
+    pub fn poles(&self) -> Result<Vec<Complex<f64>>, 
+    ChemEngProcessControlSimulatorError> {
+        step_response_metrics::polynomial_roots(&self.denominator())
+    }
+
+    /// zeros of the transfer function in 1/s, 
+    /// ie. the roots of a1 s^2 + b1 s + c1
+    ///
+    /// a zero in the right half plane gives an inverse response
+    pub fn zeros(&self) -> Result<Vec<Complex<f64>>, 
+    ChemEngProcessControlSimulatorError> {
+        step_response_metrics::polynomial_roots(&self.numerator())
+    }
+
+    /// steady state gain k_p = c1/c2, 
+    /// a pole at s = 0 gives an error
+    pub fn steady_state_gain(&self) -> Result<Ratio, 
+    ChemEngProcessControlSimulatorError> {
+        step_response_metrics::steady_state_gain(&self.numerator(), 
+            &self.denominator())
+    }
+
+    /// natural period tau_p = sqrt(a2/c2)
+    ///
+    /// only defined if a2 and c2 have the same sign, 
+    /// otherwise there are real poles either side of s = 0
+    pub fn natural_period(&self) -> Result<Time, 
+    ChemEngProcessControlSimulatorError> {
+        // a2 = 1 s^2 here
+        let c2: f64 = self.denominator()[2];
+        if c2 <= 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "natural period needs a2 and c2 of the same sign"
+                    .to_owned()));
+        }
+        Ok(Time::new::<second>(1.0/c2.sqrt()))
+    }
+
+    /// damping ratio zeta = b2/(2 sqrt(a2 c2))
+    ///
+    /// zeta < 1 is underdamped, zeta = 0 is undamped and 
+    /// zeta < 0 has growing oscillations
+    pub fn damping_ratio(&self) -> Result<Ratio, 
+    ChemEngProcessControlSimulatorError> {
+        let denominator = self.denominator();
+        let tau_p: f64 = self.natural_period()?.get::<second>();
+        Ok(Ratio::new::<ratio>(0.5 * denominator[1] * tau_p))
+    }
+
+    /// rise time, overshoot, decay ratio, settling time and 
+    /// inverse response for a unit step, without simulating
+    ///
+    /// only for stable processes
+    pub fn step_response_metrics(&self) -> Result<StepResponseMetrics, 
+    ChemEngProcessControlSimulatorError> {
+        step_response_metrics::step_response_metrics(&self.numerator(), 
+            &self.denominator(), self.dead_time())
+    }
+
+    /// works the polynomial coefficients back from the terms,
+    /// with a2 = 1 s^2
+    ///
+    /// (a1 s^2 + b1 s + c1)/(s^2 + b2 s + c2)
+    fn coefficients(&self) -> (Vec<f64>, Vec<f64>) {
+
+        // Kp/(tau_p^2 s^2 + 2 zeta tau_p s + 1) gives 
+        // c1, b2 and c2 for the stable systems
+        let no_zeroes_coefficients = 
+            |transfer_fn_no_zeroes: &SecondOrderStableTransferFnNoZeroes| {
+            let tau_p: f64 = transfer_fn_no_zeroes.process_time.get::<second>();
+            let zeta: f64 = transfer_fn_no_zeroes.damping_factor.get::<ratio>();
+            let k_p: f64 = transfer_fn_no_zeroes.process_gain.get::<ratio>();
+            (k_p/tau_p/tau_p, 2.0 * zeta/tau_p, 1.0/tau_p/tau_p)
+        };
+
+        match self {
+            TransferFnSecondOrder::StableUnderdamped(
+                transfer_fn_no_zeroes, 
+                cosine_term, 
+                sine_term) => {
+                    let (c1, b2, c2) = no_zeroes_coefficients(
+                        transfer_fn_no_zeroes);
+                    let lambda: f64 = cosine_term.a.get::<hertz>();
+                    let omega: f64 = cosine_term.omega.get::<hertz>();
+                    // cosine magnitude is a1/a2, 
+                    // sine magnitude is (b1/a2 - lambda a1/a2)/omega
+                    let a1: f64 = cosine_term.magnitude.get::<ratio>();
+                    let b1: f64 = omega * sine_term.magnitude.get::<ratio>() 
+                        + lambda * a1;
+                    (vec![a1, b1, c1], vec![1.0, b2, c2])
+            },
+            TransferFnSecondOrder::StableCriticallydamped(
+                transfer_fn_no_zeroes, 
+                decaying_exponential) => {
+                    let (c1, b2, c2) = no_zeroes_coefficients(
+                        transfer_fn_no_zeroes);
+                    let lambda: f64 = decaying_exponential.alpha.get::<hertz>();
+                    // exponential magnitude is a1/a2, 
+                    // t exponential magnitude is b1/a2 - lambda a1/a2
+                    let a1: f64 = decaying_exponential.magnitude_beta
+                        .get::<ratio>();
+                    let b1: f64 = decaying_exponential.magnitude_alpha
+                        .get::<ratio>() + lambda * a1;
+                    (vec![a1, b1, c1], vec![1.0, b2, c2])
+            },
+            TransferFnSecondOrder::StableOverdamped(
+                transfer_fn_no_zeroes, 
+                decaying_exponential) => {
+                    let (c1, b2, c2) = no_zeroes_coefficients(
+                        transfer_fn_no_zeroes);
+                    let alpha: f64 = decaying_exponential.alpha.get::<hertz>();
+                    let beta: f64 = decaying_exponential.beta.get::<hertz>();
+                    let magnitude_alpha: f64 = decaying_exponential
+                        .magnitude_alpha.get::<ratio>();
+                    let magnitude_beta: f64 = decaying_exponential
+                        .magnitude_beta.get::<ratio>();
+                    // the magnitudes add up to a1/a2
+                    let a1: f64 = magnitude_alpha + magnitude_beta;
+                    let b1: f64 = magnitude_alpha * (beta - alpha) + alpha * a1;
+                    (vec![a1, b1, c1], vec![1.0, b2, c2])
+            },
+            TransferFnSecondOrder::Unstable(unstable_transfer_fn) => {
+                // K + (p s + q)/(s^2 + b2 s + c2) 
+                let step_gain: f64 = unstable_transfer_fn.step_gain
+                    .get::<ratio>();
+                let (p, q, b2, c2) = match unstable_transfer_fn.mode {
+                    SecondOrderUnstableMode::GrowingSinusoid { 
+                        cosine_magnitude, sine_magnitude, sigma, omega } => {
+                        let sigma: f64 = sigma.get::<hertz>();
+                        let omega: f64 = omega.get::<radian_per_second>();
+                        let p: f64 = cosine_magnitude.get::<ratio>();
+                        let q: f64 = sine_magnitude.get::<ratio>() * omega 
+                            - p * sigma;
+                        (p, q, -2.0 * sigma, sigma * sigma + omega * omega)
+                    },
+                    SecondOrderUnstableMode::TwoRealRoots { 
+                        magnitude_one, magnitude_two, root_one, root_two } => {
+                        let root_one: f64 = root_one.get::<hertz>();
+                        let root_two: f64 = root_two.get::<hertz>();
+                        let magnitude_one: f64 = magnitude_one.get::<ratio>();
+                        let p: f64 = magnitude_one + magnitude_two.get::<ratio>();
+                        let q: f64 = magnitude_one * (root_one - root_two) 
+                            - p * root_one;
+                        (p, q, -(root_one + root_two), root_one * root_two)
+                    },
+                    SecondOrderUnstableMode::RepeatedRealRoot { 
+                        exponential_magnitude, t_exponential_magnitude, root } => {
+                        let root: f64 = root.get::<hertz>();
+                        let p: f64 = exponential_magnitude.get::<ratio>();
+                        let q: f64 = t_exponential_magnitude.get::<hertz>() 
+                            - p * root;
+                        (p, q, -2.0 * root, root * root)
+                    },
+                };
+                (vec![p + step_gain, q + step_gain * b2, step_gain * c2],
+                    vec![1.0, b2, c2])
+            },
+            TransferFnSecondOrder::Undamped(undamped_transfer_fn) => {
+                // K + p cos(omega t) + q/omega sin(omega t)
+                let step_gain: f64 = undamped_transfer_fn.step_gain
+                    .get::<ratio>();
+                let omega: f64 = undamped_transfer_fn.omega
+                    .get::<radian_per_second>();
+                let p: f64 = undamped_transfer_fn.cosine_magnitude.get::<ratio>();
+                let q: f64 = undamped_transfer_fn.sine_magnitude.get::<ratio>() 
+                    * omega;
+                let c2: f64 = omega * omega;
+                (vec![p + step_gain, q, step_gain * c2], vec![1.0, 0.0, c2])
+            },
+        }
+    }
+
 }
 
 
@@ -490,6 +775,98 @@ pub fn test_dead_time(){
 
 }
 
+#[test]
+pub fn test_undamped_and_unstable_step_responses(){
+    use uom::si::time::second;
+
+    let one_second = Time::new::<second>(1.0);
+    let step = Ratio::new::<ratio>(1.0);
+    let time = Time::new::<second>(2.0);
+
+    // undamped: 1/(s^2 + 1)
+    // y(t) = 1 - cos(t)
+    let mut undamped = TransferFnSecondOrder::new(
+        TimeSquared::ZERO, Time::ZERO, Ratio::new::<ratio>(1.0),
+        one_second * one_second, Time::ZERO, 
+        Ratio::new::<ratio>(1.0)).unwrap();
+    assert!(matches!(undamped, TransferFnSecondOrder::Undamped(_)));
+
+    undamped.set_user_input_and_calc(step, Time::ZERO).unwrap();
+    let output = undamped.set_user_input_and_calc(step, time).unwrap();
+    approx::assert_relative_eq!(output.get::<ratio>(), 1.0 - 2.0_f64.cos(),
+        max_relative = 1e-9);
+
+    // real roots of opposite sign: 1/(s^2 + s - 2), roots 1 and -2
+    // y(t) = -1/2 + exp(t)/3 + exp(-2t)/6
+    let mut saddle = TransferFnSecondOrder::new(
+        TimeSquared::ZERO, Time::ZERO, Ratio::new::<ratio>(1.0),
+        one_second * one_second, one_second, 
+        Ratio::new::<ratio>(-2.0)).unwrap();
+    assert!(matches!(saddle, TransferFnSecondOrder::Unstable(_)));
+
+    saddle.set_user_input_and_calc(step, Time::ZERO).unwrap();
+    let output = saddle.set_user_input_and_calc(step, time).unwrap();
+    let expected = -0.5 + 2.0_f64.exp()/3.0 + (-4.0_f64).exp()/6.0;
+    approx::assert_relative_eq!(output.get::<ratio>(), expected,
+        max_relative = 1e-9);
+
+    // growing oscillation: (s + 1)/(s^2 - 0.4 s + 1.04), 
+    // roots 0.2 +/- j
+    //
+    // y(t) = 1/1.04 
+    // + exp(0.2 t) [ -1/1.04 cos(t) + (1 + 0.2/1.04) sin(t) ]
+    let mut growing = TransferFnSecondOrder::new(
+        TimeSquared::ZERO, one_second, Ratio::new::<ratio>(1.0),
+        one_second * one_second, -0.4 * one_second, 
+        Ratio::new::<ratio>(1.04)).unwrap();
+    assert!(matches!(growing, TransferFnSecondOrder::Unstable(_)));
+
+    growing.set_user_input_and_calc(step, Time::ZERO).unwrap();
+    let output = growing.set_user_input_and_calc(step, time).unwrap();
+    let expected = 1.0/1.04 + (0.4_f64).exp() * 
+        (-1.0/1.04 * 2.0_f64.cos() + (1.0 + 0.2/1.04) * 2.0_f64.sin());
+    approx::assert_relative_eq!(output.get::<ratio>(), expected,
+        max_relative = 1e-9);
+
+    // a pole at the origin: 1/(s^2 + s), integrating
+    let integrating = TransferFnSecondOrder::new(
+        TimeSquared::ZERO, Time::ZERO, Ratio::new::<ratio>(1.0),
+        one_second * one_second, one_second, Ratio::ZERO);
+    assert!(matches!(integrating, Err(ChemEngProcessControlSimulatorError::
+        UndampedOrIntegratingSecondOrderTransferFunction)));
+
+    // 1/(s + 1) is first order, and s^2/(s + 1) is improper
+    let first_order = TransferFnSecondOrder::new(
+        TimeSquared::ZERO, Time::ZERO, Ratio::new::<ratio>(1.0),
+        TimeSquared::ZERO, one_second, Ratio::new::<ratio>(1.0));
+    assert!(matches!(first_order, Err(ChemEngProcessControlSimulatorError::
+        GenericStringError(_))));
+    let improper = TransferFnSecondOrder::new(
+        one_second * one_second, Time::ZERO, Ratio::ZERO,
+        TimeSquared::ZERO, one_second, Ratio::new::<ratio>(1.0));
+    assert!(matches!(improper, Err(ChemEngProcessControlSimulatorError::
+        ImproperTransferFunction)));
+
+    // an undamped system is neither stable nor unstable
+    let undamped_coefficients = SecondOrderCoefficients {
+        a1: TimeSquared::ZERO,
+        b1: Time::ZERO,
+        c1: Ratio::new::<ratio>(1.0),
+        a2: one_second * one_second,
+        b2: Time::ZERO,
+        c2: Ratio::new::<ratio>(1.0),
+    };
+    let unstable = SecondOrderUnstableTransferFn::new(undamped_coefficients,
+        Ratio::ZERO, Ratio::ZERO, Time::ZERO);
+    assert!(matches!(unstable, Err(ChemEngProcessControlSimulatorError::
+        UndampedOrIntegratingSecondOrderTransferFunction)));
+    let double_integrator = SecondOrderUndampedTransferFn::new(
+        SecondOrderCoefficients { c2: Ratio::ZERO, ..undamped_coefficients },
+        Ratio::ZERO, Ratio::ZERO, Time::ZERO);
+    assert!(matches!(double_integrator, Err(ChemEngProcessControlSimulatorError::
+        UndampedOrIntegratingSecondOrderTransferFunction)));
+}
+
 impl Into<TransferFn> for TransferFnSecondOrder {
     fn into(self) -> TransferFn {
         TransferFn::SecondOrder(self)
@@ -512,3 +889,88 @@ impl TryFrom<TransferFn> for TransferFnSecondOrder {
         }
 }
 
+
+#[test]
+pub fn test_poles_zeros_and_step_response_metrics(){
+
+    let one_second = Time::new::<second>(1.0);
+
+    // underdamped, G(s) = 2/(s^2 + 0.6 s + 1), with 1.5 s dead time
+    //
+    // tau_p = 1 s, zeta = 0.3
+    let mut underdamped = TransferFnSecondOrder::new(
+        one_second * Time::ZERO, Time::ZERO, Ratio::new::<ratio>(2.0),
+        one_second * one_second, one_second * 0.6, 
+        Ratio::new::<ratio>(1.0)).unwrap();
+    underdamped.set_dead_time(Time::new::<second>(1.5));
+
+    let zeta: f64 = 0.3;
+    approx::assert_relative_eq!(underdamped.damping_ratio().unwrap()
+        .get::<ratio>(), zeta, max_relative = 1e-12);
+    approx::assert_relative_eq!(underdamped.natural_period().unwrap()
+        .get::<second>(), 1.0, max_relative = 1e-12);
+    approx::assert_relative_eq!(underdamped.steady_state_gain().unwrap()
+        .get::<ratio>(), 2.0, max_relative = 1e-12);
+    assert_eq!(underdamped.dead_time(), Time::new::<second>(1.5));
+    assert!(underdamped.zeros().unwrap().is_empty());
+
+    // poles at -zeta/tau_p +/- j sqrt(1 - zeta^2)/tau_p
+    let poles = underdamped.poles().unwrap();
+    assert_eq!(poles.len(), 2);
+    for pole in poles.iter() {
+        approx::assert_relative_eq!(pole.re, -zeta, max_relative = 1e-9);
+        approx::assert_relative_eq!(pole.im.abs(), (1.0 - zeta*zeta).sqrt(),
+            max_relative = 1e-9);
+    }
+
+    // textbook formulas (Seborg et al.) for second order 
+    // systems without zeros
+    let overshoot: f64 = (-std::f64::consts::PI * zeta
+        /(1.0 - zeta*zeta).sqrt()).exp();
+    let metrics = underdamped.step_response_metrics().unwrap();
+    approx::assert_relative_eq!(metrics.overshoot.get::<ratio>(), 
+        overshoot, max_relative = 1e-9);
+    approx::assert_relative_eq!(metrics.decay_ratio.get::<ratio>(), 
+        overshoot * overshoot, max_relative = 1e-9);
+    assert!(!metrics.inverse_response);
+
+    // the settling time is when the response last leaves the 
+    // 5% band, so a simulation should be right at the edge of 
+    // the band there
+    let mut simulated = underdamped.clone();
+    let settling_time = metrics.settling_time;
+    let step = Ratio::new::<ratio>(1.0);
+    simulated.set_user_input_and_calc(step, Time::ZERO).unwrap();
+    let output_at_settling = simulated.set_user_input_and_calc(step, 
+        settling_time).unwrap();
+    approx::assert_relative_eq!((output_at_settling.get::<ratio>() - 2.0).abs(),
+        0.05 * 2.0, max_relative = 1e-6);
+
+    // overdamped with a right half plane zero,
+    // G(s) = (-2 s + 1)/(s^2 + 3 s + 2)
+    let inverse_response = TransferFnSecondOrder::new(
+        one_second * Time::ZERO, -one_second * 2.0, Ratio::new::<ratio>(1.0),
+        one_second * one_second, one_second * 3.0, 
+        Ratio::new::<ratio>(2.0)).unwrap();
+
+    let zeros = inverse_response.zeros().unwrap();
+    assert_eq!(zeros.len(), 1);
+    approx::assert_relative_eq!(zeros[0].re, 0.5, max_relative = 1e-9);
+
+    // zeta = 3/(2 sqrt 2) > 1
+    approx::assert_relative_eq!(inverse_response.damping_ratio().unwrap()
+        .get::<ratio>(), 1.5/2.0_f64.sqrt(), max_relative = 1e-12);
+
+    let metrics = inverse_response.step_response_metrics().unwrap();
+    assert!(metrics.inverse_response);
+    assert_eq!(metrics.decay_ratio.get::<ratio>(), 0.0);
+    assert_eq!(metrics.overshoot.get::<ratio>(), 0.0);
+
+    // unstable systems have no step response metrics
+    let unstable = TransferFnSecondOrder::new(
+        one_second * Time::ZERO, Time::ZERO, Ratio::new::<ratio>(1.0),
+        one_second * one_second, -one_second * 0.5, 
+        Ratio::new::<ratio>(1.0)).unwrap();
+    assert!(unstable.damping_ratio().unwrap().get::<ratio>() < 0.0);
+    assert!(unstable.step_response_metrics().is_err());
+}
@@ -0,0 +1,245 @@
+use csv::Writer;
+use uom::si::ratio::ratio;
+use uom::si::f64::*;
+use uom::si::time::second;
+
+use crate::alpha_nightly::TimeSquared;
+use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
+use crate::alpha_nightly::polynomials;
+
+use super::{StateSpaceModel, TransferFn, TransferFnTraits};
+
+/// a transfer function of any order, simulated using an exact
+/// zero order hold discretisation of its state space form
+///
+/// you are meant to put in:
+///
+/// G(s) =
+///
+/// b_m s^m + ... + b_1 s + b_0
+/// ---------------------------
+/// a_n s^n + ... + a_1 s + a_0
+///
+/// as two vectors of coefficients in descending powers of s,
+/// the same way as TransferFnNthOrder. Coefficients of s^k are
+/// taken to be in seconds^k.
+///
+/// The other transfer functions store one response for every input
+/// change and only drop them after about 20 time constants. If the
+/// input changes every timestep (eg. a PID controller with a noisy
+/// measurement), the cost per timestep grows with the history.
+///
+/// Here, the transfer function is written in controllable canonical
+/// form and simulated by a StateSpaceModel, so for a fixed timestep
+/// each call costs the same no matter how long the simulation has
+/// run. Stable, integrating and unstable processes are all handled
+/// the same way.
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub struct TransferFnZeroOrderHold {
+    /// numerator coefficients, descending powers of s
+    pub(crate) numerator: Vec<f64>,
+    /// denominator coefficients, descending powers of s
+    pub(crate) denominator: Vec<f64>,
+    /// controllable canonical form of the transfer function
+    pub(crate) state_space_model: StateSpaceModel,
+}
+
+impl Default for TransferFnZeroOrderHold {
+    /// default is:
+    ///
+    /// 1 / (s + 1)
+    fn default() -> Self {
+        Self::new(vec![1.0], vec![1.0, 1.0]).unwrap()
+    }
+}
+
+impl TransferFnTraits for TransferFnZeroOrderHold {
+    fn set_dead_time(&mut self, dead_time: Time) {
+        self.state_space_model.set_dead_time(dead_time);
+    }
+
+    fn set_user_input_and_calc(&mut self, user_input: Ratio,
+        time: Time) ->
+    Result<Ratio, ChemEngProcessControlSimulatorError> {
+        self.state_space_model.set_user_input_and_calc(user_input, time)
+    }
+
+    fn spawn_writer(&mut self, name: String) -> Result<Writer<std::fs::File>,
+    ChemEngProcessControlSimulatorError>{
+        let title_string: String = name + "zoh_transfer_fn.csv";
+        let wtr = Writer::from_path(title_string)?;
+        Ok(wtr)
+    }
+
+    fn csv_write_values(&mut self,
+        wtr: &mut Writer<std::fs::File>,
+        time: Time,
+        input: Ratio,
+        output: Ratio) -> Result<(),
+    ChemEngProcessControlSimulatorError> {
+        self.state_space_model.csv_write_values(wtr, time, input, output)
+    }
+}
+
+impl TransferFnZeroOrderHold {
+
+    /// generic constructor based on polynomials
+    ///
+    /// G(s) =
+    ///
+    /// b_m s^m + ... + b_1 s + b_0
+    /// ---------------------------
+    /// a_n s^n + ... + a_1 s + a_0
+    ///
+    /// numerator is [b_m, ... b_1, b_0],
+    /// denominator is [a_n, ... a_1, a_0]
+    ///
+    /// m must not be larger than n
+    pub fn new(numerator: Vec<f64>,
+        denominator: Vec<f64>) -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        let numerator = polynomials::trim_leading_zeroes(&numerator);
+        let denominator = polynomials::trim_leading_zeroes(&denominator);
+
+        let state_space_model = StateSpaceModel::new_controllable_canonical(
+            numerator.clone(), denominator.clone())?;
+
+        Ok(Self {
+            numerator,
+            denominator,
+            state_space_model,
+        })
+    }
+
+    /// first order constructor, same as TransferFnFirstOrder
+    ///
+    /// G(s) =
+    ///
+    /// a1 s + b1
+    /// ----------
+    /// a2 s + b2
+    pub fn new_first_order(a1: Time,
+        b1: Ratio,
+        a2: Time,
+        b2: Ratio) -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        Self::new(vec![a1.get::<second>(), b1.get::<ratio>()],
+            vec![a2.get::<second>(), b2.get::<ratio>()])
+    }
+
+    /// second order constructor, same as TransferFnSecondOrder
+    ///
+    /// G(s) =
+    ///
+    /// a1 s^2 + b1 s + c1
+    /// ------------------
+    /// a2 s^2 + b2 s + c2
+    pub fn new_second_order(a1: TimeSquared,
+        b1: Time,
+        c1: Ratio,
+        a2: TimeSquared,
+        b2: Time,
+        c2: Ratio) -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        // there is no time squared unit in uom yet, so everything
+        // is worked out in SI base units (seconds)
+        Self::new(vec![a1.value, b1.get::<second>(), c1.get::<ratio>()],
+            vec![a2.value, b2.get::<second>(), c2.get::<ratio>()])
+    }
+
+    /// numerator coefficients, descending powers of s
+    pub fn numerator(&self) -> Vec<f64> {
+        self.numerator.clone()
+    }
+
+    /// denominator coefficients, descending powers of s
+    pub fn denominator(&self) -> Vec<f64> {
+        self.denominator.clone()
+    }
+}
+
+impl From<TransferFnZeroOrderHold> for TransferFn {
+    fn from(transfer_fn: TransferFnZeroOrderHold) -> Self {
+        TransferFn::ZeroOrderHold(transfer_fn)
+    }
+}
+
+impl TryFrom<TransferFn> for TransferFnZeroOrderHold {
+    type Error = ChemEngProcessControlSimulatorError;
+    fn try_from(generic_transfer_function: TransferFn)
+    -> Result<Self, Self::Error> {
+
+        if let TransferFn::ZeroOrderHold(
+        zero_order_hold) = generic_transfer_function {
+            Ok(zero_order_hold)
+        } else {
+            Err(ChemEngProcessControlSimulatorError::WrongTransferFnType)
+        }
+    }
+}
+
+#[test]
+pub fn test_zero_order_hold_matches_analytical_transfer_fns(){
+    use uom::si::time::second;
+    use super::{TransferFnFirstOrder, TransferFnSecondOrder};
+
+    // underdamped second order system with a zero,
+    //
+    // G(s) = (0.5 s^2 + 2 s + 1)/(4 s^2 + 1.2 s + 1) exp(-0.73 s)
+    //
+    // the input changes every timestep, the dead time is not a
+    // multiple of the timestep
+    let a1: TimeSquared = Time::new::<second>(0.5) * Time::new::<second>(1.0);
+    let b1: Time = Time::new::<second>(2.0);
+    let c1: Ratio = Ratio::new::<ratio>(1.0);
+    let a2: TimeSquared = Time::new::<second>(4.0) * Time::new::<second>(1.0);
+    let b2: Time = Time::new::<second>(1.2);
+    let c2: Ratio = Ratio::new::<ratio>(1.0);
+    let dead_time = Time::new::<second>(0.73);
+
+    let mut analytical_second_order = TransferFnSecondOrder::new(
+        a1, b1, c1, a2, b2, c2).unwrap();
+    let mut zoh_second_order = TransferFnZeroOrderHold::new_second_order(
+        a1, b1, c1, a2, b2, c2).unwrap();
+    analytical_second_order.set_dead_time(dead_time);
+    zoh_second_order.set_dead_time(dead_time);
+
+    // first order system with a zero
+    //
+    // G(s) = (3 s + 2)/(5 s + 1) exp(-0.73 s)
+    let mut analytical_first_order: TransferFn = TransferFnFirstOrder::new(
+        Time::new::<second>(3.0), Ratio::new::<ratio>(2.0),
+        Time::new::<second>(5.0), Ratio::new::<ratio>(1.0)).unwrap().into();
+    let mut zoh_first_order: TransferFn = TransferFnZeroOrderHold::
+        new_first_order(
+            Time::new::<second>(3.0), Ratio::new::<ratio>(2.0),
+            Time::new::<second>(5.0), Ratio::new::<ratio>(1.0)).unwrap().into();
+    analytical_first_order.set_dead_time(dead_time);
+    zoh_first_order.set_dead_time(dead_time);
+
+    let timestep: f64 = 0.05;
+    for step in 0..1000 {
+        let t: f64 = step as f64 * timestep;
+        let time = Time::new::<second>(t);
+        let input = Ratio::new::<ratio>(1.0 + 0.3 * (0.7 * t).sin()
+            + 0.05 * ((step * 7919 % 13) as f64 - 6.0));
+
+        let analytical_output = analytical_second_order
+            .set_user_input_and_calc(input, time).unwrap();
+        let zoh_output = zoh_second_order
+            .set_user_input_and_calc(input, time).unwrap();
+        approx::assert_abs_diff_eq!(analytical_output.get::<ratio>(),
+            zoh_output.get::<ratio>(), epsilon = 1e-8);
+
+        let analytical_output = analytical_first_order
+            .set_user_input_and_calc(input, time).unwrap();
+        let zoh_output = zoh_first_order
+            .set_user_input_and_calc(input, time).unwrap();
+        approx::assert_abs_diff_eq!(analytical_output.get::<ratio>(),
+            zoh_output.get::<ratio>(), epsilon = 1e-8);
+    }
+
+    // fixed timestep, the cache should not keep growing
+    assert!(zoh_second_order.state_space_model.step_cache.len()
+        <= super::state_space_model::STEP_CACHE_SIZE);
+}
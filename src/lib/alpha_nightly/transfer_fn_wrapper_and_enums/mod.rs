@@ -5,6 +5,9 @@ use uom::si::f64::*;
 pub enum TransferFn {
     FirstOrder(TransferFnFirstOrder),
     SecondOrder(TransferFnSecondOrder),
+    NthOrder(TransferFnNthOrder),
+    ZeroOrderHold(TransferFnZeroOrderHold),
+    StateSpace(StateSpaceModel),
 }
 
 impl Default for TransferFn {
@@ -22,6 +25,15 @@ impl TransferFnTraits for TransferFn {
             TransferFn::SecondOrder(second_order) => {
                 second_order.set_dead_time(dead_time)
             },
+            TransferFn::NthOrder(nth_order) => {
+                nth_order.set_dead_time(dead_time)
+            },
+            TransferFn::ZeroOrderHold(zero_order_hold) => {
+                zero_order_hold.set_dead_time(dead_time)
+            },
+            TransferFn::StateSpace(state_space_model) => {
+                state_space_model.set_dead_time(dead_time)
+            },
         }
     }
 
@@ -37,6 +49,17 @@ impl TransferFnTraits for TransferFn {
             TransferFn::SecondOrder(second_order) => {
                 second_order.set_user_input_and_calc(user_input, time_of_input)
             },
+            TransferFn::NthOrder(nth_order) => {
+                nth_order.set_user_input_and_calc(user_input, time_of_input)
+            },
+            TransferFn::ZeroOrderHold(zero_order_hold) => {
+                zero_order_hold.set_user_input_and_calc(user_input, 
+                    time_of_input)
+            },
+            TransferFn::StateSpace(state_space_model) => {
+                state_space_model.set_user_input_and_calc(user_input, 
+                    time_of_input)
+            },
         }
     }
 
@@ -49,6 +72,15 @@ impl TransferFnTraits for TransferFn {
             TransferFn::SecondOrder(second_order) => {
                 second_order.spawn_writer(name)
             },
+            TransferFn::NthOrder(nth_order) => {
+                nth_order.spawn_writer(name)
+            },
+            TransferFn::ZeroOrderHold(zero_order_hold) => {
+                zero_order_hold.spawn_writer(name)
+            },
+            TransferFn::StateSpace(state_space_model) => {
+                state_space_model.spawn_writer(name)
+            },
         }
     }
 
@@ -66,6 +98,18 @@ impl TransferFnTraits for TransferFn {
                 second_order.csv_write_values(wtr, time,
                     input, output)
             },
+            TransferFn::NthOrder(nth_order) => {
+                nth_order.csv_write_values(wtr, time,
+                    input, output)
+            },
+            TransferFn::ZeroOrderHold(zero_order_hold) => {
+                zero_order_hold.csv_write_values(wtr, time,
+                    input, output)
+            },
+            TransferFn::StateSpace(state_space_model) => {
+                state_space_model.csv_write_values(wtr, time,
+                    input, output)
+            },
         }
     }
 
@@ -73,6 +117,70 @@ impl TransferFnTraits for TransferFn {
 }
 
 
+impl TransferFn {
+
+    /// numerator coefficients in descending powers of s,
+    /// with coefficients of s^k in seconds^k
+    ///
+    /// first and second order transfer functions are worked back 
+    /// from their terms, so they may be scaled differently from 
+    /// what was put into the constructor
+    pub fn numerator(&self) -> Vec<f64> {
+        match self {
+            TransferFn::FirstOrder(first_order) => first_order.numerator(),
+            TransferFn::SecondOrder(second_order) => second_order.numerator(),
+            TransferFn::NthOrder(nth_order) => nth_order.numerator(),
+            TransferFn::ZeroOrderHold(zero_order_hold) => {
+                zero_order_hold.numerator()
+            },
+            TransferFn::StateSpace(state_space_model) => {
+                state_space_model.transfer_fn_coefficients().0
+            },
+        }
+    }
+
+    /// denominator coefficients in descending powers of s,
+    /// scaled the same way as the numerator
+    pub fn denominator(&self) -> Vec<f64> {
+        match self {
+            TransferFn::FirstOrder(first_order) => first_order.denominator(),
+            TransferFn::SecondOrder(second_order) => second_order.denominator(),
+            TransferFn::NthOrder(nth_order) => nth_order.denominator(),
+            TransferFn::ZeroOrderHold(zero_order_hold) => {
+                zero_order_hold.denominator()
+            },
+            TransferFn::StateSpace(state_space_model) => {
+                state_space_model.transfer_fn_coefficients().1
+            },
+        }
+    }
+
+    /// dead time of the transfer function
+    pub fn dead_time(&self) -> Time {
+        match self {
+            TransferFn::FirstOrder(first_order) => first_order.dead_time(),
+            TransferFn::SecondOrder(second_order) => second_order.dead_time(),
+            TransferFn::NthOrder(nth_order) => nth_order.dead_time,
+            TransferFn::ZeroOrderHold(zero_order_hold) => {
+                zero_order_hold.state_space_model.dead_time()
+            },
+            TransferFn::StateSpace(state_space_model) => {
+                state_space_model.dead_time()
+            },
+        }
+    }
+
+    /// steady state gain G(0), 
+    ///
+    /// returns an error if there is a pole at s = 0 
+    /// (integrating process) as the gain is infinite
+    pub fn steady_state_gain(&self) -> Result<Ratio, 
+    ChemEngProcessControlSimulatorError> {
+        step_response_metrics::steady_state_gain(&self.numerator(),
+            &self.denominator())
+    }
+}
+
 
 pub trait TransferFnTraits {
     fn set_dead_time(&mut self, dead_time: Time);
@@ -94,9 +202,20 @@ pub trait TransferFnTraits {
 
 
 pub mod generic_second_order;
-pub use generic_second_order::TransferFnSecondOrder;
+pub use generic_second_order::{TransferFnSecondOrder, SecondOrderCoefficients};
 pub mod generic_first_order;
 pub use generic_first_order::TransferFnFirstOrder;
+pub mod generic_nth_order;
+pub use generic_nth_order::TransferFnNthOrder;
+pub mod generic_zero_order_hold;
+pub use generic_zero_order_hold::TransferFnZeroOrderHold;
+pub mod state_space_model;
+pub use state_space_model::StateSpaceModel;
+pub mod transfer_fn_matrix;
+pub use transfer_fn_matrix::TransferFnMatrix;
+pub(crate) mod repeated_pole_transfer_fn;
+pub mod step_response_metrics;
+pub use step_response_metrics::StepResponseMetrics;
 
 use super::errors::ChemEngProcessControlSimulatorError;
 
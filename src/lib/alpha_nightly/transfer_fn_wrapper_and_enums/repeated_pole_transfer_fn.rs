@@ -0,0 +1,231 @@
+use num_complex::Complex;
+use uom::si::f64::*;
+use uom::si::frequency::hertz;
+use uom::si::ratio::ratio;
+use uom::si::time::second;
+use uom::ConstZero;
+
+use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
+
+/// partial fraction terms belonging to one pole p of
+/// multiplicity m
+///
+/// G(s) = A_1/(s - p) + A_2/(s - p)^2 + ... + A_m/(s - p)^m
+///
+/// The step response of A_k/(s - p)^k is
+///
+/// A_k * f_k(t)
+///
+/// f_k(t) = 1/(-p)^k [1 - exp(p t) * sum_(j=0)^(k-1) (-p t)^j / j!]
+///
+/// for p not equal to zero, and
+///
+/// f_k(t) = t^k / k!
+///
+/// for p = 0
+///
+/// This works for real or complex poles, stable or unstable.
+/// If the pole is complex, only the pole in the upper half plane is
+/// stored, and the conjugate pole is accounted for by taking twice
+/// the real part of the response.
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub struct RepeatedPoleTransferFn {
+    /// real part of the pole
+    pub(crate) pole_real_part: Frequency,
+    /// imaginary part of the pole
+    pub(crate) pole_imaginary_part: Frequency,
+    /// residues A_1 to A_m, stored as (real part, imaginary part)
+    /// in SI units (seconds^-k)
+    pub(crate) residues: Vec<(f64, f64)>,
+    pub(crate) previous_timestep_input: Ratio,
+    /// previous timestep output
+    pub(crate) offset: Ratio,
+    /// delay
+    pub(crate) delay: Time,
+    /// input changes, stored as (start time, change in input)
+    pub(crate) response_vec: Vec<(Time, Ratio)>,
+}
+
+impl RepeatedPoleTransferFn {
+
+    /// constructor, pole and residues are in SI units
+    pub fn new(pole: Complex<f64>,
+        residues: Vec<Complex<f64>>,
+        initial_input: Ratio,
+        initial_value: Ratio,
+        delay: Time) -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        if residues.is_empty() {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "repeated pole needs at least one residue".to_owned()));
+        }
+
+        Ok(Self {
+            pole_real_part: Frequency::new::<hertz>(pole.re),
+            pole_imaginary_part: Frequency::new::<hertz>(pole.im),
+            residues: residues.iter().map(|residue| (residue.re, residue.im))
+                .collect(),
+            previous_timestep_input: initial_input,
+            offset: initial_value,
+            delay,
+            response_vec: vec![],
+        })
+    }
+
+    /// sets the user input to some value
+    pub fn set_user_input_and_calc_output(&mut self,
+        current_time: Time,
+        current_input: Ratio)
+    -> Result<Ratio, ChemEngProcessControlSimulatorError> {
+
+        // case where input is not the same to 9 decimal places
+        let input_changed: bool =
+            (current_input.get::<ratio>() * 1e9).round()
+            - (self.previous_timestep_input
+                .get::<ratio>()*1e9).round() != 0.0 ;
+
+        if input_changed {
+            let user_input = current_input - self.previous_timestep_input;
+            let start_time = current_time + self.delay;
+            self.response_vec.push((start_time, user_input));
+            self.previous_timestep_input = current_input;
+        }
+
+        self.clear_response_vector(current_time);
+
+        let summation_of_responses: Ratio = self.response_vec.iter().map(
+            |&(start_time, user_input)| {
+                if current_time < start_time {
+                    return Ratio::ZERO;
+                }
+                user_input * self.unit_step_response(
+                    current_time - start_time)
+            }).sum();
+
+        Ok(self.offset + summation_of_responses)
+    }
+
+    /// response to a unit step at a time after the step
+    pub fn unit_step_response(&self, time_elapsed: Time) -> f64 {
+        let pole = self.pole();
+        let t: f64 = time_elapsed.get::<second>();
+
+        let mut response = Complex::new(0.0, 0.0);
+
+        if pole.norm() == 0.0 {
+            // f_k(t) = t^k / k!
+            let mut t_power_over_factorial: f64 = 1.0;
+            for (index, residue) in self.residues.iter().enumerate() {
+                let k = (index + 1) as f64;
+                t_power_over_factorial *= t/k;
+                response += Complex::new(residue.0, residue.1)
+                    * t_power_over_factorial;
+            }
+        } else {
+            let minus_p_t = -pole * t;
+            let exponential = (pole * t).exp();
+
+            // running sum of (-p t)^j / j!
+            let mut series = Complex::new(0.0, 0.0);
+            let mut series_term = Complex::new(1.0, 0.0);
+            // running value of 1/(-p)^k
+            let mut inverse_minus_p_power = Complex::new(1.0, 0.0);
+
+            for (index, residue) in self.residues.iter().enumerate() {
+                series += series_term;
+                series_term = series_term * minus_p_t / ((index + 1) as f64);
+                inverse_minus_p_power /= -pole;
+
+                let f_k = inverse_minus_p_power
+                    * (1.0 - exponential * series);
+                response += Complex::new(residue.0, residue.1) * f_k;
+            }
+        }
+
+        if self.is_conjugate_pair() {
+            2.0 * response.re
+        } else {
+            response.re
+        }
+    }
+
+    /// steady state value of the response to a unit step,
+    /// only meaningful for stable poles
+    ///
+    /// sum of A_k/(-p)^k
+    pub fn unit_step_steady_state_value(&self) -> f64 {
+        let pole = self.pole();
+        let mut inverse_minus_p_power = Complex::new(1.0, 0.0);
+        let mut steady_state_value = Complex::new(0.0, 0.0);
+
+        for residue in self.residues.iter() {
+            inverse_minus_p_power /= -pole;
+            steady_state_value += Complex::new(residue.0, residue.1)
+                * inverse_minus_p_power;
+        }
+
+        if self.is_conjugate_pair() {
+            2.0 * steady_state_value.re
+        } else {
+            steady_state_value.re
+        }
+    }
+
+    /// pole in SI units
+    pub fn pole(&self) -> Complex<f64> {
+        Complex::new(self.pole_real_part.get::<hertz>(),
+            self.pole_imaginary_part.get::<hertz>())
+    }
+
+    /// multiplicity of the pole
+    pub fn multiplicity(&self) -> usize {
+        self.residues.len()
+    }
+
+    #[inline]
+    fn is_conjugate_pair(&self) -> bool {
+        self.pole_imaginary_part.get::<hertz>() != 0.0
+    }
+
+    /// checks if a response has more or less reached
+    /// steady state,
+    ///
+    /// I consider this where the exponential has decayed to
+    /// exp(-20) (as with the other transfer functions), plus
+    /// some extra time for the t^k terms which multiply the
+    /// exponential
+    fn is_steady_state(&self, time_elapsed: Time) -> bool {
+        let decay_rate: f64 = -self.pole_real_part.get::<hertz>();
+
+        if decay_rate <= 0.0 {
+            return false;
+        }
+
+        let decay_ratio: f64 = decay_rate * time_elapsed.get::<second>();
+        let extra_ratio_for_polynomial_terms: f64 =
+            5.0 * self.multiplicity() as f64;
+
+        decay_ratio > 20.0 + extra_ratio_for_polynomial_terms
+    }
+
+    /// clears the responses which have reached steady state,
+    /// and adds their steady state value to the offset
+    fn clear_response_vector(&mut self, current_time: Time) {
+        let steady_state_value = self.unit_step_steady_state_value();
+        let mut cleared_offset = Ratio::ZERO;
+
+        let mut remaining_responses: Vec<(Time, Ratio)> = vec![];
+        for &(start_time, user_input) in self.response_vec.iter() {
+            if current_time > start_time &&
+                self.is_steady_state(current_time - start_time) {
+                cleared_offset += user_input * steady_state_value;
+            } else {
+                remaining_responses.push((start_time, user_input));
+            }
+        }
+
+        self.offset += cleared_offset;
+        self.response_vec = remaining_responses;
+    }
+}
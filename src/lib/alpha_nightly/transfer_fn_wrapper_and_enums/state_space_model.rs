@@ -0,0 +1,667 @@
+use csv::Writer;
+use uom::si::ratio::ratio;
+use uom::si::f64::*;
+use uom::si::time::second;
+use uom::ConstZero;
+
+use crate::alpha_nightly::TimeSquared;
+use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
+use crate::alpha_nightly::{matrices, polynomials};
+
+use super::{TransferFn, TransferFnFirstOrder, TransferFnSecondOrder, TransferFnTraits};
+
+/// single input single output state space model
+///
+/// dx/dt = A x + B u
+/// y = C x + D u
+///
+/// in deviation variables, ie. x = 0 and y = 0 when u = 0.
+///
+/// This is meant for linearised plant models, which come out of
+/// first principles codes as matrices rather than polynomials.
+/// Such codes may not work in seconds, so the constructor takes
+/// the time unit that A and B are written in (eg. per minute or
+/// per hour). Internally everything is stored in SI units.
+///
+/// The model is simulated with an exact zero order hold
+/// discretisation, ie. the input is held constant between calls,
+/// and over a timestep h the state moves as:
+///
+/// x(t + h) = exp(A h) x(t) + [integral_0^h exp(A tau) dtau] B u
+///
+/// Both matrices come from the exponential of the augmented matrix
+///
+/// | A h  B h |
+/// |  0    0  |
+///
+/// They are cached for the last few step sizes, so for a fixed
+/// timestep each call costs the same no matter how long the
+/// simulation has run.
+///
+/// The dead time is handled by holding the input changes until
+/// the dead time has passed.
+///
+/// Time cannot go backwards, if the time given is earlier than
+/// the time of the last call, the state is not moved.
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub struct StateSpaceModel {
+    /// A matrix, in SI units (per second)
+    pub(crate) state_matrix: Vec<Vec<f64>>,
+    /// B vector, in SI units (per second)
+    pub(crate) input_vector: Vec<f64>,
+    /// C vector
+    pub(crate) output_vector: Vec<f64>,
+    /// D, direct feedthrough
+    pub(crate) feedthrough: Ratio,
+    /// state vector x, in deviation variables
+    pub(crate) state: Vec<f64>,
+    /// time at which the state vector is valid
+    pub(crate) state_time: Time,
+    pub(crate) previous_timestep_input: Ratio,
+    /// input seen by the process after the dead time
+    pub(crate) delayed_input: Ratio,
+    /// previous timestep output
+    pub(crate) offset: Ratio,
+    /// dead time
+    pub(crate) dead_time: Time,
+    /// input changes waiting for the dead time to pass,
+    /// stored as (start time, input level after the change)
+    pub(crate) delayed_input_vec: Vec<(Time, Ratio)>,
+    /// discretised matrices for the most recent step sizes
+    pub(crate) step_cache: Vec<ZeroOrderHoldStep>,
+}
+
+/// discretised state space matrices for one step size
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub(crate) struct ZeroOrderHoldStep {
+    pub(crate) step_size: Time,
+    /// exp(A h)
+    pub(crate) state_transition_matrix: Vec<Vec<f64>>,
+    /// integral_0^h exp(A tau) dtau B
+    pub(crate) input_vector: Vec<f64>,
+}
+
+/// how many step sizes are kept in the cache,
+/// a fixed timestep with a dead time that is not a multiple of
+/// the timestep needs about three
+pub(crate) const STEP_CACHE_SIZE: usize = 4;
+
+/// step sizes closer than this (relative) share the same matrices,
+/// so that rounding errors in the simulation time do not force
+/// a new matrix exponential every timestep
+const STEP_SIZE_RELATIVE_TOLERANCE: f64 = 1e-9;
+
+impl Default for StateSpaceModel {
+    /// default is:
+    ///
+    /// dx/dt = -x + u, y = x
+    ///
+    /// which is 1 / (s + 1)
+    fn default() -> Self {
+        Self::new(vec![vec![-1.0]], vec![1.0], vec![1.0],
+            Ratio::ZERO, Time::new::<second>(1.0)).unwrap()
+    }
+}
+
+impl TransferFnTraits for StateSpaceModel {
+    fn set_dead_time(&mut self, dead_time: Time) {
+        self.dead_time = dead_time;
+    }
+
+    fn set_user_input_and_calc(&mut self, user_input: Ratio,
+        time: Time) ->
+    Result<Ratio, ChemEngProcessControlSimulatorError> {
+
+        // case where input is not the same to 9 decimal places
+        let input_changed: bool =
+            (user_input.get::<ratio>() * 1e9).round()
+            - (self.previous_timestep_input
+                .get::<ratio>()*1e9).round() != 0.0 ;
+
+        if input_changed {
+            let start_time = time + self.dead_time;
+            self.delayed_input_vec.push((start_time, user_input));
+            self.previous_timestep_input = user_input;
+        }
+
+        // move the state up to each input change whose dead time
+        // has passed, then apply the change
+        while let Some(&(start_time, input)) = self.delayed_input_vec.first() {
+            if start_time > time {
+                break;
+            }
+            self.advance_state(start_time)?;
+            self.delayed_input = input;
+            self.delayed_input_vec.remove(0);
+        }
+
+        self.advance_state(time)?;
+
+        let state_response: f64 = self.output_vector.iter()
+            .zip(self.state.iter())
+            .map(|(c, x)| c * x).sum();
+
+        Ok(self.offset + Ratio::new::<ratio>(state_response)
+            + self.feedthrough * self.delayed_input)
+    }
+
+    fn spawn_writer(&mut self, name: String) -> Result<Writer<std::fs::File>,
+    ChemEngProcessControlSimulatorError>{
+        let title_string: String = name + "state_space_model.csv";
+        let wtr = Writer::from_path(title_string)?;
+        Ok(wtr)
+    }
+
+    fn csv_write_values(&mut self,
+        wtr: &mut Writer<std::fs::File>,
+        time: Time,
+        input: Ratio,
+        output: Ratio) -> Result<(),
+    ChemEngProcessControlSimulatorError> {
+
+        let current_time_string = time.get::<second>().to_string();
+        let input_string = input.get::<ratio>().to_string();
+        let output_string = output.get::<ratio>().to_string();
+
+        wtr.write_record(&[current_time_string,
+            input_string,
+            output_string])?;
+
+        wtr.flush().unwrap();
+
+        Ok(())
+    }
+}
+
+impl StateSpaceModel {
+
+    /// constructor from the A, B, C and D matrices
+    ///
+    /// A and B are written per time_unit, eg. if the linearised
+    /// model is in minutes, A = [[-0.5]] and time_unit = 1 min
+    /// means a time constant of 2 minutes.
+    ///
+    /// C and D are dimensionless.
+    pub fn new(state_matrix: Vec<Vec<f64>>,
+        input_vector: Vec<f64>,
+        output_vector: Vec<f64>,
+        feedthrough: Ratio,
+        time_unit: Time) -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        let order = state_matrix.len();
+
+        if state_matrix.iter().any(|row| row.len() != order)
+            || input_vector.len() != order
+            || output_vector.len() != order {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "A must be n by n, B and C must have n elements"
+                    .to_owned()));
+        }
+
+        let time_unit_seconds: f64 = time_unit.get::<second>();
+        if time_unit_seconds <= 0.0 || !time_unit_seconds.is_finite() {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError("time unit must be positive".to_owned()));
+        }
+
+        Ok(Self {
+            state_matrix: matrices::scale(&state_matrix, 1.0/time_unit_seconds),
+            input_vector: input_vector.iter()
+                .map(|b| b/time_unit_seconds).collect(),
+            output_vector,
+            feedthrough,
+            state: vec![0.0; order],
+            state_time: Time::ZERO,
+            previous_timestep_input: Ratio::ZERO,
+            delayed_input: Ratio::ZERO,
+            offset: Ratio::ZERO,
+            dead_time: Time::ZERO,
+            delayed_input_vec: vec![],
+            step_cache: vec![],
+        })
+    }
+
+    /// controllable canonical form of
+    ///
+    /// G(s) =
+    ///
+    /// b_m s^m + ... + b_1 s + b_0
+    /// ---------------------------
+    /// a_n s^n + ... + a_1 s + a_0
+    ///
+    /// numerator is [b_m, ... b_1, b_0],
+    /// denominator is [a_n, ... a_1, a_0],
+    /// with coefficients of s^k in seconds^k
+    ///
+    /// x_1 = U/D(s), x_(k+1) = s^k x_1
+    ///
+    /// so the last row of A is -a_0, -a_1 ... -a_(n-1) (for a monic
+    /// denominator), B is the last unit vector and C holds the
+    /// numerator coefficients in ascending powers of s
+    pub fn new_controllable_canonical(numerator: Vec<f64>,
+        denominator: Vec<f64>) -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        let (monic_denominator, strictly_proper_numerator, feedthrough) =
+            Self::split_transfer_fn(numerator, denominator)?;
+        let order = monic_denominator.len() - 1;
+
+        let mut state_matrix = matrices::zeroes(order, order);
+        for i in 0..order.saturating_sub(1) {
+            state_matrix[i][i+1] = 1.0;
+        }
+        if order > 0 {
+            for j in 0..order {
+                state_matrix[order - 1][j] = -monic_denominator[order - j];
+            }
+        }
+
+        let mut input_vector = vec![0.0; order];
+        if order > 0 {
+            input_vector[order - 1] = 1.0;
+        }
+
+        let output_vector: Vec<f64> = strictly_proper_numerator.iter()
+            .rev().cloned().collect();
+
+        Self::new(state_matrix, input_vector, output_vector,
+            Ratio::new::<ratio>(feedthrough), Time::new::<second>(1.0))
+    }
+
+    /// observable canonical form of the same transfer function
+    /// as new_controllable_canonical
+    ///
+    /// this is the transpose of the controllable canonical form,
+    /// the last column of A is -a_0, -a_1 ... -a_(n-1),
+    /// B holds the numerator coefficients in ascending powers of s
+    /// and C is the last unit vector
+    pub fn new_observable_canonical(numerator: Vec<f64>,
+        denominator: Vec<f64>) -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        let controllable_form = Self::new_controllable_canonical(
+            numerator, denominator)?;
+        let order = controllable_form.state.len();
+
+        let mut state_matrix = matrices::zeroes(order, order);
+        for (i, row) in state_matrix.iter_mut().enumerate() {
+            for (j, element) in row.iter_mut().enumerate() {
+                *element = controllable_form.state_matrix[j][i];
+            }
+        }
+
+        Self::new(state_matrix,
+            controllable_form.output_vector,
+            controllable_form.input_vector,
+            controllable_form.feedthrough, Time::new::<second>(1.0))
+    }
+
+    /// makes the denominator monic and takes out the direct
+    /// feedthrough, returns (monic denominator, strictly proper
+    /// numerator, feedthrough)
+    ///
+    /// the strictly proper numerator always has n coefficients
+    fn split_transfer_fn(numerator: Vec<f64>,
+        denominator: Vec<f64>)
+    -> Result<(Vec<f64>, Vec<f64>, f64), ChemEngProcessControlSimulatorError> {
+
+        let numerator = polynomials::trim_leading_zeroes(&numerator);
+        let denominator = polynomials::trim_leading_zeroes(&denominator);
+
+        if denominator == vec![0.0] {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError("denominator is zero".to_owned()));
+        }
+
+        if numerator.len() > denominator.len() {
+            return Err(ChemEngProcessControlSimulatorError::
+                ImproperTransferFunction);
+        }
+
+        // s^n + a_(n-1) s^(n-1) + ... + a_0
+        let monic_denominator = polynomials::scale(&denominator,
+            1.0/denominator[0]);
+        let scaled_numerator = polynomials::scale(&numerator,
+            1.0/denominator[0]);
+
+        // G(s) = D + N'(s)/D(s)
+        let mut feedthrough: f64 = 0.0;
+        let mut strictly_proper_numerator = polynomials::add(
+            &scaled_numerator, &vec![0.0; denominator.len()]);
+        if numerator.len() == denominator.len() {
+            feedthrough = scaled_numerator[0];
+            strictly_proper_numerator = polynomials::add(&scaled_numerator,
+                &polynomials::scale(&monic_denominator, -feedthrough));
+        }
+        strictly_proper_numerator.remove(0);
+
+        Ok((monic_denominator, strictly_proper_numerator, feedthrough))
+    }
+
+    /// transfer function of the state space model,
+    ///
+    /// G(s) = C (sI - A)^-1 B + D
+    ///
+    /// returned as (numerator, denominator) in descending powers of
+    /// s with coefficients of s^k in seconds^k. The denominator is
+    /// the characteristic polynomial of A, so it is monic and
+    /// nothing is cancelled out.
+    ///
+    /// I'm using the Faddeev-LeVerrier method, which gives both the
+    /// characteristic polynomial and the adjugate of (sI - A):
+    ///
+    /// M_1 = I, c_(n-1) = -tr(A M_1)
+    /// M_k = A M_(k-1) + c_(n-k+1) I, c_(n-k) = -tr(A M_k)/k
+    ///
+    /// adj(sI - A) = sum_k M_k s^(n-k)
+    pub fn transfer_fn_coefficients(&self) -> (Vec<f64>, Vec<f64>) {
+        let order = self.state.len();
+        let identity = matrices::identity(order);
+        let feedthrough: f64 = self.feedthrough.get::<ratio>();
+
+        let mut characteristic_polynomial = vec![1.0];
+        let mut adjugate_numerator = vec![0.0];
+        let mut m_k = identity.clone();
+
+        for k in 1..=order {
+            if k > 1 {
+                let previous_coefficient = characteristic_polynomial[k - 1];
+                m_k = matrices::add(
+                    &matrices::multiply(&self.state_matrix, &m_k),
+                    &matrices::scale(&identity, previous_coefficient));
+            }
+            // C M_k B
+            let c_m_k_b: f64 = self.output_vector.iter()
+                .zip(matrices::multiply_vector(&m_k, &self.input_vector))
+                .map(|(c, m_k_b)| c * m_k_b).sum();
+            adjugate_numerator.push(c_m_k_b);
+
+            let a_m_k = matrices::multiply(&self.state_matrix, &m_k);
+            let trace: f64 = (0..order).map(|i| a_m_k[i][i]).sum();
+            characteristic_polynomial.push(-trace/(k as f64));
+        }
+
+        let numerator = polynomials::add(&adjugate_numerator,
+            &polynomials::scale(&characteristic_polynomial, feedthrough));
+
+        (numerator, characteristic_polynomial)
+    }
+
+    /// A matrix, written per time_unit
+    pub fn state_matrix(&self, time_unit: Time) -> Vec<Vec<f64>> {
+        matrices::scale(&self.state_matrix, time_unit.get::<second>())
+    }
+
+    /// B vector, written per time_unit
+    pub fn input_vector(&self, time_unit: Time) -> Vec<f64> {
+        self.input_vector.iter()
+            .map(|b| b * time_unit.get::<second>()).collect()
+    }
+
+    /// C vector
+    pub fn output_vector(&self) -> Vec<f64> {
+        self.output_vector.clone()
+    }
+
+    /// D
+    pub fn feedthrough(&self) -> Ratio {
+        self.feedthrough
+    }
+
+    /// dead time
+    pub fn dead_time(&self) -> Time {
+        self.dead_time
+    }
+
+    /// moves the state vector from state_time to end_time,
+    /// holding the delayed input constant
+    fn advance_state(&mut self, end_time: Time)
+    -> Result<(), ChemEngProcessControlSimulatorError> {
+
+        let step_size: Time = end_time - self.state_time;
+        if step_size <= Time::ZERO {
+            return Ok(());
+        }
+
+        // nothing moves if the process is at rest, this also avoids
+        // multiplying zero by a huge exponential for unstable
+        // processes if the first call is at a large time
+        let input: f64 = self.delayed_input.get::<ratio>();
+        let at_rest: bool = input == 0.0
+            && self.state.iter().all(|x| *x == 0.0);
+
+        if !at_rest && !self.state.is_empty() {
+            let step = self.zero_order_hold_step(step_size)?;
+
+            let mut next_state = matrices::multiply_vector(
+                &step.state_transition_matrix, &self.state);
+            for (x, gamma) in next_state.iter_mut()
+                .zip(step.input_vector.iter()) {
+                *x += gamma * input;
+            }
+            self.state = next_state;
+        }
+
+        self.state_time = end_time;
+        Ok(())
+    }
+
+    /// gets the discretised matrices for a step size from the cache,
+    /// or works them out if they are not there
+    fn zero_order_hold_step(&mut self, step_size: Time)
+    -> Result<ZeroOrderHoldStep, ChemEngProcessControlSimulatorError> {
+
+        let h: f64 = step_size.get::<second>();
+
+        let cached_step = self.step_cache.iter().find(|cached_step| {
+            let cached_h: f64 = cached_step.step_size.get::<second>();
+            (cached_h - h).abs() <= STEP_SIZE_RELATIVE_TOLERANCE * h
+        });
+
+        if let Some(cached_step) = cached_step {
+            return Ok(cached_step.clone());
+        }
+
+        // exponential of the augmented matrix
+        //
+        // | A h  B h |
+        // |  0    0  |
+        let order = self.state.len();
+        let mut augmented_matrix = matrices::zeroes(order + 1, order + 1);
+        for ((augmented_row, row), b) in augmented_matrix.iter_mut()
+            .zip(self.state_matrix.iter())
+            .zip(self.input_vector.iter()) {
+            for (augmented_element, element) in augmented_row.iter_mut()
+                .zip(row.iter()) {
+                *augmented_element = element * h;
+            }
+            augmented_row[order] = b * h;
+        }
+
+        let augmented_exponential = matrices::exponential(&augmented_matrix)?;
+
+        let state_transition_matrix: Vec<Vec<f64>> = augmented_exponential
+            .iter().take(order)
+            .map(|row| row[..order].to_vec())
+            .collect();
+        let input_vector: Vec<f64> = augmented_exponential
+            .iter().take(order)
+            .map(|row| row[order])
+            .collect();
+
+        let step = ZeroOrderHoldStep {
+            step_size,
+            state_transition_matrix,
+            input_vector,
+        };
+
+        if self.step_cache.len() >= STEP_CACHE_SIZE {
+            self.step_cache.remove(0);
+        }
+        self.step_cache.push(step.clone());
+
+        Ok(step)
+    }
+}
+
+impl From<StateSpaceModel> for TransferFn {
+    fn from(transfer_fn: StateSpaceModel) -> Self {
+        TransferFn::StateSpace(transfer_fn)
+    }
+}
+
+impl TryFrom<TransferFn> for StateSpaceModel {
+    type Error = ChemEngProcessControlSimulatorError;
+    fn try_from(generic_transfer_function: TransferFn)
+    -> Result<Self, Self::Error> {
+
+        if let TransferFn::StateSpace(
+        state_space_model) = generic_transfer_function {
+            Ok(state_space_model)
+        } else {
+            Err(ChemEngProcessControlSimulatorError::WrongTransferFnType)
+        }
+    }
+}
+
+impl TryFrom<TransferFnFirstOrder> for StateSpaceModel {
+    type Error = ChemEngProcessControlSimulatorError;
+    /// controllable canonical form of the first order transfer
+    /// function, the dead time is carried over
+    fn try_from(first_order: TransferFnFirstOrder)
+    -> Result<Self, Self::Error> {
+        let mut state_space_model = Self::new_controllable_canonical(
+            first_order.numerator(), first_order.denominator())?;
+        state_space_model.set_dead_time(first_order.dead_time());
+        Ok(state_space_model)
+    }
+}
+
+impl TryFrom<TransferFnSecondOrder> for StateSpaceModel {
+    type Error = ChemEngProcessControlSimulatorError;
+    /// controllable canonical form of the second order transfer
+    /// function, the dead time is carried over
+    fn try_from(second_order: TransferFnSecondOrder)
+    -> Result<Self, Self::Error> {
+        let mut state_space_model = Self::new_controllable_canonical(
+            second_order.numerator(), second_order.denominator())?;
+        state_space_model.set_dead_time(second_order.dead_time());
+        Ok(state_space_model)
+    }
+}
+
+impl TryFrom<StateSpaceModel> for TransferFnFirstOrder {
+    type Error = ChemEngProcessControlSimulatorError;
+    /// only works for models with one state,
+    /// the dead time is carried over
+    fn try_from(state_space_model: StateSpaceModel)
+    -> Result<Self, Self::Error> {
+
+        if state_space_model.state.len() != 1 {
+            return Err(ChemEngProcessControlSimulatorError::
+                WrongTransferFnType);
+        }
+        let (numerator, denominator) =
+            state_space_model.transfer_fn_coefficients();
+
+        let mut first_order = TransferFnFirstOrder::new(
+            Time::new::<second>(numerator[0]),
+            Ratio::new::<ratio>(numerator[1]),
+            Time::new::<second>(denominator[0]),
+            Ratio::new::<ratio>(denominator[1]))?;
+        first_order.set_dead_time(state_space_model.dead_time);
+        Ok(first_order)
+    }
+}
+
+impl TryFrom<StateSpaceModel> for TransferFnSecondOrder {
+    type Error = ChemEngProcessControlSimulatorError;
+    /// only works for models with two states,
+    /// the dead time is carried over
+    fn try_from(state_space_model: StateSpaceModel)
+    -> Result<Self, Self::Error> {
+
+        if state_space_model.state.len() != 2 {
+            return Err(ChemEngProcessControlSimulatorError::
+                WrongTransferFnType);
+        }
+        let (numerator, denominator) =
+            state_space_model.transfer_fn_coefficients();
+
+        let one_second = Time::new::<second>(1.0);
+        let one_second_squared: TimeSquared = one_second * one_second;
+
+        let mut second_order = TransferFnSecondOrder::new(
+            one_second_squared * numerator[0],
+            one_second * numerator[1],
+            Ratio::new::<ratio>(numerator[2]),
+            one_second_squared * denominator[0],
+            one_second * denominator[1],
+            Ratio::new::<ratio>(denominator[2]))?;
+        second_order.set_dead_time(state_space_model.dead_time);
+        Ok(second_order)
+    }
+}
+
+#[test]
+pub fn test_state_space_conversions(){
+    use uom::si::time::minute;
+
+    // linearised tank model in minutes
+    //
+    // dx/dt = -0.5 x + 0.25 u, y = 2 x
+    //
+    // G(s) = 0.5/(s + 0.5) per minute
+    // = 1/(120 s + 1) in seconds
+    let tank_model = StateSpaceModel::new(vec![vec![-0.5]], vec![0.25],
+        vec![2.0], Ratio::ZERO, Time::new::<minute>(1.0)).unwrap();
+    let tank_transfer_fn: TransferFnFirstOrder = tank_model.try_into().unwrap();
+    let numerator = tank_transfer_fn.numerator();
+    let denominator = tank_transfer_fn.denominator();
+    approx::assert_relative_eq!(numerator[1]/denominator[1], 1.0,
+        max_relative = 1e-12);
+    approx::assert_relative_eq!(denominator[0]/denominator[1], 120.0,
+        max_relative = 1e-12);
+
+    // each kind of second order system should come back the same
+    // after going to state space and back
+    //
+    // (a1 s^2 + b1 s + c1)/(s^2 + b2 s + c2)
+    let one_second = Time::new::<second>(1.0);
+    let one_second_squared: TimeSquared = one_second * one_second;
+    let second_order_coefficients = [
+        // underdamped
+        [0.5, 2.0, 1.0, 1.0, 0.3, 0.25],
+        // critically damped
+        [0.5, 2.0, 1.0, 1.0, 1.0, 0.25],
+        // overdamped
+        [0.5, 2.0, 1.0, 1.0, 3.0, 2.0],
+        // unstable
+        [0.0, 1.0, 3.0, 1.0, -0.4, 1.0],
+        // undamped
+        [0.0, 0.0, 4.0, 1.0, 0.0, 4.0],
+    ];
+
+    for coefficients in second_order_coefficients {
+        let second_order = TransferFnSecondOrder::new(
+            one_second_squared * coefficients[0],
+            one_second * coefficients[1],
+            Ratio::new::<ratio>(coefficients[2]),
+            one_second_squared * coefficients[3],
+            one_second * coefficients[4],
+            Ratio::new::<ratio>(coefficients[5])).unwrap();
+
+        let mut observable_form = StateSpaceModel::new_observable_canonical(
+            second_order.numerator(), second_order.denominator()).unwrap();
+        observable_form.set_dead_time(Time::new::<second>(0.5));
+        let round_trip: TransferFnSecondOrder = observable_form
+            .try_into().unwrap();
+
+        let mut round_trip_coefficients = round_trip.numerator();
+        round_trip_coefficients.extend(round_trip.denominator());
+        for (expected, actual) in coefficients.iter()
+            .zip(round_trip_coefficients.iter()) {
+            approx::assert_abs_diff_eq!(*expected, *actual, epsilon = 1e-9);
+        }
+        assert_eq!(round_trip.dead_time(), Time::new::<second>(0.5));
+    }
+}
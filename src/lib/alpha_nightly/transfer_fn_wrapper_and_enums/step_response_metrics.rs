@@ -0,0 +1,224 @@
+use num_complex::Complex;
+use uom::si::f64::*;
+use uom::si::ratio::ratio;
+use uom::si::time::second;
+
+use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
+use crate::alpha_nightly::numerical_search::{bisect, golden_section_maximum};
+use crate::alpha_nightly::polynomials;
+
+/// step response characteristics of a stable transfer function,
+/// worked out from its poles and zeros rather than by simulation
+///
+/// for a unit step at t = 0, with y normalised by the
+/// steady state gain so that it settles at 1:
+#[derive(Debug,PartialEq, PartialOrd, Clone, Copy)]
+pub struct StepResponseMetrics {
+    /// time taken to go from 10% to 90% of the final value
+    pub rise_time: Time,
+    /// (peak - final value)/final value, zero if the response
+    /// never goes past the final value
+    pub overshoot: Ratio,
+    /// ratio of successive overshoots, exp(-2 pi zeta/sqrt(1 - zeta^2))
+    /// for complex poles, zero if the response does not oscillate
+    pub decay_ratio: Ratio,
+    /// time after the step (including the dead time) after which
+    /// the response stays within 5% of the final value
+    pub settling_time: Time,
+    /// true if there is a zero in the right half plane, the
+    /// response then starts off going the wrong way
+    pub inverse_response: bool,
+}
+
+/// settling band for the settling time, +/- 5%
+const SETTLING_BAND: f64 = 0.05;
+
+/// roots of a polynomial, or no roots if it is a constant
+/// (including zero)
+pub(crate) fn polynomial_roots(polynomial: &[f64])
+-> Result<Vec<Complex<f64>>, ChemEngProcessControlSimulatorError> {
+    let polynomial = polynomials::trim_leading_zeroes(polynomial);
+    if polynomial.len() == 1 {
+        return Ok(vec![]);
+    }
+    polynomials::roots(&polynomial)
+}
+
+/// steady state gain G(0) = N(0)/D(0),
+///
+/// returns an error if there is a pole at s = 0 
+/// (integrating process) as the gain is infinite
+pub(crate) fn steady_state_gain(numerator: &[f64], denominator: &[f64])
+-> Result<Ratio, ChemEngProcessControlSimulatorError> {
+
+    let numerator_constant: f64 = *numerator.last().unwrap_or(&0.0);
+    let denominator_constant: f64 = *denominator.last().unwrap_or(&0.0);
+
+    if denominator_constant == 0.0 {
+        return Err(ChemEngProcessControlSimulatorError::
+            GenericStringError(
+                "pole at s = 0, steady state gain is infinite"
+                .to_owned()));
+    }
+
+    Ok(Ratio::new::<ratio>(numerator_constant/denominator_constant))
+}
+
+/// step response metrics for a transfer function with at most
+/// double poles (which covers first and second order systems)
+///
+/// the unit step response is
+///
+/// Y(s) = N(s)/(s D(s))
+///
+/// y(t) = K + sum of (a + b t) exp(p t)
+///
+/// where K = N(0)/D(0) and the sum is over the poles p of D(s).
+/// For a simple pole, a = N(p)/(p D'(p)) and b = 0.
+/// For a double pole, D(s) = d (s - p)^2, b = N(p)/(d p) and
+/// a = (N'(p) p - N(p))/(d p^2).
+///
+/// the rise and settling times need the crossing points of y(t),
+/// which are found by bisection.
+pub(crate) fn step_response_metrics(numerator: &[f64],
+    denominator: &[f64],
+    dead_time: Time) -> Result<StepResponseMetrics,
+ChemEngProcessControlSimulatorError> {
+
+    let numerator = polynomials::trim_leading_zeroes(numerator);
+    let denominator = polynomials::trim_leading_zeroes(denominator);
+
+    let final_value: f64 = numerator.last().unwrap_or(&0.0)
+        /denominator.last().unwrap_or(&0.0);
+
+    let poles = polynomials::distinct_roots(&denominator)?;
+
+    if poles.iter().any(|(pole, _)| pole.re >= 0.0)
+        || !final_value.is_finite()
+        || final_value == 0.0 {
+        return Err(ChemEngProcessControlSimulatorError::
+            GenericStringError(
+                "step response metrics need a stable transfer function \
+                with a non zero steady state gain".to_owned()));
+    }
+
+    let numerator_derivative = polynomials::derivative(&numerator);
+    let denominator_derivative = polynomials::derivative(&denominator);
+    let leading_coefficient: f64 = denominator[0];
+
+    // (pole, a, b) for each pole, (a + b t) exp(p t)
+    let mut terms: Vec<(Complex<f64>, Complex<f64>, Complex<f64>)> = vec![];
+    for (pole, multiplicity) in poles.iter() {
+        let numerator_at_pole = polynomials::evaluate(&numerator, *pole);
+        match multiplicity {
+            1 => {
+                let a = numerator_at_pole/(pole
+                    * polynomials::evaluate(&denominator_derivative, *pole));
+                terms.push((*pole, a, Complex::new(0.0, 0.0)));
+            },
+            2 => {
+                let b = numerator_at_pole/(leading_coefficient * pole);
+                let a = (polynomials::evaluate(&numerator_derivative, *pole)
+                    * pole - numerator_at_pole)
+                    /(leading_coefficient * pole * pole);
+                terms.push((*pole, a, b));
+            },
+            _ => {
+                return Err(ChemEngProcessControlSimulatorError::
+                    GenericStringError(
+                        "step response metrics only handle up to double poles"
+                        .to_owned()));
+            },
+        }
+    }
+
+    // y(t)/K
+    let normalised_response = |t: f64| -> f64 {
+        let transient: Complex<f64> = terms.iter()
+            .map(|(pole, a, b)| (a + b * t) * (pole * t).exp())
+            .sum();
+        1.0 + transient.re/final_value
+    };
+
+    // upper bound on |y(t)/K - 1|, used to decide how far to look
+    let envelope = |t: f64| -> f64 {
+        terms.iter()
+            .map(|(pole, a, b)| (a.norm() + b.norm() * t) * (pole.re * t).exp())
+            .sum::<f64>()/final_value.abs()
+    };
+
+    let slowest_rate: f64 = poles.iter()
+        .map(|(pole, _)| pole.re.abs()).fold(f64::MAX, f64::min);
+    let fastest_rate: f64 = poles.iter()
+        .map(|(pole, _)| pole.norm()).fold(0.0_f64, f64::max);
+
+    let mut horizon: f64 = 1.0/slowest_rate;
+    while envelope(horizon) > 0.2 * SETTLING_BAND {
+        horizon *= 2.0;
+    }
+
+    let number_of_steps: usize = ((horizon * fastest_rate * 50.0).ceil() as usize)
+        .clamp(1000, 200000);
+    let timestep: f64 = horizon/number_of_steps as f64;
+    let times: Vec<f64> = (0..=number_of_steps)
+        .map(|i| i as f64 * timestep).collect();
+    let response: Vec<f64> = times.iter()
+        .map(|t| normalised_response(*t)).collect();
+
+    // first time y/K reaches a level, refined by bisection
+    let first_crossing = |level: f64| -> f64 {
+        let index = response.iter().position(|value| *value >= level)
+            .unwrap_or(number_of_steps);
+        if index == 0 {
+            return 0.0;
+        }
+        bisect(times[index - 1], times[index],
+            |t| normalised_response(t) - level)
+    };
+    let rise_time: f64 = first_crossing(0.9) - first_crossing(0.1);
+
+    // largest value of y/K, refined between the neighbouring
+    // grid points
+    let peak_index = response.iter().enumerate()
+        .fold(0, |best, (i, value)| {
+            if *value > response[best] { i } else { best }
+        });
+    let peak_value: f64 = if peak_index == 0 || peak_index == number_of_steps {
+        response[peak_index]
+    } else {
+        let peak_time = golden_section_maximum(times[peak_index - 1],
+            times[peak_index + 1], normalised_response);
+        normalised_response(peak_time)
+    };
+    let overshoot: f64 = (peak_value - 1.0).max(0.0);
+
+    // complex poles, the oscillations decay by exp(-2 pi sigma/omega)
+    // every period
+    let decay_ratio: f64 = poles.iter()
+        .filter(|(pole, _)| pole.im != 0.0)
+        .map(|(pole, _)| (-2.0 * std::f64::consts::PI * pole.re.abs()
+                /pole.im.abs()).exp())
+        .fold(0.0_f64, f64::max);
+
+    // last time the response is outside the settling band
+    let settling_time: f64 = match response.iter()
+        .rposition(|value| (value - 1.0).abs() > SETTLING_BAND) {
+        Some(index) if index < number_of_steps => {
+            bisect(times[index], times[index + 1],
+                |t| (normalised_response(t) - 1.0).abs() - SETTLING_BAND)
+        },
+        Some(_) => horizon,
+        None => 0.0,
+    };
+
+    let inverse_response = polynomial_roots(&numerator)?
+        .iter().any(|zero| zero.re > 0.0);
+
+    Ok(StepResponseMetrics {
+        rise_time: Time::new::<second>(rise_time),
+        overshoot: Ratio::new::<ratio>(overshoot),
+        decay_ratio: Ratio::new::<ratio>(decay_ratio),
+        settling_time: Time::new::<second>(settling_time) + dead_time,
+        inverse_response,
+    })
+}
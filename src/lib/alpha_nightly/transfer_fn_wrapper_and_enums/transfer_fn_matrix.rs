@@ -0,0 +1,296 @@
+use csv::Writer;
+use uom::si::ratio::ratio;
+use uom::si::f64::*;
+use uom::si::time::second;
+use uom::ConstZero;
+
+use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
+use crate::alpha_nightly::matrices;
+
+use super::{TransferFn, TransferFnTraits};
+
+/// a multiple input multiple output process, written as a matrix
+/// of transfer functions
+///
+/// y_i = sum_j G_ij(s) u_j
+///
+/// row i is output i, column j is input j. For example, a
+/// distillation column with reflux and boilup as inputs and top
+/// and bottom compositions as outputs:
+///
+/// | x_D |   | G_11  G_12 | | reflux |
+/// |     | = |            | |        |
+/// | x_B |   | G_21  G_22 | | boilup |
+///
+/// Each entry is its own TransferFn, with its own dead time.
+///
+/// For pairing decisions, the steady state gain matrix K = G(0)
+/// is used for the relative gain array and Niederlinski index.
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub struct TransferFnMatrix {
+    /// transfer functions, entries[output][input]
+    pub(crate) entries: Vec<Vec<TransferFn>>,
+}
+
+impl TransferFnMatrix {
+
+    /// constructor, entries[output][input]
+    ///
+    /// every row must have the same number of entries
+    pub fn new(entries: Vec<Vec<TransferFn>>)
+    -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        let number_of_inputs = entries.first().map_or(0, |row| row.len());
+
+        if number_of_inputs == 0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "transfer function matrix is empty".to_owned()));
+        }
+
+        if entries.iter().any(|row| row.len() != number_of_inputs) {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "every row must have the same number of inputs"
+                    .to_owned()));
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// number of outputs (rows)
+    pub fn number_of_outputs(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// number of inputs (columns)
+    pub fn number_of_inputs(&self) -> usize {
+        self.entries[0].len()
+    }
+
+    /// transfer function from input to output
+    pub fn entry(&self, output_index: usize, input_index: usize)
+    -> &TransferFn {
+        &self.entries[output_index][input_index]
+    }
+
+    /// transfer function from input to output,
+    /// eg. to change it mid simulation
+    pub fn entry_mut(&mut self, output_index: usize, input_index: usize)
+    -> &mut TransferFn {
+        &mut self.entries[output_index][input_index]
+    }
+
+    /// sets the dead time of one entry
+    pub fn set_dead_time(&mut self, output_index: usize,
+        input_index: usize,
+        dead_time: Time) {
+        self.entries[output_index][input_index].set_dead_time(dead_time);
+    }
+
+    /// sets all the inputs at once, and returns all the outputs
+    pub fn set_user_inputs_and_calc(&mut self,
+        user_inputs: &[Ratio],
+        time: Time) -> Result<Vec<Ratio>, ChemEngProcessControlSimulatorError> {
+
+        if user_inputs.len() != self.number_of_inputs() {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(format!(
+                    "expected {} inputs, got {}",
+                    self.number_of_inputs(), user_inputs.len())));
+        }
+
+        let mut outputs: Vec<Ratio> = vec![];
+
+        for row in self.entries.iter_mut() {
+            let mut output = Ratio::ZERO;
+            for (transfer_fn, user_input) in row.iter_mut()
+                .zip(user_inputs.iter()) {
+                output += transfer_fn.set_user_input_and_calc(
+                    *user_input, time)?;
+            }
+            outputs.push(output);
+        }
+
+        Ok(outputs)
+    }
+
+    /// steady state gain matrix K = G(0),
+    /// K[output][input]
+    pub fn steady_state_gain_matrix(&self)
+    -> Result<Vec<Vec<f64>>, ChemEngProcessControlSimulatorError> {
+
+        let mut gain_matrix: Vec<Vec<f64>> = vec![];
+        for row in self.entries.iter() {
+            let mut gain_row: Vec<f64> = vec![];
+            for transfer_fn in row.iter() {
+                gain_row.push(transfer_fn.steady_state_gain()?
+                    .get::<ratio>());
+            }
+            gain_matrix.push(gain_row);
+        }
+
+        Ok(gain_matrix)
+    }
+
+    /// relative gain array (Bristol)
+    ///
+    /// lambda_ij = K_ij (K^-1)_ji
+    ///
+    /// which is the open loop gain from u_j to y_i divided by the
+    /// same gain with all other loops closed. Pair inputs and outputs
+    /// with relative gains close to 1, and avoid negative ones.
+    ///
+    /// only works for square systems
+    pub fn relative_gain_array(&self)
+    -> Result<Vec<Vec<f64>>, ChemEngProcessControlSimulatorError> {
+
+        let gain_matrix = self.steady_state_gain_matrix()?;
+        let inverse_gain_matrix = matrices::inverse(&gain_matrix)?;
+
+        let relative_gain_array = gain_matrix.iter().enumerate()
+            .map(|(i, gain_row)| {
+                gain_row.iter().enumerate()
+                    .map(|(j, gain)| gain * inverse_gain_matrix[j][i])
+                    .collect()
+            }).collect();
+
+        Ok(relative_gain_array)
+    }
+
+    /// Niederlinski index for the diagonal pairing
+    /// (output i with input i)
+    ///
+    /// NI = det(K) / product of K_ii
+    ///
+    /// if NI is negative, the closed loop system is unstable for
+    /// that pairing once integral action is used in all loops.
+    /// (this is sufficient, and for 2 by 2 systems also necessary)
+    pub fn niederlinski_index(&self)
+    -> Result<f64, ChemEngProcessControlSimulatorError> {
+        let diagonal_pairing: Vec<usize> =
+            (0..self.number_of_outputs()).collect();
+        self.niederlinski_index_for_pairing(&diagonal_pairing)
+    }
+
+    /// Niederlinski index for another pairing,
+    /// pairing[i] is the input paired with output i
+    pub fn niederlinski_index_for_pairing(&self, pairing: &[usize])
+    -> Result<f64, ChemEngProcessControlSimulatorError> {
+
+        let size = self.number_of_outputs();
+        let mut sorted_pairing = pairing.to_vec();
+        sorted_pairing.sort_unstable();
+        sorted_pairing.dedup();
+
+        if self.number_of_inputs() != size
+            || pairing.len() != size
+            || sorted_pairing.len() != size
+            || sorted_pairing.iter().any(|input_index| *input_index >= size) {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "pairing must match each output with a different input"
+                    .to_owned()));
+        }
+
+        let gain_matrix = self.steady_state_gain_matrix()?;
+
+        // reorder the columns so the paired gains are on the diagonal
+        let paired_gain_matrix: Vec<Vec<f64>> = gain_matrix.iter()
+            .map(|gain_row| pairing.iter()
+                .map(|input_index| gain_row[*input_index]).collect())
+            .collect();
+
+        let diagonal_product: f64 = (0..size)
+            .map(|i| paired_gain_matrix[i][i]).product();
+
+        if diagonal_product == 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "paired steady state gain is zero".to_owned()));
+        }
+
+        Ok(matrices::determinant(&paired_gain_matrix)?/diagonal_product)
+    }
+
+    /// spawns a csv writer, columns are time, the inputs and
+    /// then the outputs
+    pub fn spawn_writer(&mut self, name: String) -> Result<Writer<std::fs::File>,
+    ChemEngProcessControlSimulatorError>{
+        let title_string: String = name + "_transfer_fn_matrix.csv";
+        let wtr = Writer::from_path(title_string)?;
+        Ok(wtr)
+    }
+
+    /// writes time, the inputs and the outputs into the csv file
+    pub fn csv_write_values(&mut self,
+        wtr: &mut Writer<std::fs::File>,
+        time: Time,
+        inputs: &[Ratio],
+        outputs: &[Ratio]) -> Result<(),
+    ChemEngProcessControlSimulatorError> {
+
+        let mut record: Vec<String> = vec![time.get::<second>().to_string()];
+        record.extend(inputs.iter().map(|input| input.get::<ratio>().to_string()));
+        record.extend(outputs.iter().map(|output| output.get::<ratio>().to_string()));
+
+        wtr.write_record(&record)?;
+
+        wtr.flush().unwrap();
+
+        Ok(())
+    }
+}
+
+#[test]
+pub fn test_wood_berry_column(){
+    use super::TransferFnFirstOrder;
+
+    // Wood and Berry (1973) methanol water column, time in minutes
+    //
+    // | 12.8 e^-s/(16.7s + 1)    -18.9 e^-3s/(21s + 1)   |
+    // | 6.6 e^-7s/(10.9s + 1)    -19.4 e^-3s/(14.4s + 1) |
+    //
+    // I'm using seconds as minutes here, it does not change
+    // the gains
+    let first_order = |gain: f64, time_constant: f64, dead_time: f64| {
+        let mut transfer_fn: TransferFn = TransferFnFirstOrder::new(
+            Time::ZERO, Ratio::new::<ratio>(gain),
+            Time::new::<second>(time_constant), Ratio::new::<ratio>(1.0))
+            .unwrap().into();
+        transfer_fn.set_dead_time(Time::new::<second>(dead_time));
+        transfer_fn
+    };
+
+    let mut wood_berry = TransferFnMatrix::new(vec![
+        vec![first_order(12.8, 16.7, 1.0), first_order(-18.9, 21.0, 3.0)],
+        vec![first_order(6.6, 10.9, 7.0), first_order(-19.4, 14.4, 3.0)],
+    ]).unwrap();
+
+    // lambda_11 = 1/(1 - K12 K21/(K11 K22))
+    let lambda_11: f64 = 1.0/(1.0 - (-18.9 * 6.6)/(12.8 * -19.4));
+    let relative_gain_array = wood_berry.relative_gain_array().unwrap();
+    approx::assert_relative_eq!(relative_gain_array[0][0], lambda_11,
+        max_relative = 1e-12);
+    approx::assert_relative_eq!(relative_gain_array[0][1], 1.0 - lambda_11,
+        max_relative = 1e-12);
+    approx::assert_relative_eq!(relative_gain_array[1][1], lambda_11,
+        max_relative = 1e-12);
+
+    // NI = 1/lambda_11 for 2 by 2 systems
+    approx::assert_relative_eq!(wood_berry.niederlinski_index().unwrap(),
+        1.0/lambda_11, max_relative = 1e-12);
+    // the off diagonal pairing has a negative index
+    assert!(wood_berry.niederlinski_index_for_pairing(&[1, 0]).unwrap() < 0.0);
+
+    // step in reflux only
+    let inputs = [Ratio::new::<ratio>(1.0), Ratio::ZERO];
+    wood_berry.set_user_inputs_and_calc(&inputs, Time::ZERO).unwrap();
+    let outputs = wood_berry.set_user_inputs_and_calc(&inputs,
+        Time::new::<second>(20.0)).unwrap();
+
+    approx::assert_relative_eq!(outputs[0].get::<ratio>(),
+        12.8 * (1.0 - (-19.0/16.7_f64).exp()), max_relative = 1e-9);
+    approx::assert_relative_eq!(outputs[1].get::<ratio>(),
+        6.6 * (1.0 - (-13.0/10.9_f64).exp()), max_relative = 1e-9);
+}
@@ -0,0 +1,503 @@
+use uom::si::f64::*;
+use uom::si::ratio::ratio;
+use uom::si::time::second;
+
+use crate::alpha_nightly::controllers::{AnalogController, PidStructure};
+use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
+use crate::alpha_nightly::transfer_fn_wrapper_and_enums::{TransferFnFirstOrder,
+    TransferFnSecondOrder};
+
+pub mod relay_autotune;
+pub use relay_autotune::{relay_experiment, RelayController, UltimateGainAndPeriod,
+    UltimateGainRule};
+
+/// simple process models used for controller tuning
+#[derive(Debug,PartialEq, PartialOrd, Clone, Copy)]
+pub enum ProcessModel {
+    /// first order plus dead time (FOPDT),
+    ///
+    /// G(s) = K exp(-theta s)/(tau s + 1)
+    FirstOrderPlusDeadTime {
+        process_gain: Ratio,
+        time_constant: Time,
+        dead_time: Time,
+    },
+    /// second order plus dead time (SOPDT), overdamped,
+    ///
+    /// G(s) = K exp(-theta s)/((tau_1 s + 1)(tau_2 s + 1))
+    ///
+    /// with tau_1 the larger (dominant) time constant
+    SecondOrderPlusDeadTime {
+        process_gain: Ratio,
+        dominant_time_constant: Time,
+        second_time_constant: Time,
+        dead_time: Time,
+    },
+}
+
+/// controller tuning rules based on a process model
+///
+/// the closed loop time constant tau_c is the main tuning knob
+/// for the model based rules, a smaller tau_c is faster but less
+/// robust. tau_c = theta is a good starting point for SIMC.
+#[derive(Debug,PartialEq, PartialOrd, Clone, Copy)]
+pub enum TuningRule {
+    /// Ziegler Nichols open loop (process reaction curve) rules,
+    /// quarter decay ratio, quite aggressive
+    ZieglerNichols,
+    /// Cohen Coon rules, meant for larger dead time to time
+    /// constant ratios than Ziegler Nichols
+    CohenCoon,
+    /// internal model control, the PID rule uses a first order
+    /// Pade approximation of the dead time
+    Imc { closed_loop_time_constant: Time },
+    /// Skogestad's simple IMC rules, the integral time is capped at
+    /// 4 (tau_c + theta) for better disturbance rejection on slow
+    /// processes. The PID rule is in series form with the
+    /// derivative time equal to tau_2.
+    Simc { closed_loop_time_constant: Time },
+    /// lambda tuning, as used in the pulp and paper industry,
+    /// the integral time cancels the process time constant and
+    /// lambda is the closed loop time constant
+    Lambda { closed_loop_time_constant: Time },
+    /// Astrom and Hagglund's AMIGO rules, which aim for a maximum
+    /// sensitivity of about 1.4
+    Amigo,
+}
+
+impl ProcessModel {
+
+    /// FOPDT model, the time constant must be positive and the
+    /// dead time must not be negative
+    pub fn new_first_order_plus_dead_time(process_gain: Ratio,
+        time_constant: Time,
+        dead_time: Time) -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        let model = Self::FirstOrderPlusDeadTime {
+            process_gain, time_constant, dead_time };
+        model.validate()?;
+        Ok(model)
+    }
+
+    /// SOPDT model, the time constants are sorted so that the
+    /// dominant one comes first
+    pub fn new_second_order_plus_dead_time(process_gain: Ratio,
+        first_time_constant: Time,
+        second_time_constant: Time,
+        dead_time: Time) -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        let (dominant_time_constant, second_time_constant) =
+            if first_time_constant >= second_time_constant {
+                (first_time_constant, second_time_constant)
+            } else {
+                (second_time_constant, first_time_constant)
+            };
+
+        let model = Self::SecondOrderPlusDeadTime {
+            process_gain, dominant_time_constant, second_time_constant,
+            dead_time };
+        model.validate()?;
+        Ok(model)
+    }
+
+    fn validate(&self) -> Result<(), ChemEngProcessControlSimulatorError> {
+        let (process_gain, time_constant, second_time_constant, dead_time) =
+            self.parameters();
+        if process_gain.get::<ratio>() == 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "process gain must not be zero".to_owned()));
+        }
+        if time_constant.get::<second>() <= 0.0
+            || second_time_constant.get::<second>() < 0.0
+            || dead_time.get::<second>() < 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "time constants must be positive and dead time \
+                    must not be negative".to_owned()));
+        }
+        Ok(())
+    }
+
+    /// (K, tau_1, tau_2, theta), tau_2 is zero for a FOPDT model
+    fn parameters(&self) -> (Ratio, Time, Time, Time) {
+        match *self {
+            ProcessModel::FirstOrderPlusDeadTime { process_gain,
+                time_constant, dead_time } => {
+                (process_gain, time_constant, Time::new::<second>(0.0),
+                    dead_time)
+            },
+            ProcessModel::SecondOrderPlusDeadTime { process_gain,
+                dominant_time_constant, second_time_constant,
+                dead_time } => {
+                (process_gain, dominant_time_constant, second_time_constant,
+                    dead_time)
+            },
+        }
+    }
+
+    /// FOPDT approximation using Skogestad's half rule,
+    /// half of tau_2 is added to tau_1 and the other half to the
+    /// dead time
+    ///
+    /// a FOPDT model is returned as it is
+    pub fn first_order_approximation(&self) -> Self {
+        let (process_gain, time_constant, second_time_constant, dead_time) =
+            self.parameters();
+        Self::FirstOrderPlusDeadTime {
+            process_gain,
+            time_constant: time_constant + 0.5 * second_time_constant,
+            dead_time: dead_time + 0.5 * second_time_constant,
+        }
+    }
+
+    /// (Kc, tau_I) of a PI controller in ISA form,
+    ///
+    /// a SOPDT model is first approximated as FOPDT
+    /// with the half rule
+    pub fn pi_parameters(&self, rule: TuningRule)
+    -> Result<(Ratio, Time), ChemEngProcessControlSimulatorError> {
+
+        let (process_gain, tau, _, theta) =
+            self.first_order_approximation().parameters();
+        let process_gain: f64 = process_gain.get::<ratio>();
+        let tau: f64 = tau.get::<second>();
+        let theta: f64 = theta.get::<second>();
+
+        let (controller_gain, integral_time): (f64, f64) = match rule {
+            TuningRule::ZieglerNichols => {
+                needs_dead_time(theta)?;
+                (0.9 * tau/(process_gain * theta), 3.33 * theta)
+            },
+            TuningRule::CohenCoon => {
+                needs_dead_time(theta)?;
+                let r: f64 = theta/tau;
+                (tau/(process_gain * theta) * (0.9 + r/12.0),
+                    theta * (30.0 + 3.0 * r)/(9.0 + 20.0 * r))
+            },
+            TuningRule::Imc { closed_loop_time_constant }
+            | TuningRule::Lambda { closed_loop_time_constant } => {
+                let tau_c = closed_loop_time_constant_seconds(
+                    closed_loop_time_constant)?;
+                (tau/(process_gain * (tau_c + theta)), tau)
+            },
+            TuningRule::Simc { closed_loop_time_constant } => {
+                let tau_c = closed_loop_time_constant_seconds(
+                    closed_loop_time_constant)?;
+                (tau/(process_gain * (tau_c + theta)),
+                    tau.min(4.0 * (tau_c + theta)))
+            },
+            TuningRule::Amigo => {
+                needs_dead_time(theta)?;
+                (0.15/process_gain + (0.35 - theta * tau/(theta + tau).powi(2))
+                    * tau/(process_gain * theta),
+                    0.35 * theta + 13.0 * theta * tau * tau
+                    /(tau * tau + 12.0 * theta * tau + 7.0 * theta * theta))
+            },
+        };
+
+        Ok((Ratio::new::<ratio>(controller_gain),
+            Time::new::<second>(integral_time)))
+    }
+
+    /// (Kc, tau_I, tau_D) of a PID controller in ISA form
+    ///
+    /// SIMC and lambda tuning use both time constants of a SOPDT
+    /// model (a FOPDT model gives tau_D = 0 for SIMC),
+    /// the other rules use the half rule approximation
+    pub fn pid_parameters(&self, rule: TuningRule)
+    -> Result<(Ratio, Time, Time), ChemEngProcessControlSimulatorError> {
+
+        let (process_gain, tau, _, theta) =
+            self.first_order_approximation().parameters();
+        let process_gain: f64 = process_gain.get::<ratio>();
+        let tau: f64 = tau.get::<second>();
+        let theta: f64 = theta.get::<second>();
+
+        let (controller_gain, integral_time, derivative_time): (f64, f64, f64) =
+            match rule {
+            TuningRule::ZieglerNichols => {
+                needs_dead_time(theta)?;
+                (1.2 * tau/(process_gain * theta), 2.0 * theta, 0.5 * theta)
+            },
+            TuningRule::CohenCoon => {
+                needs_dead_time(theta)?;
+                let r: f64 = theta/tau;
+                (tau/(process_gain * theta) * (4.0/3.0 + r/4.0),
+                    theta * (32.0 + 6.0 * r)/(13.0 + 8.0 * r),
+                    4.0 * theta/(11.0 + 2.0 * r))
+            },
+            TuningRule::Imc { closed_loop_time_constant } => {
+                let tau_c = closed_loop_time_constant_seconds(
+                    closed_loop_time_constant)?;
+                ((tau + 0.5 * theta)/(process_gain * (tau_c + 0.5 * theta)),
+                    tau + 0.5 * theta,
+                    tau * theta/(2.0 * tau + theta))
+            },
+            TuningRule::Simc { closed_loop_time_constant }
+            | TuningRule::Lambda { closed_loop_time_constant } => {
+                // series form, tau_I' cancels tau_1 (capped for SIMC)
+                // and tau_D' cancels tau_2
+                let tau_c = closed_loop_time_constant_seconds(
+                    closed_loop_time_constant)?;
+                let (process_gain, tau_1, tau_2, theta) = self.parameters();
+                let process_gain: f64 = process_gain.get::<ratio>();
+                let tau_1: f64 = tau_1.get::<second>();
+                let tau_2: f64 = tau_2.get::<second>();
+                let theta: f64 = theta.get::<second>();
+
+                let series_integral_time = match rule {
+                    TuningRule::Simc { .. } => tau_1.min(4.0 * (tau_c + theta)),
+                    _ => tau_1,
+                };
+                let (controller_gain, integral_time, derivative_time) =
+                    PidStructure::Series {
+                        controller_gain: Ratio::new::<ratio>(
+                            tau_1/(process_gain * (tau_c + theta))),
+                        integral_time: Time::new::<second>(series_integral_time),
+                        derivative_time: Time::new::<second>(tau_2),
+                    }.isa_parameters()?;
+                (controller_gain.get::<ratio>(), integral_time.get::<second>(),
+                    derivative_time.get::<second>())
+            },
+            TuningRule::Amigo => {
+                needs_dead_time(theta)?;
+                ((0.2 + 0.45 * tau/theta)/process_gain,
+                    (0.4 * theta + 0.8 * tau)/(theta + 0.1 * tau) * theta,
+                    0.5 * theta * tau/(0.3 * theta + tau))
+            },
+        };
+
+        Ok((Ratio::new::<ratio>(controller_gain),
+            Time::new::<second>(integral_time),
+            Time::new::<second>(derivative_time)))
+    }
+
+    /// PI controller tuned with the given rule
+    pub fn pi_controller(&self, rule: TuningRule)
+    -> Result<AnalogController, ChemEngProcessControlSimulatorError> {
+        let (controller_gain, integral_time) = self.pi_parameters(rule)?;
+        AnalogController::new_pi_controller(controller_gain, integral_time)
+    }
+
+    /// filtered PID controller tuned with the given rule,
+    /// the derivative filter time is alpha tau_D
+    pub fn pid_controller(&self, rule: TuningRule, alpha: Ratio)
+    -> Result<AnalogController, ChemEngProcessControlSimulatorError> {
+        let (controller_gain, integral_time, derivative_time) =
+            self.pid_parameters(rule)?;
+        AnalogController::new_filtered_pid_controller(controller_gain,
+            integral_time, derivative_time, alpha)
+    }
+}
+
+/// the reaction curve rules divide by the dead time
+fn needs_dead_time(dead_time: f64) -> Result<(), ChemEngProcessControlSimulatorError> {
+    if dead_time <= 0.0 {
+        return Err(ChemEngProcessControlSimulatorError::
+            GenericStringError(
+                "this tuning rule needs a process with dead time".to_owned()));
+    }
+    Ok(())
+}
+
+fn closed_loop_time_constant_seconds(closed_loop_time_constant: Time)
+-> Result<f64, ChemEngProcessControlSimulatorError> {
+    let tau_c: f64 = closed_loop_time_constant.get::<second>();
+    if tau_c <= 0.0 {
+        return Err(ChemEngProcessControlSimulatorError::
+            GenericStringError(
+                "closed loop time constant must be positive".to_owned()));
+    }
+    Ok(tau_c)
+}
+
+impl TryFrom<TransferFnFirstOrder> for ProcessModel {
+    type Error = ChemEngProcessControlSimulatorError;
+    /// FOPDT model from a stable first order transfer function
+    /// without a zero, the dead time is carried over
+    ///
+    /// a pure gain (pole and zero cancelling out) has no time 
+    /// constant to tune with, so it gives an error
+    fn try_from(first_order: TransferFnFirstOrder)
+    -> Result<Self, Self::Error> {
+
+        if first_order.is_pure_gain() {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "FOPDT model needs a lag, a pure gain transfer \
+                    function has none".to_owned()));
+        }
+        if !first_order.zeros()?.is_empty() {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "FOPDT model needs a first order transfer function \
+                    without a zero".to_owned()));
+        }
+
+        Self::new_first_order_plus_dead_time(first_order.steady_state_gain()?,
+            first_order.time_constant()?, first_order.dead_time())
+    }
+}
+
+impl TryFrom<TransferFnSecondOrder> for ProcessModel {
+    type Error = ChemEngProcessControlSimulatorError;
+    /// SOPDT model from an overdamped (or critically damped) second
+    /// order transfer function without zeros, the dead time is
+    /// carried over
+    fn try_from(second_order: TransferFnSecondOrder)
+    -> Result<Self, Self::Error> {
+
+        if !second_order.zeros()?.is_empty() {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "SOPDT model needs a second order transfer function \
+                    without zeros".to_owned()));
+        }
+        let poles = second_order.poles()?;
+        if poles.len() != 2
+            || poles.iter().any(|pole| pole.im != 0.0 || pole.re >= 0.0) {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "SOPDT model needs two real stable poles".to_owned()));
+        }
+
+        Self::new_second_order_plus_dead_time(second_order.steady_state_gain()?,
+            Time::new::<second>(-1.0/poles[0].re),
+            Time::new::<second>(-1.0/poles[1].re),
+            second_order.dead_time())
+    }
+}
+
+#[test]
+pub fn test_tuning_rules(){
+    use crate::alpha_nightly::transfer_fn_wrapper_and_enums::TransferFnTraits;
+    use crate::alpha_nightly::TimeSquared;
+    use uom::ConstZero;
+
+    // K = 2, tau = 10 s, theta = 2 s
+    let fopdt = ProcessModel::new_first_order_plus_dead_time(
+        Ratio::new::<ratio>(2.0), Time::new::<second>(10.0),
+        Time::new::<second>(2.0)).unwrap();
+
+    let (controller_gain, integral_time, derivative_time) =
+        fopdt.pid_parameters(TuningRule::ZieglerNichols).unwrap();
+    approx::assert_abs_diff_eq!(controller_gain.get::<ratio>(), 3.0,
+        epsilon = 1e-12);
+    approx::assert_abs_diff_eq!(integral_time.get::<second>(), 4.0,
+        epsilon = 1e-12);
+    approx::assert_abs_diff_eq!(derivative_time.get::<second>(), 1.0,
+        epsilon = 1e-12);
+
+    // SIMC with tau_c = theta, Kc = 10/(2 x 4), tau_I = min(10, 16)
+    let simc = TuningRule::Simc {
+        closed_loop_time_constant: Time::new::<second>(2.0) };
+    let (controller_gain, integral_time) = fopdt.pi_parameters(simc).unwrap();
+    approx::assert_abs_diff_eq!(controller_gain.get::<ratio>(), 1.25,
+        epsilon = 1e-12);
+    approx::assert_abs_diff_eq!(integral_time.get::<second>(), 10.0,
+        epsilon = 1e-12);
+
+    // AMIGO PI, Kc = 0.15/2 + (0.35 - 20/144) 10/4
+    let (controller_gain, _) = fopdt.pi_parameters(TuningRule::Amigo).unwrap();
+    approx::assert_abs_diff_eq!(controller_gain.get::<ratio>(),
+        0.075 + (0.35 - 20.0/144.0) * 2.5, epsilon = 1e-12);
+
+    // SOPDT from a transfer function, 2/((10 s + 1)(2 s + 1))
+    // with 1 s dead time
+    let mut second_order = TransferFnSecondOrder::new(TimeSquared::ZERO,
+        Time::ZERO, Ratio::new::<ratio>(2.0),
+        Time::new::<second>(10.0) * Time::new::<second>(2.0),
+        Time::new::<second>(12.0), Ratio::new::<ratio>(1.0)).unwrap();
+    second_order.set_dead_time(Time::new::<second>(1.0));
+    let sopdt = ProcessModel::try_from(second_order).unwrap();
+
+    // SIMC PID, series Kc' = 10/(2 x 2), tau_I' = 8, tau_D' = 2
+    let simc = TuningRule::Simc {
+        closed_loop_time_constant: Time::new::<second>(1.0) };
+    let (controller_gain, integral_time, derivative_time) =
+        sopdt.pid_parameters(simc).unwrap();
+    approx::assert_abs_diff_eq!(controller_gain.get::<ratio>(),
+        2.5 * 10.0/8.0, epsilon = 1e-9);
+    approx::assert_abs_diff_eq!(integral_time.get::<second>(), 10.0,
+        epsilon = 1e-9);
+    approx::assert_abs_diff_eq!(derivative_time.get::<second>(), 1.6,
+        epsilon = 1e-9);
+
+    // half rule, tau = 11 s, theta = 2 s
+    let approximation = sopdt.first_order_approximation();
+    assert!(approx::abs_diff_eq!(approximation.parameters().1.get::<second>(),
+        11.0, epsilon = 1e-9));
+    assert!(approx::abs_diff_eq!(approximation.parameters().3.get::<second>(),
+        2.0, epsilon = 1e-9));
+
+    // ready built controllers
+    let controller = fopdt.pid_controller(TuningRule::CohenCoon,
+        Ratio::new::<ratio>(0.1)).unwrap();
+    assert!(matches!(controller, AnalogController::PIDFiltered(..)));
+
+    // SIMC gives no derivative action for a FOPDT model, 
+    // Kc = 1.25, tau_I = 10 s, tau_D = 0, which can still be 
+    // retuned online, error of 1 so the output at 5 s is 1.875
+    let simc = TuningRule::Simc {
+        closed_loop_time_constant: Time::new::<second>(2.0) };
+    let mut controller = fopdt.pid_controller(simc,
+        Ratio::new::<ratio>(0.1)).unwrap();
+    assert_eq!(controller.derivative_time().unwrap(), Time::ZERO);
+    let run_to = |controller: &mut AnalogController, 
+        start: f64, end: f64| -> f64 {
+        let mut output = Ratio::ZERO;
+        let mut time = start;
+        while time <= end + 1e-9 {
+            output = controller.set_user_input_and_calc(
+                Ratio::new::<ratio>(1.0), Time::new::<second>(time)).unwrap();
+            time += 0.5;
+        }
+        output.get::<ratio>()
+    };
+    approx::assert_abs_diff_eq!(run_to(&mut controller, 0.0, 5.0), 
+        1.875, epsilon = 1e-9);
+
+    // no bump, then rising at 2.5/10 and 2.5/5 per second
+    controller.set_controller_gain(Ratio::new::<ratio>(2.5), 
+        Time::new::<second>(5.0)).unwrap();
+    approx::assert_abs_diff_eq!(run_to(&mut controller, 5.0, 5.0), 
+        1.875, epsilon = 1e-9);
+    controller.set_integral_time(Time::new::<second>(5.0), 
+        Time::new::<second>(7.0)).unwrap();
+    approx::assert_abs_diff_eq!(run_to(&mut controller, 5.5, 9.0), 
+        1.875 + 0.5 + 1.0, epsilon = 1e-9);
+
+    // switching derivative action on, with tau_D = 1 s, and off 
+    // again, the error is steady so the output does not bump
+    controller.retune(Ratio::new::<ratio>(2.5), Time::new::<second>(5.0), 
+        Time::new::<second>(1.0), Time::new::<second>(9.0)).unwrap();
+    approx::assert_abs_diff_eq!(run_to(&mut controller, 12.0, 12.0), 
+        3.375 + 1.5, epsilon = 1e-3);
+    controller.retune(Ratio::new::<ratio>(2.5), Time::new::<second>(5.0), 
+        Time::ZERO, Time::new::<second>(12.0)).unwrap();
+    approx::assert_abs_diff_eq!(run_to(&mut controller, 12.0, 14.0), 
+        4.875 + 1.0, epsilon = 1e-3);
+    assert!(controller.retune(Ratio::new::<ratio>(2.5), 
+            Time::new::<second>(5.0), Time::new::<second>(-1.0), 
+            Time::new::<second>(14.0)).is_err());
+
+    // FOPDT from a first order transfer function
+    let mut first_order = TransferFnFirstOrder::new(Time::ZERO,
+        Ratio::new::<ratio>(2.0), Time::new::<second>(10.0),
+        Ratio::new::<ratio>(1.0)).unwrap();
+    first_order.set_dead_time(Time::new::<second>(2.0));
+    assert_eq!(ProcessModel::try_from(first_order).unwrap(), fopdt);
+
+    // a pure gain has no lag to build a FOPDT model from
+    let pure_gain = TransferFnFirstOrder::new(Time::ZERO,
+        Ratio::new::<ratio>(2.0), Time::ZERO,
+        Ratio::new::<ratio>(1.0)).unwrap();
+    assert!(ProcessModel::try_from(pure_gain).is_err());
+
+    // no dead time, no reaction curve tuning
+    let no_dead_time = ProcessModel::new_first_order_plus_dead_time(
+        Ratio::new::<ratio>(2.0), Time::new::<second>(10.0),
+        Time::ZERO).unwrap();
+    assert!(no_dead_time.pi_parameters(TuningRule::ZieglerNichols).is_err());
+}
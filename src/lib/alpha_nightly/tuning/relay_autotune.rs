@@ -0,0 +1,328 @@
+use csv::Writer;
+use uom::si::f64::*;
+use uom::si::ratio::ratio;
+use uom::si::time::second;
+use uom::ConstZero;
+
+use crate::alpha_nightly::controllers::AnalogController;
+use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
+use crate::alpha_nightly::transfer_fn_wrapper_and_enums::TransferFnTraits;
+
+/// relay (on/off) controller with hysteresis, for the
+/// Astrom Hagglund relay autotuning experiment
+///
+/// the input is the error e = r - y, the output is +d once
+/// e > epsilon and -d once e < -epsilon, and stays where it was
+/// in between. Put in a loop with most processes, this gives a
+/// sustained oscillation (limit cycle) close to the ultimate
+/// period, the relay keeps track of each cycle so the ultimate
+/// gain and period can be worked out from it.
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub struct RelayController {
+    /// d, use a negative amplitude for a process with negative gain
+    pub(crate) amplitude: Ratio,
+    /// epsilon, stops measurement noise from switching the relay
+    pub(crate) hysteresis: Ratio,
+    /// true if the output is +d
+    pub(crate) output_high: bool,
+    /// time the relay last switched to +d
+    pub(crate) last_switch_time: Option<Time>,
+    /// smallest and largest error since the last switch to +d
+    pub(crate) error_range: Option<(Ratio, Ratio)>,
+    /// (period, amplitude of the error) of every complete cycle
+    pub(crate) cycles: Vec<(Time, Ratio)>,
+}
+
+/// ultimate gain Ku and ultimate period Pu of a process, ie. the
+/// proportional gain which puts the loop at the edge of
+/// stability and the period of the oscillation there
+#[derive(Debug,PartialEq, PartialOrd, Clone, Copy)]
+pub struct UltimateGainAndPeriod {
+    pub ultimate_gain: Ratio,
+    pub ultimate_period: Time,
+}
+
+/// tuning rules based on the ultimate gain and period
+#[derive(Debug,PartialEq, PartialOrd, Clone, Copy)]
+pub enum UltimateGainRule {
+    /// Ziegler Nichols closed loop rules, quarter decay ratio
+    ZieglerNichols,
+    /// Tyreus Luyben rules, more conservative than Ziegler Nichols,
+    /// with less overshoot and better robustness
+    TyreusLuyben,
+}
+
+/// relative change in period and amplitude between the last two
+/// cycles for the limit cycle to count as sustained
+const LIMIT_CYCLE_TOLERANCE: f64 = 0.01;
+
+impl RelayController {
+
+    /// relay with amplitude d and hysteresis epsilon,
+    /// starts off at +d
+    pub fn new(amplitude: Ratio,
+        hysteresis: Ratio) -> Result<Self, ChemEngProcessControlSimulatorError> {
+        if amplitude.get::<ratio>() == 0.0 || hysteresis.get::<ratio>() < 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "relay amplitude must not be zero and hysteresis \
+                    must not be negative".to_owned()));
+        }
+
+        Ok(Self {
+            amplitude,
+            hysteresis,
+            output_high: true,
+            last_switch_time: None,
+            error_range: None,
+            cycles: vec![],
+        })
+    }
+
+    /// current relay output, +d or -d
+    pub fn output(&self) -> Ratio {
+        if self.output_high {
+            self.amplitude
+        } else {
+            -self.amplitude
+        }
+    }
+
+    /// number of complete cycles so far
+    pub fn number_of_cycles(&self) -> usize {
+        self.cycles.len()
+    }
+
+    /// ultimate gain and period once the limit cycle is sustained,
+    /// ie. after at least three cycles, with the last two having
+    /// the same period and amplitude to within 1%
+    ///
+    /// Pu is the period of the limit cycle, and from the
+    /// describing function of a relay with hysteresis,
+    ///
+    /// Ku = 4 d/(pi sqrt(a^2 - epsilon^2))
+    ///
+    /// where a is the amplitude of the oscillation
+    pub fn ultimate_gain_and_period(&self) -> Option<UltimateGainAndPeriod> {
+        let number_of_cycles = self.cycles.len();
+        if number_of_cycles < 3 {
+            return None;
+        }
+        let (last_period, last_amplitude) = self.cycles[number_of_cycles - 1];
+        let (previous_period, previous_amplitude) =
+            self.cycles[number_of_cycles - 2];
+
+        let period_change: f64 = ((last_period - previous_period)/last_period)
+            .get::<ratio>().abs();
+        let amplitude_change: f64 = ((last_amplitude - previous_amplitude)
+            /last_amplitude).get::<ratio>().abs();
+        if period_change > LIMIT_CYCLE_TOLERANCE
+            || amplitude_change > LIMIT_CYCLE_TOLERANCE {
+            return None;
+        }
+
+        let oscillation_amplitude: f64 = last_amplitude.get::<ratio>();
+        let hysteresis: f64 = self.hysteresis.get::<ratio>();
+        if oscillation_amplitude <= hysteresis {
+            return None;
+        }
+        let ultimate_gain: f64 = 4.0 * self.amplitude.get::<ratio>()
+            /(std::f64::consts::PI
+                * (oscillation_amplitude.powi(2) - hysteresis.powi(2)).sqrt());
+
+        Some(UltimateGainAndPeriod {
+            ultimate_gain: Ratio::new::<ratio>(ultimate_gain),
+            ultimate_period: last_period,
+        })
+    }
+}
+
+impl TransferFnTraits for RelayController {
+    /// a relay acts straight away, so this does nothing
+    fn set_dead_time(&mut self, _dead_time: Time) {
+    }
+
+    fn set_user_input_and_calc(&mut self,
+        user_input: Ratio,
+        time_of_input: Time) -> Result<Ratio,
+    ChemEngProcessControlSimulatorError> {
+
+        let error = user_input;
+        self.error_range = match self.error_range {
+            Some((lowest, highest)) => {
+                Some((if error < lowest { error } else { lowest },
+                    if error > highest { error } else { highest }))
+            },
+            None => Some((error, error)),
+        };
+
+        if self.output_high && error < -self.hysteresis {
+            self.output_high = false;
+        } else if !self.output_high && error > self.hysteresis {
+            self.output_high = true;
+
+            // one cycle is from one switch to +d to the next
+            if let (Some(last_switch_time), Some((lowest, highest))) =
+                (self.last_switch_time, self.error_range) {
+                self.cycles.push((time_of_input - last_switch_time,
+                    0.5 * (highest - lowest)));
+            }
+            self.last_switch_time = Some(time_of_input);
+            self.error_range = Some((error, error));
+        }
+
+        Ok(self.output())
+    }
+
+    fn spawn_writer(&mut self, name: String) -> Result<Writer<std::fs::File>,
+    ChemEngProcessControlSimulatorError> {
+        let title_string: String = name + "_relay.csv";
+        let wtr = Writer::from_path(title_string)?;
+        Ok(wtr)
+    }
+
+    fn csv_write_values(&mut self,
+        wtr: &mut Writer<std::fs::File>,
+        time: Time,
+        input: Ratio,
+        output: Ratio) -> Result<(),
+    ChemEngProcessControlSimulatorError> {
+        let current_time_string = time.get::<second>().to_string();
+        let input_string = input.get::<ratio>().to_string();
+        let output_string = output.get::<ratio>().to_string();
+
+        wtr.write_record(&[current_time_string,
+            input_string,
+            output_string])?;
+
+        wtr.flush().unwrap();
+        Ok(())
+    }
+}
+
+/// runs the relay experiment on a process, in deviation variables
+/// with the setpoint at zero, until the limit cycle is sustained
+///
+/// the process should start at steady state, and is left where
+/// the experiment ends. Returns an error if there is no sustained
+/// limit cycle by max_time.
+pub fn relay_experiment<T: TransferFnTraits>(process: &mut T,
+    relay: &mut RelayController,
+    timestep: Time,
+    max_time: Time) -> Result<UltimateGainAndPeriod,
+ChemEngProcessControlSimulatorError> {
+
+    let mut time = Time::ZERO;
+    let mut measurement = Ratio::ZERO;
+    while time <= max_time {
+        let relay_output = relay.set_user_input_and_calc(-measurement, time)?;
+        measurement = process.set_user_input_and_calc(relay_output, time)?;
+
+        if let Some(ultimate_gain_and_period) = relay.ultimate_gain_and_period() {
+            return Ok(ultimate_gain_and_period);
+        }
+        time += timestep;
+    }
+
+    Err(ChemEngProcessControlSimulatorError::
+        GenericStringError(
+            "relay experiment did not reach a sustained limit cycle"
+            .to_owned()))
+}
+
+impl UltimateGainAndPeriod {
+
+    /// (Kc, tau_I) of a PI controller in ISA form
+    ///
+    /// Ziegler Nichols: Kc = 0.45 Ku, tau_I = Pu/1.2
+    ///
+    /// Tyreus Luyben: Kc = Ku/3.2, tau_I = 2.2 Pu
+    pub fn pi_parameters(&self, rule: UltimateGainRule) -> (Ratio, Time) {
+        match rule {
+            UltimateGainRule::ZieglerNichols => {
+                (0.45 * self.ultimate_gain, self.ultimate_period/1.2)
+            },
+            UltimateGainRule::TyreusLuyben => {
+                (self.ultimate_gain/3.2, 2.2 * self.ultimate_period)
+            },
+        }
+    }
+
+    /// (Kc, tau_I, tau_D) of a PID controller in ISA form
+    ///
+    /// Ziegler Nichols: Kc = 0.6 Ku, tau_I = Pu/2, tau_D = Pu/8
+    ///
+    /// Tyreus Luyben: Kc = Ku/2.2, tau_I = 2.2 Pu, tau_D = Pu/6.3
+    pub fn pid_parameters(&self, rule: UltimateGainRule) -> (Ratio, Time, Time) {
+        match rule {
+            UltimateGainRule::ZieglerNichols => {
+                (0.6 * self.ultimate_gain, self.ultimate_period/2.0,
+                    self.ultimate_period/8.0)
+            },
+            UltimateGainRule::TyreusLuyben => {
+                (self.ultimate_gain/2.2, 2.2 * self.ultimate_period,
+                    self.ultimate_period/6.3)
+            },
+        }
+    }
+
+    /// PI controller tuned with the given rule
+    pub fn pi_controller(&self, rule: UltimateGainRule)
+    -> Result<AnalogController, ChemEngProcessControlSimulatorError> {
+        let (controller_gain, integral_time) = self.pi_parameters(rule);
+        AnalogController::new_pi_controller(controller_gain, integral_time)
+    }
+
+    /// filtered PID controller tuned with the given rule,
+    /// the derivative filter time is alpha tau_D
+    pub fn pid_controller(&self, rule: UltimateGainRule, alpha: Ratio)
+    -> Result<AnalogController, ChemEngProcessControlSimulatorError> {
+        let (controller_gain, integral_time, derivative_time) =
+            self.pid_parameters(rule);
+        AnalogController::new_filtered_pid_controller(controller_gain,
+            integral_time, derivative_time, alpha)
+    }
+}
+
+#[test]
+pub fn test_relay_autotune(){
+    use crate::alpha_nightly::transfer_fn_wrapper_and_enums::
+        generic_zero_order_hold::TransferFnZeroOrderHold;
+
+    // 1/(s + 1)^3 has Ku = 8 and Pu = 2 pi/sqrt(3) s
+    let mut process = TransferFnZeroOrderHold::new(vec![1.0],
+        vec![1.0, 3.0, 3.0, 1.0]).unwrap();
+    let mut relay = RelayController::new(Ratio::new::<ratio>(0.1),
+        Ratio::new::<ratio>(1e-4)).unwrap();
+
+    let ultimate_gain_and_period = relay_experiment(&mut process,
+        &mut relay, Time::new::<second>(0.01),
+        Time::new::<second>(200.0)).unwrap();
+
+    // the describing function ignores the harmonics of the square 
+    // wave, so the estimates are only good to within about 10%
+    let ultimate_gain: f64 = ultimate_gain_and_period.ultimate_gain
+        .get::<ratio>();
+    let ultimate_period: f64 = ultimate_gain_and_period.ultimate_period
+        .get::<second>();
+    assert!((ultimate_gain - 8.0).abs() < 0.8, "Ku = {}", ultimate_gain);
+    assert!((ultimate_period - 2.0 * std::f64::consts::PI/3.0_f64.sqrt()).abs()
+        < 0.2, "Pu = {}", ultimate_period);
+    assert!(relay.number_of_cycles() >= 3);
+
+    let (controller_gain, integral_time, derivative_time) =
+        ultimate_gain_and_period.pid_parameters(UltimateGainRule::TyreusLuyben);
+    approx::assert_abs_diff_eq!(controller_gain.get::<ratio>(),
+        ultimate_gain/2.2, epsilon = 1e-12);
+    approx::assert_abs_diff_eq!(integral_time.get::<second>(),
+        2.2 * ultimate_period, epsilon = 1e-12);
+    approx::assert_abs_diff_eq!(derivative_time.get::<second>(),
+        ultimate_period/6.3, epsilon = 1e-12);
+
+    let controller = ultimate_gain_and_period.pi_controller(
+        UltimateGainRule::ZieglerNichols).unwrap();
+    assert!(matches!(controller, AnalogController::PI(..)));
+
+    assert!(RelayController::new(Ratio::new::<ratio>(0.0),
+        Ratio::new::<ratio>(0.0)).is_err());
+}
@@ -0,0 +1,140 @@
+use uom::si::ratio::ratio;
+use uom::si::time::second;
+use uom::si::f64::*;
+use uom::ConstZero;
+
+use crate::alpha_nightly::controllers::integral_controller::RampResponseRealTime;
+use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
+
+/// integrating (pure capacity) first order process
+///
+/// G(s) =
+///
+/// a1 s + b1
+/// ----------
+///   a2 s
+///
+/// this is split into a static gain and a pure integrator:
+///
+/// G(s) = a1/a2 + (b1/a2) / s
+///
+/// The integrator is handled by RampResponseRealTime, the same
+/// ramp logic used by the integral controller.
+///
+/// The dead time is handled by holding the input changes
+/// until the dead time has passed. Only then are they fed
+/// into the static gain and the ramp function.
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub struct FirstOrderIntegratingTransferFn {
+    /// integrator part, (b1/a2) / s
+    pub(crate) ramp_function: RampResponseRealTime,
+    /// static gain part, a1/a2
+    pub(crate) static_gain: Ratio,
+    pub(crate) previous_timestep_input: Ratio,
+    /// input at the start of the simulation
+    pub(crate) initial_input: Ratio,
+    /// input seen by the process after the dead time
+    pub(crate) delayed_input: Ratio,
+    /// previous timestep output
+    pub(crate) offset: Ratio,
+    /// delay
+    pub(crate) delay: Time,
+    /// input changes waiting for the dead time to pass,
+    /// stored as (start time, input level after the change)
+    pub(crate) delayed_input_vec: Vec<(Time, Ratio)>,
+}
+
+impl Default for FirstOrderIntegratingTransferFn {
+    /// default is:
+    ///
+    /// 1 / s
+    ///
+    /// with initial user input of 0.0
+    /// and initial user value of 0.0
+    fn default() -> Self {
+        Self::new(Time::ZERO,
+            Ratio::new::<ratio>(1.0),
+            Time::new::<second>(1.0),
+            Ratio::ZERO,
+            Ratio::ZERO,
+            Time::ZERO).unwrap()
+    }
+}
+
+impl FirstOrderIntegratingTransferFn {
+
+    /// constructor for
+    ///
+    /// G(s) = (a1 s + b1)/(a2 s)
+    pub fn new(a1: Time,
+        b1: Ratio,
+        a2: Time,
+        initial_input: Ratio,
+        initial_value: Ratio,
+        delay: Time) -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        if a2.get::<second>() == 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                ImproperTransferFunction);
+        }
+
+        // the ramp function gradient is
+        // (change in input) * controller_gain/integral_time
+        // so controller gain is b1 and integral time is a2
+        let mut ramp_function = RampResponseRealTime::new(a2, b1)?;
+        ramp_function.previous_timestep_input = initial_input;
+
+        Ok(Self {
+            ramp_function,
+            static_gain: a1/a2,
+            previous_timestep_input: initial_input,
+            initial_input,
+            delayed_input: initial_input,
+            offset: initial_value,
+            delay,
+            delayed_input_vec: vec![],
+        })
+    }
+
+    /// sets the user input to some value
+    pub fn set_user_input_and_calc_output(&mut self,
+        current_time: Time,
+        current_input: Ratio)
+    -> Result<Ratio, ChemEngProcessControlSimulatorError> {
+
+        // case where input is not the same to 9 decimal places
+        let input_changed: bool =
+            (current_input.get::<ratio>() * 1e9).round()
+            - (self.previous_timestep_input
+                .get::<ratio>()*1e9).round() != 0.0 ;
+
+        if input_changed {
+            let start_time = current_time + self.delay;
+            self.delayed_input_vec.push((start_time, current_input));
+            self.previous_timestep_input = current_input;
+        }
+
+        // feed the input changes whose dead time has passed
+        // into the ramp function, in the order they came in
+        while let Some(&(start_time, input)) = self.delayed_input_vec.first() {
+            if start_time > current_time {
+                break;
+            }
+            self.ramp_function.set_user_input_and_calc(input, start_time)?;
+            self.delayed_input = input;
+            self.delayed_input_vec.remove(0);
+        }
+
+        // the static gain acts on the deviation of the delayed
+        // input from its initial value, the ramp function already
+        // accounts for this through its previous_timestep_input
+        let static_response = self.static_gain *
+            (self.delayed_input - self.initial_input);
+
+        let ramp_response = self.ramp_function.set_user_input_and_calc(
+            self.delayed_input, current_time)?;
+
+        Ok(self.offset + static_response + ramp_response)
+    }
+
+}
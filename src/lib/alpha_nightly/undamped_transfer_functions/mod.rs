@@ -0,0 +1,2 @@
+pub mod first_order_integrating_transfer_fn;
+pub mod second_order_undamped_transfer_fn;
@@ -0,0 +1,185 @@
+use uom::{si::{f64::*, ratio::ratio}, ConstZero};
+use uom::si::angular_velocity::radian_per_second;
+use uom::si::time::second;
+
+use crate::alpha_nightly::transfer_fn_wrapper_and_enums::generic_second_order::SecondOrderCoefficients;
+use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
+
+/// undamped second order system (zeta = 0)
+///
+/// G(s) =
+///
+/// a1 s^2 + b1 s + c1
+/// ------------------
+///   a2 s^2 + c2
+///
+/// for example 1/(s^2 + 1)
+///
+/// the step response Y(s) = G(s)/s is split using partial fractions:
+///
+/// Y(s) = K/s + (p s + q)/(s^2 + omega^2)
+///
+/// where K = c1/c2, p = (a1 - K a2)/a2, q = b1/a2 and
+/// omega^2 = c2/a2
+///
+/// so for each step change in input, we get a sustained oscillation
+/// about K:
+///
+/// u1(t - t1) * [K + p cos (omega [t-t1]) + q/omega sin (omega [t-t1])]
+///
+/// The oscillations never die out, so nothing is ever removed
+/// from the response vector.
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub struct SecondOrderUndampedTransferFn {
+    /// K = c1/c2, the value the output oscillates about
+    pub(crate) step_gain: Ratio,
+    pub(crate) cosine_magnitude: Ratio,
+    pub(crate) sine_magnitude: Ratio,
+    /// natural frequency of the oscillation
+    pub(crate) omega: AngularVelocity,
+    pub(crate) previous_timestep_input: Ratio,
+    /// previous timestep output
+    pub(crate) offset: Ratio,
+    /// delay
+    pub(crate) delay: Time,
+    /// input changes, stored as (start time, change in input)
+    pub(crate) response_vec: Vec<(Time, Ratio)>,
+}
+
+impl Default for SecondOrderUndampedTransferFn {
+    /// default is:
+    ///
+    /// 1 / (s^2 + 1)
+    ///
+    /// with initial user input of 0.0
+    /// and initial user value of 0.0
+    fn default() -> Self {
+        Self {
+            step_gain: Ratio::new::<ratio>(1.0),
+            cosine_magnitude: Ratio::new::<ratio>(-1.0),
+            sine_magnitude: Ratio::ZERO,
+            omega: AngularVelocity::new::<radian_per_second>(1.0),
+            previous_timestep_input: Ratio::ZERO,
+            offset: Ratio::ZERO,
+            delay: Time::ZERO,
+            response_vec: vec![],
+        }
+    }
+}
+
+impl SecondOrderUndampedTransferFn {
+
+    /// constructor from the polynomial coefficients
+    ///
+    /// a1 s^2 + b1 s + c1
+    /// ------------------
+    ///   a2 s^2 + c2
+    ///
+    /// b2 must be zero and c2/a2 must be positive, otherwise 
+    /// the system is damped, integrating or has real roots
+    pub fn new(coefficients: SecondOrderCoefficients,
+        initial_input: Ratio,
+        initial_value: Ratio,
+        delay: Time) -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        // there is no time squared unit in uom yet, so everything 
+        // is worked out in SI base units (seconds)
+        let a1: f64 = coefficients.a1.value;
+        let b1: f64 = coefficients.b1.get::<second>();
+        let c1: f64 = coefficients.c1.get::<ratio>();
+        let a2: f64 = coefficients.a2.value;
+        let b2: f64 = coefficients.b2.get::<second>();
+        let c2: f64 = coefficients.c2.get::<ratio>();
+
+        if b2 != 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "an undamped second order system needs b2 = 0"
+                    .to_owned()));
+        }
+
+        let omega_squared: f64 = c2/a2;
+
+        if omega_squared == 0.0 {
+            // pole at the origin, a double integrator
+            return Err(ChemEngProcessControlSimulatorError::
+                UndampedOrIntegratingSecondOrderTransferFunction);
+        }
+        if omega_squared < 0.0 || !omega_squared.is_finite() {
+            return Err(ChemEngProcessControlSimulatorError::
+                GenericStringError(
+                    "c2/a2 must be positive for an undamped second order \
+                    system, otherwise the poles are real"
+                    .to_owned()));
+        }
+
+        let omega: f64 = omega_squared.sqrt();
+        let step_gain: f64 = c1/c2;
+        let p: f64 = (a1 - step_gain * a2)/a2;
+        let q: f64 = b1/a2;
+
+        Ok(Self {
+            step_gain: Ratio::new::<ratio>(step_gain),
+            cosine_magnitude: Ratio::new::<ratio>(p),
+            sine_magnitude: Ratio::new::<ratio>(q/omega),
+            omega: AngularVelocity::new::<radian_per_second>(omega),
+            previous_timestep_input: initial_input,
+            offset: initial_value,
+            delay,
+            response_vec: vec![],
+        })
+    }
+
+    /// sets the user input to some value
+    pub fn set_user_input_and_calc_output(&mut self,
+        current_time: Time,
+        current_input: Ratio)
+    -> Result<Ratio, ChemEngProcessControlSimulatorError> {
+
+        // case where input is not the same to 9 decimal places
+        let input_changed: bool =
+            (current_input.get::<ratio>() * 1e9).round()
+            - (self.previous_timestep_input
+                .get::<ratio>()*1e9).round() != 0.0 ;
+
+        if input_changed {
+            let user_input = current_input - self.previous_timestep_input;
+            // the time where the response kicks in
+            let start_time = current_time + self.delay;
+
+            self.response_vec.push((start_time, user_input));
+
+            self.previous_timestep_input = current_input;
+        }
+
+        // O(t) = summing:: u2(t - t2) * [K + p cos + q/omega sin]
+        // + offset
+        let summation_of_responses: Ratio = self.response_vec.
+            iter().map(
+                |&(start_time, user_input)|{
+                    self.calculate_response(current_time, 
+                        start_time, user_input)}
+            ).sum();
+
+        Ok(self.offset + summation_of_responses)
+    }
+
+    /// calculates the response to one input change at a given time
+    fn calculate_response(&self, 
+        simulation_time: Time,
+        start_time: Time,
+        user_input: Ratio) -> Ratio {
+
+        // heaviside function
+        if simulation_time < start_time {
+            return Ratio::ZERO;
+        }
+
+        let omega_t: f64 = self.omega.get::<radian_per_second>()
+            * (simulation_time - start_time).get::<second>();
+
+        user_input * (self.step_gain 
+            + self.cosine_magnitude * omega_t.cos()
+            + self.sine_magnitude * omega_t.sin())
+    }
+}
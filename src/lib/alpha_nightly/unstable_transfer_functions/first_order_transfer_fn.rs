@@ -0,0 +1,231 @@
+use uom::{si::{f64::*, time::second, ratio::ratio}, ConstZero};
+
+use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
+
+/// first order system with a right half plane pole
+///
+/// G(s) = K_p / (tau_p s + 1)
+///
+/// where tau_p is negative, so the pole -1/tau_p is positive
+///
+/// The step response is the same as the stable case:
+///
+/// u1(t - t1) * Kp * [1-exp(- [t-t1] / tau])
+///
+/// except that exp(- [t-t1]/tau) now grows without bound.
+/// None of the responses ever reach steady state, so unlike the
+/// stable transfer functions, nothing is ever removed from the
+/// response vector.
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub struct FirstOrderUnstableTransferFnNoZeroes {
+    pub(crate) process_gain: Ratio,
+    /// process time (negative for unstable systems)
+    pub(crate) process_time: Time,
+    /// previous timestep output
+    pub(crate) previous_timestep_input: Ratio,
+    pub(crate) offset: Ratio,
+    /// delay
+    pub(crate) delay: Time,
+
+    /// vector of unstable first order responses
+    pub(crate) response_vec: Vec<FirstOrderUnstableResponse>,
+}
+
+impl Default for FirstOrderUnstableTransferFnNoZeroes {
+    /// default is:
+    ///
+    /// 1 / (-s + 1)
+    ///
+    /// with initial user input of 0.0
+    /// and initial user value of 0.0
+    fn default() -> Self {
+        FirstOrderUnstableTransferFnNoZeroes {
+            process_gain: Ratio::new::<ratio>(1.0),
+            process_time: Time::new::<second>(-1.0),
+            previous_timestep_input: Ratio::new::<ratio>(0.0),
+            offset: Ratio::new::<ratio>(0.0),
+            delay: Time::new::<second>(0.0),
+            response_vec: vec![],
+        }
+    }
+}
+
+impl FirstOrderUnstableTransferFnNoZeroes {
+
+    /// constructors
+    /// process_time must be negative
+    pub fn new(process_gain: Ratio,
+        process_time: Time,
+        initial_input: Ratio,
+        initial_value: Ratio,
+        delay: Time,) -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        if process_time.get::<second>() >= 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                StableDampingFactorForUnstableTransferFunction);
+        }
+        Ok(FirstOrderUnstableTransferFnNoZeroes {
+            process_gain,
+            process_time,
+            previous_timestep_input: initial_input,
+            offset: initial_value,
+            delay,
+            response_vec: vec![],
+        })
+    }
+
+    /// sets the user input to some value
+    pub fn set_user_input_and_calc_output(&mut self,
+        current_time: Time,
+        current_input: Ratio)
+    -> Result<Ratio, ChemEngProcessControlSimulatorError> {
+        // check if input is equal to current input
+
+        // case where input is not the same to 9 decimal places
+
+        let input_changed: bool =
+            (current_input.get::<ratio>() * 1e9).round()
+            - (self.previous_timestep_input
+                .get::<ratio>()*1e9).round() != 0.0 ;
+
+        if input_changed {
+            // need to add a response to the vector
+
+            let user_input = current_input - self.previous_timestep_input;
+            // the time where the first order response kicks in
+            let start_time = current_time + self.delay;
+
+            let new_response = FirstOrderUnstableResponse::new(
+                self.process_gain,
+                self.process_time,
+                start_time,
+                user_input,
+                current_time
+            )?;
+
+            self.response_vec.push(new_response);
+
+            self.previous_timestep_input = current_input;
+        }
+
+        // O(t) = summing:: u2(t - t2) * Kp [1-exp(-[t-t2]/tau)]
+        // + offset
+        //
+        // no clean up here, unstable responses never settle
+        let summation_of_responses: Ratio = self.response_vec.
+            iter_mut().map(
+                |first_order_response|{
+                    first_order_response.calculate_response(current_time)}
+            ).sum();
+
+        let output = self.offset + summation_of_responses;
+
+        Ok(output)
+
+    }
+}
+
+/// unstable first order response struct,
+/// will help to caluclate
+/// u1(t - t1) * Kp * [1-exp(- [t-t1] / tau])
+///
+/// with tau negative
+#[derive(Debug,PartialEq, PartialOrd, Clone, Copy)]
+pub struct FirstOrderUnstableResponse {
+    process_gain: Ratio,
+    process_time: Time,
+    start_time: Time,
+    user_input: Ratio,
+    current_time: Time,
+}
+
+impl Default for FirstOrderUnstableResponse {
+    fn default() -> Self {
+        FirstOrderUnstableResponse {
+            process_gain: Ratio::new::<ratio>(1.0),
+            process_time: Time::new::<second>(-1.0),
+            start_time: Time::new::<second>(0.0),
+            user_input: Ratio::new::<ratio>(1.0),
+            current_time: Time::new::<second>(0.0),
+        }
+    }
+}
+
+impl FirstOrderUnstableResponse {
+
+    /// constructor
+    pub fn new(
+        process_gain: Ratio,
+        process_time: Time,
+        start_time: Time,
+        user_input: Ratio,
+        current_time: Time,) -> Result<Self, ChemEngProcessControlSimulatorError> {
+        if process_time.get::<second>() >= 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                StableDampingFactorForUnstableTransferFunction);
+        }
+        Ok(FirstOrderUnstableResponse {
+            process_gain,
+            process_time,
+            start_time,
+            user_input,
+            current_time,
+        })
+    }
+
+    /// calculates the response of the first order system
+    /// at a given time
+    /// u1(t - t1) * Kp * [1-exp(- [t-t1] / tau])
+    pub fn calculate_response(&mut self, simulation_time: Time) -> Ratio {
+
+        self.current_time = simulation_time;
+        let time_elapsed = self.current_time - self.start_time;
+
+        // heaviside function
+        let heaviside_on: bool = self.current_time >= self.start_time;
+
+        if !heaviside_on {
+            return Ratio::ZERO;
+        }
+
+        // tau is negative, so this exponent is positive
+        let time_ratio: Ratio = time_elapsed /  self.process_time;
+        let exponent_ratio: f64 = -time_ratio.get::<ratio>();
+
+        self.user_input * self.process_gain
+            * (1.0 - exponent_ratio.exp())
+    }
+
+    /// time where the response kicks in (after any dead time)
+    pub(crate) fn start_time(&self) -> Time {
+        self.start_time
+    }
+
+    /// size of the input step causing this response
+    pub(crate) fn user_input(&self) -> Ratio {
+        self.user_input
+    }
+
+    /// the unstable exponential part only,
+    /// u1(t - t1) * Kp * exp(- [t-t1] / tau)
+    ///
+    /// used for transfer functions with zeroes
+    pub fn calculate_exponential_response(&mut self,
+        simulation_time: Time) -> Ratio {
+
+        self.current_time = simulation_time;
+        let time_elapsed = self.current_time - self.start_time;
+
+        let heaviside_on: bool = self.current_time >= self.start_time;
+
+        if !heaviside_on {
+            return Ratio::ZERO;
+        }
+
+        let time_ratio: Ratio = time_elapsed /  self.process_time;
+        let exponent_ratio: f64 = -time_ratio.get::<ratio>();
+
+        self.user_input * self.process_gain * exponent_ratio.exp()
+    }
+}
+
@@ -0,0 +1,125 @@
+use uom::si::ratio::ratio;
+use uom::si::time::second;
+use uom::si::f64::*;
+
+use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
+use super::first_order_transfer_fn::FirstOrderUnstableResponse;
+
+/// Transfer function in the form:
+///
+/// G(s) = K_p [1 - 1/(tau_p s + 1)]
+///
+/// This comes from:
+/// G(s) =  K_p tau_p s / (tau_p s + 1)
+///
+/// where tau_p is negative.
+///
+/// The step response is a step minus a first order response,
+/// which collapses into a single growing exponential:
+///
+/// u1(t - t1) * Kp * exp(- [t-t1] / tau)
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub struct FirstOrderUnstableTransferFnForZeroes {
+    pub(crate) process_gain: Ratio,
+    /// process time (negative for unstable systems)
+    pub(crate) process_time: Time,
+    pub(crate) previous_timestep_input: Ratio,
+    /// previous timestep output
+    pub(crate) offset: Ratio,
+    /// delay
+    pub(crate) delay: Time,
+
+    /// vector of unstable first order responses
+    pub(crate) response_vec: Vec<FirstOrderUnstableResponse>,
+}
+
+impl Default for FirstOrderUnstableTransferFnForZeroes {
+    /// default is:
+    ///
+    /// -s / (-s + 1)
+    ///
+    /// with initial user input of 0.0
+    /// and initial user value of 0.0
+    fn default() -> Self {
+        FirstOrderUnstableTransferFnForZeroes {
+            process_gain: Ratio::new::<ratio>(1.0),
+            process_time: Time::new::<second>(-1.0),
+            previous_timestep_input: Ratio::new::<ratio>(0.0),
+            offset: Ratio::new::<ratio>(0.0),
+            delay: Time::new::<second>(0.0),
+            response_vec: vec![],
+        }
+    }
+}
+
+impl FirstOrderUnstableTransferFnForZeroes {
+
+    /// constructors
+    /// G(s) =  K_p [1 - 1/(tau_p s + 1)]
+    pub fn new(process_gain: Ratio,
+        process_time: Time,
+        initial_input: Ratio,
+        initial_value: Ratio,
+        delay: Time,) -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        if process_time.get::<second>() >= 0.0 {
+            return Err(ChemEngProcessControlSimulatorError::
+                StableDampingFactorForUnstableTransferFunction);
+        }
+        Ok(FirstOrderUnstableTransferFnForZeroes {
+            process_gain,
+            process_time,
+            previous_timestep_input: initial_input,
+            offset: initial_value,
+            delay,
+            response_vec: vec![],
+        })
+    }
+
+    /// sets the user input to some value
+    /// The transfer function is:
+    ///
+    /// K_p [1 - 1/(tau_p s + 1)]
+    pub fn set_user_input_and_calc_output(&mut self,
+        current_time: Time,
+        current_input: Ratio)
+    -> Result<Ratio, ChemEngProcessControlSimulatorError> {
+
+        // case where input is not the same to 9 decimal places
+        let input_changed: bool =
+            (current_input.get::<ratio>() * 1e9).round()
+            - (self.previous_timestep_input
+                .get::<ratio>()*1e9).round() != 0.0 ;
+
+        if input_changed {
+            let user_input = current_input - self.previous_timestep_input;
+            // the time where the response kicks in
+            let start_time = current_time + self.delay;
+
+            let new_response = FirstOrderUnstableResponse::new(
+                self.process_gain,
+                self.process_time,
+                start_time,
+                user_input,
+                current_time
+            )?;
+
+            self.response_vec.push(new_response);
+
+            self.previous_timestep_input = current_input;
+        }
+
+        // O(t) = summing:: u2(t - t2) * Kp exp(-[t-t2]/tau)
+        // + offset
+        let summation_of_responses: Ratio = self.response_vec.
+            iter_mut().map(
+                |first_order_response|{
+                    first_order_response
+                        .calculate_exponential_response(current_time)}
+            ).sum();
+
+        let output = self.offset + summation_of_responses;
+
+        Ok(output)
+    }
+}
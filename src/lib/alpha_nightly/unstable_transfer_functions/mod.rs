@@ -0,0 +1,3 @@
+pub mod first_order_transfer_fn;
+pub mod first_order_transfer_fn_with_zeroes;
+pub mod second_order_transfer_fn;
@@ -0,0 +1,275 @@
+use uom::{si::{f64::*, frequency::hertz, ratio::ratio}, ConstZero};
+use uom::si::angular_velocity::radian_per_second;
+use uom::si::time::second;
+
+use crate::alpha_nightly::transfer_fn_wrapper_and_enums::generic_second_order::SecondOrderCoefficients;
+
+use crate::alpha_nightly::errors::ChemEngProcessControlSimulatorError;
+
+/// second order system with at least one right half plane pole
+///
+/// G(s) =
+///
+/// a1 s^2 + b1 s + c1
+/// ------------------
+/// a2 s^2 + b2 s + c2
+///
+/// The step response Y(s) = G(s)/s is split using partial fractions:
+///
+/// Y(s) = K/s + (p s + q)/(a2 s^2 + b2 s + c2)
+///
+/// where K = c1/c2, p = a1 - K a2 and q = b1 - K b2
+///
+/// The first term is a step of height K. The second term is
+/// made of the modes of the denominator, which grow with time.
+/// There are three kinds of modes, see SecondOrderUnstableMode.
+///
+/// None of the responses ever reach steady state, so nothing is
+/// ever removed from the response vector.
+#[derive(Debug,PartialEq, PartialOrd, Clone)]
+pub struct SecondOrderUnstableTransferFn {
+    /// K = c1/c2, height of the step term
+    pub(crate) step_gain: Ratio,
+    /// growing modes
+    pub(crate) mode: SecondOrderUnstableMode,
+    pub(crate) previous_timestep_input: Ratio,
+    /// previous timestep output
+    pub(crate) offset: Ratio,
+    /// delay
+    pub(crate) delay: Time,
+    /// input changes, stored as (start time, change in input)
+    pub(crate) response_vec: Vec<(Time, Ratio)>,
+}
+
+/// modes of an unstable second order system,
+/// (p s + q)/(a2 s^2 + b2 s + c2)
+///
+/// this is written in terms of the roots of
+/// a2 s^2 + b2 s + c2 = 0
+#[derive(Debug,PartialEq, PartialOrd, Clone, Copy)]
+pub enum SecondOrderUnstableMode {
+    /// complex roots sigma +/- j omega, with sigma > 0
+    ///
+    /// cosine_magnitude * exp(sigma t) cos (omega t)
+    /// + sine_magnitude * exp(sigma t) sin (omega t)
+    GrowingSinusoid {
+        cosine_magnitude: Ratio,
+        sine_magnitude: Ratio,
+        sigma: Frequency,
+        omega: AngularVelocity,
+    },
+    /// two distinct real roots, at least one is positive
+    ///
+    /// magnitude_one * exp(root_one t)
+    /// + magnitude_two * exp(root_two t)
+    TwoRealRoots {
+        magnitude_one: Ratio,
+        magnitude_two: Ratio,
+        root_one: Frequency,
+        root_two: Frequency,
+    },
+    /// two equal positive real roots
+    ///
+    /// exponential_magnitude * exp(root t)
+    /// + t_exponential_magnitude * t * exp(root t)
+    RepeatedRealRoot {
+        exponential_magnitude: Ratio,
+        t_exponential_magnitude: Frequency,
+        root: Frequency,
+    },
+}
+
+impl SecondOrderUnstableMode {
+
+    /// value of the modes at a time after the input change,
+    /// for a unit step input
+    pub fn calculate_response(&self, time_elapsed: Time) -> Ratio {
+        match *self {
+            SecondOrderUnstableMode::GrowingSinusoid {
+                cosine_magnitude, sine_magnitude, sigma, omega } => {
+                let sigma_t: f64 = (sigma * time_elapsed).get::<ratio>();
+                let omega_t: f64 = omega.get::<radian_per_second>()
+                    * time_elapsed.get::<second>();
+
+                sigma_t.exp() * (cosine_magnitude * omega_t.cos()
+                    + sine_magnitude * omega_t.sin())
+            },
+            SecondOrderUnstableMode::TwoRealRoots {
+                magnitude_one, magnitude_two, root_one, root_two } => {
+                let root_one_t: f64 = (root_one * time_elapsed).get::<ratio>();
+                let root_two_t: f64 = (root_two * time_elapsed).get::<ratio>();
+
+                magnitude_one * root_one_t.exp()
+                    + magnitude_two * root_two_t.exp()
+            },
+            SecondOrderUnstableMode::RepeatedRealRoot {
+                exponential_magnitude, t_exponential_magnitude, root } => {
+                let root_t: f64 = (root * time_elapsed).get::<ratio>();
+                let t_exponential: Ratio = t_exponential_magnitude
+                    * time_elapsed;
+
+                (exponential_magnitude + t_exponential) * root_t.exp()
+            },
+        }
+    }
+}
+
+impl SecondOrderUnstableTransferFn {
+
+    /// constructor from the polynomial coefficients
+    ///
+    /// a1 s^2 + b1 s + c1
+    /// ------------------
+    /// a2 s^2 + b2 s + c2
+    ///
+    /// returns an error if all roots of the denominator are
+    /// in the left half plane, or if the system is undamped or 
+    /// integrating rather than unstable
+    pub fn new(coefficients: SecondOrderCoefficients,
+        initial_input: Ratio,
+        initial_value: Ratio,
+        delay: Time) -> Result<Self, ChemEngProcessControlSimulatorError> {
+
+        // there is no time squared unit in uom yet, so everything 
+        // is worked out in SI base units (seconds)
+        let a1: f64 = coefficients.a1.value;
+        let b1: f64 = coefficients.b1.get::<second>();
+        let c1: f64 = coefficients.c1.get::<ratio>();
+        let a2: f64 = coefficients.a2.value;
+        let b2: f64 = coefficients.b2.get::<second>();
+        let c2: f64 = coefficients.c2.get::<ratio>();
+
+        if c2 == 0.0 {
+            // pole at the origin
+            return Err(ChemEngProcessControlSimulatorError::
+                UndampedOrIntegratingSecondOrderTransferFunction);
+        }
+
+        // partial fractions
+        let step_gain: f64 = c1/c2;
+        let p: f64 = (a1 - step_gain * a2)/a2;
+        let q: f64 = (b1 - step_gain * b2)/a2;
+
+        // roots of s^2 + (b2/a2) s + c2/a2
+        let half_b: f64 = 0.5 * b2/a2;
+        let discriminant: f64 = half_b * half_b - c2/a2;
+
+        let mode = if discriminant < 0.0 {
+            // complex roots
+            // (p s + q)/((s - sigma)^2 + omega^2)
+            let sigma: f64 = -half_b;
+            let omega: f64 = (-discriminant).sqrt();
+
+            if sigma == 0.0 {
+                return Err(ChemEngProcessControlSimulatorError::
+                    UndampedOrIntegratingSecondOrderTransferFunction);
+            }
+            if sigma < 0.0 {
+                return Err(ChemEngProcessControlSimulatorError::
+                    StableDampingFactorForUnstableTransferFunction);
+            }
+
+            SecondOrderUnstableMode::GrowingSinusoid {
+                cosine_magnitude: Ratio::new::<ratio>(p),
+                sine_magnitude: Ratio::new::<ratio>((q + p * sigma)/omega),
+                sigma: Frequency::new::<hertz>(sigma),
+                omega: AngularVelocity::new::<radian_per_second>(omega),
+            }
+        } else if discriminant == 0.0 {
+            // repeated root
+            // (p s + q)/(s - r)^2 = p/(s - r) + (q + p r)/(s - r)^2
+            let root: f64 = -half_b;
+
+            if root <= 0.0 {
+                return Err(ChemEngProcessControlSimulatorError::
+                    StableDampingFactorForUnstableTransferFunction);
+            }
+
+            SecondOrderUnstableMode::RepeatedRealRoot {
+                exponential_magnitude: Ratio::new::<ratio>(p),
+                t_exponential_magnitude:
+                    Frequency::new::<hertz>(q + p * root),
+                root: Frequency::new::<hertz>(root),
+            }
+        } else {
+            // two distinct real roots
+            let root_one: f64 = -half_b + discriminant.sqrt();
+            let root_two: f64 = -half_b - discriminant.sqrt();
+
+            if root_one <= 0.0 {
+                return Err(ChemEngProcessControlSimulatorError::
+                    StableDampingFactorForUnstableTransferFunction);
+            }
+
+            SecondOrderUnstableMode::TwoRealRoots {
+                magnitude_one: Ratio::new::<ratio>(
+                    (p * root_one + q)/(root_one - root_two)),
+                magnitude_two: Ratio::new::<ratio>(
+                    (p * root_two + q)/(root_two - root_one)),
+                root_one: Frequency::new::<hertz>(root_one),
+                root_two: Frequency::new::<hertz>(root_two),
+            }
+        };
+
+        Ok(Self {
+            step_gain: Ratio::new::<ratio>(step_gain),
+            mode,
+            previous_timestep_input: initial_input,
+            offset: initial_value,
+            delay,
+            response_vec: vec![],
+        })
+    }
+
+    /// sets the user input to some value
+    pub fn set_user_input_and_calc_output(&mut self,
+        current_time: Time,
+        current_input: Ratio)
+    -> Result<Ratio, ChemEngProcessControlSimulatorError> {
+
+        // case where input is not the same to 9 decimal places
+        let input_changed: bool =
+            (current_input.get::<ratio>() * 1e9).round()
+            - (self.previous_timestep_input
+                .get::<ratio>()*1e9).round() != 0.0 ;
+
+        if input_changed {
+            let user_input = current_input - self.previous_timestep_input;
+            // the time where the response kicks in
+            let start_time = current_time + self.delay;
+
+            self.response_vec.push((start_time, user_input));
+
+            self.previous_timestep_input = current_input;
+        }
+
+        // O(t) = summing:: u2(t - t2) * [K + modes(t - t2)]
+        // + offset
+        let summation_of_responses: Ratio = self.response_vec.
+            iter().map(
+                |&(start_time, user_input)|{
+                    self.calculate_response(current_time, 
+                        start_time, user_input)}
+            ).sum();
+
+        Ok(self.offset + summation_of_responses)
+    }
+
+    /// calculates the response to one input change at a given time
+    /// u1(t - t1) * [K + modes(t - t1)]
+    fn calculate_response(&self, 
+        simulation_time: Time,
+        start_time: Time,
+        user_input: Ratio) -> Ratio {
+
+        // heaviside function
+        if simulation_time < start_time {
+            return Ratio::ZERO;
+        }
+
+        let time_elapsed = simulation_time - start_time;
+
+        user_input * (self.step_gain 
+            + self.mode.calculate_response(time_elapsed))
+    }
+}
@@ -29,6 +29,7 @@ fn main() {
 
     examples::first_order_demos::stable_first_order_with_delay_simulation_no_zeroes();
     examples::first_order_demos::stable_first_order_with_delay_simulation_with_zeroes();
+    examples::first_order_demos::unstable_first_order_with_delay_simulation();
     examples::generic_transfer_fn_demos::stable_second_order_simulation_with_delay();
     examples::analog_pid_demos::integral_controller_ramp_test();
     examples::analog_pid_demos::proportional_integral_test();
@@ -38,6 +39,7 @@ fn main() {
     examples::analog_pid_demos::proportional_standalone_feedback_test();
     examples::analog_pid_demos::proportional_controller_step_test();
     examples::feedback_control_examples::proportional_derivative_kick_eliminator_feedback_loop_example();
+    examples::feedback_control_examples::block_diagram_feedback_loop_example();
 
     // uncomment for debug 
     //_debug();